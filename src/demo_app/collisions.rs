@@ -0,0 +1,66 @@
+//! Updater module that detects contacts between all instances in the world each frame
+use crate::{
+    framework::{
+        math::Transform,
+        updater::{
+            AfterRenderContext, InputUpdateResult, ResizeContext, UpdateContext,
+            UpdateResultAction, UpdaterModule,
+        },
+    },
+    sdf::{
+        collision::{CollisionDetector, ContactPair},
+        geometry::GeometryID,
+    },
+};
+
+use super::scene::Scene;
+
+/// Contacts between instances found in the last frame.
+#[derive(Default)]
+pub struct CollisionState {
+    pub enabled: bool,
+    pub pairs: Vec<ContactPair<hecs::Entity>>,
+}
+
+impl Scene {
+    /// Broad phase over all entities with a geometry and a transform, returns pairs which penetrate each other.
+    pub fn contact_pairs(
+        &self,
+        detector: &mut CollisionDetector,
+    ) -> Vec<ContactPair<hecs::Entity>> {
+        let instances: Vec<(hecs::Entity, GeometryID, Transform)> = self
+            .world
+            .query::<(hecs::Entity, &GeometryID, &Transform)>()
+            .iter()
+            .map(|(entity, geometry_id, transform)| (entity, *geometry_id, transform.clone()))
+            .collect();
+        detector.contact_pairs(&self.geometry_pool, &instances)
+    }
+}
+
+#[derive(Default)]
+pub struct CollisionUpdater {
+    detector: CollisionDetector,
+}
+
+impl UpdaterModule<Scene> for CollisionUpdater {
+    fn input(&mut self, _: &mut UpdateContext<Scene>) -> InputUpdateResult {
+        InputUpdateResult::default()
+    }
+
+    fn update(&mut self, context: &mut UpdateContext<Scene>) -> UpdateResultAction {
+        let scene = &mut context.scene;
+        if !scene.collisions.enabled {
+            scene.collisions.pairs.clear();
+            return UpdateResultAction::None;
+        }
+        scene.collisions.pairs = scene.contact_pairs(&mut self.detector);
+        UpdateResultAction::None
+    }
+
+    fn resize(&mut self, _: &mut ResizeContext<Scene>) -> UpdateResultAction {
+        UpdateResultAction::None
+    }
+
+    fn after_render(&mut self, _: &mut AfterRenderContext<Scene>) {}
+}
@@ -0,0 +1,322 @@
+use hecs::World;
+
+use crate::{
+    framework::math::Transform,
+    sdf::geometry::{GeometryID, GeometryPool, Operation},
+};
+
+use super::{history::SceneChange, scene::Scene};
+
+/// Selection of instances to combine in GUI.
+pub struct CsgBakeState {
+    pub target: Option<hecs::Entity>,
+    pub tool: Option<hecs::Entity>,
+    pub operation: Operation,
+    pub message: Option<Result<String, String>>,
+}
+
+impl Default for CsgBakeState {
+    fn default() -> Self {
+        Self {
+            target: None,
+            tool: None,
+            operation: Operation::Subtract,
+            message: None,
+        }
+    }
+}
+
+/// A tool instance combined into a target instance, the target shows the baked geometry and the tool is hidden.
+///   - The tool entity keeps its transform, only its geometry is taken away, so reverting can give it back.
+///   - The baked geometry stays in the pool under the same key while the bake is reverted, it is just not instanced.
+///     Later changes which saved its key stay valid and redo does not evaluate it again.
+pub struct CsgBake {
+    target: hecs::Entity,
+    tool: hecs::Entity,
+    target_geometry: GeometryID,
+    tool_geometry: GeometryID,
+    baked_geometry: GeometryID,
+}
+
+impl CsgBake {
+    /// Combines the tool instance into a new geometry inserted into the pool, the bake is not applied yet.
+    pub fn new(
+        world: &World,
+        geometry_pool: &mut GeometryPool,
+        target: hecs::Entity,
+        tool: hecs::Entity,
+        operation: Operation,
+    ) -> Result<Self, String> {
+        if target == tool {
+            return Err("Target and tool have to be different entities".to_string());
+        }
+        let instance = |entity| -> Result<(GeometryID, Transform), String> {
+            let mut query = world.query_one::<(&GeometryID, &Transform)>(entity);
+            let (geometry_id, transform) = query
+                .get()
+                .map_err(|_| format!("Entity {:?} has no geometry", entity))?;
+            Ok((*geometry_id, transform.clone()))
+        };
+        let (target_geometry, target_transform) = instance(target)?;
+        let (tool_geometry, tool_transform) = instance(tool)?;
+        let (Some(geometry), Some(tool_geometry_data)) = (
+            geometry_pool.get(target_geometry),
+            geometry_pool.get(tool_geometry),
+        ) else {
+            return Err("Geometry was removed".to_string());
+        };
+        let baked = geometry.combined(
+            &target_transform,
+            tool_geometry_data,
+            &tool_transform,
+            operation,
+        )?;
+
+        Ok(Self {
+            target,
+            tool,
+            target_geometry,
+            tool_geometry,
+            baked_geometry: geometry_pool.insert(baked),
+        })
+    }
+
+    pub fn apply(&mut self, world: &mut World, geometry_pool: &GeometryPool) -> Result<(), String> {
+        if !world.contains(self.target) || !world.contains(self.tool) {
+            return Err("Baked entities no longer exist".to_string());
+        }
+        if !geometry_pool.contains_key(self.baked_geometry) {
+            return Err("Baked geometry was removed".to_string());
+        }
+        world
+            .insert_one(self.target, self.baked_geometry)
+            .map_err(|err| err.to_string())?;
+        world
+            .remove_one::<GeometryID>(self.tool)
+            .map_err(|err| err.to_string())?;
+        Ok(())
+    }
+
+    pub fn revert(&mut self, world: &mut World) -> Result<(), String> {
+        if !world.contains(self.target) || !world.contains(self.tool) {
+            return Err("Baked entities no longer exist".to_string());
+        }
+        world
+            .insert_one(self.target, self.target_geometry)
+            .map_err(|err| err.to_string())?;
+        world
+            .insert_one(self.tool, self.tool_geometry)
+            .map_err(|err| err.to_string())?;
+        Ok(())
+    }
+
+    /// Removes the baked geometry of a reverted bake which will not be applied again.
+    pub fn discard(self, geometry_pool: &mut GeometryPool) {
+        geometry_pool.remove(self.baked_geometry);
+    }
+}
+
+impl Scene {
+    /// Combines the tool instance into a new geometry of the target instance, the change is pushed into history.
+    pub fn bake_csg(
+        &mut self,
+        target: hecs::Entity,
+        tool: hecs::Entity,
+        operation: Operation,
+    ) -> Result<GeometryID, String> {
+        let mut bake = CsgBake::new(
+            &self.world,
+            &mut self.geometry_pool,
+            target,
+            tool,
+            operation,
+        )?;
+        if let Err(err) = bake.apply(&mut self.world, &self.geometry_pool) {
+            bake.discard(&mut self.geometry_pool);
+            return Err(err);
+        }
+        let baked_geometry = bake.baked_geometry;
+        self.history
+            .push(SceneChange::CsgBake(bake), &mut self.geometry_pool);
+        Ok(baked_geometry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        demo_app::history::SceneHistory,
+        sdf::geometry::{sphere_edit, Edit, EditSampler, Geometry, Primitive},
+    };
+
+    /// A unit sphere target at the origin and a hollow sphere tool placed by a scaled transform.
+    fn target_and_tool() -> (World, GeometryPool, hecs::Entity, hecs::Entity) {
+        let mut geometry_pool = GeometryPool::with_key();
+        let target_geometry =
+            geometry_pool.insert(Geometry::new(0.01).with_edits(vec![sphere_edit(
+                1.0,
+                Operation::Add,
+                glam::Vec3::ZERO,
+            )]));
+        let tool_geometry = geometry_pool.insert(Geometry::new(0.01).with_edits(vec![
+            sphere_edit(0.25, Operation::Add, glam::Vec3::ZERO),
+            sphere_edit(0.1, Operation::Subtract, glam::Vec3::ZERO),
+        ]));
+        let mut world = World::new();
+        let target = world.spawn((target_geometry, Transform::IDENTITY));
+        let tool = world.spawn((
+            tool_geometry,
+            Transform::from_uniform_scale(2.0).with_position(glam::Vec3::new(0.5, 0.0, 0.0)),
+        ));
+        (world, geometry_pool, target, tool)
+    }
+
+    fn instance_edits(
+        world: &World,
+        geometry_pool: &GeometryPool,
+        entity: hecs::Entity,
+    ) -> Vec<Edit> {
+        let geometry_id = *world.get::<&GeometryID>(entity).unwrap();
+        geometry_pool[geometry_id].edits().to_vec()
+    }
+
+    #[test]
+    fn subtraction_keeps_subtractive_edits_of_tool() {
+        let (mut world, mut geometry_pool, target, tool) = target_and_tool();
+        let tool_edits = instance_edits(&world, &geometry_pool, tool);
+        let mut bake = CsgBake::new(
+            &world,
+            &mut geometry_pool,
+            target,
+            tool,
+            Operation::Subtract,
+        )
+        .unwrap();
+        bake.apply(&mut world, &geometry_pool).unwrap();
+
+        let baked = instance_edits(&world, &geometry_pool, target);
+        assert_eq!(baked.len(), 4);
+        assert_eq!(baked[1].primitive, Primitive::Group { edit_count: 2 });
+        assert_eq!(baked[1].operation, Operation::Subtract);
+        for (placed, edit) in baked[2..].iter().zip(tool_edits.iter()) {
+            assert_eq!(placed.operation, edit.operation);
+        }
+        assert_eq!(baked[2].primitive, Primitive::Sphere { radius: 0.5 });
+        assert!(baked[2].transform.scale.abs_diff_eq(glam::Vec3::ONE, 1e-6));
+
+        // The shell of the tool is carved out of the target and its hollow inside is kept
+        let sampler = EditSampler::new(&baked).unwrap();
+        assert!(sampler.sample(glam::Vec3::new(0.5, 0.0, 0.0)).distance < -0.15);
+        assert!(sampler.sample(glam::Vec3::new(0.85, 0.0, 0.0)).distance > 0.1);
+        assert!(sampler.sample(glam::Vec3::new(-0.7, 0.0, 0.0)).distance < -0.25);
+        assert!(world.get::<&GeometryID>(tool).is_err());
+    }
+
+    #[test]
+    fn intersection_is_baked() {
+        let (mut world, mut geometry_pool, target, tool) = target_and_tool();
+        let mut bake = CsgBake::new(
+            &world,
+            &mut geometry_pool,
+            target,
+            tool,
+            Operation::Intersect,
+        )
+        .unwrap();
+        bake.apply(&mut world, &geometry_pool).unwrap();
+
+        let baked = instance_edits(&world, &geometry_pool, target);
+        let sampler = EditSampler::new(&baked).unwrap();
+        assert!(sampler.sample(glam::Vec3::new(0.85, 0.0, 0.0)).distance < -0.1);
+        assert!(sampler.sample(glam::Vec3::new(0.5, 0.0, 0.0)).distance > 0.15);
+        assert!(sampler.sample(glam::Vec3::new(1.15, 0.0, 0.0)).distance > 0.1);
+        assert!(sampler.sample(glam::Vec3::new(-0.7, 0.0, 0.0)).distance > 0.5);
+    }
+
+    #[test]
+    fn undo_restores_original_edits_and_redo_bakes_again() {
+        let (mut world, mut geometry_pool, target, tool) = target_and_tool();
+        let target_edits = instance_edits(&world, &geometry_pool, target);
+        let tool_edits = instance_edits(&world, &geometry_pool, tool);
+        let geometry_count = geometry_pool.len();
+
+        let mut history = SceneHistory::default();
+        let mut bake = CsgBake::new(
+            &world,
+            &mut geometry_pool,
+            target,
+            tool,
+            Operation::Subtract,
+        )
+        .unwrap();
+        bake.apply(&mut world, &geometry_pool).unwrap();
+        history.push(SceneChange::CsgBake(bake), &mut geometry_pool);
+        let baked = instance_edits(&world, &geometry_pool, target);
+
+        history.undo(&mut world).unwrap();
+        assert_eq!(instance_edits(&world, &geometry_pool, target), target_edits);
+        assert_eq!(instance_edits(&world, &geometry_pool, tool), tool_edits);
+        // The baked geometry is kept for redo, it is just not instanced
+        assert_eq!(geometry_pool.len(), geometry_count + 1);
+
+        history.redo(&mut world, &mut geometry_pool).unwrap();
+        assert_eq!(instance_edits(&world, &geometry_pool, target), baked);
+        assert!(world.get::<&GeometryID>(tool).is_err());
+
+        history.undo(&mut world).unwrap();
+        assert_eq!(instance_edits(&world, &geometry_pool, target), target_edits);
+        assert_eq!(instance_edits(&world, &geometry_pool, tool), tool_edits);
+    }
+
+    /// Bakes the tool into the target and pushes the bake into history.
+    fn bake(
+        world: &mut World,
+        geometry_pool: &mut GeometryPool,
+        history: &mut SceneHistory,
+        target: hecs::Entity,
+        tool: hecs::Entity,
+    ) -> GeometryID {
+        let mut bake =
+            CsgBake::new(world, geometry_pool, target, tool, Operation::Subtract).unwrap();
+        bake.apply(world, geometry_pool).unwrap();
+        let baked_geometry = bake.baked_geometry;
+        history.push(SceneChange::CsgBake(bake), geometry_pool);
+        baked_geometry
+    }
+
+    #[test]
+    fn undone_and_redone_bakes_keep_geometry_keys() {
+        let (mut world, mut geometry_pool, target, tool) = target_and_tool();
+        let original = *world.get::<&GeometryID>(target).unwrap();
+        let second_tool = world.spawn((original, Transform::from_xyz(-0.5, 0.0, 0.0)));
+        let mut history = SceneHistory::default();
+        let first = bake(&mut world, &mut geometry_pool, &mut history, target, tool);
+        let second = bake(
+            &mut world,
+            &mut geometry_pool,
+            &mut history,
+            target,
+            second_tool,
+        );
+        let target_geometry = |world: &World| *world.get::<&GeometryID>(target).unwrap();
+
+        history.undo(&mut world).unwrap();
+        history.undo(&mut world).unwrap();
+        assert_eq!(target_geometry(&world), original);
+        history.redo(&mut world, &mut geometry_pool).unwrap();
+        history.redo(&mut world, &mut geometry_pool).unwrap();
+        assert_eq!(target_geometry(&world), second);
+        history.undo(&mut world).unwrap();
+        assert_eq!(target_geometry(&world), first);
+        assert!(geometry_pool.contains_key(first));
+        assert!(geometry_pool.contains_key(second));
+
+        // A new change forgets the undone bake and removes its geometry
+        history.undo(&mut world).unwrap();
+        bake(&mut world, &mut geometry_pool, &mut history, target, tool);
+        assert!(!geometry_pool.contains_key(first));
+        assert!(!geometry_pool.contains_key(second));
+        assert!(geometry_pool.contains_key(original));
+    }
+}
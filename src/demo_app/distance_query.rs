@@ -0,0 +1,19 @@
+use crate::{
+    demo_app::scene::Scene,
+    framework::math::Transform,
+    sdf::{geometry::GeometryID, query::InstanceSdf},
+};
+
+impl Scene {
+    /// The SDF of an instance in world space, None when the entity has no geometry or its geometry has no edits
+    /// which can be evaluated.
+    pub fn instance_sdf(&self, entity: hecs::Entity) -> Option<InstanceSdf<'_>> {
+        let mut query = self.world.query_one::<(&GeometryID, &Transform)>(entity);
+        let (geometry_id, transform) = query.get().ok()?;
+        let geometry = self.geometry_pool.get(*geometry_id)?;
+        if geometry.edits().is_empty() {
+            return None;
+        }
+        InstanceSdf::new(geometry.edits(), transform).ok()
+    }
+}
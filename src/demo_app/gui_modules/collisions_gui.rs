@@ -0,0 +1,47 @@
+use crate::{demo_app::scene::Scene, framework::gui::GuiModule};
+
+/// Lists pairs of instances in contact with their deepest contact point.
+pub struct CollisionsGui;
+
+impl GuiModule<Scene> for CollisionsGui {
+    fn gui_window(&mut self, _: &mut Scene, _: &egui::Context) {}
+
+    fn gui_section(&mut self, scene: &mut Scene, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Collisions").show(ui, |ui| {
+            ui.checkbox(&mut scene.collisions.enabled, "Detect collisions");
+            if !scene.collisions.enabled {
+                return;
+            }
+            if scene.collisions.pairs.is_empty() {
+                ui.label("No contacts");
+                return;
+            }
+            egui::Grid::new("collision_pairs")
+                .num_columns(6)
+                .show(ui, |ui| {
+                    ui.label("Entity A");
+                    ui.label("Entity B");
+                    ui.label("Contacts");
+                    ui.label("Max Depth");
+                    ui.label("Position");
+                    ui.label("Normal");
+                    ui.end_row();
+                    for pair in scene.collisions.pairs.iter() {
+                        ui.label(format!("{:?}", pair.a));
+                        ui.label(format!("{:?}", pair.b));
+                        ui.label(format!("{}", pair.contacts.len()));
+                        if let Some(contact) = pair.deepest_contact() {
+                            let (position, normal) = (contact.position, contact.normal);
+                            ui.label(format!("{:.4}", contact.depth));
+                            ui.label(format!(
+                                "{:.3} {:.3} {:.3}",
+                                position.x, position.y, position.z
+                            ));
+                            ui.label(format!("{:.3} {:.3} {:.3}", normal.x, normal.y, normal.z));
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{
+    demo_app::scene::Scene,
+    framework::gui::GuiModule,
+    sdf::geometry::{GeometryID, Operation},
+};
+
+/// Bakes one instance into another one by a CSG operation and undoes or redoes the bakes.
+pub struct CsgBakeGui;
+
+impl GuiModule<Scene> for CsgBakeGui {
+    fn gui_window(&mut self, _: &mut Scene, _: &egui::Context) {}
+
+    fn gui_section(&mut self, scene: &mut Scene, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("CSG Bake").show(ui, |ui| {
+            let entities = scene
+                .world
+                .query::<(hecs::Entity, &GeometryID)>()
+                .iter()
+                .map(|(entity, _)| entity)
+                .collect::<Vec<_>>();
+            let state = &mut scene.csg_bake;
+            let entity_text = |entity: Option<hecs::Entity>| {
+                entity.map_or("None".to_string(), |entity| format!("{:?}", entity))
+            };
+            egui::Grid::new("csg_bake").num_columns(2).show(ui, |ui| {
+                ui.label("Target:");
+                egui::ComboBox::from_id_source("csg_bake_target")
+                    .selected_text(entity_text(state.target))
+                    .show_ui(ui, |ui| {
+                        for entity in entities.iter() {
+                            ui.selectable_value(
+                                &mut state.target,
+                                Some(*entity),
+                                format!("{:?}", entity),
+                            );
+                        }
+                    });
+                ui.end_row();
+                ui.label("Tool:");
+                egui::ComboBox::from_id_source("csg_bake_tool")
+                    .selected_text(entity_text(state.tool))
+                    .show_ui(ui, |ui| {
+                        for entity in entities.iter() {
+                            ui.selectable_value(
+                                &mut state.tool,
+                                Some(*entity),
+                                format!("{:?}", entity),
+                            );
+                        }
+                    });
+                ui.end_row();
+                ui.label("Operation:");
+                egui::ComboBox::from_id_source("csg_bake_operation")
+                    .selected_text(format!("{:?}", state.operation))
+                    .show_ui(ui, |ui| {
+                        for operation in
+                            [Operation::Add, Operation::Subtract, Operation::Intersect]
+                        {
+                            let text = format!("{:?}", operation);
+                            ui.selectable_value(&mut state.operation, operation, text);
+                        }
+                    });
+                ui.end_row();
+            });
+
+            let (target, tool, operation) = (state.target, state.tool, state.operation.clone());
+            ui.horizontal(|ui| {
+                if let (Some(target), Some(tool)) = (target, tool) {
+                    if ui.button("Bake").clicked() {
+                        let result = scene.bake_csg(target, tool, operation);
+                        scene.csg_bake.message =
+                            Some(result.map(|geometry_id| {
+                                format!("Baked into geometry {:?}", geometry_id)
+                            }));
+                    }
+                }
+                if let Some(change) = scene.history.next_undo() {
+                    if ui.button(format!("Undo {}", change.name())).clicked() {
+                        let result = scene.history.undo(&mut scene.world);
+                        scene.csg_bake.message = Some(result.map(|_| "Undone".to_string()));
+                    }
+                }
+                if let Some(change) = scene.history.next_redo() {
+                    if ui.button(format!("Redo {}", change.name())).clicked() {
+                        let result = scene
+                            .history
+                            .redo(&mut scene.world, &mut scene.geometry_pool);
+                        scene.csg_bake.message = Some(result.map(|_| "Redone".to_string()));
+                    }
+                }
+            });
+            match &scene.csg_bake.message {
+                Some(Ok(message)) => {
+                    ui.label(message);
+                }
+                Some(Err(err)) => {
+                    ui.label(format!("Bake failed: {}", err));
+                }
+                None => {}
+            }
+        });
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{
+    demo_app::{scene::Scene, svo_tools::SvoToolRequest},
+    framework::gui::GuiModule,
+};
+
+/// Shows distances and closest surface points of all instances to a chosen position.
+///   - CPU and GPU batch queries can be compared on random positions.
+pub struct DistanceQueriesGui;
+
+impl GuiModule<Scene> for DistanceQueriesGui {
+    fn gui_window(&mut self, _: &mut Scene, _: &egui::Context) {}
+
+    fn gui_section(&mut self, scene: &mut Scene, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Distance Queries").show(ui, |ui| {
+            let settings = &mut scene.svo_tools.distance_query;
+            ui.horizontal(|ui| {
+                ui.label("Position:");
+                ui.add(egui::DragValue::new(&mut settings.position.x).speed(0.01));
+                ui.add(egui::DragValue::new(&mut settings.position.y).speed(0.01));
+                ui.add(egui::DragValue::new(&mut settings.position.z).speed(0.01));
+            });
+            let position = settings.position;
+            egui::Grid::new("distance_queries")
+                .num_columns(3)
+                .show(ui, |ui| {
+                    ui.label("Entity");
+                    ui.label("Distance");
+                    ui.label("Closest Point");
+                    ui.end_row();
+                    for entity in scene.world.iter().map(|entity| entity.entity()) {
+                        let Some(sdf) = scene.instance_sdf(entity) else {
+                            continue;
+                        };
+                        let closest = sdf.closest_surface_point(position);
+                        ui.label(format!("{:?}", entity));
+                        ui.label(format!("{:.3}", sdf.distance_at(position)));
+                        ui.label(format!(
+                            "{:.3} {:.3} {:.3}",
+                            closest.x, closest.y, closest.z
+                        ));
+                        ui.end_row();
+                    }
+                });
+
+            ui.horizontal(|ui| {
+                ui.label("Batch Size:");
+                ui.add(
+                    egui::DragValue::new(&mut scene.svo_tools.distance_query.batch_size)
+                        .speed(100.0)
+                        .clamp_range(1..=1_000_000),
+                );
+                if ui.button("Compare CPU and GPU").clicked() {
+                    scene
+                        .svo_tools
+                        .requests
+                        .push(SvoToolRequest::CompareDistanceQueries(
+                            scene.svo_tools.distance_query.batch_size,
+                        ));
+                }
+            });
+            match &scene.svo_tools.distance_query_comparison {
+                Some(Ok(message)) => {
+                    ui.label(message);
+                }
+                Some(Err(err)) => {
+                    ui.label(format!("Comparison failed: {}", err));
+                }
+                None => {}
+            }
+        });
+    }
+}
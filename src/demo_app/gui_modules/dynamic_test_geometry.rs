@@ -3,9 +3,13 @@ use egui_extras::{Column, TableBuilder};
 use strum::IntoEnumIterator;
 
 use crate::{
-    demo_app::scene::Scene,
+    demo_app::{scene::Scene, svo_tools::SvoToolRequest},
     framework::{gui::GuiModule, math::Transform},
-    sdf::geometry::{Operation, Primitive, PrimitiveType},
+    sdf::{
+        codegen::{ShaderLanguage, ShaderSource},
+        geometry::{set_geometry_edits, GeometryID, Operation, Primitive, PrimitiveType},
+        volume::VoxModel,
+    },
     shape_builder::{Shape, ShapeRecord},
     warn,
 };
@@ -15,18 +19,27 @@ use crate::{
 #[derive(Default)]
 pub struct DynamicTestGeometry {
     shape: Option<Shape>,
+    /// Number of voxels along the longest side of exported MagicaVoxel models.
+    vox_resolution: u32,
+    /// Size of a voxel of imported MagicaVoxel models.
+    vox_voxel_size: f32,
+    /// Whether exported shaders contain a raymarcher besides the `map` function.
+    shader_raymarcher: bool,
 }
 
 impl DynamicTestGeometry {
     pub fn new() -> Self {
         Self {
             shape: Some(Shape::default()),
+            vox_resolution: 64,
+            vox_voxel_size: 0.05,
+            shader_raymarcher: true,
         }
     }
 
     fn update_geometry(&self, scene: &mut Scene) {
         // Replace first unit geometry in scene
-        let Some((_, geometry)) = scene.geometry_pool.iter_mut().next() else {
+        let Some(geometry_id) = scene.geometry_pool.keys().next() else {
             warn!("DynamicTestGeometry::update_geometry called with no geometry");
             return;
         };
@@ -34,7 +47,10 @@ impl DynamicTestGeometry {
             warn!("DynamicTestGeometry::update_geometry called with no shape");
             return;
         };
-        geometry.set_edits(shape.build());
+        if let Err(_err) = set_geometry_edits(&mut scene.geometry_pool, geometry_id, shape.build())
+        {
+            warn!("Failed to update geometry: {}", _err);
+        }
     }
 }
 
@@ -73,6 +89,83 @@ impl GuiModule<Scene> for DynamicTestGeometry {
                 }
             });
 
+            ui.horizontal(|ui| {
+                let fd = rfd::FileDialog::new().add_filter("vox", &["vox", "VOX"]);
+
+                if ui.button("Import VOX").clicked() {
+                    if let Some(file_name) = fd.clone().pick_file() {
+                        match VoxModel::load(file_name) {
+                            Ok(model) => {
+                                shape = Shape::composite_from_edits(
+                                    model.to_edits(self.vox_voxel_size),
+                                );
+                                changed = true;
+                            }
+                            Err(_err) => {
+                                warn!("Failed to load voxel model: {}", _err);
+                            }
+                        }
+                    }
+                }
+                ui.label("voxel size:");
+                ui.add(
+                    egui::DragValue::new(&mut self.vox_voxel_size)
+                        .speed(0.001)
+                        .max_decimals(3)
+                        .clamp_range(0.001..=1.0),
+                );
+
+                if ui.button("Export VOX").clicked() {
+                    if let Some(geometry_id) = scene.geometry_pool.keys().next() {
+                        if let Some(file_name) = fd.save_file() {
+                            scene.svo_tools.requests.push(SvoToolRequest::ExportVox(
+                                geometry_id,
+                                file_name,
+                                self.vox_resolution,
+                            ));
+                        }
+                    }
+                }
+                ui.label("resolution:");
+                ui.add(
+                    egui::DragValue::new(&mut self.vox_resolution)
+                        .clamp_range(1..=VoxModel::MAX_SIZE),
+                );
+            });
+
+            ui.horizontal(|ui| {
+                if ui.button("Export Shader").clicked() {
+                    if let Some(file_name) = rfd::FileDialog::new()
+                        .add_filter("wgsl", &["wgsl", "WGSL"])
+                        .add_filter("glsl", &["glsl", "GLSL", "frag"])
+                        .save_file()
+                    {
+                        let is_wgsl = file_name
+                            .extension()
+                            .is_none_or(|extension| extension.eq_ignore_ascii_case("wgsl"));
+                        let language = if is_wgsl {
+                            ShaderLanguage::Wgsl
+                        } else {
+                            ShaderLanguage::Glsl
+                        };
+                        let source = ShaderSource::new(language)
+                            .with_raymarcher(self.shader_raymarcher)
+                            .generate(&shape.build());
+                        match source {
+                            Ok(source) => {
+                                if let Err(_err) = std::fs::write(file_name, source) {
+                                    warn!("Failed to save shader: {}", _err);
+                                }
+                            }
+                            Err(_err) => {
+                                warn!("Failed to generate shader: {}", _err);
+                            }
+                        }
+                    }
+                }
+                ui.checkbox(&mut self.shader_raymarcher, "with raymarcher");
+            });
+
             // Obtain list of shapes
             let shapes = match &mut shape {
                 Shape::Composite(a) => a,
@@ -82,6 +175,7 @@ impl GuiModule<Scene> for DynamicTestGeometry {
                 }
             };
 
+            let geometry_ids: Vec<GeometryID> = scene.geometry_pool.keys().collect();
             TableBuilder::new(ui)
                 .cell_layout(Layout::left_to_right(Align::Center))
                 .column(Column::auto()) // Primitive
@@ -354,6 +448,23 @@ impl GuiModule<Scene> for DynamicTestGeometry {
                                                 .min_decimals(3),
                                         );
                                     }
+                                    Primitive::GeometryRef(geometry_id) => {
+                                        let previous_id = *geometry_id;
+                                        egui::ComboBox::from_id_source(format!("{i}_geometry"))
+                                            .selected_text(format!("{:?}", geometry_id))
+                                            .show_ui(ui, |ui| {
+                                                for id in geometry_ids.iter() {
+                                                    ui.selectable_value(
+                                                        geometry_id,
+                                                        *id,
+                                                        format!("{:?}", id),
+                                                    );
+                                                }
+                                            });
+                                        changed = changed || previous_id != *geometry_id;
+                                    }
+                                    // Groups come only from expanded references
+                                    Primitive::Group { .. } => {}
                                 }
                                 changed =
                                     changed || dimension_data != primitive.dimension_data().into();
@@ -0,0 +1,284 @@
+use strum::IntoEnumIterator;
+
+use crate::{
+    demo_app::{scene::Scene, svo_tools::SvoToolRequest},
+    framework::gui::GuiModule,
+    sdf::{mesh::WatertightMesher, volume::VolumeSource},
+};
+
+/// Exports a selected geometry into meshes, volumes, point clouds and cross sections, or the whole scene into glTF.
+///   - Exports run in the SVO tools updater, the result of the last one is shown below the buttons.
+pub struct ExportGui;
+
+impl GuiModule<Scene> for ExportGui {
+    fn gui_window(&mut self, _: &mut Scene, _: &egui::Context) {}
+
+    fn gui_section(&mut self, scene: &mut Scene, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Export").show(ui, |ui| {
+            // Keep selection valid when geometries are removed
+            let tools = &mut scene.svo_tools;
+            if !tools
+                .selected_geometry
+                .is_some_and(|id| scene.geometry_pool.contains_key(id))
+            {
+                tools.selected_geometry = scene.geometry_pool.keys().next();
+            }
+
+            egui::Grid::new("export_geometry")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Geometry:");
+                    egui::ComboBox::from_id_source("export_geometry_selection")
+                        .selected_text(
+                            tools
+                                .selected_geometry
+                                .map_or("None".to_string(), |id| format!("{:?}", id)),
+                        )
+                        .show_ui(ui, |ui| {
+                            for geometry_id in scene.geometry_pool.keys() {
+                                ui.selectable_value(
+                                    &mut tools.selected_geometry,
+                                    Some(geometry_id),
+                                    format!("{:?}", geometry_id),
+                                );
+                            }
+                        });
+                    ui.end_row();
+                });
+
+            if let Some(geometry_id) = scene.svo_tools.selected_geometry {
+                ui.horizontal(|ui| {
+                    if ui.button("Export Mesh").clicked() {
+                        let file_dialog = rfd::FileDialog::new()
+                            .add_filter("obj", &["obj", "OBJ"])
+                            .add_filter("ply", &["ply", "PLY"]);
+                        if let Some(file_name) = file_dialog.save_file() {
+                            scene
+                                .svo_tools
+                                .requests
+                                .push(SvoToolRequest::ExportMesh(geometry_id, file_name));
+                        }
+                    }
+                    if ui.button("Export STL").clicked() {
+                        let file_dialog = rfd::FileDialog::new().add_filter("stl", &["stl", "STL"]);
+                        if let Some(file_name) = file_dialog.save_file() {
+                            scene.svo_tools.requests.push(SvoToolRequest::ExportStl(
+                                geometry_id,
+                                file_name,
+                                scene.svo_tools.stl_export,
+                            ));
+                        }
+                    }
+                    if ui.button("Export Volume").clicked() {
+                        let file_dialog = rfd::FileDialog::new()
+                            .add_filter("npy", &["npy", "NPY"])
+                            .add_filter("raw", &["raw", "RAW"])
+                            .add_filter("narrow band", &["band", "BAND"]);
+                        if let Some(file_name) = file_dialog.save_file() {
+                            scene.svo_tools.requests.push(SvoToolRequest::ExportVolume(
+                                geometry_id,
+                                file_name,
+                                scene.svo_tools.volume_export,
+                            ));
+                        }
+                    }
+                    if ui.button("Export Points").clicked() {
+                        let file_dialog = rfd::FileDialog::new().add_filter("ply", &["ply", "PLY"]);
+                        if let Some(file_name) = file_dialog.save_file() {
+                            scene
+                                .svo_tools
+                                .requests
+                                .push(SvoToolRequest::ExportPointCloud(
+                                    geometry_id,
+                                    file_name,
+                                    scene.svo_tools.point_cloud_export,
+                                ));
+                        }
+                    }
+                    if ui.button("Export Slices").clicked() {
+                        let file_dialog = rfd::FileDialog::new().add_filter("svg", &["svg", "SVG"]);
+                        if let Some(file_name) = file_dialog.save_file() {
+                            scene.svo_tools.requests.push(SvoToolRequest::ExportSlices(
+                                geometry_id,
+                                file_name,
+                                scene.svo_tools.slice_export,
+                            ));
+                        }
+                    }
+                });
+            }
+
+            egui::CollapsingHeader::new("STL Export").show(ui, |ui| {
+                let settings = &mut scene.svo_tools.stl_export;
+                egui::Grid::new("stl_export_settings")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Resolution:");
+                        ui.add(egui::DragValue::new(&mut settings.resolution).clamp_range(
+                            WatertightMesher::MIN_RESOLUTION..=WatertightMesher::MAX_RESOLUTION,
+                        ));
+                        ui.end_row();
+                        ui.label("mm per unit:");
+                        ui.add(
+                            egui::DragValue::new(&mut settings.millimeters_per_unit)
+                                .speed(0.1)
+                                .clamp_range(0.001..=10000.0),
+                        );
+                        ui.end_row();
+                    });
+            });
+            egui::CollapsingHeader::new("Volume Export").show(ui, |ui| {
+                let settings = &mut scene.svo_tools.volume_export;
+                egui::Grid::new("volume_export_settings")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Resolution:");
+                        ui.add(egui::DragValue::new(&mut settings.resolution).clamp_range(1..=512));
+                        ui.end_row();
+                        ui.label("Source:");
+                        egui::ComboBox::from_id_source("volume_export_source")
+                            .selected_text(settings.source.as_ref())
+                            .show_ui(ui, |ui| {
+                                for source in VolumeSource::iter() {
+                                    ui.selectable_value(
+                                        &mut settings.source,
+                                        source,
+                                        source.as_ref(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+                        ui.label("Colors:");
+                        ui.checkbox(&mut settings.with_colors, "");
+                        ui.end_row();
+                        ui.label("Band width (voxels):");
+                        ui.add(
+                            egui::DragValue::new(&mut settings.band_width)
+                                .speed(0.1)
+                                .clamp_range(0.5..=16.0),
+                        );
+                        ui.end_row();
+                    });
+            });
+            egui::CollapsingHeader::new("Point Cloud Export").show(ui, |ui| {
+                let settings = &mut scene.svo_tools.point_cloud_export;
+                egui::Grid::new("point_cloud_export_settings")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Points:");
+                        ui.add(
+                            egui::DragValue::new(&mut settings.point_count)
+                                .speed(100.0)
+                                .clamp_range(1..=10_000_000),
+                        );
+                        ui.end_row();
+                        ui.label("Source:");
+                        egui::ComboBox::from_id_source("point_cloud_export_source")
+                            .selected_text(settings.source.as_ref())
+                            .show_ui(ui, |ui| {
+                                for source in VolumeSource::iter() {
+                                    ui.selectable_value(
+                                        &mut settings.source,
+                                        source,
+                                        source.as_ref(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+                        ui.label("Binary:");
+                        ui.checkbox(&mut settings.binary, "");
+                        ui.end_row();
+                    });
+            });
+            egui::CollapsingHeader::new("Slice Export").show(ui, |ui| {
+                let settings = &mut scene.svo_tools.slice_export;
+                egui::Grid::new("slice_export_settings")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Normal:");
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(&mut settings.normal.x).speed(0.01));
+                            ui.add(egui::DragValue::new(&mut settings.normal.y).speed(0.01));
+                            ui.add(egui::DragValue::new(&mut settings.normal.z).speed(0.01));
+                        });
+                        ui.end_row();
+                        ui.label("Offset:");
+                        ui.add(egui::DragValue::new(&mut settings.offset).speed(0.01));
+                        ui.end_row();
+                        ui.label("Planes:");
+                        ui.add(egui::DragValue::new(&mut settings.count).clamp_range(1..=256));
+                        ui.end_row();
+                        ui.label("Spacing:");
+                        ui.add(
+                            egui::DragValue::new(&mut settings.spacing)
+                                .speed(0.001)
+                                .clamp_range(0.001..=10.0),
+                        );
+                        ui.end_row();
+                        ui.label("Resolution:");
+                        ui.add(
+                            egui::DragValue::new(&mut settings.resolution).clamp_range(8..=4096),
+                        );
+                        ui.end_row();
+                        ui.label("Source:");
+                        egui::ComboBox::from_id_source("slice_export_source")
+                            .selected_text(settings.source.as_ref())
+                            .show_ui(ui, |ui| {
+                                for source in VolumeSource::iter() {
+                                    ui.selectable_value(
+                                        &mut settings.source,
+                                        source,
+                                        source.as_ref(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+                        ui.label("mm per unit:");
+                        ui.add(
+                            egui::DragValue::new(&mut settings.millimeters_per_unit)
+                                .speed(0.1)
+                                .clamp_range(0.001..=10000.0),
+                        );
+                        ui.end_row();
+                    });
+            });
+            if ui.button("Export Scene").clicked() {
+                let file_dialog = rfd::FileDialog::new().add_filter("glb", &["glb", "GLB"]);
+                if let Some(file_name) = file_dialog.save_file() {
+                    scene
+                        .svo_tools
+                        .requests
+                        .push(SvoToolRequest::ExportScene(file_name));
+                }
+            }
+            match &scene.svo_tools.last_export {
+                Some(Ok(message)) => {
+                    ui.label(message);
+                }
+                Some(Err(err)) => {
+                    ui.label(format!("Export failed: {}", err));
+                }
+                None => {}
+            }
+
+            if ui.button("Export Scene").clicked() {
+                let file_dialog = rfd::FileDialog::new().add_filter("glb", &["glb", "GLB"]);
+                if let Some(file_name) = file_dialog.save_file() {
+                    scene
+                        .svo_tools
+                        .requests
+                        .push(SvoToolRequest::ExportScene(file_name));
+                }
+            }
+            match &scene.svo_tools.last_export {
+                Some(Ok(message)) => {
+                    ui.label(message);
+                }
+                Some(Err(err)) => {
+                    ui.label(format!("Export failed: {}", err));
+                }
+                None => {}
+            }
+        });
+    }
+}
@@ -3,10 +3,15 @@ use crate::{
         components::{Active, AxisMesh},
         scene::Scene,
         svo_sdf_brick::DisplayOptions,
+        svo_tools::SvoToolRequest,
     },
     framework::{camera::Camera, gui::GuiModule},
-    sdf::geometry::Geometry,
+    sdf::{
+        geometry::Geometry,
+        svo::{ColorFormat, DistanceFormat},
+    },
 };
+use strum::IntoEnumIterator;
 
 pub struct LegacyAppsGui;
 
@@ -30,6 +35,15 @@ impl GuiModule<Scene> for LegacyAppsGui {
                 .step_by(0.001)
                 .clamp_to_range(true),
             );
+            ui.end_row();
+            ui.label("Evaluation Budget (ms):");
+            ui.add(
+                egui::Slider::new(
+                    &mut scene.tmp_evaluator_config.evaluation_time_budget_ms,
+                    1.0..=100.0,
+                )
+                .clamp_to_range(true),
+            );
         });
 
         ui.separator();
@@ -64,36 +78,130 @@ impl GuiModule<Scene> for LegacyAppsGui {
 
             ui.label("Max Step Count:");
             ui.add(egui::Slider::new(&mut scene.max_step_count, 3..=300).clamp_to_range(true));
+            ui.end_row();
         });
 
         ui.separator();
 
         ui.label("TMP SVO Stats");
-        for (geometry_id, geometry) in scene.geometry_pool.iter() {
+        for (geometry_id, geometry) in scene.geometry_pool.iter_mut() {
             let id = format!("{:?}", geometry_id);
             egui::CollapsingHeader::new(format!("Geometry: {}", id)).show(ui, |ui| {
+                ui.add(
+                    egui::ProgressBar::new(geometry.evaluation_progress())
+                        .text("Evaluation")
+                        .show_percentage(),
+                );
+
+                let mut voxel_format = geometry.brick_voxel_format();
+                egui::Grid::new(format!("{}_format", id))
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Distance Format:");
+                        egui::ComboBox::from_id_source(format!("{}_distance_format", id))
+                            .selected_text(voxel_format.distance.as_ref())
+                            .show_ui(ui, |ui| {
+                                for f in DistanceFormat::iter() {
+                                    ui.selectable_value(&mut voxel_format.distance, f, f.as_ref());
+                                }
+                            });
+                        ui.end_row();
+                        ui.label("Color Format:");
+                        egui::ComboBox::from_id_source(format!("{}_color_format", id))
+                            .selected_text(voxel_format.color.as_ref())
+                            .show_ui(ui, |ui| {
+                                for f in ColorFormat::iter() {
+                                    ui.selectable_value(&mut voxel_format.color, f, f.as_ref());
+                                }
+                            });
+                        ui.end_row();
+                    });
+                geometry.set_brick_voxel_format(voxel_format);
+                if let Some(format) = geometry.unsupported_brick_voxel_format() {
+                    ui.label(format!(
+                        "{} distance with {} color is not supported by the GPU, the default format is used",
+                        format.distance.as_ref(),
+                        format.color.as_ref()
+                    ));
+                }
+                if let Some(err) = geometry.reference_error() {
+                    ui.label(format!("Invalid references: {}", err));
+                }
+
                 if let Some(svo) = geometry.svo.as_ref() {
                     egui::Grid::new(&id).num_columns(2).show(ui, |ui| {
-                        ui.label(format!("Levels:"));
+                        ui.label("Levels:");
                         ui.label(format!("{}", svo.levels.len()));
                         ui.end_row();
-                        ui.label(format!("Node Count:"));
+                        ui.label("Node Count:");
                         ui.label(format!("{}", svo.node_pool.count().unwrap_or(0)));
                         ui.end_row();
-                        ui.label(format!("Level Count:"));
+                        ui.label("Level Count:");
                         ui.label(format!("{}", svo.levels.len()));
                         ui.end_row();
-                        ui.label(format!("Capacity:"));
+                        ui.label("Capacity:");
                         ui.label(format!("{}", svo.node_pool.capacity()));
                         ui.end_row();
+                        ui.label("Brick Count:");
+                        ui.label(format!("{}", svo.brick_pool.count().unwrap_or(0)));
+                        ui.end_row();
+                        ui.label("Free Bricks:");
+                        ui.label(format!("{}", svo.brick_pool.free_count().unwrap_or(0)));
+                        ui.end_row();
+                        ui.label("Brick Capacity:");
+                        ui.label(format!("{}", svo.brick_pool.capacity()));
+                        ui.end_row();
+                        ui.label("Brick Atlas Size:");
+                        ui.label(format!(
+                            "{:.1} MB",
+                            svo.brick_pool.capacity() as f64
+                                * svo.brick_pool.format().bytes_per_brick() as f64
+                                / (1024.0 * 1024.0)
+                        ));
+                        ui.end_row();
                     });
 
+                    ui.horizontal(|ui| {
+                        if svo.brick_pool.format().voxel_format.distance != DistanceFormat::Unorm8
+                            && ui.button("Compare with 8-bit distances").clicked()
+                        {
+                            scene
+                                .svo_tools
+                                .requests
+                                .push(SvoToolRequest::QuantizationError(geometry_id));
+                        }
+                    });
+                    match scene.svo_tools.quantization_errors.get(&geometry_id) {
+                        Some(Ok(report)) => {
+                            egui::Grid::new(format!("{}_quantization", id))
+                                .num_columns(2)
+                                .show(ui, |ui| {
+                                    ui.label("Compared voxels:");
+                                    ui.label(format!("{}", report.sample_count));
+                                    ui.end_row();
+                                    ui.label("Max error:");
+                                    ui.label(format!(
+                                        "{:.6} ({:.3} voxels)",
+                                        report.max_error, report.max_error_in_voxels
+                                    ));
+                                    ui.end_row();
+                                    ui.label("Mean error:");
+                                    ui.label(format!("{:.6}", report.mean_error));
+                                    ui.end_row();
+                                });
+                        }
+                        Some(Err(err)) => {
+                            ui.label(format!("Comparison failed: {}", err));
+                        }
+                        None => {}
+                    }
+
                     egui::CollapsingHeader::new("Levels").show(ui, |ui| {
                         egui::Grid::new(&id).num_columns(4).show(ui, |ui| {
-                            ui.label(format!("Level"));
-                            ui.label(format!("Start"));
-                            ui.label(format!("Count"));
-                            ui.label(format!("Total count"));
+                            ui.label("Level");
+                            ui.label("Start");
+                            ui.label("Count");
+                            ui.label("Total count");
                             ui.end_row();
                             for (level_index, level) in svo.levels.iter().enumerate() {
                                 ui.label(format!("{}", level_index));
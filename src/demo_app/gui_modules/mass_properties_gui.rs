@@ -0,0 +1,135 @@
+use strum::IntoEnumIterator;
+
+use crate::{
+    demo_app::{scene::Scene, svo_tools::SvoToolRequest},
+    framework::gui::GuiModule,
+    sdf::volume::VolumeSource,
+};
+
+/// Shows volume, surface area, center of mass and inertia tensor of a selected geometry.
+///   - Properties are integrated only when requested, because the integration takes a while.
+pub struct MassPropertiesGui;
+
+impl GuiModule<Scene> for MassPropertiesGui {
+    fn gui_window(&mut self, _: &mut Scene, _: &egui::Context) {}
+
+    fn gui_section(&mut self, scene: &mut Scene, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Mass Properties").show(ui, |ui| {
+            // Keep selection valid when geometries are removed
+            let tools = &mut scene.svo_tools;
+            if !tools
+                .selected_geometry
+                .is_some_and(|id| scene.geometry_pool.contains_key(id))
+            {
+                tools.selected_geometry = scene.geometry_pool.keys().next();
+            }
+
+            let settings = &mut tools.mass_properties_settings;
+            egui::Grid::new("mass_properties_settings")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Geometry:");
+                    egui::ComboBox::from_id_source("mass_properties_geometry")
+                        .selected_text(
+                            tools
+                                .selected_geometry
+                                .map_or("None".to_string(), |id| format!("{:?}", id)),
+                        )
+                        .show_ui(ui, |ui| {
+                            for geometry_id in scene.geometry_pool.keys() {
+                                ui.selectable_value(
+                                    &mut tools.selected_geometry,
+                                    Some(geometry_id),
+                                    format!("{:?}", geometry_id),
+                                );
+                            }
+                        });
+                    ui.end_row();
+                    ui.label("Density:");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.density)
+                            .speed(0.01)
+                            .clamp_range(0.0001..=100000.0),
+                    );
+                    ui.end_row();
+                    ui.label("Accuracy (%):");
+                    let mut accuracy = settings.accuracy * 100.0;
+                    ui.add(
+                        egui::DragValue::new(&mut accuracy)
+                            .speed(0.01)
+                            .clamp_range(0.01..=10.0),
+                    );
+                    settings.accuracy = accuracy / 100.0;
+                    ui.end_row();
+                    ui.label("Source:");
+                    egui::ComboBox::from_id_source("mass_properties_source")
+                        .selected_text(settings.source.as_ref())
+                        .show_ui(ui, |ui| {
+                            for source in VolumeSource::iter() {
+                                ui.selectable_value(&mut settings.source, source, source.as_ref());
+                            }
+                        });
+                    ui.end_row();
+                });
+
+            let Some(geometry_id) = tools.selected_geometry else {
+                return;
+            };
+            if ui.button("Compute").clicked() {
+                tools.requests.push(SvoToolRequest::MassProperties(
+                    geometry_id,
+                    tools.mass_properties_settings,
+                ));
+            }
+
+            let properties = match tools.mass_properties.get(&geometry_id) {
+                Some(Ok(properties)) => properties,
+                Some(Err(err)) => {
+                    ui.label(format!("Integration failed: {}", err));
+                    return;
+                }
+                None => {
+                    ui.label("No properties, press Compute.");
+                    return;
+                }
+            };
+            egui::Grid::new("mass_properties_grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Volume:");
+                    ui.label(format!(
+                        "{:.6} ± {:.6}",
+                        properties.volume, properties.volume_error
+                    ));
+                    ui.end_row();
+                    ui.label("Surface area:");
+                    ui.label(format!("{:.6}", properties.surface_area));
+                    ui.end_row();
+                    ui.label("Mass:");
+                    ui.label(format!(
+                        "{:.6} (density {})",
+                        properties.mass, properties.density
+                    ));
+                    ui.end_row();
+                    ui.label("Center of mass:");
+                    let center = properties.center_of_mass;
+                    ui.label(format!("{:.4} {:.4} {:.4}", center.x, center.y, center.z));
+                    ui.end_row();
+                    ui.label("Inertia tensor:");
+                    ui.vertical(|ui| {
+                        for row in 0..3 {
+                            let row = properties.inertia.row(row);
+                            ui.label(format!("{:.6} {:.6} {:.6}", row.x, row.y, row.z));
+                        }
+                    });
+                    ui.end_row();
+                    ui.label("Cell size:");
+                    ui.label(format!("{:.5}", properties.cell_size));
+                    ui.end_row();
+                    ui.label("Samples:");
+                    ui.label(format!("{}", properties.sample_count));
+                    ui.end_row();
+                });
+        });
+    }
+}
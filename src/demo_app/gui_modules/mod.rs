@@ -7,6 +7,33 @@ pub use legacy_apps_gui::LegacyAppsGui;
 mod camera_gui_module;
 pub use camera_gui_module::CameraGuiModule;
 
+mod svo_statistics_gui;
+pub use svo_statistics_gui::SvoStatisticsGui;
+
+mod mass_properties_gui;
+pub use mass_properties_gui::MassPropertiesGui;
+
+mod picking_gui;
+pub use picking_gui::PickingGui;
+
+mod distance_queries_gui;
+pub use distance_queries_gui::DistanceQueriesGui;
+
+mod collisions_gui;
+pub use collisions_gui::CollisionsGui;
+
+mod physics_gui;
+pub use physics_gui::PhysicsGui;
+
+mod csg_bake_gui;
+pub use csg_bake_gui::CsgBakeGui;
+
+mod shadows_gui;
+pub use shadows_gui::ShadowsGui;
+
+mod export_gui;
+pub use export_gui::ExportGui;
+
 #[cfg(feature = "stats")]
 pub mod stats_gui;
 
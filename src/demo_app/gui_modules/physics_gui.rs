@@ -0,0 +1,64 @@
+use crate::{
+    demo_app::{rigid_body::RigidBody, scene::Scene},
+    framework::gui::GuiModule,
+    sdf::geometry::GeometryID,
+};
+
+/// Controls the rigid body simulation and which entities take part in it as dynamic bodies.
+pub struct PhysicsGui;
+
+impl GuiModule<Scene> for PhysicsGui {
+    fn gui_window(&mut self, _: &mut Scene, _: &egui::Context) {}
+
+    fn gui_section(&mut self, scene: &mut Scene, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Physics").show(ui, |ui| {
+            let settings = &mut scene.physics;
+            ui.checkbox(&mut settings.running, "Running");
+            egui::Grid::new("physics_settings")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Gravity:");
+                    ui.add(egui::DragValue::new(&mut settings.gravity.y).speed(0.1));
+                    ui.end_row();
+                    ui.label("Ground Height:");
+                    ui.add(egui::DragValue::new(&mut settings.ground_height).speed(0.1));
+                    ui.end_row();
+                    ui.label("Dynamic Bodies:");
+                    ui.label(format!(
+                        "{}",
+                        scene.world.query::<&RigidBody>().iter().count()
+                    ));
+                    ui.end_row();
+                });
+
+            // Entities with a geometry become dynamic bodies, the rest stays as static obstacles
+            ui.horizontal(|ui| {
+                if ui.button("Make All Dynamic").clicked() {
+                    let entities = scene
+                        .world
+                        .query::<(hecs::Entity, &GeometryID)>()
+                        .without::<&RigidBody>()
+                        .iter()
+                        .map(|(entity, _)| entity)
+                        .collect::<Vec<_>>();
+                    for entity in entities {
+                        if let Err(_err) = scene.world.insert_one(entity, RigidBody::new(1.0)) {
+                            crate::error!("Failed to insert RigidBody: {}", _err);
+                        }
+                    }
+                }
+                if ui.button("Make All Static").clicked() {
+                    let entities = scene
+                        .world
+                        .query::<(hecs::Entity, &RigidBody)>()
+                        .iter()
+                        .map(|(entity, _)| entity)
+                        .collect::<Vec<_>>();
+                    for entity in entities {
+                        let _ = scene.world.remove_one::<RigidBody>(entity);
+                    }
+                }
+            });
+        });
+    }
+}
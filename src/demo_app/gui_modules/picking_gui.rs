@@ -0,0 +1,58 @@
+use crate::{demo_app::scene::Scene, framework::gui::GuiModule};
+
+/// Shows what is under the mouse cursor, the scene is ray cast from the camera through the cursor every frame.
+pub struct PickingGui;
+
+impl GuiModule<Scene> for PickingGui {
+    fn gui_window(&mut self, _: &mut Scene, _: &egui::Context) {}
+
+    fn gui_section(&mut self, scene: &mut Scene, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Picking").show(ui, |ui| {
+            let ctx = ui.ctx().clone();
+            let hit = ctx
+                .pointer_hover_pos()
+                .filter(|_| !ctx.is_pointer_over_area())
+                .and_then(|pointer| {
+                    let screen = ctx.screen_rect();
+                    let ndc = glam::Vec2::new(
+                        (pointer.x - screen.min.x) / screen.width() * 2.0 - 1.0,
+                        1.0 - (pointer.y - screen.min.y) / screen.height() * 2.0,
+                    );
+                    scene.raycast(&scene.camera_rig.camera().ray(ndc))
+                });
+            let Some(hit) = hit else {
+                ui.label("Nothing under the cursor");
+                return;
+            };
+            egui::Grid::new("picking").num_columns(2).show(ui, |ui| {
+                ui.label("Entity:");
+                ui.label(format!("{:?}", hit.entity));
+                ui.end_row();
+                ui.label("Geometry:");
+                ui.label(format!("{:?}", hit.geometry_id));
+                ui.end_row();
+                ui.label("Edit:");
+                ui.label(
+                    hit.edit_index
+                        .map_or("-".to_string(), |index| index.to_string()),
+                );
+                ui.end_row();
+                ui.label("Position:");
+                ui.label(format!(
+                    "{:.3} {:.3} {:.3}",
+                    hit.position.x, hit.position.y, hit.position.z
+                ));
+                ui.end_row();
+                ui.label("Normal:");
+                ui.label(format!(
+                    "{:.3} {:.3} {:.3}",
+                    hit.normal.x, hit.normal.y, hit.normal.z
+                ));
+                ui.end_row();
+                ui.label("Distance:");
+                ui.label(format!("{:.3}", hit.distance));
+                ui.end_row();
+            });
+        });
+    }
+}
@@ -0,0 +1,37 @@
+use crate::{
+    demo_app::{scene::Scene, svo_sdf_brick::DisplayOptions},
+    framework::gui::GuiModule,
+};
+
+/// Toggles soft shadows of bricks and sets their quality.
+pub struct ShadowsGui;
+
+impl GuiModule<Scene> for ShadowsGui {
+    fn gui_window(&mut self, _: &mut Scene, _: &egui::Context) {}
+
+    fn gui_section(&mut self, scene: &mut Scene, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Shadows").show(ui, |ui| {
+            let options = &mut scene.display_toggles.brick_display_options;
+            let mut enabled = options.contains(DisplayOptions::SHADOWS);
+            ui.checkbox(&mut enabled, "Shadows");
+            options.set(DisplayOptions::SHADOWS, enabled);
+
+            egui::Grid::new("shadows").num_columns(2).show(ui, |ui| {
+                ui.label("Penumbra:");
+                ui.add(
+                    egui::Slider::new(&mut scene.shadows.penumbra, 0.0..=1.0)
+                        .step_by(0.005)
+                        .clamp_to_range(true),
+                );
+                ui.end_row();
+
+                ui.label("Max Step Count:");
+                ui.add(
+                    egui::Slider::new(&mut scene.shadows.max_step_count, 8..=256)
+                        .clamp_to_range(true),
+                );
+                ui.end_row();
+            });
+        });
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{
+    demo_app::{scene::Scene, svo_tools::SvoToolRequest},
+    framework::gui::GuiModule,
+};
+
+/// Shows statistics of the SVO of a selected geometry.
+///   - Statistics are read back from GPU only when requested, because the readback stalls the GPU.
+pub struct SvoStatisticsGui;
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.2} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+impl GuiModule<Scene> for SvoStatisticsGui {
+    fn gui_window(&mut self, _: &mut Scene, _: &egui::Context) {}
+
+    fn gui_section(&mut self, scene: &mut Scene, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("SVO Statistics").show(ui, |ui| {
+            // Keep selection valid when geometries are removed
+            let tools = &mut scene.svo_tools;
+            if !tools
+                .selected_geometry
+                .is_some_and(|id| scene.geometry_pool.contains_key(id))
+            {
+                tools.selected_geometry = scene.geometry_pool.keys().next();
+            }
+
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("svo_statistics_geometry")
+                    .selected_text(
+                        tools
+                            .selected_geometry
+                            .map_or("None".to_string(), |id| format!("{:?}", id)),
+                    )
+                    .show_ui(ui, |ui| {
+                        for geometry_id in scene.geometry_pool.keys() {
+                            ui.selectable_value(
+                                &mut tools.selected_geometry,
+                                Some(geometry_id),
+                                format!("{:?}", geometry_id),
+                            );
+                        }
+                    });
+                if let Some(geometry_id) = tools.selected_geometry {
+                    if ui.button("Refresh").clicked() {
+                        tools.requests.push(SvoToolRequest::Statistics(geometry_id));
+                    }
+                }
+            });
+
+            let Some(statistics) = tools
+                .selected_geometry
+                .and_then(|id| tools.statistics.get(&id))
+            else {
+                ui.label("No statistics, press Refresh.");
+                return;
+            };
+
+            egui::Grid::new("svo_statistics_grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Nodes:");
+                    ui.label(format!(
+                        "{} / {}",
+                        statistics.node_count, statistics.node_capacity
+                    ));
+                    ui.end_row();
+                    ui.label("Subdivided / Leaf:");
+                    ui.label(format!(
+                        "{} / {}",
+                        statistics.subdivided_count, statistics.leaf_count
+                    ));
+                    ui.end_row();
+                    ui.label("Nodes with brick:");
+                    ui.label(format!("{}", statistics.brick_node_count));
+                    ui.end_row();
+                    ui.label("Bricks:");
+                    ui.label(format!(
+                        "{} / {} ({:.1}%)",
+                        statistics.brick_count,
+                        statistics.brick_capacity,
+                        statistics.atlas_occupancy() * 100.0
+                    ));
+                    ui.end_row();
+                    ui.label("Free bricks:");
+                    ui.label(format!("{}", statistics.free_brick_count));
+                    ui.end_row();
+
+                    let memory = &statistics.memory;
+                    ui.label("Node headers:");
+                    ui.label(megabytes(memory.node_headers));
+                    ui.end_row();
+                    ui.label("Node payloads:");
+                    ui.label(megabytes(memory.node_payloads));
+                    ui.end_row();
+                    ui.label("Node vertices:");
+                    ui.label(megabytes(memory.node_vertices));
+                    ui.end_row();
+                    ui.label("Distance atlas:");
+                    ui.label(megabytes(memory.distance_atlas));
+                    ui.end_row();
+                    ui.label("Color atlas:");
+                    ui.label(megabytes(memory.color_atlas));
+                    ui.end_row();
+                    ui.label("Distance ranges:");
+                    ui.label(megabytes(memory.distance_ranges));
+                    ui.end_row();
+                    ui.label("Brick free list:");
+                    ui.label(megabytes(memory.brick_free_list));
+                    ui.end_row();
+                    ui.label("Total:");
+                    ui.label(megabytes(memory.total()));
+                    ui.end_row();
+                });
+
+            egui::CollapsingHeader::new("Levels")
+                .id_source("svo_statistics_levels")
+                .show(ui, |ui| {
+                    egui::Grid::new("svo_statistics_levels_grid")
+                        .num_columns(7)
+                        .show(ui, |ui| {
+                            ui.label("Level");
+                            ui.label("Start");
+                            ui.label("Nodes");
+                            ui.label("Subdivided");
+                            ui.label("Leaf");
+                            ui.label("Bricks");
+                            ui.label("Voxel size");
+                            ui.end_row();
+                            for (level_index, level) in statistics.levels.iter().enumerate() {
+                                ui.label(format!("{}", level_index));
+                                ui.label(format!("{}", level.start_index));
+                                ui.label(format!("{}", level.node_count));
+                                ui.label(format!("{}", level.subdivided_count));
+                                ui.label(format!("{}", level.leaf_count));
+                                ui.label(format!("{}", level.brick_node_count));
+                                ui.label(format!("{:.5}", level.voxel_size));
+                                ui.end_row();
+                            }
+                        });
+                });
+
+            if statistics.error_count == 0 {
+                ui.label("No errors found.");
+            } else {
+                egui::CollapsingHeader::new(format!("Errors: {}", statistics.error_count))
+                    .id_source("svo_statistics_errors")
+                    .show(ui, |ui| {
+                        for error in statistics.errors.iter() {
+                            ui.label(error);
+                        }
+                        if statistics.error_count > statistics.errors.len() {
+                            ui.label(format!(
+                                "... and {} more",
+                                statistics.error_count - statistics.errors.len()
+                            ));
+                        }
+                    });
+            }
+        });
+    }
+}
@@ -0,0 +1,90 @@
+use hecs::World;
+
+use crate::sdf::geometry::GeometryPool;
+
+use super::csg_bake::CsgBake;
+
+/// A change of the scene which can be reverted and applied again.
+pub enum SceneChange {
+    CsgBake(CsgBake),
+}
+
+impl SceneChange {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SceneChange::CsgBake(_) => "CSG Bake",
+        }
+    }
+
+    fn apply(&mut self, world: &mut World, geometry_pool: &GeometryPool) -> Result<(), String> {
+        match self {
+            SceneChange::CsgBake(bake) => bake.apply(world, geometry_pool),
+        }
+    }
+
+    fn revert(&mut self, world: &mut World) -> Result<(), String> {
+        match self {
+            SceneChange::CsgBake(bake) => bake.revert(world),
+        }
+    }
+
+    /// Frees resources of a reverted change which will not be applied again.
+    fn discard(self, geometry_pool: &mut GeometryPool) {
+        match self {
+            SceneChange::CsgBake(bake) => bake.discard(geometry_pool),
+        }
+    }
+}
+
+/// Undo and redo stacks of scene changes.
+///   - Changes are pushed after they were applied, pushing a change forgets changes which were undone.
+#[derive(Default)]
+pub struct SceneHistory {
+    undo_stack: Vec<SceneChange>,
+    redo_stack: Vec<SceneChange>,
+}
+
+impl SceneHistory {
+    pub fn push(&mut self, change: SceneChange, geometry_pool: &mut GeometryPool) {
+        self.undo_stack.push(change);
+        // Undone changes are reverted, nothing else refers to geometries they created
+        for change in self.redo_stack.drain(..) {
+            change.discard(geometry_pool);
+        }
+    }
+
+    pub fn next_undo(&self) -> Option<&SceneChange> {
+        self.undo_stack.last()
+    }
+
+    pub fn next_redo(&self) -> Option<&SceneChange> {
+        self.redo_stack.last()
+    }
+
+    /// Reverts the last applied change, a change which cannot be reverted is dropped.
+    pub fn undo(&mut self, world: &mut World) -> Result<(), String> {
+        let Some(mut change) = self.undo_stack.pop() else {
+            return Err("Nothing to undo".to_string());
+        };
+        change.revert(world)?;
+        self.redo_stack.push(change);
+        Ok(())
+    }
+
+    /// Applies the last reverted change again, a change which cannot be applied is discarded.
+    pub fn redo(
+        &mut self,
+        world: &mut World,
+        geometry_pool: &mut GeometryPool,
+    ) -> Result<(), String> {
+        let Some(mut change) = self.redo_stack.pop() else {
+            return Err("Nothing to redo".to_string());
+        };
+        if let Err(err) = change.apply(world, geometry_pool) {
+            change.discard(geometry_pool);
+            return Err(err);
+        }
+        self.undo_stack.push(change);
+        Ok(())
+    }
+}
@@ -6,7 +6,10 @@ use crate::{
         camera::{Camera, CameraRig, FreeCameraRig, OrbitCameraRig},
         math::Transform,
     },
-    sdf::geometry::{Geometry, GeometryPool},
+    sdf::{
+        evaluator::Evaluator,
+        geometry::{Geometry, GeometryPool},
+    },
 };
 
 use super::{
@@ -224,12 +227,19 @@ pub fn init_scene(context: &application::Context) -> Scene {
         tmp_evaluator_config: TmpEvaluatorConfigProps {
             render_level: 0,
             min_voxel_size,
+            evaluation_time_budget_ms: Evaluator::DEFAULT_TIME_BUDGET.as_secs_f32() * 1000.0,
         },
         display_toggles: Default::default(),
+        svo_tools: Default::default(),
+        collisions: Default::default(),
+        physics: Default::default(),
+        csg_bake: Default::default(),
+        history: Default::default(),
         brick_level_break_size: 0.03,
 
         // Empirically obtained rendering settings
         hit_distance: 0.01, // not terribly tight and accurate but visually non distracting (0.001 would be better but more expensive as it would require about 180 steps)
         max_step_count: 130, // for 0.01 130 seems to be enough
+        shadows: Default::default(),
     }
 }
@@ -3,10 +3,17 @@ use crate::framework::{
 };
 
 use super::{
+    collisions::CollisionUpdater,
     continuous_rotation::ContinuousRotator,
-    gui_modules::{CameraGuiModule, DynamicTestGeometry, LegacyAppsGui},
+    gui_modules::{
+        CameraGuiModule, CollisionsGui, CsgBakeGui, DistanceQueriesGui, DynamicTestGeometry,
+        ExportGui, LegacyAppsGui, MassPropertiesGui, PhysicsGui, PickingGui, ShadowsGui,
+        SvoStatisticsGui,
+    },
+    rigid_body::RigidBodyUpdater,
     scene::Scene,
     svo_evaluator::SvoEvaluatorUpdater,
+    svo_tools::SvoToolsUpdater,
     tmp_evaluator_config::TmpEvaluatorConfig,
 };
 
@@ -23,13 +30,25 @@ pub fn init_updater(context: &Context) -> Updater<Scene> {
             Box::new(CountersGui),
             Box::new(CameraGuiModule),
             Box::new(LegacyAppsGui),
+            Box::new(ExportGui),
+            Box::new(SvoStatisticsGui),
+            Box::new(MassPropertiesGui),
+            Box::new(PickingGui),
+            Box::new(DistanceQueriesGui),
+            Box::new(CollisionsGui),
+            Box::new(PhysicsGui),
+            Box::new(CsgBakeGui),
+            Box::new(ShadowsGui),
             Box::new(DynamicTestGeometry::new()),
             #[cfg(feature = "stats")]
             Box::new(StatsGui),
         ]))
         .with_module(ContinuousRotator)
+        .with_module(RigidBodyUpdater::default())
         .with_module(TmpEvaluatorConfig::default())
         .with_module(CameraUpdater)
         // .with_module(VoxelSizeReferenceDisplayer { visible: false })
         .with_module(SvoEvaluatorUpdater::new(context.gpu.clone())) // SVO updater needs arc reference to GPU context because it spawns threads sharing the GPU context
+        .with_module(SvoToolsUpdater::new(context.gpu.clone()))
+        .with_module(CollisionUpdater::default())
 }
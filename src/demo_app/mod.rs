@@ -2,11 +2,18 @@ pub mod components;
 pub mod gui_modules;
 pub mod scene;
 
+mod collisions;
 mod continuous_rotation;
+mod csg_bake;
 mod cube;
+mod distance_query;
+mod history;
 mod line;
+mod raycast;
+mod rigid_body;
 mod svo_evaluator;
 mod svo_sdf_brick;
+mod svo_tools;
 mod svo_wireframe;
 mod tmp_evaluator_config;
 
@@ -0,0 +1,237 @@
+use hecs::World;
+
+use crate::{
+    demo_app::scene::Scene,
+    framework::math::{Ray, Transform},
+    sdf::geometry::{EditSampler, GeometryID, GeometryPool},
+    warn,
+};
+
+/// The closest intersection of a ray with scene geometry.
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub entity: hecs::Entity,
+    pub geometry_id: GeometryID,
+    /// World space position on the surface.
+    pub position: glam::Vec3,
+    /// World space normal of the surface.
+    pub normal: glam::Vec3,
+    /// Distance from the ray origin in world space.
+    pub distance: f32,
+    /// Index of the geometry edit contributing most to the surface at the hit position.
+    pub edit_index: Option<usize>,
+}
+
+impl Scene {
+    /// Finds the closest instance surface hit by the ray.
+    ///   - The ray is transformed into local space of each instance and culled by total AABB of its geometry.
+    ///   - Geometry is sphere traced on CPU using its edits, with the same hit distance and step count as rendering.
+    #[profiler::function]
+    pub fn raycast(&self, ray: &Ray) -> Option<Hit> {
+        raycast(
+            &self.world,
+            &self.geometry_pool,
+            ray,
+            self.hit_distance,
+            self.max_step_count,
+        )
+    }
+}
+
+/// Finds the closest surface of instances in the world hit by the ray, see `Scene::raycast`.
+fn raycast(
+    world: &World,
+    geometry_pool: &GeometryPool,
+    ray: &Ray,
+    hit_distance: f32,
+    max_step_count: u32,
+) -> Option<Hit> {
+    let mut closest: Option<Hit> = None;
+    for (entity, geometry_id, transform) in world
+        .query::<(hecs::Entity, &GeometryID, &Transform)>()
+        .iter()
+    {
+        let Some(geometry) = geometry_pool.get(*geometry_id) else {
+            continue;
+        };
+        if geometry.edits().is_empty() {
+            continue;
+        }
+        let to_local = transform.as_mat().inverse();
+        let local_ray = ray.transform(&to_local);
+        let Some((enter, exit)) = geometry.total_aabb().intersect_ray(&local_ray) else {
+            continue;
+        };
+        let exit = closest.as_ref().map_or(exit, |hit| exit.min(hit.distance));
+        if enter > exit {
+            continue;
+        }
+
+        let sampler = match EditSampler::new(geometry.edits()) {
+            Ok(sampler) => sampler,
+            Err(_err) => {
+                warn!("Cannot raycast geometry {:?}: {}", geometry_id, _err);
+                continue;
+            }
+        };
+        // Local distances are converted to world distances along the ray by length of the transformed direction
+        let local_units_per_world_unit = local_ray.direction.length();
+        let mut t = enter;
+        for _ in 0..max_step_count {
+            let local_position = local_ray.at(t);
+            let sample = sampler.sample(local_position);
+            if sample.distance < hit_distance {
+                let local_normal = gradient(&sampler, local_position, hit_distance);
+                closest = Some(Hit {
+                    entity,
+                    geometry_id: *geometry_id,
+                    position: ray.at(t),
+                    normal: to_local
+                        .transpose()
+                        .transform_vector3(local_normal)
+                        .normalize_or_zero(),
+                    distance: t,
+                    edit_index: sample.edit_index,
+                });
+                break;
+            }
+            t += sample.distance / local_units_per_world_unit;
+            if t > exit {
+                break;
+            }
+        }
+    }
+    closest
+}
+
+/// Central differences of the distance field.
+fn gradient(sampler: &EditSampler, position: glam::Vec3, epsilon: f32) -> glam::Vec3 {
+    let epsilon = epsilon.max(1e-4);
+    let difference = |axis: glam::Vec3| {
+        sampler.sample(position + axis * epsilon).distance
+            - sampler.sample(position - axis * epsilon).distance
+    };
+    glam::Vec3::new(
+        difference(glam::Vec3::X),
+        difference(glam::Vec3::Y),
+        difference(glam::Vec3::Z),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdf::geometry::{sphere_edit, Geometry, Operation};
+
+    const HIT_DISTANCE: f32 = 0.001;
+    const MAX_STEP_COUNT: u32 = 256;
+
+    fn cast(
+        world: &World,
+        geometry_pool: &GeometryPool,
+        origin: glam::Vec3,
+        direction: glam::Vec3,
+    ) -> Option<Hit> {
+        raycast(
+            world,
+            geometry_pool,
+            &Ray::new(origin, direction),
+            HIT_DISTANCE,
+            MAX_STEP_COUNT,
+        )
+    }
+
+    /// Pool with a sphere of radius 0.5 at the origin.
+    fn sphere_pool() -> (GeometryPool, GeometryID) {
+        let mut geometry_pool = GeometryPool::with_key();
+        let geometry_id = geometry_pool.insert(Geometry::new(0.01).with_edits(vec![sphere_edit(
+            0.5,
+            Operation::Add,
+            glam::Vec3::ZERO,
+        )]));
+        (geometry_pool, geometry_id)
+    }
+
+    #[test]
+    fn hits_scaled_and_translated_sphere() {
+        let (geometry_pool, geometry_id) = sphere_pool();
+        let mut world = World::new();
+        let center = glam::Vec3::new(3.0, 0.0, 0.0);
+        let entity = world.spawn((
+            geometry_id,
+            Transform::from_uniform_scale(2.0).with_position(center),
+        ));
+
+        let hit = cast(
+            &world,
+            &geometry_pool,
+            glam::Vec3::new(3.0, 0.0, -5.0),
+            glam::Vec3::Z,
+        )
+        .unwrap();
+        assert_eq!(hit.entity, entity);
+        assert_eq!(hit.geometry_id, geometry_id);
+        assert!((hit.distance - 4.0).abs() < 0.01);
+        assert!(hit.position.abs_diff_eq(center - glam::Vec3::Z, 0.01));
+        assert!(hit.normal.abs_diff_eq(-glam::Vec3::Z, 0.01));
+
+        // The world radius is 1, rays passing further from the center or pointing away miss it
+        for (origin, direction) in [
+            (glam::Vec3::new(4.2, 0.0, -5.0), glam::Vec3::Z),
+            (glam::Vec3::new(3.0, 1.2, -5.0), glam::Vec3::Z),
+            (glam::Vec3::new(3.0, 0.0, -5.0), -glam::Vec3::Z),
+        ] {
+            assert!(cast(&world, &geometry_pool, origin, direction).is_none());
+        }
+    }
+
+    #[test]
+    fn returns_nearest_of_instances_along_ray() {
+        let (geometry_pool, geometry_id) = sphere_pool();
+        let mut world = World::new();
+        world.spawn((geometry_id, Transform::from_xyz(0.0, 0.0, 6.0)));
+        let near = world.spawn((geometry_id, Transform::from_xyz(0.0, 0.0, 2.0)));
+
+        let hit = cast(&world, &geometry_pool, glam::Vec3::ZERO, glam::Vec3::Z).unwrap();
+        assert_eq!(hit.entity, near);
+        assert!((hit.distance - 1.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn reports_world_normal_and_edit_of_hit() {
+        // Second edit lies at local x = 1, which the rotation moves to world z = -1
+        let mut geometry_pool = GeometryPool::with_key();
+        let geometry_id = geometry_pool.insert(Geometry::new(0.01).with_edits(vec![
+            sphere_edit(0.5, Operation::Add, glam::Vec3::ZERO),
+            sphere_edit(0.5, Operation::Add, glam::Vec3::X),
+        ]));
+        let mut world = World::new();
+        world.spawn((
+            geometry_id,
+            Transform::default()
+                .with_rotation(glam::Quat::from_rotation_y(std::f32::consts::FRAC_PI_2)),
+        ));
+
+        let hit = cast(
+            &world,
+            &geometry_pool,
+            glam::Vec3::new(0.0, 0.0, -5.0),
+            glam::Vec3::Z,
+        )
+        .unwrap();
+        assert_eq!(hit.edit_index, Some(1));
+        assert!((hit.distance - 3.5).abs() < 0.01);
+        assert!(hit.normal.abs_diff_eq(-glam::Vec3::Z, 0.01));
+
+        let hit = cast(
+            &world,
+            &geometry_pool,
+            glam::Vec3::new(0.0, 0.0, 5.0),
+            -glam::Vec3::Z,
+        )
+        .unwrap();
+        assert_eq!(hit.edit_index, Some(0));
+        assert!((hit.distance - 4.5).abs() < 0.01);
+        assert!(hit.normal.abs_diff_eq(glam::Vec3::Z, 0.01));
+    }
+}
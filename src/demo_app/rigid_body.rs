@@ -0,0 +1,441 @@
+//! Updater module that simulates rigid bodies colliding with each other and with a ground plane
+use std::time::Duration;
+
+use hecs::World;
+
+use crate::{
+    framework::{
+        math::Transform,
+        updater::{
+            AfterRenderContext, InputUpdateResult, ResizeContext, UpdateContext,
+            UpdateResultAction, UpdaterModule,
+        },
+    },
+    sdf::{
+        collision::{CollisionDetector, Contact},
+        geometry::{GeometryID, GeometryPool},
+    },
+};
+
+use super::scene::Scene;
+
+/// Makes an entity with a geometry and a transform move, entities without it are static obstacles.
+#[derive(Debug, Clone)]
+pub struct RigidBody {
+    pub mass: f32,
+    pub velocity: glam::Vec3,
+    pub angular_velocity: glam::Vec3,
+    /// Fraction of the normal velocity kept after a collision, 0 stops the body, 1 bounces it back fully.
+    pub restitution: f32,
+}
+
+impl RigidBody {
+    pub fn new(mass: f32) -> Self {
+        Self {
+            mass,
+            velocity: glam::Vec3::ZERO,
+            angular_velocity: glam::Vec3::ZERO,
+            restitution: 0.3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PhysicsSettings {
+    pub running: bool,
+    pub gravity: glam::Vec3,
+    pub ground_height: f32,
+    /// Length of a simulation step in seconds.
+    pub timestep: f32,
+}
+
+impl Default for PhysicsSettings {
+    fn default() -> Self {
+        Self {
+            running: false,
+            gravity: glam::Vec3::new(0.0, -9.81, 0.0),
+            ground_height: -2.0,
+            timestep: 1.0 / 120.0,
+        }
+    }
+}
+
+/// State of an instance during a step.
+///   - Bodies rotate around their geometry origin, which is taken as their center of mass.
+///   - Static obstacles and the ground have zero inverse mass and inertia, so impulses do not move them.
+#[derive(Clone)]
+struct Body {
+    entity: Option<hecs::Entity>,
+    transform: Transform,
+    inverse_mass: f32,
+    inverse_inertia: glam::Mat3,
+    velocity: glam::Vec3,
+    angular_velocity: glam::Vec3,
+    restitution: f32,
+}
+
+impl Body {
+    fn fixed(transform: Transform) -> Self {
+        Self {
+            entity: None,
+            transform,
+            inverse_mass: 0.0,
+            inverse_inertia: glam::Mat3::ZERO,
+            velocity: glam::Vec3::ZERO,
+            angular_velocity: glam::Vec3::ZERO,
+            restitution: 1.0,
+        }
+    }
+
+    fn is_static(&self) -> bool {
+        self.inverse_mass == 0.0
+    }
+
+    fn point_velocity(&self, offset: glam::Vec3) -> glam::Vec3 {
+        self.velocity + self.angular_velocity.cross(offset)
+    }
+}
+
+/// Advances rigid bodies of a world in fixed steps.
+///   - The simulation is deterministic, the same world and elapsed times always give the same result.
+///   - Each pair of touching bodies is resolved by a single impulse at the average of its contacts,
+///     there is no friction, so bodies slide freely along each other and the ground.
+#[derive(Default)]
+pub struct RigidBodySimulation {
+    detector: CollisionDetector,
+    /// Elapsed time not simulated yet.
+    accumulator: f32,
+}
+
+impl RigidBodySimulation {
+    /// Time which would take more steps than this in a single update is dropped, so the simulation can keep up.
+    const MAX_STEPS_PER_UPDATE: u32 = 8;
+    /// Fraction of penetration removed by moving bodies apart each step.
+    const CORRECTION_FRACTION: f32 = 0.8;
+    /// Penetration left without positional correction, so resting bodies do not jitter.
+    const PENETRATION_SLOP: f32 = 0.005;
+    /// Bodies hitting slower than this do not bounce, so they come to rest.
+    const RESTING_SPEED: f32 = 0.2;
+
+    /// Simulates elapsed time in fixed steps, the remainder is kept for the next call, returns the number of steps.
+    pub fn advance(
+        &mut self,
+        settings: &PhysicsSettings,
+        world: &mut World,
+        geometry_pool: &GeometryPool,
+        elapsed: Duration,
+    ) -> u32 {
+        self.accumulator += elapsed.as_secs_f32();
+        let mut step_count = 0;
+        while self.accumulator >= settings.timestep {
+            if step_count == Self::MAX_STEPS_PER_UPDATE {
+                self.accumulator = 0.0;
+                break;
+            }
+            self.step(settings, world, geometry_pool);
+            self.accumulator -= settings.timestep;
+            step_count += 1;
+        }
+        step_count
+    }
+
+    /// Performs a single step of the simulation.
+    #[profiler::function]
+    pub fn step(
+        &mut self,
+        settings: &PhysicsSettings,
+        world: &mut World,
+        geometry_pool: &GeometryPool,
+    ) {
+        let dt = settings.timestep;
+        let mut bodies = Self::gather_bodies(world, geometry_pool);
+        for (_, body) in bodies.iter_mut().filter(|(_, body)| !body.is_static()) {
+            body.velocity += settings.gravity * dt;
+        }
+
+        // Collisions between bodies
+        let instances: Vec<(usize, GeometryID, Transform)> = bodies
+            .iter()
+            .enumerate()
+            .map(|(index, (geometry_id, body))| (index, *geometry_id, body.transform.clone()))
+            .collect();
+        let pairs = self
+            .detector
+            .contact_pairs_where(geometry_pool, &instances, |a, b| {
+                !(bodies[a].1.is_static() && bodies[b].1.is_static())
+            });
+        for pair in pairs {
+            let Some(contact) = average_contact(&pair.contacts) else {
+                continue;
+            };
+            let mut b = bodies[pair.b].1.clone();
+            resolve_contact(&mut bodies[pair.a].1, &mut b, &contact);
+            bodies[pair.b].1 = b;
+        }
+
+        // Collisions with the ground
+        for (geometry_id, body) in bodies.iter_mut().filter(|(_, body)| !body.is_static()) {
+            let contacts = self.detector.ground_contacts(
+                geometry_pool,
+                (*geometry_id, &body.transform),
+                settings.ground_height,
+            );
+            let Some(contact) = average_contact(&contacts) else {
+                continue;
+            };
+            let mut ground = Body::fixed(Transform::from_xyz(0.0, settings.ground_height, 0.0));
+            resolve_contact(body, &mut ground, &contact);
+        }
+
+        // Integrate and store results
+        for (_, body) in bodies.into_iter().filter(|(_, body)| !body.is_static()) {
+            let Some(entity) = body.entity else {
+                continue;
+            };
+            let Ok((transform, rigid_body)) =
+                world.query_one_mut::<(&mut Transform, &mut RigidBody)>(entity)
+            else {
+                continue;
+            };
+            *transform = Transform {
+                position: body.transform.position + body.velocity * dt,
+                rotation: (glam::Quat::from_scaled_axis(body.angular_velocity * dt)
+                    * body.transform.rotation)
+                    .normalize(),
+                ..body.transform
+            };
+            rigid_body.velocity = body.velocity;
+            rigid_body.angular_velocity = body.angular_velocity;
+        }
+    }
+
+    /// Collects all instances, inertia of rigid bodies is approximated by a solid box filling their AABB.
+    fn gather_bodies(world: &World, geometry_pool: &GeometryPool) -> Vec<(GeometryID, Body)> {
+        world
+            .query::<(hecs::Entity, &GeometryID, &Transform, Option<&RigidBody>)>()
+            .iter()
+            .filter_map(|(entity, geometry_id, transform, rigid_body)| {
+                let geometry = geometry_pool.get(*geometry_id)?;
+                let mut body = Body {
+                    entity: Some(entity),
+                    ..Body::fixed(transform.clone())
+                };
+                let Some(rigid_body) = rigid_body.filter(|rigid_body| rigid_body.mass > 0.0) else {
+                    return Some((*geometry_id, body));
+                };
+
+                let aabb = geometry.total_aabb();
+                let size = ((aabb.max - aabb.min) * transform.scale.abs())
+                    .max(glam::Vec3::splat(f32::EPSILON));
+                let squared = size * size;
+                let inertia = glam::Vec3::new(
+                    squared.y + squared.z,
+                    squared.x + squared.z,
+                    squared.x + squared.y,
+                ) * rigid_body.mass
+                    / 12.0;
+                let rotation = glam::Mat3::from_quat(transform.rotation);
+                body.inverse_mass = 1.0 / rigid_body.mass;
+                body.inverse_inertia =
+                    rotation * glam::Mat3::from_diagonal(inertia.recip()) * rotation.transpose();
+                body.velocity = rigid_body.velocity;
+                body.angular_velocity = rigid_body.angular_velocity;
+                body.restitution = rigid_body.restitution;
+                Some((*geometry_id, body))
+            })
+            .collect()
+    }
+}
+
+/// Merges contacts of a pair into one at their average position, pushing along their depth weighted normal.
+fn average_contact(contacts: &[Contact]) -> Option<Contact> {
+    if contacts.is_empty() {
+        return None;
+    }
+    let position = contacts
+        .iter()
+        .map(|contact| contact.position)
+        .sum::<glam::Vec3>()
+        / contacts.len() as f32;
+    let normal = contacts
+        .iter()
+        .map(|contact| contact.normal * contact.depth)
+        .sum::<glam::Vec3>()
+        .try_normalize()?;
+    let depth = contacts
+        .iter()
+        .map(|contact| contact.depth)
+        .fold(0.0, f32::max);
+    Some(Contact {
+        position,
+        normal,
+        depth,
+    })
+}
+
+/// Pushes bodies out of each other and applies an impulse, the contact normal points from `b` towards `a`.
+fn resolve_contact(a: &mut Body, b: &mut Body, contact: &Contact) {
+    let inverse_mass = a.inverse_mass + b.inverse_mass;
+    if inverse_mass == 0.0 {
+        return;
+    }
+    let normal = contact.normal;
+    let correction = normal
+        * (contact.depth - RigidBodySimulation::PENETRATION_SLOP).max(0.0)
+        * RigidBodySimulation::CORRECTION_FRACTION
+        / inverse_mass;
+    a.transform.position += correction * a.inverse_mass;
+    b.transform.position -= correction * b.inverse_mass;
+
+    let offset_a = contact.position - a.transform.position;
+    let offset_b = contact.position - b.transform.position;
+    let normal_speed = (a.point_velocity(offset_a) - b.point_velocity(offset_b)).dot(normal);
+    if normal_speed >= 0.0 {
+        return;
+    }
+    let restitution = if -normal_speed < RigidBodySimulation::RESTING_SPEED {
+        0.0
+    } else {
+        a.restitution.min(b.restitution)
+    };
+    let angular_a = (a.inverse_inertia * offset_a.cross(normal)).cross(offset_a);
+    let angular_b = (b.inverse_inertia * offset_b.cross(normal)).cross(offset_b);
+    let impulse = normal * -(1.0 + restitution) * normal_speed
+        / (inverse_mass + normal.dot(angular_a + angular_b));
+
+    a.velocity += impulse * a.inverse_mass;
+    a.angular_velocity += a.inverse_inertia * offset_a.cross(impulse);
+    b.velocity -= impulse * b.inverse_mass;
+    b.angular_velocity -= b.inverse_inertia * offset_b.cross(impulse);
+}
+
+#[derive(Default)]
+pub struct RigidBodyUpdater {
+    simulation: RigidBodySimulation,
+}
+
+impl UpdaterModule<Scene> for RigidBodyUpdater {
+    fn input(&mut self, _: &mut UpdateContext<Scene>) -> InputUpdateResult {
+        InputUpdateResult::default()
+    }
+
+    fn update(&mut self, context: &mut UpdateContext<Scene>) -> UpdateResultAction {
+        let scene = &mut *context.scene;
+        if !scene.physics.running {
+            return UpdateResultAction::None;
+        }
+        let step_count = self.simulation.advance(
+            &scene.physics,
+            &mut scene.world,
+            &scene.geometry_pool,
+            context.tick.delta,
+        );
+        if step_count > 0 {
+            UpdateResultAction::Redraw
+        } else {
+            UpdateResultAction::None
+        }
+    }
+
+    fn resize(&mut self, _: &mut ResizeContext<Scene>) -> UpdateResultAction {
+        UpdateResultAction::None
+    }
+
+    fn after_render(&mut self, _: &mut AfterRenderContext<Scene>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdf::geometry::{sphere_edit, Geometry, Operation};
+
+    const RADIUS: f32 = 0.5;
+
+    /// World with a single sphere rigid body above the default ground.
+    fn sphere_world(height: f32, rigid_body: RigidBody) -> (World, GeometryPool, hecs::Entity) {
+        let mut geometry_pool = GeometryPool::with_key();
+        let geometry_id = geometry_pool.insert(Geometry::new(0.01).with_edits(vec![sphere_edit(
+            RADIUS,
+            Operation::Add,
+            glam::Vec3::ZERO,
+        )]));
+        let mut world = World::new();
+        let ground_height = PhysicsSettings::default().ground_height;
+        let entity = world.spawn((
+            geometry_id,
+            Transform::from_xyz(0.0, ground_height + height, 0.0),
+            rigid_body,
+        ));
+        (world, geometry_pool, entity)
+    }
+
+    #[test]
+    fn sphere_rests_on_ground() {
+        let settings = PhysicsSettings::default();
+        let (mut world, geometry_pool, entity) = sphere_world(RADIUS, RigidBody::new(1.0));
+        let mut simulation = RigidBodySimulation::default();
+        for _ in 0..240 {
+            simulation.step(&settings, &mut world, &geometry_pool);
+        }
+
+        let transform = world.get::<&Transform>(entity).unwrap();
+        let rigid_body = world.get::<&RigidBody>(entity).unwrap();
+        let height = transform.position.y - settings.ground_height;
+        assert!((height - RADIUS).abs() < 0.02, "height {height}");
+        assert!(
+            rigid_body.velocity.length() < 0.1,
+            "velocity {}",
+            rigid_body.velocity
+        );
+    }
+
+    #[test]
+    fn elastic_sphere_bounces_back() {
+        let settings = PhysicsSettings::default();
+        let rigid_body = RigidBody {
+            velocity: glam::Vec3::new(0.0, -5.0, 0.0),
+            restitution: 1.0,
+            ..RigidBody::new(1.0)
+        };
+        let (mut world, geometry_pool, entity) = sphere_world(RADIUS + 1.0, rigid_body);
+        let impact_speed = (5.0f32.powi(2) + 2.0 * -settings.gravity.y * 1.0).sqrt();
+
+        let mut simulation = RigidBodySimulation::default();
+        let mut velocity = glam::Vec3::ZERO;
+        for _ in 0..120 {
+            simulation.step(&settings, &mut world, &geometry_pool);
+            velocity = world.get::<&RigidBody>(entity).unwrap().velocity;
+            if velocity.y > 0.0 {
+                break;
+            }
+        }
+        assert!(
+            (velocity.y - impact_speed).abs() < 0.1 * impact_speed,
+            "velocity {velocity}, impact speed {impact_speed}"
+        );
+        assert!(velocity.x.abs() < 0.01 && velocity.z.abs() < 0.01);
+    }
+
+    #[test]
+    fn simulation_is_deterministic() {
+        let settings = PhysicsSettings::default();
+        let positions: Vec<glam::Vec3> = (0..2)
+            .map(|_| {
+                let (mut world, geometry_pool, entity) = sphere_world(2.0, RigidBody::new(1.0));
+                let mut simulation = RigidBodySimulation::default();
+                simulation.advance(
+                    &settings,
+                    &mut world,
+                    &geometry_pool,
+                    Duration::from_millis(50),
+                );
+                for _ in 0..100 {
+                    simulation.step(&settings, &mut world, &geometry_pool);
+                }
+                let transform = world.get::<&Transform>(entity).unwrap();
+                transform.position
+            })
+            .collect();
+        assert_eq!(positions[0], positions[1]);
+    }
+}
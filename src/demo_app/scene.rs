@@ -5,7 +5,15 @@ use crate::{
     sdf::geometry::GeometryPool,
 };
 
-use super::{svo_sdf_brick::DisplayOptions, tmp_evaluator_config::TmpEvaluatorConfigProps};
+use super::{
+    collisions::CollisionState,
+    csg_bake::CsgBakeState,
+    history::SceneHistory,
+    rigid_body::PhysicsSettings,
+    svo_sdf_brick::{DisplayOptions, ShadowSettings},
+    svo_tools::SvoToolsState,
+    tmp_evaluator_config::TmpEvaluatorConfigProps,
+};
 
 #[derive(Debug, Default)]
 pub struct SceneCounters {
@@ -29,10 +37,16 @@ pub struct Scene {
     pub brick_level_break_size: f32,
     pub hit_distance: f32,
     pub max_step_count: u32,
+    pub shadows: ShadowSettings,
 
     // tmp?
     pub counters: SceneCounters,
     pub tmp_evaluator_config: TmpEvaluatorConfigProps,
+    pub svo_tools: SvoToolsState,
+    pub collisions: CollisionState,
+    pub physics: PhysicsSettings,
+    pub csg_bake: CsgBakeState,
+    pub history: SceneHistory,
 }
 
 impl SceneWithCamera for Scene {
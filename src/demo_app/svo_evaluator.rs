@@ -1,6 +1,6 @@
 ///! This is updater module which checks if there is a geometry requesting an SVO evaluation
 ///! And sends the svo of the geometry for evaluation
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use crate::demo_app::scene::Scene;
 use crate::framework::{
@@ -18,20 +18,35 @@ pub struct SvoEvaluatorUpdater {
 
 impl SvoEvaluatorUpdater {
     pub fn new(gpu: Arc<gpu::Context>) -> SvoEvaluatorUpdater {
-        Self {
-            evaluator: Evaluator::new(gpu),
-        }
+        let mut evaluator = Evaluator::new(gpu);
+        // Caching is opt-in by the svo_cache feature
+        evaluator.set_cache(cfg!(feature = "svo_cache").then(|| {
+            crate::sdf::evaluator::SvoCache::new(
+                std::env::temp_dir().join("sdf-edit-rs").join("svo_cache"),
+                crate::sdf::evaluator::SvoCache::DEFAULT_MAX_SIZE,
+            )
+        }));
+        Self { evaluator }
     }
 }
 
 impl UpdaterModule<Scene> for SvoEvaluatorUpdater {
     #[profiler::function]
     fn update(&mut self, context: &mut UpdateContext<Scene>) -> UpdateResultAction {
+        self.evaluator.set_time_budget(Duration::from_secs_f32(
+            context.scene.tmp_evaluator_config.evaluation_time_budget_ms / 1000.0,
+        ));
+
         let geometry_pool = &mut context.scene.geometry_pool;
 
         self.evaluator.evaluate_geometries(geometry_pool);
+        if !self.evaluator.is_evaluating() {
+            return UpdateResultAction::None;
+        }
+
+        // Newly evaluated levels are to be displayed
         self.evaluator.update_evaluated_geometries(geometry_pool);
-        UpdateResultAction::None
+        UpdateResultAction::Redraw
     }
 
     fn input(&mut self, _: &mut UpdateContext<Scene>) -> InputUpdateResult {
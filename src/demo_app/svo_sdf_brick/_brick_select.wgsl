@@ -53,7 +53,15 @@ struct BrickInstance {
     instance_id: u32,
 }
 @group(1) @binding(0) var<storage, read_write> brick_index_buffer: array<BrickInstance>;
-@group(1) @binding(1) var<storage, read_write> brick_count:        atomic<u32>;
+// Arguments of indexed indirect draw of the selected bricks, only instance count is written here
+struct DrawIndexedIndirectArgs {
+    index_count:    u32,
+    instance_count: atomic<u32>,
+    first_index:    u32,
+    base_vertex:    i32,
+    first_instance: u32,
+}
+@group(1) @binding(1) var<storage, read_write> draw_args:          DrawIndexedIndirectArgs;
 
 
 // Instance buffer where currently evaluated svo has one transform mer instance
@@ -118,81 +126,116 @@ fn bounding_cube_screen_size(center: vec3<f32>, side_size: f32) -> f32 {
     return -3.14159 * pc.camera_focal_length * pc.camera_focal_length * r2 * sqrt(abs((l2 - r2) / (r2 - z2))) / (r2 - z2);
 }
 
-// Compute a parent boundign sphere
-fn compute_parent_position(node_id: u32, node_position: vec3<f32>, node_diameter: f32) -> vec3<f32> {
-    var shift_vector: array<vec3<f32>, 8> = array<vec3<f32>, 8>(
-        vec3( 0.25,  0.25,  0.25),
-        vec3( 0.25,  0.25, -0.25),
-        vec3( 0.25, -0.25,  0.25),
-        vec3( 0.25, -0.25, -0.25),
-        vec3(-0.25, -0.25,  0.25),
-        vec3(-0.25, -0.25, -0.25),
-        vec3(-0.25,  0.25,  0.25),
-        vec3(-0.25,  0.25, -0.25),
-    );
-    
-    let child_index = node_id & 7u; // modulo 8 (in tile index)
-    return node_position + (shift_vector[child_index] * node_diameter);
+// Work queues of nodes to be visited
+// -----------------------------------------------------------------------------------
+
+struct WorkItem {
+    node_id: u32,
+    instance_id: u32,
 }
 
+// Header of a queue doubles as arguments of the indirect dispatch processing the queue
+//   - Overflow flag is set when a subtree does not fit into the queue, it is kept over level passes and read back.
+struct WorkQueueIn {
+    dispatch_x: u32,
+    dispatch_y: u32,
+    dispatch_z: u32,
+    count:      u32,
+    overflow:   u32,
+    _padding:   u32,
+    items:      array<WorkItem>,
+}
+struct WorkQueueOut {
+    dispatch_x: atomic<u32>,
+    dispatch_y: u32,
+    dispatch_z: u32,
+    count:      atomic<u32>,
+    overflow:   atomic<u32>,
+    _padding:   u32,
+    items:      array<WorkItem>,
+}
+@group(4) @binding(0) var<storage, read>       queue_in:  WorkQueueIn;
+@group(4) @binding(1) var<storage, read_write> queue_out: WorkQueueOut;
+
 const WORKGROUP_SIZE = 128u;
-@compute
-@workgroup_size(128u, 1, 1)
-fn main(in: ShaderInput) {
-    let node_id = in.workgroup_id.x * WORKGROUP_SIZE + in.local_invocation_id.x;
-    if (node_id >= node_count) {
+const MAX_WORKGROUP_COUNT = 65535u;
+
+// Children of the node are visited in the next level pass
+fn push_children(tile_index: u32, instance_id: u32) {
+    let index = atomicAdd(&queue_out.count, 8u);
+    if (index + 8u > arrayLength(&queue_out.items)) {
+        // Queue is full, subtree is skipped
+        atomicStore(&queue_out.overflow, 1u);
         return;
     }
+    for (var i = 0u; i < 8u; i = i + 1u) {
+        queue_out.items[index + i] = WorkItem((tile_index << 3u) + i, instance_id);
+    }
+    // Workgroups loop over items, so their count can be limited
+    let workgroup_count = (index + 8u + WORKGROUP_SIZE - 1u) / WORKGROUP_SIZE;
+    atomicMax(&queue_out.dispatch_x, min(workgroup_count, MAX_WORKGROUP_COUNT));
+}
+
+fn emit_brick(node_id: u32, instance_id: u32) {
+    let index = atomicAdd(&draw_args.instance_count, 1u);
+    if (index >= arrayLength(&brick_index_buffer)) {
+        // Buffer is full, brick is skipped and the count is taken back, so only stored bricks are drawn
+        atomicSub(&draw_args.instance_count, 1u);
+        return;
+    }
+    brick_index_buffer[index] = BrickInstance(node_id, instance_id);
+}
+
+// Decides whether a node of an instance is rendered or its children are visited instead
+//   - Nodes of a size close to the treshhold are rendered together with their children, so there are no gaps between levels.
+fn select_node(node_id: u32, node_vertex: vec4<f32>, has_brick: bool, is_subdivided: bool, tile_index: u32, instance_id: u32) {
+    let transform = instance_transforms[instance_id];
+    let position = apply_transform(node_vertex.xyz, transform);
+    let size = node_vertex.w * extract_scaling(transform);
     
-    let header = deconstruct_node_header(node_headers[node_id]);
-    if (header.has_brick != HEADER_HAS_BRICK_FLAG) {
-        return; // No brick
+    // Children are inside of the bounding sphere of the node, so they are culled as well
+    if (!in_frustum(position, size)) {
+        return;
     }
     
-    let vertex = node_vertices[node_id];
     let treshhold = 0.1 * pc.level_break_size;
+    let projected_size = bounding_cube_screen_size(position, size);
     
-    // compute size on screen of current node
-    let me_position = (vertex.xyz * pc.domain.w) + pc.domain.xyz;
-    let me_size = vertex.w * pc.domain.w;
-    
-    // compute size on screen of parent node
-    let parent_position = compute_parent_position(node_id, me_position, me_size);
-    let parent_size = me_size * 2.0;
-    
-    for (var i = 0u; i < instance_count; i = i + 1u) {
-        let transform = instance_transforms[i];
-        let scaling = extract_scaling(transform);
-        
-        let me_position_transformed = apply_transform(me_position, transform);
-        let me_size_scaled = me_size * scaling;
-        
-        if (!in_frustum(me_position_transformed, me_size_scaled)) {
-            continue;
-        }
-        
-        let projected_me_size = bounding_cube_screen_size(me_position_transformed, me_size_scaled);
-        
-        let parent_position_transformed = apply_transform(parent_position, transform);
-        let parent_size_scaled = parent_size * scaling;
-        let projected_parent_size = bounding_cube_screen_size(parent_position_transformed, parent_size_scaled);
+    // Non positive size means the node cannot be projected (camera is too close), only its children might be rendered
+    if (is_subdivided && (projected_size <= 0.0 || projected_size >= treshhold)) {
+        push_children(tile_index, instance_id);
+    }
+    if (has_brick && projected_size > 0.0 && (projected_size < (1.01 * treshhold) || !is_subdivided)) {
+        emit_brick(node_id, instance_id);
+    }
+}
+
+// Visits root node of each instance, root is not in the node tree, its children are the first tile
+@compute
+@workgroup_size(128u, 1, 1)
+fn select_root(in: ShaderInput) {
+    let instance_id = in.workgroup_id.x * WORKGROUP_SIZE + in.local_invocation_id.x;
+    if (instance_id >= instance_count) {
+        return;
+    }
+    select_node(ROOT_ID, pc.domain, true, pc.node_count > 0u, 0u, instance_id);
+}
+
+// Visits nodes of one level pushed into the queue by the previous pass
+@compute
+@workgroup_size(128u, 1, 1)
+fn select_nodes(in: ShaderInput) {
+    let count = min(queue_in.count, arrayLength(&queue_in.items));
+    let stride = in.num_workgroups.x * WORKGROUP_SIZE;
+    for (var index = in.workgroup_id.x * WORKGROUP_SIZE + in.local_invocation_id.x; index < count; index = index + stride) {
+        let item = queue_in.items[index];
+        let header = deconstruct_node_header(node_headers[item.node_id]);
         
-        if (projected_parent_size <=0.0 || projected_me_size <= 0.0) {
-            continue; // Not possible
-        }
-        if (projected_parent_size < treshhold) {
-            // only root node is supposed to be rendered, but it is not in the node tree, hence add it manually
-            if (node_id == 0u) {
-                let index = atomicAdd(&brick_count, 1u);
-                brick_index_buffer[index] = BrickInstance(ROOT_ID, i);
-            }
-            continue; // Parent of this node will be rendered instead
-        }
-        if (projected_me_size >= (1.01 * treshhold) && header.is_subdivided == HEADER_SUBDIVIDED_FLAG) {
-            continue; // Node is too big to be rendered and has children which will be rendered instead
-        }
+        // Children are considered only when they are already evaluated (svo might be still being refined)
+        let is_subdivided = header.is_subdivided == HEADER_SUBDIVIDED_FLAG && (header.tile_index << 3u) < pc.node_count;
         
-        let index = atomicAdd(&brick_count, 1u);
-        brick_index_buffer[index] = BrickInstance(node_id, i);
+        let vertex = node_vertices[item.node_id];
+        let node_vertex = vec4((vertex.xyz * pc.domain.w) + pc.domain.xyz, vertex.w * pc.domain.w);
+        select_node(item.node_id, node_vertex, header.has_brick == HEADER_HAS_BRICK_FLAG, is_subdivided, header.tile_index, item.instance_id);
     }
 }
@@ -17,6 +17,7 @@ const SHOW_SOLID      = 0x01u; // 0b00000001;
 const SHOW_NORMALS    = 0x02u; // 0b00000010;
 const SHOW_STEP_COUNT = 0x04u; // 0b00000100;
 const SHOW_DEPTH      = 0x08u; // 0b00001000;
+const SHOW_SHADOWS    = 0x10u; // 0b00010000;
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
@@ -46,6 +47,7 @@ struct InstanceInput {
 @group(1) @binding(3) var                color_atlas_sampler:    sampler;
 @group(1) @binding(4) var<storage, read> brick_count:            atomic<u32>; // Number of bricks in brick texture, use to atomically add new bricks
 @group(1) @binding(5) var<uniform>       brick_pool_side_size:   u32;         // Number of bricks in one side of the brick atlas texture
+@group(1) @binding(6) var                distance_ranges:        texture_3d<f32>; // (offset, scale) of distances of each brick at its brick coordinates
 
 
 // Instance buffer where currently evaluated svo has one transform mer instance
@@ -54,6 +56,20 @@ struct InstanceInput {
 @group(2) @binding(1) var<storage, read> instance_inverse_transforms: array<mat4x4<f32>>;
 @group(2) @binding(2) var<uniform>       instance_count:              u32;
 
+
+// Scene shadow volume: coarse distances of all instances
+// -----------------------------------------------------------------------------------
+struct Shadows {
+    volume_min:     vec3<f32>,
+    voxel_size:     f32,
+    resolution:     u32,
+    margin:         f32, // distances further than this from all instances are not stored
+    penumbra:       f32,
+    max_step_count: u32,
+}
+@group(3) @binding(0) var<storage, read> shadow_distances: array<u32>; // ordered unsigned integers
+@group(3) @binding(1) var<uniform>       shadows:          Shadows;
+
 const ROOT_ID = 0xFFFFFFFFu;
 
 const HEADER_TILE_INDEX_MASK = 0x3FFFFFFFu;
@@ -118,15 +134,20 @@ struct VertexOutput {
     // tmp
     @location(11) @interpolate(flat) subdivided: u32,
     // end tmp
+    
+    @location(12) @interpolate(flat) voxel_size: f32,
+    
+    // (offset, scale) decoding distances stored in the brick
+    @location(13) @interpolate(flat) distance_range: vec2<f32>,
 };
 
-fn calculate_atlas_lookup_shift(index: u32) -> vec3<f32> {
+fn node_brick_coords(index: u32) -> vec3<u32> {
     let payload = node_payload[index];
-    let x = (payload >> 20u) & 0x3FFu;
-    let y = (payload >> 10u) & 0x3FFu;
-    let z = payload & 0x3FFu;
-    let brick_coord = vec3(f32(x), f32(y), f32(z));
-    return (pc.distance_atlas_stride * brick_coord) + vec3(pc.brick_voxel_size);
+    return vec3((payload >> 20u) & 0x3FFu, (payload >> 10u) & 0x3FFu, payload & 0x3FFu);
+}
+
+fn calculate_atlas_lookup_shift(brick_coords: vec3<u32>) -> vec3<f32> {
+    return (pc.distance_atlas_stride * vec3<f32>(brick_coords)) + vec3(pc.brick_voxel_size);
 }
 
 fn bounding_cube_transform(bc: vec4<f32>, position: vec3<f32>) -> vec3<f32> {
@@ -139,7 +160,7 @@ fn vs_main(vertex_input: VertexInput, instance_input: InstanceInput) -> VertexOu
     
     // values for root node display
     var node_vertex = pc.domain;
-    out.brick_lookup_shift = vec3(pc.brick_voxel_size);
+    var brick_coords = vec3(0u);
     
     // Set values for non-root nodes
     // TODO maybe make a directive in preprocessor and make two versions of the shader
@@ -149,8 +170,10 @@ fn vs_main(vertex_input: VertexInput, instance_input: InstanceInput) -> VertexOu
             (node_vertex.xyz * pc.domain.w) + pc.domain.xyz,
             node_vertex.w * pc.domain.w,
         );
-        out.brick_lookup_shift = calculate_atlas_lookup_shift(instance_input.node_index);
+        brick_coords = node_brick_coords(instance_input.node_index);
     }
+    out.brick_lookup_shift = calculate_atlas_lookup_shift(brick_coords);
+    out.distance_range = textureLoad(distance_ranges, vec3<i32>(brick_coords), 0).xy;
     
     let brick_shift = node_vertex.www * 0.5 - node_vertex.xyz;
     let transform = instance_transforms[instance_input.instance_id];
@@ -178,6 +201,7 @@ fn vs_main(vertex_input: VertexInput, instance_input: InstanceInput) -> VertexOu
     out.local_to_brick_transform_4 = local_to_brick_transform[3];
     
     out.subdivided = 0u;
+    out.voxel_size = node_vertex.w * 0.125; // 8 voxels in brick
     
     // tmp
     let header_data = deconstruct_node_header(node_headers[instance_input.node_index]);
@@ -220,11 +244,13 @@ fn get_distance_to_end_of_brick(position: vec3<f32>, direction: vec3<f32>) -> f3
 }
 
 fn sample_volume_distance(in: VertexOutput, act_position: vec3<f32>,) -> f32 {
-    return textureSample(
+    let distance = textureSample(
         distance_atlas,
         distance_atlas_sampler,
         act_position * pc.brick_scale + in.brick_lookup_shift
     ).r;
+    
+    return distance * in.distance_range.y + in.distance_range.x;
 }
 
 fn sample_volume_color(in: VertexOutput, act_position: vec3<f32>) -> vec4<f32> {
@@ -249,9 +275,11 @@ fn get_normal(in: VertexOutput, act_position: vec3<f32>, current_distance: f32)
     return normalize(n);
 }
 
-// Computes basic Phong lighting
-fn get_hit_color(pos: vec3<f32>, normal: vec3<f32>, objectColor: vec3<f32>, to_local_matrix: mat4x4<f32>) -> vec4<f32> {
-    let lightPos = (to_local_matrix * vec4(100.0, 100.0, 100.0, 1.0)).xyz;
+const LIGHT_POSITION = vec3<f32>(100.0, 100.0, 100.0);
+
+// Computes basic Phong lighting, shadow scales light which is not ambient
+fn get_hit_color(pos: vec3<f32>, normal: vec3<f32>, objectColor: vec3<f32>, to_local_matrix: mat4x4<f32>, shadow: f32) -> vec4<f32> {
+    let lightPos = (to_local_matrix * vec4(LIGHT_POSITION, 1.0)).xyz;
     let local_camera_pos = (to_local_matrix * pc.camera_position).xyz;
     let lightColor = vec3(1.0, 1.0, 1.0);
     let ambient = vec3(1.0, 1.0, 1.0) * 0.25;
@@ -268,10 +296,126 @@ fn get_hit_color(pos: vec3<f32>, normal: vec3<f32>, objectColor: vec3<f32>, to_l
     let spec = pow(max(dot(viewDir, reflectDir), 0.0), 32.0);
     let specular = specularStrength * spec * lightColor;
     
-    let result = (ambient + diffuse + specular) * objectColor;
+    let result = (ambient + (diffuse + specular) * shadow) * objectColor;
     return vec4(result, 1.0);
 }
 
+// Inverse of ordering floats as unsigned integers
+fn decode_ordered(value: u32) -> f32 {
+    if ((value & 0x80000000u) != 0u) {
+        return bitcast<f32>(value & 0x7FFFFFFFu);
+    }
+    return bitcast<f32>(~value);
+}
+
+fn shadow_voxel_distance(voxel: vec3<u32>) -> f32 {
+    let index = (voxel.z * shadows.resolution + voxel.y) * shadows.resolution + voxel.x;
+    return decode_ordered(shadow_distances[index]);
+}
+
+// Trilinearly interpolated distance of the scene volume, it is never more than the margin
+fn scene_distance(position: vec3<f32>) -> f32 {
+    let last_voxel = vec3(f32(shadows.resolution - 1u));
+    let coords = clamp((position - shadows.volume_min) / shadows.voxel_size - 0.5, vec3(0.0), last_voxel);
+    let low = vec3<u32>(floor(coords));
+    let high = min(low + 1u, vec3(shadows.resolution - 1u));
+    let f = fract(coords);
+    let x00 = mix(shadow_voxel_distance(low),                          shadow_voxel_distance(vec3(high.x, low.y, low.z)),   f.x);
+    let x10 = mix(shadow_voxel_distance(vec3(low.x, high.y, low.z)),   shadow_voxel_distance(vec3(high.x, high.y, low.z)),  f.x);
+    let x01 = mix(shadow_voxel_distance(vec3(low.x, low.y, high.z)),   shadow_voxel_distance(vec3(high.x, low.y, high.z)),  f.x);
+    let x11 = mix(shadow_voxel_distance(vec3(low.x, high.y, high.z)),  shadow_voxel_distance(high),                         f.x);
+    let distance = mix(mix(x00, x10, f.y), mix(x01, x11, f.y), f.z);
+    return min(distance, shadows.margin);
+}
+
+// Soft shadow factor, small distances close to the ray darken the penumbra
+// see: https://iquilezles.org/articles/rmshadows/
+fn penumbra_factor(distance: f32, travelled: f32) -> f32 {
+    return distance / max(shadows.penumbra * travelled, 0.000001);
+}
+
+struct ShadowResult {
+    shadow:    f32,
+    travelled: f32,
+}
+
+// Marches from a position towards the light through the brick, it gives shadows of details smaller than the scene volume voxels
+//   - Distances and positions are in brick space, where the brick spans size of its node
+fn brick_shadow(in: VertexOutput, position: vec3<f32>, direction: vec3<f32>) -> ShadowResult {
+    let node_size = in.voxel_size * 8.0;
+    let end = get_distance_to_end_of_brick(position, direction);
+    var result = ShadowResult(1.0, 0.125); // start one voxel away from the surface
+    for (var step = 0u; step < shadows.max_step_count && result.travelled < end; step++) {
+        let lookup = (position + direction * result.travelled) * pc.brick_scale + in.brick_lookup_shift;
+        let value = textureSampleLevel(distance_atlas, distance_atlas_sampler, lookup, 0.0).r;
+        let distance = (value * in.distance_range.y + in.distance_range.x) / node_size;
+        if (distance < pc.hit_distance / node_size) {
+            result.shadow = 0.0;
+            return result;
+        }
+        result.shadow = min(result.shadow, penumbra_factor(distance, result.travelled));
+        result.travelled += max(distance, 0.01);
+    }
+    result.travelled = min(result.travelled, end);
+    return result;
+}
+
+// Marches from a world position towards the light through the scene volume, it gives shadows of other instances
+fn scene_shadow(position: vec3<f32>, direction: vec3<f32>, start: f32, end: f32) -> f32 {
+    let volume_max = shadows.volume_min + vec3(f32(shadows.resolution) * shadows.voxel_size);
+    var shadow = 1.0;
+    var travelled = start;
+    for (var step = 0u; step < shadows.max_step_count && travelled < end; step++) {
+        let sample_position = position + direction * travelled;
+        
+        // Nothing casts shadows outside of the volume
+        if (any(sample_position < shadows.volume_min) || any(sample_position > volume_max)) {
+            break;
+        }
+        
+        let distance = scene_distance(sample_position);
+        if (distance < shadows.voxel_size * 0.05) {
+            return 0.0;
+        }
+        shadow = min(shadow, penumbra_factor(distance, travelled));
+        travelled += clamp(distance, shadows.voxel_size * 0.5, shadows.margin);
+    }
+    return shadow;
+}
+
+// Soft shadow of the light at a position on the surface of a brick
+fn hit_shadow(in: VertexOutput, position: vec3<f32>, normal: vec3<f32>, brick_to_local_transform: mat4x4<f32>) -> f32 {
+    if ((pc.show_flags & SHOW_SHADOWS) == 0u) {
+        return 1.0;
+    }
+    
+    let light_position = (brick_to_local_transform * vec4(LIGHT_POSITION, 1.0)).xyz;
+    let brick_direction = normalize(light_position - position);
+    let brick = brick_shadow(in, position, brick_direction);
+    if (brick.shadow <= 0.0) {
+        return 0.0;
+    }
+    
+    let local_to_brick_transform = mat4x4(
+        in.local_to_brick_transform_1,
+        in.local_to_brick_transform_2,
+        in.local_to_brick_transform_3,
+        in.local_to_brick_transform_4,
+    );
+    let world_position = (local_to_brick_transform * vec4(position, 1.0)).xyz;
+    let world_normal = normalize((local_to_brick_transform * vec4(normal, 0.0)).xyz);
+    let to_light = LIGHT_POSITION - world_position;
+    let direction = normalize(to_light);
+    
+    // Scene volume contains this instance too, its coarse surface is skipped by moving away from it
+    let brick_travelled = length((local_to_brick_transform * vec4(brick_direction * brick.travelled, 0.0)).xyz);
+    let start = max(brick_travelled, shadows.voxel_size * 2.0);
+    let origin = world_position + world_normal * shadows.voxel_size;
+    let scene = scene_shadow(origin, direction, start, length(to_light));
+    
+    return clamp(min(brick.shadow, scene), 0.0, 1.0);
+}
+
 struct HitResult {
     hit:          bool,
     color:        vec4<f32>,
@@ -324,7 +468,8 @@ fn ray_march(in: VertexOutput, origin: vec3<f32>, brick_to_local_transform: mat4
                 act_position,
                 hit.normal,
                 sample_volume_color(in, act_position).rgb,
-                brick_to_local_transform
+                brick_to_local_transform,
+                hit_shadow(in, act_position, hit.normal, brick_to_local_transform)
             );
             break;
         }
@@ -0,0 +1,125 @@
+// Builds a coarse signed distance volume of the whole scene from bricks of SVO instances.
+
+// Scene shadow volume
+// -----------------------------------------------------------------------------------
+
+struct Shadows {
+    volume_min:     vec3<f32>,
+    voxel_size:     f32,
+    resolution:     u32,
+    margin:         f32, // instances write distances only up to this distance from their bounding box
+    penumbra:       f32,
+    max_step_count: u32,
+}
+
+// Distances are stored as ordered unsigned integers, so instances can be combined by atomic minimum
+@group(0) @binding(0) var<storage, read_write> distances: array<atomic<u32>>;
+@group(0) @binding(1) var<uniform>             shadows:   Shadows;
+
+
+// SVO: Node pool Read-only bind group
+// -----------------------------------------------------------------------------------
+
+@group(1) @binding(0) var<storage, read> node_count:         u32;
+@group(1) @binding(1) var<storage, read> node_headers:       array<u32>;
+@group(1) @binding(2) var<storage, read> node_payload:       array<u32>;
+@group(1) @binding(3) var<storage, read> node_vertices:      array<vec4<f32>>;
+@group(1) @binding(4) var<uniform>       node_pool_capacity: u32;
+
+
+// SVO: Brick pool Read-only bind group
+// -----------------------------------------------------------------------------------
+
+@group(2) @binding(0) var                distance_atlas:         texture_3d<f32>;
+@group(2) @binding(1) var                distance_atlas_sampler: sampler;
+@group(2) @binding(2) var                color_atlas:            texture_3d<f32>;
+@group(2) @binding(3) var                color_atlas_sampler:    sampler;
+@group(2) @binding(4) var<storage, read> brick_count:            u32;
+@group(2) @binding(5) var<uniform>       brick_pool_side_size:   u32;
+@group(2) @binding(6) var                distance_ranges:        texture_3d<f32>;
+
+
+// Instances of the SVO written into the volume
+// -----------------------------------------------------------------------------------
+
+struct Instance {
+    to_local:       mat4x4<f32>, // inverse transform of the instance
+    voxel_min:      vec3<u32>,   // first voxel of the box written by the instance
+    distance_scale: f32,         // scale of local distances into world distances
+    voxel_count:    vec3<u32>,   // number of voxels of the box written by the instance
+    _padding:       u32,
+}
+
+@group(3) @binding(0) var<storage, read> instances: array<Instance>;
+
+struct PushConstants {
+    domain:                vec4<f32>, // bounding cube of the SVO
+    atlas_scale:           f32,
+    atlas_stride:          f32,
+    atlas_voxel_size:      f32,
+    first_instance:        u32,
+    instance_count:        u32,
+}
+var<push_constant> pc: PushConstants;
+
+
+const WORKGROUP_SIZE = 64u;
+
+// Maps floats to unsigned integers of the same order
+fn encode_ordered(value: f32) -> u32 {
+    let bits = bitcast<u32>(value);
+    if ((bits & 0x80000000u) != 0u) {
+        return ~bits;
+    }
+    return bits | 0x80000000u;
+}
+
+// Sampling of the SVO whose instances are written, see `_svo_sampling.wgsl`
+fn instance_svo() -> SvoSampling {
+    return SvoSampling(
+        pc.domain,
+        pc.atlas_scale,
+        pc.atlas_stride,
+        pc.atlas_voxel_size,
+    );
+}
+
+fn voxel_index(voxel: vec3<u32>) -> u32 {
+    return (voxel.z * shadows.resolution + voxel.y) * shadows.resolution + voxel.x;
+}
+
+// Resets all voxels to the empty distance, voxels which are not close to any instance keep it
+@compute @workgroup_size(64, 1, 1)
+fn clear(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if (global_id.x >= arrayLength(&distances)) {
+        return;
+    }
+    atomicStore(&distances[global_id.x], encode_ordered(EMPTY_DISTANCE));
+}
+
+// Writes distances of instances into voxels of their boxes, one row of workgroups per instance
+@compute @workgroup_size(64, 1, 1)
+fn splat(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    if (workgroup_id.y >= pc.instance_count) {
+        return;
+    }
+    let instance = instances[pc.first_instance + workgroup_id.y];
+    let count = instance.voxel_count;
+    if (global_id.x >= count.x * count.y * count.z) {
+        return;
+    }
+
+    let box_voxel = vec3(
+        global_id.x % count.x,
+        (global_id.x / count.x) % count.y,
+        global_id.x / (count.x * count.y),
+    );
+    let voxel = instance.voxel_min + box_voxel;
+    let position = shadows.volume_min + (vec3<f32>(voxel) + vec3(0.5)) * shadows.voxel_size;
+    atomicMin(&distances[voxel_index(voxel)], encode_ordered(
+        distance_at(instance_svo(), instance.to_local, instance.distance_scale, position)
+    ));
+}
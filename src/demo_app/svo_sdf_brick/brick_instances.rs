@@ -1,4 +1,9 @@
-use crate::{framework::gpu, warn};
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc,
+};
+
+use crate::{demo_app::cube::CUBE_INDICES_TRIANGLE_STRIP, framework::gpu, warn};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -9,11 +14,111 @@ pub struct BrickInstance {
     instance_id: u32,
 }
 
+/// Arguments of an indexed indirect draw call, the brick select pass counts selected bricks into `instance_count`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawIndexedIndirectArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+impl DrawIndexedIndirectArgs {
+    /// Draws no instances of the brick cube.
+    fn empty() -> Self {
+        Self {
+            index_count: CUBE_INDICES_TRIANGLE_STRIP.len() as u32,
+            instance_count: 0,
+            first_index: 0,
+            base_vertex: 0,
+            first_instance: 0,
+        }
+    }
+}
+
+/// Staging buffer which is mapped without waiting for it, so small results can be read back without stalling the GPU.
+#[derive(Debug)]
+pub(super) struct Readback {
+    label: &'static str,
+    staging_buffer: wgpu::Buffer,
+    /// One of the states below, shared with the map callback so a failed mapping releases the buffer too.
+    state: Arc<AtomicU8>,
+}
+
+impl Readback {
+    /// The staging buffer is free for the next copy.
+    const IDLE: u8 = 0;
+    /// A copy was issued and its mapping did not finish yet.
+    const PENDING: u8 = 1;
+    /// The map callback succeeded and the staging buffer can be read.
+    const MAPPED: u8 = 2;
+
+    pub(super) fn new(gpu: &gpu::Context, label: &'static str, size: u64) -> Self {
+        Self {
+            label,
+            staging_buffer: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            state: Arc::new(AtomicU8::new(Self::IDLE)),
+        }
+    }
+
+    /// Returns the staging buffer to copy into, unless the previous copy is still being read back.
+    ///   - `map` has to be called after the encoder with the copy is submitted.
+    pub(super) fn start(&self) -> Option<&wgpu::Buffer> {
+        if self.state.load(Ordering::Acquire) != Self::IDLE {
+            return None;
+        }
+        self.state.store(Self::PENDING, Ordering::Release);
+        Some(&self.staging_buffer)
+    }
+
+    /// Requests mapping of the staging buffer if a copy was started, the device has to be polled for it to finish.
+    pub(super) fn map(&self) {
+        if self.state.load(Ordering::Acquire) != Self::PENDING {
+            return;
+        }
+        let _label = self.label;
+        let state = self.state.clone();
+        self.staging_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| match result {
+                Ok(()) => state.store(Self::MAPPED, Ordering::Release),
+                Err(_err) => {
+                    warn!("Cannot map {}: {:?}", _label, _err);
+                    // Nothing is mapped, so the next copy can be issued right away
+                    state.store(Self::IDLE, Ordering::Release);
+                }
+            });
+    }
+
+    /// Returns content of the staging buffer if it was mapped and releases it for the next copy.
+    pub(super) fn read<T: bytemuck::Pod>(&self) -> Option<Vec<T>> {
+        if self.state.load(Ordering::Acquire) != Self::MAPPED {
+            return None;
+        }
+        let data = {
+            let view = self.staging_buffer.slice(..).get_mapped_range();
+            bytemuck::cast_slice(&view).to_vec()
+        };
+        self.staging_buffer.unmap();
+        self.state.store(Self::IDLE, Ordering::Release);
+        Some(data)
+    }
+}
+
 #[derive(Debug)]
 pub struct BrickInstances {
+    /// Number of selected bricks read back from GPU, it lags a few frames behind the rendered bricks.
     pub count: Option<u32>,
     pub buffer: gpu::Buffer<BrickInstance>,
-    pub count_buffer: gpu::Buffer<u32>,
+    pub draw_args_buffer: gpu::Buffer<DrawIndexedIndirectArgs>,
+    count_readback: Readback,
 }
 
 impl BrickInstances {
@@ -29,21 +134,28 @@ impl BrickInstances {
                     | wgpu::BufferUsages::STORAGE,
             )
             .with_grow_rate(1.5),
-            count_buffer: gpu::Buffer::new(
+            draw_args_buffer: gpu::Buffer::new(
                 &gpu,
-                Some("Brick instances counter buffer"),
-                &[0],
+                Some("Brick instances draw args buffer"),
+                &[DrawIndexedIndirectArgs::empty()],
                 wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::INDIRECT
                     | wgpu::BufferUsages::COPY_DST
-                    | wgpu::BufferUsages::MAP_READ, // TODO: this is needed for load_count to work. Will not be needed, when we will have indirect draws.
+                    | wgpu::BufferUsages::COPY_SRC,
+            ),
+            count_readback: Readback::new(
+                gpu,
+                "Brick instances count readback buffer",
+                std::mem::size_of::<DrawIndexedIndirectArgs>() as u64,
             ),
         }
     }
 
+    /// Resets draw args, the last read back count is kept until a new one arrives.
     #[profiler::function]
     pub fn clear(&mut self, gpu: &gpu::Context) {
-        self.count = None;
-        self.count_buffer.queue_update(gpu, &[0]);
+        self.draw_args_buffer
+            .queue_update(gpu, &[DrawIndexedIndirectArgs::empty()]);
     }
 
     /// Returns true if any of the buffers was recreated.
@@ -53,6 +165,34 @@ impl BrickInstances {
         self.buffer.resize(gpu, capacity)
     }
 
+    /// Copies draw args into the staging buffer, unless the previous copy is still being read back.
+    ///   - Returns true if the copy was encoded, `map_count_readback` has to be called after the encoder is submitted.
+    pub fn queue_count_readback(&mut self, encoder: &mut wgpu::CommandEncoder) -> bool {
+        let Some(staging_buffer) = self.count_readback.start() else {
+            return false;
+        };
+        encoder.copy_buffer_to_buffer(
+            &self.draw_args_buffer.buffer,
+            0,
+            staging_buffer,
+            0,
+            std::mem::size_of::<DrawIndexedIndirectArgs>() as u64,
+        );
+        true
+    }
+
+    /// Requests mapping of the staging buffer, the device has to be polled for it to finish.
+    pub fn map_count_readback(&self) {
+        self.count_readback.map();
+    }
+
+    /// Updates the count if the staging buffer was mapped and releases it for the next copy.
+    pub fn read_count(&mut self) {
+        if let Some(args) = self.count_readback.read::<DrawIndexedIndirectArgs>() {
+            self.count = Some(args[0].instance_count);
+        }
+    }
+
     /// Returns existing bind group or creates a new one with given layout.
     #[profiler::function]
     pub fn create_bind_group(
@@ -81,10 +221,10 @@ impl BrickInstances {
                         },
                     }),
                 },
-                // Buffer with brick instances count
+                // Buffer with draw args counting brick instances
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: self.count_buffer.buffer.as_entire_binding(),
+                    resource: self.draw_args_buffer.buffer.as_entire_binding(),
                 },
             ],
         })
@@ -112,7 +252,7 @@ impl BrickInstances {
                         },
                         count: None,
                     },
-                    // Buffer with brick instances count
+                    // Buffer with draw args counting brick instances
                     wgpu::BindGroupLayoutEntry {
                         binding: 1,
                         visibility,
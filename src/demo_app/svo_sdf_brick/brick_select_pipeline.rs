@@ -1,6 +1,6 @@
 use std::borrow::Cow;
 
-use super::{BrickInstances, GPUGeometryTransforms};
+use super::{BrickInstances, GPUGeometryTransforms, Readback};
 
 use crate::{
     framework::{gpu, math, renderer::RenderContext},
@@ -21,13 +21,20 @@ struct PushConstants {
     _padding: [u32; 3], // TODO: level select distance
 }
 
+/// Selects bricks to be rendered by traversing SVO from the root level by level.
+///   - Each level pass decides for every visited node whether its brick is rendered or its children are
+///     pushed into a work queue, which the next level pass processes with an indirect dispatch.
+///   - Culled and coarse subtrees are never visited.
+///   - Subtrees which do not fit into a full queue are skipped, the overflow is read back and logged.
 #[derive(Debug)]
 pub struct SvoBrickSelectPipeline {
-    pipeline: wgpu::ComputePipeline,
+    root_pipeline: wgpu::ComputePipeline,
+    node_pipeline: wgpu::ComputePipeline,
     node_pool_bind_group_layout: wgpu::BindGroupLayout,
     brick_instances_bind_group_layout: wgpu::BindGroupLayout,
     geometry_instances_transforms_bind_group_layout: wgpu::BindGroupLayout,
     frustum_uniform: FrustumUniform,
+    work_queues: WorkQueues,
 }
 
 impl SvoBrickSelectPipeline {
@@ -53,48 +60,65 @@ impl SvoBrickSelectPipeline {
 
         let frustum_uniform = FrustumUniform::new(&context.gpu, wgpu::ShaderStages::COMPUTE);
 
-        let pipeline = {
-            profiler::scope!("Create brick select pipeline");
-            context
-                .gpu
-                .device
-                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                    label: Some("Brick select compute pipeline"),
-                    entry_point: "main",
-                    layout: Some(&context.gpu.device.create_pipeline_layout(
-                        &wgpu::PipelineLayoutDescriptor {
-                            label: Some("Cube Outline Pipeline Layout"),
-                            // define buffers layout of the svo
-                            bind_group_layouts: &[
-                                &node_pool_bind_group_layout,
-                                &brick_instances_bind_group_layout,
-                                &geometry_instances_transforms_bind_group_layout,
-                                &frustum_uniform.bind_group_layout,
-                            ],
-                            // set camera transform matrix as shader push constant
-                            push_constant_ranges: &[wgpu::PushConstantRange {
-                                stages: wgpu::ShaderStages::COMPUTE,
-                                range: 0..std::mem::size_of::<PushConstants>() as u32,
-                            }],
-                        },
-                    )),
-                    module: &context.gpu.device.create_shader_module(
-                        wgpu::ShaderModuleDescriptor {
-                            label: Some("Brick Select Compute Shader Module"),
-                            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
-                                "_brick_select.wgsl"
-                            ))),
-                        },
-                    ),
-                })
+        let work_queues = WorkQueues::new(&context.gpu, wgpu::ShaderStages::COMPUTE);
+
+        // Bind groups below use 10 storage buffers in the compute stage, more than the default limit of 8,
+        // the device is created with `gpu::Context::MAX_STORAGE_BUFFERS_PER_SHADER_STAGE` to allow it
+        let layout = context
+            .gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Brick select pipeline layout"),
+                // define buffers layout of the svo
+                bind_group_layouts: &[
+                    &node_pool_bind_group_layout,
+                    &brick_instances_bind_group_layout,
+                    &geometry_instances_transforms_bind_group_layout,
+                    &frustum_uniform.bind_group_layout,
+                    &work_queues.bind_group_layout,
+                ],
+                // set camera transform matrix as shader push constant
+                push_constant_ranges: &[wgpu::PushConstantRange {
+                    stages: wgpu::ShaderStages::COMPUTE,
+                    range: 0..std::mem::size_of::<PushConstants>() as u32,
+                }],
+            });
+
+        let module = context
+            .gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Brick Select Compute Shader Module"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("_brick_select.wgsl"))),
+            });
+
+        let (root_pipeline, node_pipeline) = {
+            profiler::scope!("Create brick select pipelines");
+            let create_pipeline = |label, entry_point| {
+                context
+                    .gpu
+                    .device
+                    .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                        label: Some(label),
+                        entry_point,
+                        layout: Some(&layout),
+                        module: &module,
+                    })
+            };
+            (
+                create_pipeline("Brick select root compute pipeline", "select_root"),
+                create_pipeline("Brick select nodes compute pipeline", "select_nodes"),
+            )
         };
 
         Self {
-            pipeline,
+            root_pipeline,
+            node_pipeline,
             node_pool_bind_group_layout,
             brick_instances_bind_group_layout,
             geometry_instances_transforms_bind_group_layout,
             frustum_uniform,
+            work_queues,
         }
     }
 
@@ -108,10 +132,17 @@ impl SvoBrickSelectPipeline {
         transforms: &GPUGeometryTransforms,
         frustum: &math::Frustum,
     ) {
-        let Some(node_count) = svo.node_pool.count() else {
-            warn!("SvoBrickSelectPipeline::run: Svo node pool is empty or node count is not loaded back from the gpu");
+        // Only nodes of fully evaluated levels are traversed, the svo might still be being refined
+        let node_count = svo.evaluated_node_count();
+        if node_count == 0 {
+            warn!("SvoBrickSelectPipeline::run: Svo has no evaluated levels");
             return;
-        };
+        }
+
+        // Overflow of a previous run is known only now, the readback does not wait for the GPU
+        if self.work_queues.read_overflow() {
+            warn!("SvoBrickSelectPipeline::run: Work queue overflowed, subtrees which did not fit were not rendered");
+        }
 
         // Prepare encoder
         let mut encoder =
@@ -133,59 +164,293 @@ impl SvoBrickSelectPipeline {
             &self.geometry_instances_transforms_bind_group_layout,
         );
 
-        // compute pass scope
+        // Each level pushes at most all nodes of the next level for each instance
+        let instance_count = transforms.transforms.size as u32;
+        let max_level_node_count = svo
+            .levels
+            .iter()
+            .map(|level| level.node_count)
+            .max()
+            .unwrap_or(0);
+        self.work_queues.resize(
+            &context.gpu,
+            instance_count as usize * max_level_node_count as usize,
+        );
+        let work_queue_bind_groups = self.work_queues.create_bind_groups(&context.gpu);
+
+        self.frustum_uniform.update(&context.gpu, frustum);
+
+        // let frustum_camera = crate::framework::camera::Camera {
+        //     position: (0.0, 0.0, 0.0).into(),
+        //     ..context.camera.camera
+        // }.look_at((1.0, 0.0, 1.0).into());
+
+        let push_constants = PushConstants {
+            node_count,
+            level_break_size,
+            // camera_projection_matrix: frustum_camera.view_projection_matrix(),
+            // camera_focal_length:      frustum_camera.focal_length(),
+            // camera_far:               frustum_camera.far,
+            // camera_near:              frustum_camera.near,
+            camera_projection_matrix: context.camera.view_projection_matrix,
+            camera_focal_length: context.camera.camera.focal_length(),
+            camera_far: context.camera.camera.far,
+            camera_near: context.camera.camera.near,
+            domain: svo.domain,
+            ..Default::default()
+        };
+
+        let bind_groups = |queue_index: usize| {
+            [
+                &node_bind_group,
+                &brick_instances_bind_group,
+                &geometry_instances_transforms_bind_group,
+                &self.frustum_uniform.bind_group,
+                &work_queue_bind_groups[queue_index],
+            ]
+        };
+
+        // Root of each instance pushes the top level into the first queue
+        self.work_queues.reset(&mut encoder);
         {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Brick select pass"),
-            });
+            let mut compute_pass = begin_pass(
+                &mut encoder,
+                "Brick select root pass",
+                &self.root_pipeline,
+                &bind_groups(1),
+                &push_constants,
+            );
+            compute_pass.dispatch_workgroups(instance_count.div_ceil(128), 1, 1);
+        }
+
+        // Each level reads nodes from one queue and pushes their children into the other one
+        for level_index in 0..svo.levels.len() {
+            let input = level_index % 2;
+            self.work_queues.clear(&mut encoder, 1 - input);
+            let mut compute_pass = begin_pass(
+                &mut encoder,
+                "Brick select level pass",
+                &self.node_pipeline,
+                &bind_groups(input),
+                &push_constants,
+            );
+            compute_pass.dispatch_workgroups_indirect(&self.work_queues.buffers[input].buffer, 0);
+        }
+
+        let overflow_copied = self.work_queues.queue_overflow_readback(&mut encoder);
+        context.gpu.queue.submit(Some(encoder.finish()));
+        if overflow_copied {
+            self.work_queues.map_overflow_readback();
+        }
+    }
+}
+
+/// Begins a compute pass with given pipeline and bind groups set in order of their indices.
+fn begin_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    label: &str,
+    pipeline: &'a wgpu::ComputePipeline,
+    bind_groups: &[&'a wgpu::BindGroup],
+    push_constants: &PushConstants,
+) -> wgpu::ComputePass<'a> {
+    let mut compute_pass =
+        encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some(label) });
+    compute_pass.set_pipeline(pipeline);
+    for (index, bind_group) in bind_groups.iter().enumerate() {
+        compute_pass.set_bind_group(index as u32, bind_group, &[]);
+    }
+    compute_pass.set_push_constants(0, bytemuck::cast_slice(&[*push_constants]));
+    compute_pass
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct WorkItem {
+    node_id: u32,
+    instance_id: u32,
+}
+
+/// Header of a work queue, it is used as arguments of the indirect dispatch processing the queue.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct WorkQueueHeader {
+    dispatch_x: u32,
+    dispatch_y: u32,
+    dispatch_z: u32,
+    count: u32,
+    /// Non zero when an item did not fit into the queue, it is kept when the queue is cleared for the next level.
+    overflow: u32,
+    _padding: u32,
+}
+
+/// Two queues of nodes to be visited, a level pass reads one of them and fills the other one for the next level.
+#[derive(Debug)]
+struct WorkQueues {
+    /// Header followed by items of each queue.
+    buffers: [gpu::Buffer<WorkItem>; 2],
+    /// Header of an empty queue, it is copied over a queue before the queue is filled.
+    empty_header: gpu::Buffer<WorkQueueHeader>,
+    /// Headers of both queues copied after the last level, only their overflow flags are read.
+    overflow_readback: Readback,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl WorkQueues {
+    /// Number of items occupied by the header at the start of a queue buffer.
+    const HEADER_ITEM_COUNT: usize =
+        std::mem::size_of::<WorkQueueHeader>() / std::mem::size_of::<WorkItem>();
+
+    /// Bytes of the header cleared before each level, the overflow flag behind them is kept.
+    const CLEARED_HEADER_BYTES: u64 = std::mem::size_of::<[u32; 4]>() as u64;
+
+    fn new(gpu: &gpu::Context, stages: wgpu::ShaderStages) -> Self {
+        let buffer = |label| {
+            gpu::Buffer::new_empty(
+                gpu,
+                Some(label),
+                Self::HEADER_ITEM_COUNT,
+                wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::INDIRECT
+                    | wgpu::BufferUsages::COPY_DST
+                    | wgpu::BufferUsages::COPY_SRC,
+            )
+        };
+
+        let queue_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: stages,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        Self {
+            buffers: [
+                buffer("Brick select work queue A"),
+                buffer("Brick select work queue B"),
+            ],
+            empty_header: gpu::Buffer::new(
+                gpu,
+                Some("Brick select empty work queue header"),
+                &[WorkQueueHeader {
+                    dispatch_x: 0,
+                    dispatch_y: 1,
+                    dispatch_z: 1,
+                    count: 0,
+                    overflow: 0,
+                    _padding: 0,
+                }],
+                wgpu::BufferUsages::COPY_SRC,
+            ),
+            overflow_readback: Readback::new(
+                gpu,
+                "Brick select work queue overflow readback buffer",
+                2 * std::mem::size_of::<WorkQueueHeader>() as u64,
+            ),
+            bind_group_layout: gpu.device.create_bind_group_layout(
+                &wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Brick select work queues bind group layout"),
+                    entries: &[
+                        // Queue of nodes visited by the pass
+                        queue_entry(0, true),
+                        // Queue of nodes visited by the next pass
+                        queue_entry(1, false),
+                    ],
+                },
+            ),
+        }
+    }
 
-            self.frustum_uniform.update(&context.gpu, frustum);
+    /// Grows queues to hold given number of items, limited by the size of a buffer which can be bound.
+    ///   - Subtrees which do not fit into a full queue are not visited.
+    fn resize(&mut self, gpu: &gpu::Context, item_capacity: usize) {
+        let max_binding_items = gpu.device.limits().max_storage_buffer_binding_size as usize
+            / std::mem::size_of::<WorkItem>();
+        for buffer in self.buffers.iter_mut() {
+            buffer.resize(
+                gpu,
+                (item_capacity + Self::HEADER_ITEM_COUNT).min(max_binding_items),
+            );
+        }
+    }
 
-            compute_pass.set_pipeline(&self.pipeline);
-            compute_pass.set_bind_group(0, &node_bind_group, &[]);
-            compute_pass.set_bind_group(1, &brick_instances_bind_group, &[]);
-            compute_pass.set_bind_group(2, &geometry_instances_transforms_bind_group, &[]);
-            compute_pass.set_bind_group(3, &self.frustum_uniform.bind_group, &[]);
+    /// Returns bind groups where the queue of given index is read and the other one is filled.
+    fn create_bind_groups(&self, gpu: &gpu::Context) -> [wgpu::BindGroup; 2] {
+        let bind_group = |input: usize| {
+            gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Brick select work queues bind group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: self.buffers[input].buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: self.buffers[1 - input].buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        [bind_group(0), bind_group(1)]
+    }
 
-            // let frustum_camera = crate::framework::camera::Camera {
-            //     position: (0.0, 0.0, 0.0).into(),
-            //     ..context.camera.camera
-            // }.look_at((1.0, 0.0, 1.0).into());
+    /// Empties the queue of given index for the next level, its overflow flag is kept.
+    fn clear(&self, encoder: &mut wgpu::CommandEncoder, index: usize) {
+        encoder.copy_buffer_to_buffer(
+            &self.empty_header.buffer,
+            0,
+            &self.buffers[index].buffer,
+            0,
+            Self::CLEARED_HEADER_BYTES,
+        );
+    }
 
-            compute_pass.set_push_constants(
+    /// Empties both queues and resets their overflow flags before the traversal.
+    fn reset(&self, encoder: &mut wgpu::CommandEncoder) {
+        for buffer in self.buffers.iter() {
+            encoder.copy_buffer_to_buffer(
+                &self.empty_header.buffer,
+                0,
+                &buffer.buffer,
                 0,
-                bytemuck::cast_slice(&[PushConstants {
-                    node_count,
-                    level_break_size,
-                    // camera_projection_matrix: frustum_camera.view_projection_matrix(),
-                    // camera_focal_length:      frustum_camera.focal_length(),
-                    // camera_far:               frustum_camera.far,
-                    // camera_near:              frustum_camera.near,
-                    camera_projection_matrix: context.camera.view_projection_matrix,
-                    camera_focal_length: context.camera.camera.focal_length(),
-                    camera_far: context.camera.camera.far,
-                    camera_near: context.camera.camera.near,
-                    domain: svo.domain,
-                    ..Default::default()
-                }]),
+                std::mem::size_of::<WorkQueueHeader>() as u64,
             );
+        }
+    }
 
-            // TODO (optimization): Use hierarchical dispatch pipeline with dispatch_workgroups_indirect
-            // We know how many levels the geometry has.
-            // Queue N indirect calls, where N is the number of levels.
-            // Each call will fill up the command buffer for next level.
-            //   1. dispatch - top level cubes are selected, if they are to be subdivided, they not written to out buffer but instance is added to the job buffer.
-            //   2. dispatch do the same for second level.
-            //   ...
-            //   n th dispatch for n th level.
-            // Advantages less work for large amount of objects in the scene.
-            // Disadvantages:
-            //   - The frustum culling will have to be performed for each brick for each level if not culled completely.
-            //   - Even when no objects left for evaluation
-            compute_pass.dispatch_workgroups((node_count + 128 - 1) / 128, 1, 1);
+    /// Copies headers of both queues into the staging buffer, unless the previous copy is still being read back.
+    ///   - Returns true if the copy was encoded, `map_overflow_readback` has to be called after the encoder is submitted.
+    fn queue_overflow_readback(&self, encoder: &mut wgpu::CommandEncoder) -> bool {
+        let Some(staging_buffer) = self.overflow_readback.start() else {
+            return false;
+        };
+        let header_size = std::mem::size_of::<WorkQueueHeader>() as u64;
+        for (index, buffer) in self.buffers.iter().enumerate() {
+            encoder.copy_buffer_to_buffer(
+                &buffer.buffer,
+                0,
+                staging_buffer,
+                index as u64 * header_size,
+                header_size,
+            );
         }
+        true
+    }
 
-        context.gpu.queue.submit(Some(encoder.finish()));
+    /// Requests mapping of the staging buffer, the device is polled when brick counts are read back.
+    fn map_overflow_readback(&self) {
+        self.overflow_readback.map();
+    }
+
+    /// Returns true if any queue overflowed in the traversal which was read back last.
+    fn read_overflow(&self) -> bool {
+        self.overflow_readback
+            .read::<WorkQueueHeader>()
+            .is_some_and(|headers| headers.iter().any(|header| header.overflow != 0))
     }
 }
 
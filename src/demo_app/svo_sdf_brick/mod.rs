@@ -12,3 +12,6 @@ pub use brick_instances::*;
 
 mod gpu_geometry_transforms;
 pub use gpu_geometry_transforms::*;
+
+mod shadow_volume;
+pub use shadow_volume::*;
@@ -1,19 +1,20 @@
 use std::{borrow::Cow, collections::HashMap};
 
 use crate::{
-    demo_app::cube::{CubeSolidMesh, CUBE_INDICES_TRIANGLE_STRIP},
-    error,
+    demo_app::cube::CubeSolidMesh,
     framework::{gpu, math, renderer::RenderContext},
     info,
     sdf::{
-        geometry::GeometryID,
+        geometry::{GeometryID, GeometryPool},
         svo::{self, Svo},
     },
 };
 
-use super::{BrickInstance, BrickInstances, GPUGeometryTransforms};
+use super::{
+    BrickInstance, BrickInstances, GPUGeometryTransforms, SceneShadowVolume, ShadowSettings,
+};
 
-// bit flags for showing solid brick, normals,  step count and depth
+// bit flags for showing solid brick, normals,  step count, depth and shadows
 bitflags::bitflags! {
     #[repr(C)]
     #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
@@ -23,6 +24,7 @@ bitflags::bitflags! {
         const NORMALS    = 0b00000010;
         const STEP_COUNT = 0b00000100;
         const DEPTH      = 0b00001000;
+        const SHADOWS    = 0b00010000;
     }
 }
 
@@ -45,7 +47,7 @@ struct PushConstants {
     display_options: DisplayOptions,
     hit_distance: f32,
     max_step_count: u32,
-    _padding: [u32; 1],
+    _padding: u32,
 }
 
 #[derive(Debug)]
@@ -55,6 +57,8 @@ struct SVORenderRecord {
     pub brick_atlas_stride: f32,
     pub brick_voxel_size: f32,
     pub brick_scale: f32,
+    /// Version of SVO resources the pool bind groups were created for.
+    pub svo_resources_version: u64,
     pub node_pool_bind_group: wgpu::BindGroup,
     pub brick_pool_bind_group: wgpu::BindGroup,
     pub brick_instance_buffer: BrickInstances,
@@ -68,6 +72,7 @@ pub struct SvoSDFBrickPipeline {
     node_pool_bind_group_layout: wgpu::BindGroupLayout,
     brick_pool_bind_group_layout: wgpu::BindGroupLayout,
     instance_bind_group_layout: wgpu::BindGroupLayout,
+    shadow_volume: SceneShadowVolume,
     cube_solid_mesh: CubeSolidMesh,
     svos_to_render: HashMap<GeometryID, SVORenderRecord>,
     display_options: DisplayOptions,
@@ -92,6 +97,8 @@ impl SvoSDFBrickPipeline {
             wgpu::ShaderStages::VERTEX,
         );
 
+        let shadow_volume = SceneShadowVolume::new(&context.gpu);
+
         let shader = context
             .gpu
             .device
@@ -112,9 +119,10 @@ impl SvoSDFBrickPipeline {
                         label: Some("SDF Pipeline brick Pipeline Layout"),
                         // define buffers layout of the svo
                         bind_group_layouts: &[
-                            &node_pool_bind_group_layout,  // 0 - Node Pool
-                            &brick_pool_bind_group_layout, // 1 - Brick Pool
-                            &instance_bind_group_layout,   // 2 - Instance Buffer
+                            &node_pool_bind_group_layout,          // 0 - Node Pool
+                            &brick_pool_bind_group_layout,         // 1 - Brick Pool
+                            &instance_bind_group_layout,           // 2 - Instance Buffer
+                            &shadow_volume.read_bind_group_layout, // 3 - Scene Shadow Volume
                         ],
                         // set camera transform matrix as shader push constant
                         push_constant_ranges: &[wgpu::PushConstantRange {
@@ -184,6 +192,7 @@ impl SvoSDFBrickPipeline {
             node_pool_bind_group_layout,
             brick_pool_bind_group_layout,
             instance_bind_group_layout,
+            shadow_volume,
             cube_solid_mesh: CubeSolidMesh::new(&context.gpu.device),
             svos_to_render: HashMap::new(),
             display_options: DisplayOptions::default(),
@@ -253,6 +262,16 @@ impl SvoSDFBrickPipeline {
                 rec.brick_atlas_stride = brick_atlas_stride;
                 rec.brick_voxel_size = brick_voxel_size;
                 rec.brick_scale = brick_scale;
+                if rec.svo_resources_version != svo.resources_version() {
+                    // SVO pools were reallocated, bind groups point to old resources
+                    rec.svo_resources_version = svo.resources_version();
+                    rec.node_pool_bind_group = svo
+                        .node_pool
+                        .create_bind_group(gpu, &self.node_pool_bind_group_layout);
+                    rec.brick_pool_bind_group = svo
+                        .brick_pool
+                        .create_read_bind_group(gpu, &self.brick_pool_bind_group_layout);
+                }
                 rec.brick_instance_buffer.clear_resize(gpu, brick_count);
                 rec.instance_buffer.update(gpu, instance_transforms);
                 if rec.instance_buffer.update(gpu, instance_transforms) {
@@ -286,6 +305,7 @@ impl SvoSDFBrickPipeline {
                     brick_atlas_stride,
                     brick_voxel_size,
                     brick_scale,
+                    svo_resources_version: svo.resources_version(),
                     instance_buffer,
                     instance_bind_group,
                     brick_instance_buffer,
@@ -308,57 +328,65 @@ impl SvoSDFBrickPipeline {
         self.max_step_count = max_step_count;
     }
 
-    /// This function loads all counter buffers by issuing async map requests and then waiting for them to complete.
-    /// TODO: This is a performance bottleneck. Indirect draw calls would be a better solution and no buffer mapping would be required.
+    /// Shadows are cast by all instances in the world, including those out of view.
+    pub fn update_shadows(
+        &mut self,
+        gpu: &gpu::Context,
+        settings: &ShadowSettings,
+        geometry_pool: &GeometryPool,
+        world: &hecs::World,
+    ) {
+        self.shadow_volume
+            .update(gpu, settings, geometry_pool, world);
+    }
+
+    /// Reads back counts of selected bricks without waiting for the GPU, they are only used for statistics.
+    ///   - Draw args are copied into staging buffers which are mapped asynchronously,
+    ///     counts are updated once the mapping finishes, so they lag a few frames behind the rendered bricks.
     #[profiler::function]
-    pub fn load_counts(&mut self, gpu: &gpu::Context) {
-        let mut slices: Vec<(GeometryID, wgpu::BufferSlice)> =
-            Vec::with_capacity(self.svos_to_render.len());
+    pub fn read_back_counts(&mut self, gpu: &gpu::Context) {
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Brick count readback encoder"),
+            });
+        let copied = self
+            .svos_to_render
+            .iter_mut()
+            .filter(|(_, record)| record.render)
+            .filter_map(|(id, record)| {
+                record
+                    .brick_instance_buffer
+                    .queue_count_readback(&mut encoder)
+                    .then_some(*id)
+            })
+            .collect::<Vec<_>>();
+        gpu.queue.submit(Some(encoder.finish()));
+
         {
             profiler::scope!("Issuing buffer map requests");
-            for (id, record) in self.svos_to_render.iter_mut() {
-                if !record.render {
-                    continue;
-                }
-                let slice = record.brick_instance_buffer.count_buffer.buffer.slice(..);
-                slice.map_async(wgpu::MapMode::Read, move |_| ());
-                slices.push((*id, slice));
+            for id in copied.iter() {
+                self.svos_to_render[id]
+                    .brick_instance_buffer
+                    .map_count_readback();
             }
         }
 
-        {
-            profiler::scope!("Waiting for buffer map requests");
-            gpu.device.poll(wgpu::Maintain::Wait);
-        }
+        // Finish mappings which are ready, without blocking
+        gpu.device.poll(wgpu::Maintain::Poll);
 
-        let counts = {
-            profiler::scope!("Reading buffer map requests");
-            slices
-                .drain(..)
-                .map(|(id, slice)| {
-                    let view = slice.get_mapped_range();
-                    let data: Vec<u32> = bytemuck::cast_slice(&view).to_vec();
-                    (id, data[0])
-                })
-                .collect::<Vec<_>>()
-        };
+        for record in self.svos_to_render.values_mut() {
+            record.brick_instance_buffer.read_count();
+        }
 
         counters::sample!(
             "brick_selected_counter",
-            counts.iter().map(|(_, cnt)| cnt).sum::<u32>() as f64
+            self.svos_to_render
+                .values()
+                .filter(|record| record.render)
+                .filter_map(|record| record.brick_instance_buffer.count)
+                .sum::<u32>() as f64
         );
-
-        {
-            profiler::scope!("Unmapping buffers and storing counts");
-            for (id, count) in counts.iter() {
-                let record = self.svos_to_render.get_mut(id).unwrap();
-                if !record.render {
-                    continue;
-                }
-                record.brick_instance_buffer.count = Some(*count);
-                record.brick_instance_buffer.count_buffer.buffer.unmap();
-            }
-        }
     }
 
     /// Runs this pipeline for given render pass
@@ -381,14 +409,9 @@ impl SvoSDFBrickPipeline {
                 continue;
             }
 
-            let Some(instance_count) = record.brick_instance_buffer.count else {
-                error!("Count for brick instance buffer is not loaded.");
-                continue;
-            };
-
             info!(
-                "Rendering SVO {:?} with {} instances, buffer capacity: {}",
-                id, instance_count, record.brick_instance_buffer.buffer.capacity
+                "Rendering SVO {:?}, brick instance buffer capacity: {}",
+                id, record.brick_instance_buffer.buffer.capacity
             );
 
             pass.set_pipeline(&self.pipeline);
@@ -404,7 +427,7 @@ impl SvoSDFBrickPipeline {
                 display_options: self.display_options,
                 hit_distance: self.hit_distance,
                 max_step_count: self.max_step_count,
-                ..Default::default()
+                _padding: 0,
             };
 
             pass.set_push_constants(
@@ -418,13 +441,10 @@ impl SvoSDFBrickPipeline {
             pass.set_bind_group(0, &record.node_pool_bind_group, &[]);
             pass.set_bind_group(1, &record.brick_pool_bind_group, &[]);
             pass.set_bind_group(2, &record.instance_bind_group, &[]);
+            pass.set_bind_group(3, &self.shadow_volume.read_bind_group, &[]);
 
-            // TODO: use indirect to avoid pulling instance buffer count from gpu
-            pass.draw_indexed(
-                0..CUBE_INDICES_TRIANGLE_STRIP.len() as u32,
-                0,
-                0..instance_count.max(1),
-            );
+            // Instance count is written by the brick select pass
+            pass.draw_indexed_indirect(&record.brick_instance_buffer.draw_args_buffer.buffer, 0);
         }
     }
 }
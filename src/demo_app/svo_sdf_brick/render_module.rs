@@ -1,6 +1,6 @@
 use slotmap::Key;
 
-use super::{SvoBrickSelectPipeline, SvoSDFBrickPipeline};
+use super::{DisplayOptions, SvoBrickSelectPipeline, SvoSDFBrickPipeline};
 
 use crate::{
     demo_app::scene::Scene,
@@ -41,6 +41,18 @@ impl RenderModule<Scene> for SvoSdfBricksRenderModule {
         self.render_pipeline.set_hit_distance(scene.hit_distance);
         self.render_pipeline
             .set_max_step_count(scene.max_step_count);
+        if scene
+            .display_toggles
+            .brick_display_options
+            .contains(DisplayOptions::SHADOWS)
+        {
+            self.render_pipeline.update_shadows(
+                &context.gpu,
+                &scene.shadows,
+                &scene.geometry_pool,
+                &scene.world,
+            );
+        }
 
         let frustum_camera = scene.camera_rig.camera();
         // let frustum_camera = crate::framework::camera::Camera {
@@ -121,6 +133,11 @@ impl RenderModule<Scene> for SvoSdfBricksRenderModule {
                     return;
                 };
 
+                // svo might be just starting its evaluation, there is nothing to render yet
+                if svo.levels.is_empty() {
+                    return;
+                }
+
                 // submit instances of the svo to the render pipeline
                 let (gpu_transforms, brick_instances) =
                     self.render_pipeline
@@ -138,7 +155,7 @@ impl RenderModule<Scene> for SvoSdfBricksRenderModule {
             },
         );
 
-        self.render_pipeline.load_counts(&context.gpu);
+        self.render_pipeline.read_back_counts(&context.gpu);
     }
 
     #[profiler::function]
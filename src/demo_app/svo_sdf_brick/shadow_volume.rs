@@ -0,0 +1,512 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::{
+    framework::{
+        gpu,
+        math::{self, Transform, AABB},
+    },
+    sdf::{
+        geometry::{GeometryID, GeometryPool},
+        query::{instance_distance_scale, SVO_SAMPLING_SHADER},
+        svo,
+    },
+};
+
+/// Soft shadows of the brick renderer.
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowSettings {
+    /// Width of the penumbra relative to distance travelled towards the light, 0 gives hard shadows.
+    pub penumbra: f32,
+    /// Maximal number of steps of a shadow ray, in the brick and in the scene volume each.
+    pub max_step_count: u32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            penumbra: 0.1,
+            max_step_count: 64,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowUniform {
+    volume_min: glam::Vec3,
+    voxel_size: f32,
+    resolution: u32,
+    /// Instances write distances only up to this distance from their bounding box.
+    margin: f32,
+    penumbra: f32,
+    max_step_count: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SplatInstance {
+    to_local: glam::Mat4,
+    voxel_min: glam::UVec3,
+    distance_scale: f32,
+    voxel_count: glam::UVec3,
+    _padding: u32,
+}
+
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PushConstants {
+    domain: math::BoundingCube,
+    atlas_scale: f32,
+    atlas_stride: f32,
+    atlas_voxel_size: f32,
+    first_instance: u32,
+    instance_count: u32,
+    _padding: [u32; 3],
+}
+
+/// An instance the volume was built from.
+#[derive(Debug, Clone, PartialEq)]
+struct VolumeInstance {
+    entity: hecs::Entity,
+    geometry_id: GeometryID,
+    transform: Transform,
+    /// Resources and levels version of the SVO of the geometry.
+    svo_versions: (u64, u64),
+}
+
+///
+/// A coarse signed distance volume of the whole scene, it lets shadow rays march through all instances at once.
+///   - Distances are sampled from bricks of the most detailed evaluated nodes of each instance and combined by minimum.
+///   - Each instance writes only voxels of its bounding box inflated by a margin,
+///     so distances of other voxels are only known to be at least the margin.
+///   - The volume spans the bounding box of all instances, so its voxels and shadow details grow with the scene.
+///     Its extent is limited by `MAX_EXTENT` around the center of the scene,
+///     instances further away do not shadow other instances.
+///   - The volume is rebuilt only when an instance is added, removed or moved, or when an SVO changes.
+#[derive(Debug)]
+pub struct SceneShadowVolume {
+    clear_pipeline: wgpu::ComputePipeline,
+    splat_pipeline: wgpu::ComputePipeline,
+    node_pool_layout: wgpu::BindGroupLayout,
+    brick_pool_layout: wgpu::BindGroupLayout,
+    instances_layout: wgpu::BindGroupLayout,
+    uniform: ShadowUniform,
+    uniform_buffer: gpu::Buffer<ShadowUniform>,
+    instances: gpu::Buffer<SplatInstance>,
+    write_bind_group: wgpu::BindGroup,
+    /// Bind group for reading the volume when rendering.
+    pub read_bind_group: wgpu::BindGroup,
+    pub read_bind_group_layout: wgpu::BindGroupLayout,
+    /// Instances the volume was built from in order of the world query, None when it was not built yet.
+    built_instances: Option<Vec<VolumeInstance>>,
+}
+
+impl SceneShadowVolume {
+    /// Number of voxels along each side of the volume.
+    const RESOLUTION: u32 = 64;
+    /// Margin around instances in voxels, the volume leaves the same margin around all instances.
+    const MARGIN_VOXELS: u32 = 4;
+    /// Maximal world size of the volume without margins, it bounds the voxel size.
+    const MAX_EXTENT: f32 = 28.0;
+    const WORKGROUP_SIZE: u32 = 64;
+
+    #[profiler::function]
+    pub fn new(gpu: &gpu::Context) -> Self {
+        let node_pool_layout =
+            svo::NodePool::create_bind_group_layout(gpu, wgpu::ShaderStages::COMPUTE, true);
+        let brick_pool_layout =
+            svo::BrickPool::create_read_bind_group_layout(gpu, wgpu::ShaderStages::COMPUTE);
+        let write_layout = Self::create_volume_layout(gpu, wgpu::ShaderStages::COMPUTE, false);
+        let read_bind_group_layout =
+            Self::create_volume_layout(gpu, wgpu::ShaderStages::FRAGMENT, true);
+        let instances_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Scene shadow volume instances bind group layout"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                });
+
+        let module = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Scene shadow volume compute shader module"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Owned(
+                    [SVO_SAMPLING_SHADER, include_str!("_shadow_volume.wgsl")].concat(),
+                )),
+            });
+
+        let clear_pipeline =
+            gpu.device
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some("Scene shadow volume clear pipeline"),
+                    layout: Some(&gpu.device.create_pipeline_layout(
+                        &wgpu::PipelineLayoutDescriptor {
+                            label: Some("Scene shadow volume clear pipeline layout"),
+                            bind_group_layouts: &[&write_layout],
+                            push_constant_ranges: &[],
+                        },
+                    )),
+                    module: &module,
+                    entry_point: "clear",
+                });
+
+        let splat_pipeline =
+            gpu.device
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some("Scene shadow volume splat pipeline"),
+                    layout: Some(&gpu.device.create_pipeline_layout(
+                        &wgpu::PipelineLayoutDescriptor {
+                            label: Some("Scene shadow volume splat pipeline layout"),
+                            bind_group_layouts: &[
+                                &write_layout,      // 0 - Volume
+                                &node_pool_layout,  // 1 - Node Pool
+                                &brick_pool_layout, // 2 - Brick Pool
+                                &instances_layout,  // 3 - Instances
+                            ],
+                            push_constant_ranges: &[wgpu::PushConstantRange {
+                                stages: wgpu::ShaderStages::COMPUTE,
+                                range: 0..std::mem::size_of::<PushConstants>() as u32,
+                            }],
+                        },
+                    )),
+                    module: &module,
+                    entry_point: "splat",
+                });
+
+        // Bind groups keep the buffer alive
+        let distances = gpu::Buffer::<u32>::new_empty(
+            gpu,
+            Some("Scene shadow volume distances"),
+            Self::RESOLUTION.pow(3) as usize,
+            wgpu::BufferUsages::STORAGE,
+        );
+        let uniform = ShadowUniform {
+            resolution: Self::RESOLUTION,
+            ..Default::default()
+        };
+        let uniform_buffer = gpu::Buffer::new(
+            gpu,
+            Some("Scene shadow volume uniform"),
+            &[uniform],
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        );
+        let volume_bind_group = |layout, label| {
+            gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: distances.buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: uniform_buffer.buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        let write_bind_group =
+            volume_bind_group(&write_layout, "Scene shadow volume write bind group");
+        let read_bind_group = volume_bind_group(
+            &read_bind_group_layout,
+            "Scene shadow volume read bind group",
+        );
+
+        Self {
+            clear_pipeline,
+            splat_pipeline,
+            node_pool_layout,
+            brick_pool_layout,
+            instances_layout,
+            instances: gpu::Buffer::new_empty(
+                gpu,
+                Some("Scene shadow volume instances"),
+                1,
+                wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            )
+            .with_grow_rate(1.5),
+            uniform,
+            uniform_buffer,
+            write_bind_group,
+            read_bind_group,
+            read_bind_group_layout,
+            built_instances: None,
+        }
+    }
+
+    /// Rebuilds the volume from all instances in the world if they changed since the last build and updates settings.
+    ///   - Instances whose geometry has no evaluated SVO yet are left out.
+    #[profiler::function]
+    pub fn update(
+        &mut self,
+        gpu: &gpu::Context,
+        settings: &ShadowSettings,
+        geometry_pool: &GeometryPool,
+        world: &hecs::World,
+    ) {
+        // Instances are only compared with the last build, so an unchanged scene costs a single pass over them
+        let mut query = world.query::<(hecs::Entity, &GeometryID, &Transform)>();
+        let mut instances = query.iter().filter_map(|(entity, geometry_id, transform)| {
+            Self::volume_instance(geometry_pool, entity, geometry_id, transform)
+        });
+        let is_up_to_date = self
+            .built_instances
+            .as_ref()
+            .is_some_and(|built_instances| {
+                let mut built_instances = built_instances.iter();
+                instances.all(|instance| built_instances.next() == Some(&instance))
+                    && built_instances.next().is_none()
+            });
+        if !is_up_to_date {
+            let built_instances: Vec<VolumeInstance> = query
+                .iter()
+                .filter_map(|(entity, geometry_id, transform)| {
+                    Self::volume_instance(geometry_pool, entity, geometry_id, transform)
+                })
+                .collect();
+            // Sorted, so instances of a geometry are next to each other
+            let mut instances: Vec<(GeometryID, Transform)> = built_instances
+                .iter()
+                .map(|instance| (instance.geometry_id, instance.transform.clone()))
+                .collect();
+            instances.sort_by_key(|(geometry_id, _)| *geometry_id);
+            self.build(gpu, geometry_pool, &instances);
+            self.built_instances = Some(built_instances);
+        }
+
+        self.uniform.penumbra = settings.penumbra;
+        self.uniform.max_step_count = settings.max_step_count;
+        self.uniform_buffer.queue_update(gpu, &[self.uniform]);
+    }
+}
+
+// =================================================================================================
+// Private Implementation
+// =================================================================================================
+
+impl SceneShadowVolume {
+    fn create_volume_layout(
+        gpu: &gpu::Context,
+        visibility: wgpu::ShaderStages,
+        read_only: bool,
+    ) -> wgpu::BindGroupLayout {
+        gpu.device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Scene shadow volume bind group layout"),
+                entries: &[
+                    // Distances
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Volume placement and shadow settings
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            })
+    }
+
+    /// An instance of a geometry written into the volume, None when the geometry has no evaluated SVO yet.
+    fn volume_instance(
+        geometry_pool: &GeometryPool,
+        entity: hecs::Entity,
+        geometry_id: &GeometryID,
+        transform: &Transform,
+    ) -> Option<VolumeInstance> {
+        let svo = geometry_pool.get(*geometry_id)?.svo.as_ref()?;
+        (svo.evaluated_node_count() > 0).then(|| VolumeInstance {
+            entity,
+            geometry_id: *geometry_id,
+            transform: transform.clone(),
+            svo_versions: (svo.resources_version(), svo.levels_version()),
+        })
+    }
+
+    /// Places the volume around all instances and writes their distances into it.
+    #[profiler::function]
+    fn build(
+        &mut self,
+        gpu: &gpu::Context,
+        geometry_pool: &GeometryPool,
+        instances: &[(GeometryID, Transform)],
+    ) {
+        let world_aabbs: Vec<AABB> = instances
+            .iter()
+            .map(|(geometry_id, transform)| {
+                geometry_pool
+                    .get(*geometry_id)
+                    .map_or(AABB::ZERO, |geometry| {
+                        geometry.total_aabb().transform(transform)
+                    })
+            })
+            .collect();
+        let scene_aabb = world_aabbs.iter().skip(1).fold(
+            world_aabbs.first().cloned().unwrap_or(AABB::ZERO),
+            |acc, aabb| acc.add(aabb),
+        );
+
+        // Cube around the center of the scene, at most `MAX_EXTENT` large, with the margin left on each side
+        let extent = (scene_aabb.max - scene_aabb.min)
+            .max_element()
+            .clamp(f32::EPSILON, Self::MAX_EXTENT);
+        let voxel_size = extent / (Self::RESOLUTION - 2 * Self::MARGIN_VOXELS) as f32;
+        let center = (scene_aabb.min + scene_aabb.max) * 0.5;
+        self.uniform.volume_min =
+            center - glam::Vec3::splat(voxel_size * Self::RESOLUTION as f32 * 0.5);
+        self.uniform.voxel_size = voxel_size;
+        self.uniform.margin = voxel_size * Self::MARGIN_VOXELS as f32;
+        self.uniform_buffer.queue_update(gpu, &[self.uniform]);
+
+        let splat_instances: Vec<SplatInstance> = instances
+            .iter()
+            .zip(world_aabbs.iter())
+            .map(|((_, transform), aabb)| self.splat_instance(transform, aabb))
+            .collect();
+        if !splat_instances.is_empty() {
+            self.instances.queue_update(gpu, &splat_instances);
+        }
+        let instances_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Scene shadow volume instances bind group"),
+            layout: &self.instances_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: self.instances.buffer.as_entire_binding(),
+            }],
+        });
+
+        // Instances are sorted by geometry, so each geometry takes a continuous range of them
+        let mut ranges: HashMap<GeometryID, (u32, u32)> = HashMap::new();
+        for (index, (geometry_id, _)) in instances.iter().enumerate() {
+            ranges
+                .entry(*geometry_id)
+                .and_modify(|(_, count)| *count += 1)
+                .or_insert((index as u32, 1));
+        }
+        let bind_groups: Vec<_> = ranges
+            .iter()
+            .filter_map(|(geometry_id, range)| {
+                let svo = geometry_pool.get(*geometry_id)?.svo.as_ref()?;
+                let push_constants = PushConstants {
+                    domain: svo.domain,
+                    atlas_scale: svo.brick_pool.atlas_scale(),
+                    atlas_stride: svo.brick_pool.atlas_stride(),
+                    atlas_voxel_size: svo.brick_pool.atlas_voxel_size(),
+                    ..Default::default()
+                };
+                Some((
+                    svo.node_pool.create_bind_group(gpu, &self.node_pool_layout),
+                    svo.brick_pool
+                        .create_read_bind_group(gpu, &self.brick_pool_layout),
+                    push_constants,
+                    *range,
+                ))
+            })
+            .collect();
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Scene shadow volume encoder"),
+            });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Scene shadow volume pass"),
+            });
+            compute_pass.set_pipeline(&self.clear_pipeline);
+            compute_pass.set_bind_group(0, &self.write_bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                Self::RESOLUTION.pow(3).div_ceil(Self::WORKGROUP_SIZE),
+                1,
+                1,
+            );
+
+            compute_pass.set_pipeline(&self.splat_pipeline);
+            compute_pass.set_bind_group(0, &self.write_bind_group, &[]);
+            compute_pass.set_bind_group(3, &instances_bind_group, &[]);
+            let max_row_count = gpu.device.limits().max_compute_workgroups_per_dimension;
+            for (node_pool_bind_group, brick_pool_bind_group, push_constants, (first, count)) in
+                bind_groups.iter()
+            {
+                let range = *first as usize..(*first + *count) as usize;
+                let max_voxel_count = splat_instances[range]
+                    .iter()
+                    .map(|instance| {
+                        instance.voxel_count.x * instance.voxel_count.y * instance.voxel_count.z
+                    })
+                    .max()
+                    .unwrap_or(0);
+                compute_pass.set_bind_group(1, node_pool_bind_group, &[]);
+                compute_pass.set_bind_group(2, brick_pool_bind_group, &[]);
+
+                // One row of workgroups per instance, rows are split into dispatches, because their count is limited
+                let mut offset = 0;
+                while offset < *count {
+                    let row_count = (*count - offset).min(max_row_count);
+                    compute_pass.set_push_constants(
+                        0,
+                        bytemuck::cast_slice(&[PushConstants {
+                            first_instance: first + offset,
+                            instance_count: row_count,
+                            ..*push_constants
+                        }]),
+                    );
+                    compute_pass.dispatch_workgroups(
+                        max_voxel_count.div_ceil(Self::WORKGROUP_SIZE),
+                        row_count,
+                        1,
+                    );
+                    offset += row_count;
+                }
+            }
+        }
+        gpu.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Box of voxels whose distance is written by an instance.
+    fn splat_instance(&self, transform: &Transform, world_aabb: &AABB) -> SplatInstance {
+        let last_voxel = glam::IVec3::splat(Self::RESOLUTION as i32 - 1);
+        let to_voxel = |position: glam::Vec3| {
+            (position - self.uniform.volume_min) / self.uniform.voxel_size - 0.5
+        };
+        let aabb = world_aabb.inflate(self.uniform.margin);
+        let voxel_min = to_voxel(aabb.min)
+            .floor()
+            .as_ivec3()
+            .clamp(glam::IVec3::ZERO, last_voxel);
+        let voxel_max = to_voxel(aabb.max)
+            .ceil()
+            .as_ivec3()
+            .clamp(glam::IVec3::ZERO, last_voxel);
+        SplatInstance {
+            to_local: transform.as_mat().inverse(),
+            voxel_min: voxel_min.as_uvec3(),
+            distance_scale: instance_distance_scale(transform),
+            voxel_count: (voxel_max - voxel_min + 1).as_uvec3(),
+            _padding: 0,
+        }
+    }
+}
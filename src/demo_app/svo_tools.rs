@@ -0,0 +1,698 @@
+//! This is updater module which runs tools inspecting SVOs requested from GUI
+//! Tools need GPU access to read SVOs back, which GUI modules do not have
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+
+use rand::{Rng, SeedableRng};
+
+use crate::{
+    demo_app::scene::Scene,
+    framework::{
+        gpu,
+        math::Transform,
+        updater::{
+            AfterRenderContext, InputUpdateResult, ResizeContext, UpdateContext,
+            UpdateResultAction, UpdaterModule,
+        },
+    },
+    sdf::{
+        geometry::{EditSampler, GeometryID},
+        mesh::{GltfScene, Mesher, SurfaceSampler, WatertightMesher},
+        query::{instance_distance_scale, DistanceSample, KernelDistanceQuery},
+        slice::{CrossSection, SlicePlane},
+        svo::{QuantizationErrorReport, Svo, SvoData, SvoSampler, SvoStatistics},
+        volume::{MassIntegrator, MassProperties, SdfVolume, VolumeSource, VoxModel},
+    },
+    warn,
+};
+
+/// A tool to be run on an SVO of a geometry.
+#[derive(Debug, Clone)]
+pub enum SvoToolRequest {
+    /// Compare distances of the SVO with 8-bit quantized distances.
+    QuantizationError(GeometryID),
+    /// Read back and summarize the SVO.
+    Statistics(GeometryID),
+    /// Extract a triangle mesh from the SVO and store it into an OBJ or PLY file chosen by extension.
+    ExportMesh(GeometryID, PathBuf),
+    /// Extract a watertight mesh from the edit list and store it into a binary STL file.
+    ExportStl(GeometryID, PathBuf, StlExportSettings),
+    /// Sample distances on a regular grid and store them into a raw, npy or narrow band file chosen by extension.
+    ExportVolume(GeometryID, PathBuf, VolumeExportSettings),
+    /// Voxelize the SVO with given number of voxels along the longest side and store it into a MagicaVoxel file.
+    ExportVox(GeometryID, PathBuf, u32),
+    /// Spread points evenly over the surface and store them with normals and colors into a PLY file.
+    ExportPointCloud(GeometryID, PathBuf, PointCloudExportSettings),
+    /// Slice the geometry by a stack of parallel planes and store contours of the cross sections into an SVG file.
+    ExportSlices(GeometryID, PathBuf, SliceExportSettings),
+    /// Extract meshes of all geometries and store them with their instances into a binary glTF file.
+    ExportScene(PathBuf),
+    /// Integrate volume, surface area, center of mass and inertia tensor of the geometry.
+    MassProperties(GeometryID, MassPropertiesSettings),
+    /// Sample distances of all instances near their surfaces on CPU from edits and on GPU from SVOs and compare them.
+    CompareDistanceQueries(usize),
+}
+
+/// Settings of STL export chosen in GUI.
+#[derive(Debug, Clone, Copy)]
+pub struct StlExportSettings {
+    /// Number of lattice cells along a side of the geometry bounding cube.
+    pub resolution: u32,
+    /// Scale from scene units to millimetres.
+    pub millimeters_per_unit: f32,
+}
+
+impl Default for StlExportSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 128,
+            millimeters_per_unit: 1.0,
+        }
+    }
+}
+
+/// Settings of dense volume export chosen in GUI.
+#[derive(Debug, Clone, Copy)]
+pub struct VolumeExportSettings {
+    /// Number of cells along the longest side of the geometry AABB.
+    pub resolution: u32,
+    pub source: VolumeSource,
+    pub with_colors: bool,
+    /// Samples further from the surface than this number of voxels are left out of narrow band files.
+    pub band_width: f32,
+}
+
+impl Default for VolumeExportSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 64,
+            source: VolumeSource::Svo,
+            with_colors: false,
+            band_width: 2.0,
+        }
+    }
+}
+
+/// Settings of point cloud export chosen in GUI.
+#[derive(Debug, Clone, Copy)]
+pub struct PointCloudExportSettings {
+    pub point_count: usize,
+    pub source: VolumeSource,
+    /// Binary PLY files are smaller, ASCII ones are easier to inspect.
+    pub binary: bool,
+}
+
+impl Default for PointCloudExportSettings {
+    fn default() -> Self {
+        Self {
+            point_count: 10000,
+            source: VolumeSource::Svo,
+            binary: true,
+        }
+    }
+}
+
+/// Settings of cross section export chosen in GUI.
+#[derive(Debug, Clone, Copy)]
+pub struct SliceExportSettings {
+    pub normal: glam::Vec3,
+    /// Distance of the first plane from the geometry AABB center along the normal.
+    pub offset: f32,
+    pub spacing: f32,
+    pub count: u32,
+    /// Number of cells along the longer side of the AABB projected into a plane.
+    pub resolution: u32,
+    pub source: VolumeSource,
+    /// Scale from scene units to millimetres.
+    pub millimeters_per_unit: f32,
+}
+
+impl Default for SliceExportSettings {
+    fn default() -> Self {
+        Self {
+            normal: glam::Vec3::Y,
+            offset: 0.0,
+            spacing: 0.1,
+            count: 1,
+            resolution: 256,
+            source: VolumeSource::Svo,
+            millimeters_per_unit: 10.0,
+        }
+    }
+}
+
+/// Settings of mass properties integration chosen in GUI.
+#[derive(Debug, Clone, Copy)]
+pub struct MassPropertiesSettings {
+    /// Mass per unit volume.
+    pub density: f32,
+    /// Acceptable volume error relative to the volume.
+    pub accuracy: f32,
+    pub source: VolumeSource,
+}
+
+impl Default for MassPropertiesSettings {
+    fn default() -> Self {
+        Self {
+            density: 1.0,
+            accuracy: 0.01,
+            source: VolumeSource::Svo,
+        }
+    }
+}
+
+/// Settings of distance queries chosen in GUI.
+#[derive(Debug, Clone, Copy)]
+pub struct DistanceQuerySettings {
+    /// A world position at which all instances are queried.
+    pub position: glam::Vec3,
+    /// Number of positions sampled per instance when comparing CPU and GPU batches.
+    pub batch_size: usize,
+}
+
+impl Default for DistanceQuerySettings {
+    fn default() -> Self {
+        Self {
+            position: glam::Vec3::ZERO,
+            batch_size: 10000,
+        }
+    }
+}
+
+/// Requests for tools and their results shared between GUI and the updater module.
+#[derive(Default)]
+pub struct SvoToolsState {
+    pub requests: Vec<SvoToolRequest>,
+    pub quantization_errors: HashMap<GeometryID, Result<QuantizationErrorReport, String>>,
+
+    /// A geometry inspected in SVO statistics and mass properties panels and exported in the export panel.
+    pub selected_geometry: Option<GeometryID>,
+    pub statistics: HashMap<GeometryID, SvoStatistics>,
+
+    pub mass_properties_settings: MassPropertiesSettings,
+    pub mass_properties: HashMap<GeometryID, Result<MassProperties, String>>,
+
+    pub stl_export: StlExportSettings,
+    pub volume_export: VolumeExportSettings,
+    pub point_cloud_export: PointCloudExportSettings,
+    pub slice_export: SliceExportSettings,
+    /// A message describing result of the last export.
+    pub last_export: Option<Result<String, String>>,
+
+    pub distance_query: DistanceQuerySettings,
+    /// A message describing result of the last comparison of CPU and GPU distance queries.
+    pub distance_query_comparison: Option<Result<String, String>>,
+}
+
+pub struct SvoToolsUpdater {
+    gpu: Arc<gpu::Context>,
+    distance_query_kernel: KernelDistanceQuery,
+}
+
+impl SvoToolsUpdater {
+    pub fn new(gpu: Arc<gpu::Context>) -> SvoToolsUpdater {
+        let distance_query_kernel = KernelDistanceQuery::new(&gpu);
+        Self {
+            gpu,
+            distance_query_kernel,
+        }
+    }
+}
+
+impl SvoToolsUpdater {
+    /// Reads SVO of a geometry back to CPU.
+    fn read_back(&self, scene: &mut Scene, geometry_id: GeometryID) -> Result<SvoData, String> {
+        match scene
+            .geometry_pool
+            .get_mut(geometry_id)
+            .and_then(|geometry| geometry.svo.as_mut())
+        {
+            Some(svo) => Ok(svo.read_back(&self.gpu)),
+            None => Err("Geometry has no evaluated SVO".to_string()),
+        }
+    }
+
+    fn export_mesh(data: &SvoData, path: &Path) -> Result<String, String> {
+        let sampler = SvoSampler::new(data);
+        let mesh = Mesher::new(&sampler).mesh();
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("obj") | Some("OBJ") => mesh.store_obj(path)?,
+            Some("ply") | Some("PLY") => mesh.store_ply(path)?,
+            _ => return Err("Mesh file has to have obj or ply extension".to_string()),
+        }
+        Ok(format!(
+            "Exported {} triangles into {}",
+            mesh.triangle_count(),
+            path.display()
+        ))
+    }
+
+    fn export_stl(
+        scene: &Scene,
+        geometry_id: GeometryID,
+        path: &Path,
+        settings: StlExportSettings,
+    ) -> Result<String, String> {
+        let Some(geometry) = scene.geometry_pool.get(geometry_id) else {
+            return Err("Geometry does not exist".to_string());
+        };
+        let sampler = EditSampler::new(geometry.edits())?;
+        let domain = geometry.total_aabb().bounding_cube();
+        let mesh = WatertightMesher::new(&sampler, domain, settings.resolution).mesh()?;
+        let scale = settings.millimeters_per_unit;
+        mesh.store_stl(path, scale)?;
+        let (min, max) = mesh.bounds().unwrap_or_default();
+        Ok(format!(
+            "Exported {} triangles into {}, volume {:.2} mm³, bounding box {:.2} to {:.2} mm",
+            mesh.triangle_count(),
+            path.display(),
+            mesh.volume() * scale * scale * scale,
+            min * scale,
+            max * scale
+        ))
+    }
+
+    fn export_volume(
+        &self,
+        scene: &mut Scene,
+        geometry_id: GeometryID,
+        path: &Path,
+        settings: VolumeExportSettings,
+    ) -> Result<String, String> {
+        let Some(geometry) = scene.geometry_pool.get(geometry_id) else {
+            return Err("Geometry does not exist".to_string());
+        };
+        let aabb = geometry.total_aabb().clone();
+        let (resolution, with_colors) = (settings.resolution, settings.with_colors);
+        let volume = match settings.source {
+            VolumeSource::Svo => {
+                let data = self.read_back(scene, geometry_id)?;
+                let sampler = SvoSampler::new(&data);
+                SdfVolume::sample(&aabb, resolution, with_colors, |position| {
+                    let sample = sampler.sample_clamped(position);
+                    (sample.distance, sample.color)
+                })
+            }
+            VolumeSource::Edits => {
+                let sampler = EditSampler::new(geometry.edits())?;
+                SdfVolume::sample(&aabb, resolution, with_colors, |position| {
+                    let sample = sampler.sample(position);
+                    (sample.distance, sample.color)
+                })
+            }
+        };
+
+        let size = volume.resolution;
+        let mut message = format!(
+            "Exported {}x{}x{} volume into {}",
+            size.x,
+            size.y,
+            size.z,
+            path.display()
+        );
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("raw") | Some("RAW") => volume.store_raw(path)?,
+            Some("npy") | Some("NPY") => volume.store_npy(path)?,
+            Some("band") | Some("BAND") => {
+                let band_width = settings.band_width * volume.voxel_size;
+                let sample_count = volume.store_narrow_band(path, band_width)?;
+                message += &format!(", {} samples in narrow band", sample_count);
+            }
+            _ => return Err("Volume file has to have raw, npy or band extension".to_string()),
+        }
+        Ok(message)
+    }
+
+    fn export_vox(
+        &self,
+        scene: &mut Scene,
+        geometry_id: GeometryID,
+        path: &Path,
+        resolution: u32,
+    ) -> Result<String, String> {
+        let Some(geometry) = scene.geometry_pool.get(geometry_id) else {
+            return Err("Geometry does not exist".to_string());
+        };
+        let aabb = geometry.total_aabb().clone();
+        let data = self.read_back(scene, geometry_id)?;
+        let sampler = SvoSampler::new(&data);
+        let resolution = resolution.min(VoxModel::MAX_SIZE);
+        let volume = SdfVolume::sample(&aabb, resolution, true, |position| {
+            let sample = sampler.sample_clamped(position);
+            (sample.distance, sample.color)
+        });
+        let model = VoxModel::from_volume(&volume)?;
+        model.store(path)?;
+        Ok(format!(
+            "Exported {} voxels with {} colors into {}",
+            model.voxels.len(),
+            model.color_count(),
+            path.display()
+        ))
+    }
+
+    fn export_point_cloud(
+        &self,
+        scene: &mut Scene,
+        geometry_id: GeometryID,
+        path: &Path,
+        settings: PointCloudExportSettings,
+    ) -> Result<String, String> {
+        let Some(geometry) = scene.geometry_pool.get(geometry_id) else {
+            return Err("Geometry does not exist".to_string());
+        };
+        let aabb = geometry.total_aabb().clone();
+        let cloud = match settings.source {
+            VolumeSource::Svo => {
+                let data = self.read_back(scene, geometry_id)?;
+                let sampler = SvoSampler::new(&data);
+                SurfaceSampler::new(|position: glam::Vec3| {
+                    let sample = sampler.sample_clamped(position);
+                    (sample.distance, sample.color)
+                })
+                .sample(&aabb, settings.point_count)?
+            }
+            VolumeSource::Edits => {
+                let sampler = EditSampler::new(geometry.edits())?;
+                SurfaceSampler::new(|position: glam::Vec3| {
+                    let sample = sampler.sample(position);
+                    (sample.distance, sample.color)
+                })
+                .sample(&aabb, settings.point_count)?
+            }
+        };
+        cloud.store_ply(path, settings.binary)?;
+        Ok(format!(
+            "Exported {} points into {}",
+            cloud.point_count(),
+            path.display()
+        ))
+    }
+
+    fn mass_properties(
+        &self,
+        scene: &mut Scene,
+        geometry_id: GeometryID,
+        settings: MassPropertiesSettings,
+    ) -> Result<MassProperties, String> {
+        let Some(geometry) = scene.geometry_pool.get(geometry_id) else {
+            return Err("Geometry does not exist".to_string());
+        };
+        match settings.source {
+            VolumeSource::Svo => {
+                let aabb = geometry.total_aabb().clone();
+                let data = self.read_back(scene, geometry_id)?;
+                let sampler = SvoSampler::new(&data);
+                MassIntegrator::new(|position| sampler.sample_clamped(position).distance).integrate(
+                    &aabb,
+                    settings.density,
+                    settings.accuracy,
+                )
+            }
+            VolumeSource::Edits => geometry.mass_properties(settings.density, settings.accuracy),
+        }
+    }
+
+    fn export_slices(
+        &self,
+        scene: &mut Scene,
+        geometry_id: GeometryID,
+        path: &Path,
+        settings: SliceExportSettings,
+    ) -> Result<String, String> {
+        let Some(geometry) = scene.geometry_pool.get(geometry_id) else {
+            return Err("Geometry does not exist".to_string());
+        };
+        let aabb = geometry.total_aabb().clone();
+        let planes = SlicePlane::stack(
+            settings.normal,
+            (aabb.min + aabb.max) * 0.5,
+            settings.offset,
+            settings.spacing,
+            settings.count,
+        );
+        let slice = |distance: &dyn Fn(glam::Vec3) -> f32| -> Vec<CrossSection> {
+            planes
+                .iter()
+                .map(|plane| CrossSection::slice(*plane, &aabb, settings.resolution, distance))
+                .collect()
+        };
+        let sections = match settings.source {
+            VolumeSource::Svo => {
+                let data = self.read_back(scene, geometry_id)?;
+                let sampler = SvoSampler::new(&data);
+                slice(&|position| sampler.sample_clamped(position).distance)
+            }
+            VolumeSource::Edits => {
+                let sampler = EditSampler::new(geometry.edits())?;
+                slice(&|position| sampler.sample(position).distance)
+            }
+        };
+
+        CrossSection::store_svg(&sections, path, settings.millimeters_per_unit)?;
+        let contour_count: usize = sections.iter().map(|section| section.contours.len()).sum();
+        let hole_count: usize = sections.iter().map(|section| section.hole_count()).sum();
+        let area: f32 = sections.iter().map(|section| section.area()).sum();
+        Ok(format!(
+            "Exported {} cross sections with {} contours ({} holes, {:.2} mm² in total) into {}",
+            sections.len(),
+            contour_count,
+            hole_count,
+            area * settings.millimeters_per_unit.powi(2),
+            path.display()
+        ))
+    }
+
+    /// Stores every geometry as a mesh and every entity with a geometry as its instance.
+    ///   - Geometries without an evaluated SVO are skipped together with their instances and a warning is logged for each.
+    fn export_scene(&self, scene: &mut Scene, path: &Path) -> Result<String, String> {
+        let mut gltf = GltfScene::default();
+        let mut mesh_indices: HashMap<GeometryID, usize> = HashMap::new();
+        let mut skipped_geometries = 0;
+        let geometry_ids: Vec<GeometryID> = scene.geometry_pool.keys().collect();
+        for geometry_id in geometry_ids {
+            match self.read_back(scene, geometry_id) {
+                Ok(data) => {
+                    let sampler = SvoSampler::new(&data);
+                    let mesh = Mesher::new(&sampler).mesh();
+                    mesh_indices.insert(geometry_id, gltf.add_mesh(mesh));
+                }
+                Err(_err) => {
+                    warn!("Skipping geometry {:?} in scene export: {}", geometry_id, _err);
+                    skipped_geometries += 1;
+                }
+            }
+        }
+
+        for (geometry_id, transform) in scene.world.query::<(&GeometryID, &Transform)>().iter() {
+            if let Some(mesh_index) = mesh_indices.get(geometry_id) {
+                gltf.add_instance(*mesh_index, transform.clone());
+            }
+        }
+
+        gltf.store_glb(path)?;
+        let mut message = format!(
+            "Exported {} meshes and {} instances into {}",
+            mesh_indices.len(),
+            gltf.instance_count(),
+            path.display()
+        );
+        if skipped_geometries > 0 {
+            message += &format!(", skipped {} geometries without SVO", skipped_geometries);
+        }
+        Ok(message)
+    }
+}
+
+impl SvoToolsUpdater {
+    /// Samples every instance with an evaluated SVO at positions scattered in a thin band around its surface,
+    /// on CPU from edits and on GPU from the SVO, and summarizes their differences.
+    ///   - Positions are projected onto the surface from random positions in the instance AABB and moved off it by up to two voxels.
+    fn compare_distance_queries(
+        &self,
+        scene: &Scene,
+        position_count: usize,
+    ) -> Result<String, String> {
+        const SEED: u64 = 0;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(SEED);
+
+        let mut instance_count = 0;
+        let mut cpu_seconds = 0.0;
+        let mut gpu_seconds = 0.0;
+        let mut error_sum = 0.0f64;
+        let mut voxel_error_sum = 0.0f64;
+        let mut max_error = 0.0f32;
+        let mut angle_sum = 0.0f64;
+        for (entity, geometry_id, transform) in scene
+            .world
+            .query::<(hecs::Entity, &GeometryID, &Transform)>()
+            .iter()
+        {
+            let (Some(geometry), Some(sdf)) = (
+                scene.geometry_pool.get(*geometry_id),
+                scene.instance_sdf(entity),
+            ) else {
+                continue;
+            };
+            let Some(svo) = geometry.svo.as_ref() else {
+                continue;
+            };
+            let aabb = geometry.total_aabb().transform(transform);
+            let finest_level = svo.levels.len().saturating_sub(1);
+            let voxel_size = Svo::level_voxel_size(&svo.domain, finest_level)
+                * instance_distance_scale(transform);
+            let positions: Vec<glam::Vec3> = (0..position_count)
+                .map(|_| {
+                    let random = glam::Vec3::new(rng.random(), rng.random(), rng.random());
+                    let surface =
+                        sdf.closest_surface_point(aabb.min + (aabb.max - aabb.min) * random);
+                    let offset =
+                        glam::Vec3::new(rng.random(), rng.random(), rng.random()) * 2.0 - 1.0;
+                    surface + offset * voxel_size * 2.0
+                })
+                .collect();
+
+            let start = Instant::now();
+            let cpu_samples = sdf.sample_batch(&positions);
+            cpu_seconds += start.elapsed().as_secs_f64();
+            let start = Instant::now();
+            let gpu_samples = self
+                .distance_query_kernel
+                .sample_batch(&self.gpu, svo, transform, &positions);
+            gpu_seconds += start.elapsed().as_secs_f64();
+
+            for (cpu, gpu) in cpu_samples.iter().zip(gpu_samples.iter()) {
+                let error = (cpu.distance - gpu.distance).abs();
+                error_sum += error as f64;
+                voxel_error_sum += (error / voxel_size) as f64;
+                max_error = max_error.max(error);
+                angle_sum += gradient_angle(cpu, gpu) as f64;
+            }
+            instance_count += 1;
+        }
+
+        if instance_count == 0 {
+            return Err("No instance has an evaluated SVO".to_string());
+        }
+        let sample_count = (instance_count * position_count).max(1) as f64;
+        Ok(format!(
+            "Compared {} positions of {} instances\n\
+             Mean distance error: {:.6} ({:.3} voxels)\n\
+             Max distance error: {:.6}\n\
+             Mean gradient angle: {:.2}°\n\
+             CPU: {:.1} ms, GPU: {:.1} ms",
+            position_count * instance_count,
+            instance_count,
+            error_sum / sample_count,
+            voxel_error_sum / sample_count,
+            max_error,
+            (angle_sum / sample_count).to_degrees(),
+            cpu_seconds * 1000.0,
+            gpu_seconds * 1000.0
+        ))
+    }
+}
+
+/// Angle between gradients of two samples in radians.
+fn gradient_angle(a: &DistanceSample, b: &DistanceSample) -> f32 {
+    a.gradient
+        .normalize_or_zero()
+        .dot(b.gradient.normalize_or_zero())
+        .clamp(-1.0, 1.0)
+        .acos()
+}
+
+impl UpdaterModule<Scene> for SvoToolsUpdater {
+    #[profiler::function]
+    fn update(&mut self, context: &mut UpdateContext<Scene>) -> UpdateResultAction {
+        let scene = &mut *context.scene;
+        if scene.svo_tools.requests.is_empty() {
+            return UpdateResultAction::None;
+        }
+
+        for request in std::mem::take(&mut scene.svo_tools.requests) {
+            match request {
+                SvoToolRequest::QuantizationError(geometry_id) => {
+                    let result = match scene
+                        .geometry_pool
+                        .get_mut(geometry_id)
+                        .and_then(|geometry| geometry.svo.as_mut())
+                    {
+                        Some(svo) => svo.quantization_error(&self.gpu),
+                        None => Err("Geometry has no evaluated SVO".to_string()),
+                    };
+                    scene
+                        .svo_tools
+                        .quantization_errors
+                        .insert(geometry_id, result);
+                }
+                SvoToolRequest::Statistics(geometry_id) => {
+                    let Some(svo) = scene
+                        .geometry_pool
+                        .get_mut(geometry_id)
+                        .and_then(|geometry| geometry.svo.as_mut())
+                    else {
+                        scene.svo_tools.statistics.remove(&geometry_id);
+                        continue;
+                    };
+                    let statistics = svo.statistics(&self.gpu);
+                    scene.svo_tools.statistics.insert(geometry_id, statistics);
+                }
+                SvoToolRequest::ExportMesh(geometry_id, path) => {
+                    let result = self
+                        .read_back(scene, geometry_id)
+                        .and_then(|data| Self::export_mesh(&data, &path));
+                    scene.svo_tools.last_export = Some(result);
+                }
+                SvoToolRequest::ExportStl(geometry_id, path, settings) => {
+                    let result = Self::export_stl(scene, geometry_id, &path, settings);
+                    scene.svo_tools.last_export = Some(result);
+                }
+                SvoToolRequest::ExportVolume(geometry_id, path, settings) => {
+                    let result = self.export_volume(scene, geometry_id, &path, settings);
+                    scene.svo_tools.last_export = Some(result);
+                }
+                SvoToolRequest::ExportVox(geometry_id, path, resolution) => {
+                    let result = self.export_vox(scene, geometry_id, &path, resolution);
+                    scene.svo_tools.last_export = Some(result);
+                }
+                SvoToolRequest::ExportPointCloud(geometry_id, path, settings) => {
+                    let result = self.export_point_cloud(scene, geometry_id, &path, settings);
+                    scene.svo_tools.last_export = Some(result);
+                }
+                SvoToolRequest::ExportSlices(geometry_id, path, settings) => {
+                    let result = self.export_slices(scene, geometry_id, &path, settings);
+                    scene.svo_tools.last_export = Some(result);
+                }
+                SvoToolRequest::ExportScene(path) => {
+                    let result = self.export_scene(scene, &path);
+                    scene.svo_tools.last_export = Some(result);
+                }
+                SvoToolRequest::MassProperties(geometry_id, settings) => {
+                    let result = self.mass_properties(scene, geometry_id, settings);
+                    scene.svo_tools.mass_properties.insert(geometry_id, result);
+                }
+                SvoToolRequest::CompareDistanceQueries(position_count) => {
+                    let result = self.compare_distance_queries(scene, position_count);
+                    scene.svo_tools.distance_query_comparison = Some(result);
+                }
+            }
+        }
+
+        UpdateResultAction::Redraw
+    }
+
+    fn input(&mut self, _: &mut UpdateContext<Scene>) -> InputUpdateResult {
+        InputUpdateResult::default()
+    }
+
+    fn resize(&mut self, _: &mut ResizeContext<Scene>) -> UpdateResultAction {
+        UpdateResultAction::None
+    }
+
+    fn after_render(&mut self, _: &mut AfterRenderContext<Scene>) {}
+}
@@ -19,6 +19,9 @@ use super::cube::CubeOutlineComponent;
 pub struct TmpEvaluatorConfigProps {
     pub render_level: u32,
     pub min_voxel_size: f32,
+
+    /// Time in milliseconds the evaluator can spend refining SVOs in each update.
+    pub evaluation_time_budget_ms: f32,
 }
 
 pub struct VoxelSizeOutlineComponent;
@@ -42,6 +45,7 @@ impl UpdaterModule<Scene> for TmpEvaluatorConfig {
         if let Some(TmpEvaluatorConfigProps {
             render_level,
             min_voxel_size,
+            ..
         }) = self.prev_props
         {
             if scene_props.min_voxel_size != min_voxel_size {
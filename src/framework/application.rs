@@ -48,11 +48,14 @@ pub struct ApplicationDescriptor<A, B, C, D> {
     pub style_gui: D,
 }
 
+/// Runs the application until its window is closed.
+///   - Fails when GPU device cannot be created.
 #[profiler::function]
 pub async fn run<S, DR, DU, IS, STG>(
     app_desc: ApplicationDescriptor<DR, DU, IS, STG>,
     params: RunParams,
-) where
+) -> Result<(), String>
+where
     S: SceneWithCamera + Sized,
     for<'a> DR: FnOnce(&'a Context) -> Renderer<S>, // init_renderer
     DU: FnOnce(&Context) -> Updater<S>,             // init_updater
@@ -68,7 +71,7 @@ pub async fn run<S, DR, DU, IS, STG>(
         ))
         .build(&event_loop)
         .unwrap();
-    let gpu = std::sync::Arc::new(gpu::Context::new(&window).await);
+    let gpu = std::sync::Arc::new(gpu::Context::new(&window).await?);
 
     let context = Context {
         params: &params,
@@ -214,4 +217,5 @@ pub async fn run<S, DR, DU, IS, STG>(
             }
         }
     });
+    Ok(())
 }
@@ -0,0 +1,42 @@
+//!
+//! Helpers for writing files of exporters, numbers are written in little endian byte order.
+//!
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Creates a file for buffered writing.
+pub fn create_file<P: AsRef<Path>>(file_name: P) -> Result<BufWriter<File>, String> {
+    match File::create(file_name) {
+        Ok(file) => Ok(BufWriter::new(file)),
+        Err(err) => Err(format!("Failed to create file: {}", err)),
+    }
+}
+
+/// Creates a file and writes all bytes into it.
+pub fn write_file<P: AsRef<Path>>(file_name: P, bytes: &[u8]) -> Result<(), String> {
+    let mut file = match File::create(file_name) {
+        Ok(file) => file,
+        Err(err) => return Err(format!("Failed to create file: {}", err)),
+    };
+    match file.write_all(bytes) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(format!("Failed to write to file: {}", err)),
+    }
+}
+
+pub fn write_u32s<W: Write>(writer: &mut W, values: &[u32]) -> std::io::Result<()> {
+    for value in values {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+pub fn write_f32s<W: Write>(writer: &mut W, values: &[f32]) -> std::io::Result<()> {
+    for value in values {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
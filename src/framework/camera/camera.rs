@@ -1,4 +1,4 @@
-use crate::framework::math::Transform;
+use crate::framework::math::{Ray, Transform};
 
 #[derive(Debug, Clone)]
 pub struct Camera {
@@ -55,6 +55,14 @@ impl Camera {
         }
     }
 
+    /// Ray from the camera position through a point on screen in normalized device coordinates,
+    /// x from -1 on the left to 1 on the right and y from -1 at the bottom to 1 at the top.
+    pub fn ray(&self, ndc: glam::Vec2) -> Ray {
+        let inverse = self.view_projection_matrix().inverse();
+        let far = inverse.project_point3(glam::Vec3::new(ndc.x, ndc.y, 1.0));
+        Ray::new(self.position, far - self.position)
+    }
+
     pub fn focal_length(&self) -> f32 {
         1.0 / (self.fov.to_radians() * 0.5).tan()
     }
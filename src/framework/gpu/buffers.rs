@@ -117,6 +117,67 @@ impl<I: Debug + Copy + Clone + bytemuck::Pod + bytemuck::Zeroable> Buffer<I> {
         data
     }
 
+    /// Reads several buffers at once, GPU is waited for only once for all of them.
+    ///   - Be ware that this panics when MAP_READ is not valid usage for any of the buffers.
+    #[profiler::function]
+    pub fn static_read_all(buffers: &[&wgpu::Buffer], gpu: &Context) -> Vec<Vec<I>> {
+        for buffer in buffers {
+            profiler::call!(buffer.slice(..).map_async(wgpu::MapMode::Read, move |_| ()));
+        }
+        profiler::call!(gpu.device.poll(wgpu::Maintain::Wait));
+        buffers
+            .iter()
+            .map(|buffer| {
+                let data = {
+                    let data = profiler::call!(buffer.slice(..).get_mapped_range());
+                    bytemuck::cast_slice(&data).to_vec()
+                };
+                profiler::call!(buffer.unmap());
+                data
+            })
+            .collect()
+    }
+
+    /// Reads `count` items starting at item `offset` from a buffer which is not mappable by copying them into a staging buffer.
+    ///   - Be ware that this panics when COPY_SRC is not valid usage for the buffer.
+    #[profiler::function]
+    pub fn static_read_range(
+        buffer: &wgpu::Buffer,
+        gpu: &Context,
+        offset: usize,
+        count: usize,
+    ) -> Vec<I> {
+        if count == 0 {
+            return vec![];
+        }
+
+        let size = Self::padded_bytes_for_item_count(count) as u64;
+        let staging_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Buffer Read Staging Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Buffer Read Encoder"),
+            });
+        encoder.copy_buffer_to_buffer(
+            buffer,
+            Self::bytes_for_item_count(offset) as u64,
+            &staging_buffer,
+            0,
+            size,
+        );
+        gpu.queue.submit(Some(encoder.finish()));
+
+        let mut data = Self::static_read(&staging_buffer, gpu);
+        data.truncate(count);
+        data
+    }
+
     fn calculate_resize_capacity(&self, gpu: &Context, new_size: usize) -> usize {
         let max_capacity = self.max_capacity(gpu);
 
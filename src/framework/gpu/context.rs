@@ -9,8 +9,15 @@ pub struct Context {
 }
 
 impl Context {
+    /// Storage buffers bound to a single shader stage at once.
+    ///   - SVO evaluator kernel binds node pool (4), brick pool with its free list (3) and edits (3).
+    ///   - Brick select kernel binds node pool (4), brick instances with draw args (2), instance transforms (2)
+    ///     and input and output work queues (2).
+    const MAX_STORAGE_BUFFERS_PER_SHADER_STAGE: u32 = 10;
+
+    /// Fails when there is no adapter supporting features and limits required by the application.
     #[profiler::function]
-    pub async fn new(window: &Window) -> Self {
+    pub async fn new(window: &Window) -> Result<Self, String> {
         let instance = {
             profiler::scope!("Creating instance");
             wgpu::Instance::new(wgpu::InstanceDescriptor {
@@ -21,7 +28,8 @@ impl Context {
 
         let surface = {
             profiler::scope!("Creating surface");
-            (unsafe { instance.create_surface(window) }).expect("Failed to create surface")
+            (unsafe { instance.create_surface(window) })
+                .map_err(|err| format!("Failed to create surface: {}", err))?
         };
 
         let adapter = profiler::call!(instance
@@ -31,20 +39,34 @@ impl Context {
                 compatible_surface: Some(&surface),
             })
             .await
-            .expect("Failed to find an appropriate adapter"));
+            .ok_or("Failed to find an appropriate adapter")?);
 
-        let (device, queue) = Self::new_device_queue(&adapter).await;
+        let (device, queue) = Self::new_device_queue(&adapter).await?;
 
-        Self {
+        Ok(Self {
             adapter,
             surface,
             device,
             queue,
-        }
+        })
     }
 
+    /// Fails when the adapter does not support features or limits required by the application.
     #[profiler::function]
-    pub async fn new_device_queue(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
+    pub async fn new_device_queue(
+        adapter: &wgpu::Adapter,
+    ) -> Result<(wgpu::Device, wgpu::Queue), String> {
+        let adapter_limits = adapter.limits();
+        if adapter_limits.max_storage_buffers_per_shader_stage
+            < Self::MAX_STORAGE_BUFFERS_PER_SHADER_STAGE
+        {
+            return Err(format!(
+                "Adapter supports only {} storage buffers per shader stage, {} are required",
+                adapter_limits.max_storage_buffers_per_shader_stage,
+                Self::MAX_STORAGE_BUFFERS_PER_SHADER_STAGE,
+            ));
+        }
+
         adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
@@ -58,12 +80,14 @@ impl Context {
                         max_push_constant_size: 128,
                         max_compute_invocations_per_workgroup: 512, // to allow 8x8x8 workgroups
                         max_bind_groups: 8,
+                        max_storage_buffers_per_shader_stage:
+                            Self::MAX_STORAGE_BUFFERS_PER_SHADER_STAGE,
                         ..Default::default()
                     },
                 },
                 None,
             )
             .await
-            .expect("Failed to create device")
+            .map_err(|err| format!("Failed to create device: {}", err))
     }
 }
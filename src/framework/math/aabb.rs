@@ -1,6 +1,6 @@
 use glam::Vec4Swizzles;
 
-use super::{BoundingCube, Frustum, HalfSpace, Plane, PositionRelativeToFrustum, Transform};
+use super::{BoundingCube, Frustum, HalfSpace, Plane, PositionRelativeToFrustum, Ray, Transform};
 
 #[derive(Debug, Clone)]
 pub struct AABB {
@@ -124,6 +124,17 @@ impl AABB {
         // if frustum.vertices().iter().all(|fv| {fv.z <  self.min.z}) { return false; }
         // true
     }
+
+    /// Range of `t` where the ray is inside of the box, clipped to the ray start, None if the ray misses it.
+    ///   - Slab method, division by zero direction components yields infinities which compare correctly.
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<(f32, f32)> {
+        let inverse_direction = ray.direction.recip();
+        let t0 = (self.min - ray.origin) * inverse_direction;
+        let t1 = (self.max - ray.origin) * inverse_direction;
+        let enter = t0.min(t1).max_element().max(0.0);
+        let exit = t0.max(t1).min_element();
+        (enter <= exit).then_some((enter, exit))
+    }
 }
 
 // GPU aligned version
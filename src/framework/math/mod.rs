@@ -9,3 +9,6 @@ pub use bounding_cube::*;
 
 mod transform;
 pub use transform::*;
+
+mod ray;
+pub use ray::*;
@@ -0,0 +1,30 @@
+/// A half-line starting at origin, positions along it are `origin + direction * t` for `t >= 0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: glam::Vec3,
+    pub direction: glam::Vec3,
+}
+
+impl Ray {
+    /// Direction is normalized, so `t` is a distance from the origin.
+    pub fn new(origin: glam::Vec3, direction: glam::Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    #[inline]
+    pub fn at(&self, t: f32) -> glam::Vec3 {
+        self.origin + self.direction * t
+    }
+
+    /// Transforms the ray by a matrix without normalizing its direction,
+    /// so the same `t` gives the transformed position in both spaces.
+    pub fn transform(&self, matrix: &glam::Mat4) -> Self {
+        Self {
+            origin: matrix.transform_point3(self.origin),
+            direction: matrix.transform_vector3(self.direction),
+        }
+    }
+}
@@ -46,6 +46,16 @@ impl Transform {
     pub fn from_xyz(x: f32, y: f32, z: f32) -> Self {
         Self::from_vec3(glam::Vec3::new(x, y, z))
     }
+
+    /// Decomposes an affine matrix, shear from non-uniform scale followed by rotation cannot be represented and is lost.
+    pub fn from_mat(matrix: &glam::Mat4) -> Self {
+        let (scale, rotation, position) = matrix.to_scale_rotation_translation();
+        Self {
+            position,
+            rotation,
+            scale,
+        }
+    }
 }
 
 // Getters
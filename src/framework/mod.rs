@@ -1,4 +1,5 @@
 pub mod application;
+pub mod binary_writer;
 pub mod camera;
 pub mod clock;
 pub mod gpu;
@@ -15,7 +15,7 @@ fn main() {
 
     info!("Starting...");
 
-    pollster::block_on(application::run(
+    if let Err(err) = pollster::block_on(application::run(
         application::ApplicationDescriptor {
             init_renderer: demo_app::init_renderer,
             init_updater: demo_app::init_updater,
@@ -25,7 +25,11 @@ fn main() {
         application::RunParams {
             ..Default::default()
         },
-    ));
+    )) {
+        error!("Failed to run application: {}", err);
+        eprintln!("Failed to run application: {}", err);
+        std::process::exit(1);
+    }
 
     counters::deinit!();
 
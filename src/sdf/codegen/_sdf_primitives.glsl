@@ -0,0 +1,65 @@
+// GLSL translation of `_sdf_primitives.wgsl`, changes of the WGSL source have to be mirrored here.
+
+float sd_sphere(vec3 p, vec4 d) {
+    return length(p) - d.x;
+}
+
+float sd_cube(vec3 p, vec4 d) {
+    vec3 q = abs(p) - d.xyz * 0.5 + d.w;
+    float e = length(max(q, vec3(0.0)));
+    float i = min(max(q.x, max(q.y, q.z)), 0.0);
+    return e + i - d.w;
+}
+
+float sd_cylinder(vec3 p, vec4 d) {
+    float w = d.x * 0.5 - d.z;
+    float h = d.y * 0.5 - d.z;
+    vec2 q = abs(vec2(length(p.xz), p.y)) - vec2(w, h);
+    return min(max(q.x, q.y), 0.0) + length(max(q, vec2(0.0))) - d.z;
+}
+
+float sd_torus(vec3 p, vec4 d) {
+    float x = length(p.xz) - d.x;
+    return length(vec2(x, p.y)) - d.y;
+}
+
+float sd_cone(vec3 p, vec4 d) {
+    float h = d.y;
+    vec3 q = p - vec3(0.0, h * 0.5, 0.0);
+    vec2 c = vec2(h, d.x * 0.5);
+    float l = length(q.xz);
+    return max(dot(c, vec2(l, q.y)), -h - q.y);
+}
+
+float sd_capsule(vec3 p, vec4 d) {
+    float h = d.y;
+    float r = d.x;
+    vec3 q = p + vec3(0.0, h * 0.5, 0.0);
+    q = q - vec3(0.0, clamp(q.y, 0.0, h), 0.0);
+    return length(q) - r;
+}
+
+float ramp(float v, float l, float h) {
+    return v * (h - l) + l;
+}
+
+vec2 smooth_volume_add(float a, float b, float k) {
+    float kk = ramp(max(k, 0.0), 0.01, 1.0);
+    float h = max(kk - abs(a - b), 0.0) / kk;
+    float m = h * h * 0.5;
+    float s = m * kk * 0.5;
+    return a < b ? vec2(a - s, m) : vec2(b - s, 1.0 - m);
+}
+
+vec2 smooth_volume_difference(float a, float b, float k) {
+    float bb = -b;
+    float kk = ramp(max(k, 0.0), 0.025, 1.0);
+    float h = max(kk - abs(a - bb), 0.0) / kk;
+    float m = h * h * 0.5;
+    float s = m * kk * 0.5;
+    return a > bb ? vec2(a + s, m) : vec2(bb + s, 1.0 - m);
+}
+
+vec2 smooth_volume_intersection(float a, float b, float k) {
+    return smooth_volume_difference(a, -b, k);
+}
@@ -0,0 +1,2 @@
+mod shader_source;
+pub use shader_source::*;
@@ -0,0 +1,906 @@
+use strum_macros::{AsRefStr, EnumIter};
+
+use crate::{
+    framework::math::AABB,
+    sdf::{
+        evaluator::SDF_PRIMITIVES_SHADER,
+        geometry::{check_edits, Edit, Operation, Primitive},
+    },
+};
+
+/// A shading language of generated source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumIter)]
+pub enum ShaderLanguage {
+    Wgsl,
+    /// GLSL compatible with Shadertoy, the raymarcher is its `mainImage` function.
+    Glsl,
+}
+
+/// Compiles an edit list into a self-contained shader function `map(p)`,
+/// returning distance in `x` component and color in `yzw` components.
+///   - Edits are unrolled with their transforms inverted and folded into constants.
+///   - Groups keep result of preceding edits in a variable until edits of the group are combined with it.
+///   - Primitive and blending functions are the ones used by the evaluation kernel, see `SDF_PRIMITIVES_SHADER`.
+///   - Optionally a minimal raymarcher with a camera looking at the edits is emitted as well.
+pub struct ShaderSource {
+    language: ShaderLanguage,
+    with_raymarcher: bool,
+}
+
+impl ShaderSource {
+    /// Steps of the raymarcher before a ray is considered a miss.
+    const MAX_STEPS: u32 = 256;
+
+    pub fn new(language: ShaderLanguage) -> Self {
+        Self {
+            language,
+            with_raymarcher: false,
+        }
+    }
+
+    pub fn with_raymarcher(mut self, with_raymarcher: bool) -> Self {
+        self.with_raymarcher = with_raymarcher;
+        self
+    }
+
+    /// Fails for edit lists which cannot be evaluated, see `check_edits`.
+    #[profiler::function]
+    pub fn generate(&self, edits: &[Edit]) -> Result<String, String> {
+        check_edits(edits)?;
+        let mut source = format!(
+            "// Generated by sdf-edit-rs from {} edits\n// map(p) returns distance in x and color in yzw\n\n",
+            edits.len()
+        );
+
+        source += match self.language {
+            ShaderLanguage::Wgsl => SDF_PRIMITIVES_SHADER,
+            ShaderLanguage::Glsl => GLSL_SDF_PRIMITIVES,
+        };
+        source += "\n";
+
+        source += &self.map_function(edits);
+        if self.with_raymarcher {
+            source += &self.raymarcher(edits);
+        }
+        Ok(source)
+    }
+
+    fn map_function(&self, edits: &[Edit]) -> String {
+        let wgsl = self.language == ShaderLanguage::Wgsl;
+        let first_color = edits.first().map_or(glam::Vec4::ZERO, |edit| edit.color);
+        let mut body = if wgsl {
+            format!(
+                "fn map(p: vec3<f32>) -> vec4<f32> {{\n    var distance = 1000000.0;\n    var color = {};\n    var res: vec2<f32>;\n",
+                self.vec3(first_color.truncate())
+            )
+        } else {
+            format!(
+                "vec4 map(vec3 p) {{\n    float distance = 1000000.0;\n    vec3 color = {};\n    vec2 res;\n",
+                self.vec3(first_color.truncate())
+            )
+        };
+
+        // Indices of group edits containing the current edit and of the first edits after them, innermost last
+        let mut groups: Vec<(usize, usize)> = vec![];
+        for (index, edit) in edits.iter().enumerate() {
+            if let Primitive::Group { edit_count } = edit.primitive {
+                let saved = if wgsl {
+                    format!("let group_{} = vec4<f32>(distance, color);", index)
+                } else {
+                    format!("vec4 group_{} = vec4(distance, color);", index)
+                };
+                let first_color = edits.get(index + 1).map_or(edit.color, |edit| edit.color);
+                body += &format!(
+                    "\n    // {}: {} Group of {} edits\n    {}\n    distance = 1000000.0;\n    color = {};\n",
+                    index,
+                    operation_name(&edit.operation),
+                    edit_count,
+                    saved,
+                    self.vec3(first_color.truncate()),
+                );
+                groups.push((index, index + 1 + edit_count as usize));
+            } else {
+                let dimensions = glam::Vec4::from(edit.primitive.dimension_data());
+                body += &format!(
+                    "\n    // {}: {} {}\n    res = {}(distance, {}({}, {}), {});\n    distance = res.x;\n    color = mix(color, {}, res.y * {});\n",
+                    index,
+                    operation_name(&edit.operation),
+                    edit.primitive.as_type().as_ref(),
+                    blend_function_name(&edit.operation),
+                    primitive_function_name(&edit.primitive),
+                    self.local_position(edit),
+                    self.vec4(dimensions),
+                    float(edit.blending),
+                    self.vec3(edit.color.truncate()),
+                    float(edit.color.w),
+                );
+            }
+
+            // Groups ending with this edit are combined with edits preceding them
+            while let Some((group_index, end)) = groups.pop() {
+                if end != index + 1 {
+                    groups.push((group_index, end));
+                    break;
+                }
+                let group = &edits[group_index];
+                body += &format!(
+                    "\n    // End of group {}\n    res = {}(group_{}.x, distance, {});\n    distance = res.x;\n    color = mix(group_{}.yzw, color, res.y * {});\n",
+                    group_index,
+                    blend_function_name(&group.operation),
+                    group_index,
+                    float(group.blending),
+                    group_index,
+                    float(group.color.w),
+                );
+            }
+        }
+
+        body += if wgsl {
+            "\n    return vec4<f32>(distance, color);\n}\n"
+        } else {
+            "\n    return vec4(distance, color);\n}\n"
+        };
+        body
+    }
+
+    /// Expression transforming `p` into local space of an edit, same as `transform_pos` in the kernel.
+    fn local_position(&self, edit: &Edit) -> String {
+        let inverse = edit.transform.as_mat().inverse();
+        let translation = inverse.w_axis.truncate();
+        let linear = glam::Mat3::from_mat4(inverse);
+        if linear.abs_diff_eq(glam::Mat3::IDENTITY, 1e-6) {
+            if translation == glam::Vec3::ZERO {
+                return "p".to_string();
+            }
+            return format!("p + {}", self.vec3(translation));
+        }
+        let matrix = match self.language {
+            ShaderLanguage::Wgsl => format!(
+                "mat3x3<f32>({}, {}, {})",
+                self.vec3(linear.x_axis),
+                self.vec3(linear.y_axis),
+                self.vec3(linear.z_axis)
+            ),
+            ShaderLanguage::Glsl => format!(
+                "mat3({}, {}, {})",
+                self.vec3(linear.x_axis),
+                self.vec3(linear.y_axis),
+                self.vec3(linear.z_axis)
+            ),
+        };
+        format!("{} * p + {}", matrix, self.vec3(translation))
+    }
+
+    /// Fullscreen raymarcher with a camera looking at the center of edits from a distance fitting them on screen.
+    fn raymarcher(&self, edits: &[Edit]) -> String {
+        let aabb = edits
+            .iter()
+            .map(|edit| edit.aabb())
+            .reduce(|a, b| a.add(&b))
+            .unwrap_or(AABB::new(glam::Vec3::splat(-1.0), glam::Vec3::ONE));
+        let target = (aabb.min + aabb.max) * 0.5;
+        let radius = ((aabb.max - aabb.min).length() * 0.5).max(0.01);
+        let position = target + glam::Vec3::new(0.6, 0.5, 1.0).normalize() * radius * 3.0;
+        let max_distance = radius * 6.0;
+
+        let constants = match self.language {
+            ShaderLanguage::Wgsl => format!(
+                "\nconst CAMERA_POSITION = {};\nconst CAMERA_TARGET = {};\nconst MAX_DISTANCE = {};\nconst MAX_STEPS = {};\n",
+                self.vec3(position),
+                self.vec3(target),
+                float(max_distance),
+                Self::MAX_STEPS
+            ),
+            ShaderLanguage::Glsl => format!(
+                "\nconst vec3 CAMERA_POSITION = {};\nconst vec3 CAMERA_TARGET = {};\nconst float MAX_DISTANCE = {};\nconst int MAX_STEPS = {};\n",
+                self.vec3(position),
+                self.vec3(target),
+                float(max_distance),
+                Self::MAX_STEPS
+            ),
+        };
+        constants
+            + match self.language {
+                ShaderLanguage::Wgsl => WGSL_RAYMARCHER,
+                ShaderLanguage::Glsl => GLSL_RAYMARCHER,
+            }
+    }
+
+    fn vec3(&self, v: glam::Vec3) -> String {
+        let name = match self.language {
+            ShaderLanguage::Wgsl => "vec3<f32>",
+            ShaderLanguage::Glsl => "vec3",
+        };
+        format!("{}({}, {}, {})", name, float(v.x), float(v.y), float(v.z))
+    }
+
+    fn vec4(&self, v: glam::Vec4) -> String {
+        let name = match self.language {
+            ShaderLanguage::Wgsl => "vec4<f32>",
+            ShaderLanguage::Glsl => "vec4",
+        };
+        format!(
+            "{}({}, {}, {}, {})",
+            name,
+            float(v.x),
+            float(v.y),
+            float(v.z),
+            float(v.w)
+        )
+    }
+}
+
+fn blend_function_name(operation: &Operation) -> &'static str {
+    match operation {
+        Operation::Add => "smooth_volume_add",
+        Operation::Subtract => "smooth_volume_difference",
+        Operation::Intersect => "smooth_volume_intersection",
+    }
+}
+
+fn operation_name(operation: &Operation) -> &'static str {
+    match operation {
+        Operation::Add => "Add",
+        Operation::Subtract => "Subtract",
+        Operation::Intersect => "Intersect",
+    }
+}
+
+/// Float literal valid in both WGSL and GLSL, it always has a decimal point or an exponent.
+fn float(value: f32) -> String {
+    format!("{:?}", value)
+}
+
+fn primitive_function_name(primitive: &Primitive) -> &'static str {
+    match primitive {
+        Primitive::Sphere { .. } => "sd_sphere",
+        Primitive::Cube { .. } => "sd_cube",
+        Primitive::Cylinder { .. } => "sd_cylinder",
+        Primitive::Torus { .. } => "sd_torus",
+        Primitive::Cone { .. } => "sd_cone",
+        Primitive::Capsule { .. } => "sd_capsule",
+        Primitive::GeometryRef(_) | Primitive::Group { .. } => "",
+    }
+}
+
+// =================================================================================================
+// WGSL
+// =================================================================================================
+
+const WGSL_RAYMARCHER: &str = "
+// Size of the render target in pixels
+@group(0) @binding(0) var<uniform> resolution: vec2<f32>;
+
+fn calc_normal(p: vec3<f32>) -> vec3<f32> {
+    let e = vec2<f32>(0.0005, 0.0);
+    return normalize(vec3<f32>(
+        map(p + e.xyy).x - map(p - e.xyy).x,
+        map(p + e.yxy).x - map(p - e.yxy).x,
+        map(p + e.yyx).x - map(p - e.yyx).x,
+    ));
+}
+
+// Fullscreen triangle, draw with 3 vertices
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
+    let uv = (vec2<f32>(frag_coord.x, resolution.y - frag_coord.y) * 2.0 - resolution) / resolution.y;
+    let forward = normalize(CAMERA_TARGET - CAMERA_POSITION);
+    let right = normalize(cross(forward, vec3<f32>(0.0, 1.0, 0.0)));
+    let up = cross(right, forward);
+    let direction = normalize(forward * 2.0 + right * uv.x + up * uv.y);
+
+    var t = 0.0;
+    for (var i = 0; i < MAX_STEPS; i = i + 1) {
+        let p = CAMERA_POSITION + direction * t;
+        let hit = map(p);
+        if (hit.x < 0.0005 * t) {
+            let light = max(dot(calc_normal(p), normalize(vec3<f32>(0.6, 0.8, 0.4))), 0.0);
+            return vec4<f32>(hit.yzw * (light * 0.8 + 0.2), 1.0);
+        }
+        t = t + hit.x;
+        if (t > MAX_DISTANCE) {
+            break;
+        }
+    }
+    return vec4<f32>(0.1, 0.1, 0.12, 1.0);
+}
+";
+
+// =================================================================================================
+// GLSL
+// =================================================================================================
+
+/// Translation of `SDF_PRIMITIVES_SHADER`, it has to be kept in sync with the WGSL source.
+const GLSL_SDF_PRIMITIVES: &str = include_str!("_sdf_primitives.glsl");
+
+const GLSL_RAYMARCHER: &str = "
+vec3 calc_normal(vec3 p) {
+    vec2 e = vec2(0.0005, 0.0);
+    return normalize(vec3(
+        map(p + e.xyy).x - map(p - e.xyy).x,
+        map(p + e.yxy).x - map(p - e.yxy).x,
+        map(p + e.yyx).x - map(p - e.yyx).x
+    ));
+}
+
+void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+    vec2 uv = (fragCoord * 2.0 - iResolution.xy) / iResolution.y;
+    vec3 forward = normalize(CAMERA_TARGET - CAMERA_POSITION);
+    vec3 right = normalize(cross(forward, vec3(0.0, 1.0, 0.0)));
+    vec3 up = cross(right, forward);
+    vec3 direction = normalize(forward * 2.0 + right * uv.x + up * uv.y);
+
+    fragColor = vec4(0.1, 0.1, 0.12, 1.0);
+    float t = 0.0;
+    for (int i = 0; i < MAX_STEPS; i++) {
+        vec3 p = CAMERA_POSITION + direction * t;
+        vec4 hit = map(p);
+        if (hit.x < 0.0005 * t) {
+            float light = max(dot(calc_normal(p), normalize(vec3(0.6, 0.8, 0.4))), 0.0);
+            fragColor = vec4(hit.yzw * (light * 0.8 + 0.2), 1.0);
+            break;
+        }
+        t += hit.x;
+        if (t > MAX_DISTANCE) {
+            break;
+        }
+    }
+}
+";
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use naga::{BinaryOperator, Expression, Handle, MathFunction, Statement};
+    use strum::IntoEnumIterator;
+
+    use super::*;
+    use crate::{
+        framework::math::Transform,
+        sdf::geometry::{sphere_edit, EditSampler},
+    };
+
+    fn edit(primitive: Primitive, operation: Operation, transform: Transform) -> Edit {
+        Edit {
+            primitive,
+            operation,
+            transform,
+            blending: 0.1,
+            color: glam::Vec4::new(0.8, 0.2, 0.1, 1.0),
+        }
+    }
+
+    /// One edit of each primitive, placed by translations and rotations and subtracted from each other.
+    fn all_primitive_edits() -> Vec<Edit> {
+        let rotated = Transform::from_xyz(0.5, 0.0, 0.0)
+            .with_rotation(glam::Quat::from_rotation_z(std::f32::consts::FRAC_PI_4));
+        vec![
+            edit(
+                Primitive::Sphere { radius: 0.5 },
+                Operation::Add,
+                Transform::default(),
+            ),
+            edit(
+                Primitive::default_cube(),
+                Operation::Subtract,
+                rotated.clone(),
+            ),
+            edit(
+                Primitive::Cylinder {
+                    diameter: 0.5,
+                    height: 1.0,
+                },
+                Operation::Add,
+                Transform::from_xyz(0.0, 1.0, 0.0),
+            ),
+            edit(
+                Primitive::Torus {
+                    inner_radius: 0.4,
+                    outer_radius: 0.1,
+                },
+                Operation::Add,
+                rotated,
+            ),
+            edit(
+                Primitive::Cone {
+                    diameter: 0.5,
+                    height: 1.0,
+                },
+                Operation::Subtract,
+                Transform::from_xyz(0.0, -1.0, 0.0),
+            ),
+            edit(
+                Primitive::Capsule {
+                    radius: 0.2,
+                    height: 1.0,
+                },
+                Operation::Add,
+                Transform::default(),
+            ),
+        ]
+    }
+
+    fn validate_wgsl(source: &str) {
+        let module = match naga::front::wgsl::parse_str(source) {
+            Ok(module) => module,
+            Err(err) => panic!("{}\n{}", err.emit_to_string(source), source),
+        };
+        let mut validator = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        );
+        if let Err(err) = validator.validate(&module) {
+            panic!("{:?}\n{}", err, source);
+        }
+    }
+
+    #[test]
+    fn generates_valid_wgsl() {
+        let edits = all_primitive_edits();
+        validate_wgsl(
+            &ShaderSource::new(ShaderLanguage::Wgsl)
+                .generate(&edits)
+                .unwrap(),
+        );
+        validate_wgsl(
+            &ShaderSource::new(ShaderLanguage::Wgsl)
+                .with_raymarcher(true)
+                .generate(&edits)
+                .unwrap(),
+        );
+        validate_wgsl(
+            &ShaderSource::new(ShaderLanguage::Wgsl)
+                .with_raymarcher(true)
+                .generate(&[])
+                .unwrap(),
+        );
+    }
+
+    #[test]
+    fn emits_map_without_raymarcher() {
+        let edits = [sphere_edit(
+            0.5,
+            Operation::Add,
+            glam::Vec3::new(1.0, 0.0, 0.0),
+        )];
+        for language in ShaderLanguage::iter() {
+            let source = ShaderSource::new(language).generate(&edits).unwrap();
+            assert!(source.contains("sd_sphere(p + "));
+            assert!(source.contains("smooth_volume_intersection("));
+            assert!(!source.contains("mainImage"));
+            assert!(!source.contains("CAMERA_POSITION"));
+        }
+    }
+
+    /// Value of an expression interpreted by `MapInterpreter`.
+    #[derive(Debug, Clone)]
+    enum Value {
+        /// Scalar or vector of floats.
+        Float(Vec<f32>),
+        Bool(bool),
+        /// Columns of a matrix.
+        Matrix(Vec<Vec<f32>>),
+        /// Local variable or one of its components.
+        Pointer(Handle<naga::LocalVariable>, Option<u32>),
+    }
+
+    impl Value {
+        fn floats(&self) -> &[f32] {
+            match self {
+                Value::Float(values) => values,
+                other => panic!("Expected floats, got {:?}", other),
+            }
+        }
+    }
+
+    /// Component of a vector, scalars are broadcast to any component.
+    fn component(values: &[f32], index: usize) -> f32 {
+        values[index.min(values.len() - 1)]
+    }
+
+    fn componentwise(a: &[f32], b: &[f32], f: impl Fn(f32, f32) -> f32) -> Value {
+        let len = a.len().max(b.len());
+        Value::Float(
+            (0..len)
+                .map(|i| f(component(a, i), component(b, i)))
+                .collect(),
+        )
+    }
+
+    /// State of a function being interpreted.
+    struct Frame<'a> {
+        function: &'a naga::Function,
+        arguments: Vec<Value>,
+        /// Values of emitted expressions and call results.
+        values: HashMap<Handle<Expression>, Value>,
+        locals: HashMap<Handle<naga::LocalVariable>, Value>,
+    }
+
+    /// Evaluates `map` of a generated WGSL module on CPU.
+    ///   - Only the subset of WGSL generated for edit lists and used by `SDF_PRIMITIVES_SHADER` is supported.
+    struct MapInterpreter {
+        module: naga::Module,
+    }
+
+    impl MapInterpreter {
+        fn new(source: &str) -> Self {
+            Self {
+                module: naga::front::wgsl::parse_str(source).unwrap(),
+            }
+        }
+
+        fn map(&self, position: glam::Vec3) -> glam::Vec4 {
+            let (_, function) = self
+                .module
+                .functions
+                .iter()
+                .find(|(_, function)| function.name.as_deref() == Some("map"))
+                .unwrap();
+            let result = self.call(function, vec![Value::Float(position.to_array().to_vec())]);
+            glam::Vec4::from_slice(result.floats())
+        }
+
+        fn call(&self, function: &naga::Function, arguments: Vec<Value>) -> Value {
+            let mut frame = Frame {
+                function,
+                arguments,
+                values: HashMap::new(),
+                locals: HashMap::new(),
+            };
+            for (handle, local) in function.local_variables.iter() {
+                if let Some(init) = local.init {
+                    frame.locals.insert(handle, self.constant(init));
+                }
+            }
+            self.execute(&mut frame, &function.body)
+                .expect("Function ended without returning a value")
+        }
+
+        /// Returns the returned value once a return statement is reached.
+        fn execute(&self, frame: &mut Frame, block: &naga::Block) -> Option<Value> {
+            for statement in block.iter() {
+                match *statement {
+                    Statement::Emit(ref range) => {
+                        for handle in range.clone() {
+                            let value = self.evaluate(frame, handle);
+                            frame.values.insert(handle, value);
+                        }
+                    }
+                    Statement::Block(ref block) => {
+                        if let Some(value) = self.execute(frame, block) {
+                            return Some(value);
+                        }
+                    }
+                    Statement::Store { pointer, value } => {
+                        let value = self.value(frame, value);
+                        match self.value(frame, pointer) {
+                            Value::Pointer(local, None) => frame.locals.insert(local, value),
+                            other => panic!("Unsupported store into {:?}", other),
+                        };
+                    }
+                    Statement::Call {
+                        function,
+                        ref arguments,
+                        result,
+                    } => {
+                        let arguments = arguments
+                            .iter()
+                            .map(|argument| self.value(frame, *argument))
+                            .collect();
+                        let value = self.call(&self.module.functions[function], arguments);
+                        if let Some(result) = result {
+                            frame.values.insert(result, value);
+                        }
+                    }
+                    Statement::Return { value: Some(value) } => {
+                        return Some(self.value(frame, value))
+                    }
+                    ref other => panic!("Unsupported statement {:?}", other),
+                }
+            }
+            None
+        }
+
+        fn value(&self, frame: &Frame, handle: Handle<Expression>) -> Value {
+            match frame.values.get(&handle) {
+                Some(value) => value.clone(),
+                None => self.evaluate(frame, handle),
+            }
+        }
+
+        fn evaluate(&self, frame: &Frame, handle: Handle<Expression>) -> Value {
+            match frame.function.expressions[handle] {
+                Expression::Constant(constant) => self.constant(constant),
+                Expression::FunctionArgument(index) => frame.arguments[index as usize].clone(),
+                Expression::LocalVariable(local) => Value::Pointer(local, None),
+                Expression::Load { pointer } => match self.value(frame, pointer) {
+                    Value::Pointer(local, None) => frame.locals[&local].clone(),
+                    Value::Pointer(local, Some(index)) => {
+                        Value::Float(vec![frame.locals[&local].floats()[index as usize]])
+                    }
+                    other => panic!("Unsupported load from {:?}", other),
+                },
+                Expression::AccessIndex { base, index } => match self.value(frame, base) {
+                    Value::Pointer(local, None) => Value::Pointer(local, Some(index)),
+                    Value::Float(values) => Value::Float(vec![values[index as usize]]),
+                    Value::Matrix(columns) => Value::Float(columns[index as usize].clone()),
+                    other => panic!("Unsupported access into {:?}", other),
+                },
+                Expression::Splat { size, value } => {
+                    Value::Float(vec![self.value(frame, value).floats()[0]; size as usize])
+                }
+                Expression::Swizzle {
+                    size,
+                    vector,
+                    pattern,
+                } => {
+                    let vector = self.value(frame, vector);
+                    Value::Float(
+                        pattern[..size as usize]
+                            .iter()
+                            .map(|component| vector.floats()[*component as usize])
+                            .collect(),
+                    )
+                }
+                Expression::Compose { ty, ref components } => self.compose(
+                    ty,
+                    components
+                        .iter()
+                        .map(|component| self.value(frame, *component))
+                        .collect(),
+                ),
+                Expression::Unary {
+                    op: naga::UnaryOperator::Negate,
+                    expr,
+                } => Value::Float(
+                    self.value(frame, expr)
+                        .floats()
+                        .iter()
+                        .map(|value| -value)
+                        .collect(),
+                ),
+                Expression::Binary { op, left, right } => {
+                    Self::binary(op, self.value(frame, left), self.value(frame, right))
+                }
+                Expression::Select {
+                    condition,
+                    accept,
+                    reject,
+                } => match self.value(frame, condition) {
+                    Value::Bool(true) => self.value(frame, accept),
+                    Value::Bool(false) => self.value(frame, reject),
+                    other => panic!("Expected a condition, got {:?}", other),
+                },
+                Expression::Math {
+                    fun,
+                    arg,
+                    arg1,
+                    arg2,
+                    ..
+                } => {
+                    let args: Vec<Value> = [Some(arg), arg1, arg2]
+                        .into_iter()
+                        .flatten()
+                        .map(|arg| self.value(frame, arg))
+                        .collect();
+                    Self::math(fun, &args)
+                }
+                ref other => panic!("Unsupported expression {:?}", other),
+            }
+        }
+
+        fn constant(&self, handle: Handle<naga::Constant>) -> Value {
+            match self.module.constants[handle].inner {
+                naga::ConstantInner::Scalar {
+                    value: naga::ScalarValue::Float(value),
+                    ..
+                } => Value::Float(vec![value as f32]),
+                naga::ConstantInner::Scalar {
+                    value: naga::ScalarValue::Bool(value),
+                    ..
+                } => Value::Bool(value),
+                naga::ConstantInner::Composite { ty, ref components } => self.compose(
+                    ty,
+                    components
+                        .iter()
+                        .map(|component| self.constant(*component))
+                        .collect(),
+                ),
+                ref other => panic!("Unsupported constant {:?}", other),
+            }
+        }
+
+        fn compose(&self, ty: Handle<naga::Type>, components: Vec<Value>) -> Value {
+            match self.module.types[ty].inner {
+                naga::TypeInner::Matrix { .. } => Value::Matrix(
+                    components
+                        .iter()
+                        .map(|column| column.floats().to_vec())
+                        .collect(),
+                ),
+                _ => Value::Float(
+                    components
+                        .iter()
+                        .flat_map(|component| component.floats().to_vec())
+                        .collect(),
+                ),
+            }
+        }
+
+        fn binary(op: BinaryOperator, left: Value, right: Value) -> Value {
+            match (op, &left, &right) {
+                (BinaryOperator::Multiply, Value::Matrix(columns), Value::Float(vector)) => {
+                    Value::Float(
+                        (0..columns[0].len())
+                            .map(|row| {
+                                columns
+                                    .iter()
+                                    .zip(vector)
+                                    .map(|(column, value)| column[row] * value)
+                                    .sum()
+                            })
+                            .collect(),
+                    )
+                }
+                (BinaryOperator::Less, Value::Float(a), Value::Float(b)) => {
+                    Value::Bool(a[0] < b[0])
+                }
+                (BinaryOperator::Greater, Value::Float(a), Value::Float(b)) => {
+                    Value::Bool(a[0] > b[0])
+                }
+                (BinaryOperator::Add, Value::Float(a), Value::Float(b)) => {
+                    componentwise(a, b, |a, b| a + b)
+                }
+                (BinaryOperator::Subtract, Value::Float(a), Value::Float(b)) => {
+                    componentwise(a, b, |a, b| a - b)
+                }
+                (BinaryOperator::Multiply, Value::Float(a), Value::Float(b)) => {
+                    componentwise(a, b, |a, b| a * b)
+                }
+                (BinaryOperator::Divide, Value::Float(a), Value::Float(b)) => {
+                    componentwise(a, b, |a, b| a / b)
+                }
+                _ => panic!("Unsupported {:?} of {:?} and {:?}", op, left, right),
+            }
+        }
+
+        fn math(fun: MathFunction, args: &[Value]) -> Value {
+            let arg = |index: usize| args[index].floats();
+            match fun {
+                MathFunction::Abs => componentwise(arg(0), &[0.0], |a, _| a.abs()),
+                MathFunction::Min => componentwise(arg(0), arg(1), f32::min),
+                MathFunction::Max => componentwise(arg(0), arg(1), f32::max),
+                MathFunction::Clamp => {
+                    let low = componentwise(arg(0), arg(1), f32::max);
+                    componentwise(low.floats(), arg(2), f32::min)
+                }
+                MathFunction::Mix => {
+                    let difference = componentwise(arg(1), arg(0), |b, a| b - a);
+                    let scaled = componentwise(difference.floats(), arg(2), |d, t| d * t);
+                    componentwise(arg(0), scaled.floats(), |a, s| a + s)
+                }
+                MathFunction::Dot => {
+                    Value::Float(vec![arg(0).iter().zip(arg(1)).map(|(a, b)| a * b).sum()])
+                }
+                MathFunction::Length => {
+                    Value::Float(vec![arg(0).iter().map(|a| a * a).sum::<f32>().sqrt()])
+                }
+                _ => panic!("Unsupported function {:?}", fun),
+            }
+        }
+    }
+
+    #[test]
+    fn generated_wgsl_samples_same_as_edit_sampler() {
+        let mut edits = all_primitive_edits();
+        edits.push(edit(
+            Primitive::Group { edit_count: 2 },
+            Operation::Intersect,
+            Transform::default(),
+        ));
+        edits.extend(all_primitive_edits().into_iter().skip(2).take(2));
+
+        let interpreter = MapInterpreter::new(
+            &ShaderSource::new(ShaderLanguage::Wgsl)
+                .generate(&edits)
+                .unwrap(),
+        );
+        let sampler = EditSampler::new(&edits).unwrap();
+        for x in -6..=6 {
+            for y in -6..=6 {
+                for z in -6..=6 {
+                    let position = glam::Vec3::new(x as f32, y as f32, z as f32) * 0.3;
+                    let expected = sampler.sample(position);
+                    let sampled = interpreter.map(position);
+                    assert!(
+                        (sampled.x - expected.distance).abs() < 1e-4,
+                        "Distance {} at {} differs from {}",
+                        sampled.x,
+                        position,
+                        expected.distance
+                    );
+                    let color = glam::Vec3::new(sampled.y, sampled.z, sampled.w);
+                    assert!(
+                        color.abs_diff_eq(expected.color.truncate(), 1e-4),
+                        "Color {} at {} differs from {}",
+                        color,
+                        position,
+                        expected.color
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generates_shadertoy_glsl() {
+        let source = ShaderSource::new(ShaderLanguage::Glsl)
+            .with_raymarcher(true)
+            .generate(&all_primitive_edits())
+            .unwrap();
+
+        assert!(source.contains("vec4 map(vec3 p)"));
+        assert!(source.contains("void mainImage(out vec4 fragColor, in vec2 fragCoord)"));
+        assert!(source.contains("mat3("));
+        assert_eq!(
+            source.matches("smooth_volume_difference(distance").count(),
+            2
+        );
+        assert!(!source.contains("<f32>"));
+        assert!(!source.contains("let "));
+    }
+
+    #[test]
+    fn generates_groups_combined_by_their_operation() {
+        let mut edits = vec![edit(
+            Primitive::default_cube(),
+            Operation::Add,
+            Transform::default(),
+        )];
+        edits.push(edit(
+            Primitive::Group { edit_count: 2 },
+            Operation::Subtract,
+            Transform::default(),
+        ));
+        edits.extend(all_primitive_edits().into_iter().take(2));
+
+        validate_wgsl(
+            &ShaderSource::new(ShaderLanguage::Wgsl)
+                .generate(&edits)
+                .unwrap(),
+        );
+        let source = ShaderSource::new(ShaderLanguage::Glsl)
+            .generate(&edits)
+            .unwrap();
+        assert!(source.contains("vec4 group_1 = vec4(distance, color);"));
+        assert!(source.contains("res = smooth_volume_difference(group_1.x, distance, 0.1);"));
+    }
+
+    #[test]
+    fn rejects_references_and_unfinished_groups() {
+        let reference = edit(
+            Primitive::GeometryRef(Default::default()),
+            Operation::Add,
+            Transform::default(),
+        );
+        assert!(ShaderSource::new(ShaderLanguage::Wgsl)
+            .generate(&[reference])
+            .is_err());
+
+        let group = edit(
+            Primitive::Group { edit_count: 2 },
+            Operation::Add,
+            Transform::default(),
+        );
+        assert!(ShaderSource::new(ShaderLanguage::Wgsl)
+            .generate(&[group])
+            .is_err());
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+
+use crate::{
+    framework::math::{Transform, AABB},
+    sdf::{
+        geometry::{Edit, EditSampler, Geometry, GeometryID, GeometryPool},
+        mesh::SurfaceSampler,
+        query::InstanceSdf,
+    },
+    warn,
+};
+
+/// A point where one instance penetrates another one.
+#[derive(Debug, Clone, Copy)]
+pub struct Contact {
+    /// World position of a surface point of one instance lying inside of the other instance.
+    pub position: glam::Vec3,
+    /// World normal pointing from the second instance towards the first one,
+    /// moving the first instance along it by the depth separates them at this contact.
+    pub normal: glam::Vec3,
+    /// Penetration depth in world units.
+    pub depth: f32,
+}
+
+/// Contacts between two instances of a broad phase, instances are identified by keys given by the caller.
+#[derive(Debug, Clone)]
+pub struct ContactPair<K> {
+    pub a: K,
+    pub b: K,
+    pub contacts: Vec<Contact>,
+}
+
+impl<K> ContactPair<K> {
+    pub fn deepest_contact(&self) -> Option<&Contact> {
+        self.contacts
+            .iter()
+            .max_by(|a, b| a.depth.total_cmp(&b.depth))
+    }
+}
+
+/// Points spread over the surface of a geometry in its local space together with edits they were sampled from.
+struct CollisionShape {
+    edits: Vec<Edit>,
+    points: Vec<glam::Vec3>,
+}
+
+/// Detects contacts between instances of geometries on CPU.
+///   - Surface points of each instance are tested against the SDF of the other instance in the overlap of their AABBs,
+///     so contacts are found even when one instance is much smaller than the other.
+///   - Surface points are sampled once per geometry and kept until edits of the geometry change.
+pub struct CollisionDetector {
+    /// Number of surface points sampled on each geometry, finer features than their spacing may be missed.
+    point_count: usize,
+    shapes: HashMap<GeometryID, CollisionShape>,
+}
+
+impl CollisionDetector {
+    pub const DEFAULT_POINT_COUNT: usize = 2000;
+
+    pub fn new(point_count: usize) -> Self {
+        Self {
+            point_count: point_count.max(1),
+            shapes: HashMap::new(),
+        }
+    }
+
+    /// Finds contacts between two instances, empty when they do not penetrate each other.
+    #[profiler::function]
+    pub fn collide(
+        &mut self,
+        geometry_pool: &GeometryPool,
+        a: (GeometryID, &Transform),
+        b: (GeometryID, &Transform),
+    ) -> Vec<Contact> {
+        let (Some(geometry_a), Some(geometry_b)) = (geometry_pool.get(a.0), geometry_pool.get(b.0))
+        else {
+            return vec![];
+        };
+        let Some(overlap) = intersection(
+            &geometry_a.total_aabb().transform(a.1),
+            &geometry_b.total_aabb().transform(b.1),
+        ) else {
+            return vec![];
+        };
+        if geometry_a.edits().is_empty() || geometry_b.edits().is_empty() {
+            return vec![];
+        }
+
+        self.update_shape(a.0, geometry_a);
+        self.update_shape(b.0, geometry_b);
+        let (sdf_a, sdf_b) = match (
+            InstanceSdf::new(geometry_a.edits(), a.1),
+            InstanceSdf::new(geometry_b.edits(), b.1),
+        ) {
+            (Ok(sdf_a), Ok(sdf_b)) => (sdf_a, sdf_b),
+            (Err(_err), _) | (_, Err(_err)) => {
+                warn!("Cannot collide geometries {:?} and {:?}: {}", a.0, b.0, _err);
+                return vec![];
+            }
+        };
+
+        // Normals of contacts found on the second instance are flipped to keep pointing towards the first one
+        let mut contacts = self.penetrating_points(a.0, a.1, &sdf_b, &overlap, 1.0);
+        contacts.extend(self.penetrating_points(b.0, b.1, &sdf_a, &overlap, -1.0));
+        contacts
+    }
+
+    /// Finds contacts of an instance with a horizontal ground plane, normals point up from the plane.
+    pub fn ground_contacts(
+        &mut self,
+        geometry_pool: &GeometryPool,
+        instance: (GeometryID, &Transform),
+        height: f32,
+    ) -> Vec<Contact> {
+        let Some(geometry) = geometry_pool.get(instance.0) else {
+            return vec![];
+        };
+        if geometry.edits().is_empty()
+            || geometry.total_aabb().transform(instance.1).min.y >= height
+        {
+            return vec![];
+        }
+
+        self.update_shape(instance.0, geometry);
+        let Some(shape) = self.shapes.get(&instance.0) else {
+            return vec![];
+        };
+        let to_world = instance.1.as_mat();
+        shape
+            .points
+            .iter()
+            .map(|point| to_world.transform_point3(*point))
+            .filter(|position| position.y < height)
+            .map(|position| Contact {
+                position,
+                normal: glam::Vec3::Y,
+                depth: height - position.y,
+            })
+            .collect()
+    }
+
+    /// Finds contacts of all pairs of instances with overlapping AABBs.
+    ///   - Pairs are found by sweep and prune along the x axis.
+    pub fn contact_pairs<K: Copy>(
+        &mut self,
+        geometry_pool: &GeometryPool,
+        instances: &[(K, GeometryID, Transform)],
+    ) -> Vec<ContactPair<K>> {
+        self.contact_pairs_where(geometry_pool, instances, |_, _| true)
+    }
+
+    /// Finds contacts of pairs of instances with overlapping AABBs which are accepted by `accept`.
+    ///   - Rejected pairs are skipped before the narrow phase, so their surface points are not tested at all.
+    #[profiler::function]
+    pub fn contact_pairs_where<K: Copy>(
+        &mut self,
+        geometry_pool: &GeometryPool,
+        instances: &[(K, GeometryID, Transform)],
+        accept: impl Fn(K, K) -> bool,
+    ) -> Vec<ContactPair<K>> {
+        let mut bounds: Vec<(usize, AABB)> = instances
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (_, geometry_id, transform))| {
+                let geometry = geometry_pool.get(*geometry_id)?;
+                Some((index, geometry.total_aabb().transform(transform)))
+            })
+            .collect();
+        bounds.sort_by(|(_, a), (_, b)| a.min.x.total_cmp(&b.min.x));
+
+        let mut pairs = vec![];
+        let mut active: Vec<(usize, AABB)> = vec![];
+        for (index, aabb) in bounds.into_iter() {
+            active.retain(|(_, other)| other.max.x >= aabb.min.x);
+            for (other_index, other) in active.iter() {
+                if intersection(&aabb, other).is_none() {
+                    continue;
+                }
+                let (key_a, geometry_a, transform_a) = &instances[*other_index];
+                let (key_b, geometry_b, transform_b) = &instances[index];
+                if !accept(*key_a, *key_b) {
+                    continue;
+                }
+                let contacts = self.collide(
+                    geometry_pool,
+                    (*geometry_a, transform_a),
+                    (*geometry_b, transform_b),
+                );
+                if !contacts.is_empty() {
+                    pairs.push(ContactPair {
+                        a: *key_a,
+                        b: *key_b,
+                        contacts,
+                    });
+                }
+            }
+            active.push((index, aabb));
+        }
+
+        // Forget shapes of geometries which were removed
+        self.shapes
+            .retain(|geometry_id, _| geometry_pool.contains_key(*geometry_id));
+        pairs
+    }
+
+    /// Samples surface points of a geometry when it has no shape yet or its edits have changed.
+    fn update_shape(&mut self, geometry_id: GeometryID, geometry: &Geometry) {
+        let edits = geometry.edits();
+        if self
+            .shapes
+            .get(&geometry_id)
+            .is_some_and(|shape| shape.edits == edits)
+        {
+            return;
+        }
+        let points = match EditSampler::new(edits) {
+            Ok(sampler) => SurfaceSampler::new(|position: glam::Vec3| {
+                let sample = sampler.sample(position);
+                (sample.distance, sample.color)
+            })
+            .sample(geometry.total_aabb(), self.point_count)
+            .map_or(vec![], |cloud| cloud.positions),
+            Err(_err) => {
+                warn!("Cannot sample shape of geometry {:?}: {}", geometry_id, _err);
+                vec![]
+            }
+        };
+        self.shapes.insert(
+            geometry_id,
+            CollisionShape {
+                edits: edits.to_vec(),
+                points,
+            },
+        );
+    }
+
+    /// Contacts at surface points of an instance which lie in the overlap and inside of the other instance.
+    fn penetrating_points(
+        &self,
+        geometry_id: GeometryID,
+        transform: &Transform,
+        other: &InstanceSdf,
+        overlap: &AABB,
+        normal_sign: f32,
+    ) -> Vec<Contact> {
+        let Some(shape) = self.shapes.get(&geometry_id) else {
+            return vec![];
+        };
+        let to_world = transform.as_mat();
+        shape
+            .points
+            .iter()
+            .map(|point| to_world.transform_point3(*point))
+            .filter(|position| {
+                position.cmpge(overlap.min).all() && position.cmple(overlap.max).all()
+            })
+            .filter_map(|position| {
+                let sample = other.sample(position);
+                (sample.distance < 0.0).then(|| Contact {
+                    position,
+                    normal: sample.gradient.normalize_or_zero() * normal_sign,
+                    depth: -sample.distance,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Default for CollisionDetector {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_POINT_COUNT)
+    }
+}
+
+fn intersection(a: &AABB, b: &AABB) -> Option<AABB> {
+    let min = a.min.max(b.min);
+    let max = a.max.min(b.max);
+    min.cmple(max).all().then(|| AABB::new(min, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::sdf::geometry::{sphere_edit, Operation};
+
+    const RADIUS: f32 = 0.5;
+
+    fn sphere_pool() -> (GeometryPool, GeometryID) {
+        let mut geometry_pool = GeometryPool::with_key();
+        let geometry_id = geometry_pool.insert(Geometry::new(0.01).with_edits(vec![sphere_edit(
+            RADIUS,
+            Operation::Add,
+            glam::Vec3::ZERO,
+        )]));
+        (geometry_pool, geometry_id)
+    }
+
+    #[test]
+    fn overlapping_spheres_have_contacts() {
+        let (geometry_pool, geometry_id) = sphere_pool();
+        let mut detector = CollisionDetector::default();
+        let a = Transform::default();
+        let b = Transform::from_xyz(1.8 * RADIUS, 0.0, 0.0);
+        let contacts = detector.collide(&geometry_pool, (geometry_id, &a), (geometry_id, &b));
+
+        assert!(!contacts.is_empty());
+        for contact in contacts.iter() {
+            assert!(contact.depth > 0.0 && contact.depth <= 0.2 * RADIUS + 0.02);
+            // Normals point from the second sphere towards the first one
+            assert!(contact.normal.x < 0.0, "normal {}", contact.normal);
+        }
+    }
+
+    #[test]
+    fn separated_spheres_have_no_contacts() {
+        let (geometry_pool, geometry_id) = sphere_pool();
+        let mut detector = CollisionDetector::default();
+        let a = Transform::default();
+        let b = Transform::from_xyz(2.2 * RADIUS, 0.0, 0.0);
+        let contacts = detector.collide(&geometry_pool, (geometry_id, &a), (geometry_id, &b));
+
+        assert!(contacts.is_empty());
+    }
+
+    #[test]
+    fn broad_phase_rejects_instances_with_disjoint_aabbs() {
+        let (geometry_pool, geometry_id) = sphere_pool();
+        let mut detector = CollisionDetector::default();
+        // Overlapping along the sweep axis but apart along y
+        let instances = [
+            (0, geometry_id, Transform::default()),
+            (1, geometry_id, Transform::from_xyz(0.5 * RADIUS, 3.0 * RADIUS, 0.0)),
+        ];
+        let accepted = Cell::new(0);
+        let pairs = detector.contact_pairs_where(&geometry_pool, &instances, |_, _| {
+            accepted.set(accepted.get() + 1);
+            true
+        });
+
+        assert!(pairs.is_empty());
+        assert_eq!(accepted.get(), 0);
+        assert!(detector.shapes.is_empty());
+    }
+}
@@ -0,0 +1,2 @@
+mod collision_detector;
+pub use collision_detector::*;
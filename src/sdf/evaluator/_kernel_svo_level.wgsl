@@ -65,15 +65,51 @@ fn create_node_brick_payload(brick_location: vec3<u32>) -> u32 {
 @group(1) @binding(1) var color_atlas: texture_storage_3d<rgba8unorm, write>;
 @group(1) @binding(2) var<storage, read_write> brick_count: atomic<u32>; // number of bricks in brick texture, use to atomically add new bricks
 @group(1) @binding(3) var<uniform> brick_pool_side_size: u32;            // Number of bricks in one side of the brick atlas texture
+@group(1) @binding(4) var<storage, read_write> brick_free_count: atomic<u32>; // number of released bricks in free list
+@group(1) @binding(5) var<storage, read> brick_free_list: array<u32>;        // indices of released bricks which can be reused
+@group(1) @binding(6) var distance_ranges: texture_storage_3d<rg32float, write>; // (offset, scale) of distances of each brick at its brick coordinates
 
 /// Converts brick index to brick location in brick atlas texture
+///   - Bricks are laid out in cubical shells, first `n^3` bricks always fill `n x n x n` corner of the atlas.
+///     Thanks to this, brick coordinates do not depend on the atlas size and the atlas can grow without moving any brick.
+///   - Shell `s` holds bricks which have at least one coordinate equal to `s` and it is filled in three parts:
+///     `x == s`, then `y == s` (x < s), then `z == s` (x < s, y < s).
 fn brick_index_to_coords(index: u32) -> vec3<u32> {
-    var side_size = brick_pool_side_size;
-    return  vec3<u32>(
-        index % side_size,
-        (index / side_size) % side_size,
-        (index / side_size) / side_size
-    );
+    var s = u32(pow(f32(index), 1.0 / 3.0));
+    // correct floating point imprecision of the cube root
+    while (s * s * s > index) { s = s - 1u; }
+    while ((s + 1u) * (s + 1u) * (s + 1u) <= index) { s = s + 1u; }
+    
+    var r = index - s * s * s;
+    let face = (s + 1u) * (s + 1u);
+    if (r < face) {
+        return vec3<u32>(s, r / (s + 1u), r % (s + 1u));
+    }
+    r = r - face;
+    let strip = s * (s + 1u);
+    if (r < strip) {
+        return vec3<u32>(r / (s + 1u), s, r % (s + 1u));
+    }
+    r = r - strip;
+    return vec3<u32>(r / s, r % s, s);
+}
+
+/// Number of bricks which fit into the brick atlas texture
+fn brick_pool_capacity() -> u32 {
+    return brick_pool_side_size * brick_pool_side_size * brick_pool_side_size;
+}
+
+/// Returns index of a brick to be written
+///   - Released bricks from free list are reused first, a new brick is taken only when free list is empty.
+///   - Popping from an empty free list underflows the free count, so it is treated as empty when the previous value
+///     is not a valid count. Bricks are never released while this kernel runs and host resets the underflowed count.
+///   - Brick count is always incremented so it tells how many bricks were requested even when brick pool overflows
+fn allocate_brick() -> u32 {
+    let free_count = atomicSub(&brick_free_count, 1u);
+    if (free_count - 1u < brick_pool_capacity()) {
+        return brick_free_list[free_count - 1u];
+    }
+    return atomicAdd(&brick_count, 1u);
 }
 
 
@@ -91,6 +127,10 @@ const EDIT_PRIMITIVE_CYLINDER = 2u;
 const EDIT_PRIMITIVE_TORUS    = 3u;
 const EDIT_PRIMITIVE_CONE     = 4u;
 const EDIT_PRIMITIVE_CAPSULE  = 5u;
+const EDIT_PRIMITIVE_GROUP    = 7u; // following edits are evaluated on their own and then combined by the group edit
+
+// Depth of nested groups, same as `MAX_GROUP_DEPTH` in `edit.rs`
+const MAX_GROUP_DEPTH = 8u;
 
 // TODO: Use preprocessor for constatns
 const EDIT_OPERATION_ADD       = 0u;
@@ -101,13 +141,15 @@ struct EditPacked {
     color:               vec4<f32>,
     operation_primitive: u32,
     blending:            f32,
+    group_edit_count:    u32,
 }
 
 struct Edit {
-    operation: u32,
-    primitive: u32,
-    blending:  f32,
-    color:     vec4<f32>,
+    operation:        u32,
+    primitive:        u32,
+    blending:         f32,
+    color:            vec4<f32>,
+    group_edit_count: u32,
 }
 
 fn unpack_edit(packed_edit: EditPacked) -> Edit {
@@ -115,7 +157,8 @@ fn unpack_edit(packed_edit: EditPacked) -> Edit {
         packed_edit.operation_primitive >> 16u,
         packed_edit.operation_primitive & 0xFFFFu,
         packed_edit.blending,
-        packed_edit.color
+        packed_edit.color,
+        packed_edit.group_edit_count
     );
 }
 
@@ -150,6 +193,7 @@ struct Assigment {
     minium_voxel_size:  f32,       // minimum voxel size in world space - divide node if its voxels are bigger then this value
     is_root:            u32,       // is this the root node? [0/1]
     start_index:        u32,       // node index from which to start the evaluation
+    distance_quantization: f32,    // when non zero, distances are stored in [0, 1] range of each brick, which covers at most this many voxels on each side of the surface
 }
 @group(3) @binding(0) var<uniform> assigment: Assigment;
 
@@ -182,105 +226,25 @@ fn transform_pos(edit: EditData, position: vec3<f32>) -> vec3<f32> {
     return (edit.transform * vec4(position, 1.0)).xyz;
 }
 
-fn sd_shpere(position: vec3<f32>, edit: Edit, edit_data: EditData) -> f32 {
-    let p = transform_pos(edit_data, position);
-    // let p = position;
-    return length(p) - edit_data.dimensions.x;
-}
-
-fn sd_cube(position: vec3<f32>, edit: Edit, edit_data: EditData) -> f32 {
-    let p = transform_pos(edit_data, position);
-    let d = abs(p) - edit_data.dimensions.xyz * 0.5 + edit_data.dimensions.w;
-    let e = length(max(d, vec3(0.0)));
-    let i = min(max(d.x, max(d.y, d.z)), 0.0);
-    return e + i - edit_data.dimensions.w;
-}
-
-fn sd_cylinder(position: vec3<f32>, edit: Edit, edit_data: EditData) -> f32 {
-    let p = transform_pos(edit_data, position);
-    let w = edit_data.dimensions[0] * 0.5 - edit_data.dimensions[2];
-    let h = edit_data.dimensions[1] * 0.5 - edit_data.dimensions[2];
-    let d = abs(vec2(length(p.xz), p.y)) - vec2(w, h);
-    return min(max(d.x, d.y), 0.0) + length(max(d, vec2(0.0))) - edit_data.dimensions[2];
-}
-
-fn sd_torus(position: vec3<f32>, edit: Edit, edit_data: EditData) -> f32 {
+// Primitives and blending functions are defined in `_sdf_primitives.wgsl` which is concatenated with this source
+fn distance_to_edit(position: vec3<f32>, edit: Edit, edit_data: EditData) -> f32 {
     let p = transform_pos(edit_data, position);
-    let x = length(p.xz) - edit_data.dimensions[0];
-    return length(vec2(x, p.y)) - edit_data.dimensions[1];
-}
-
-fn sd_cone(position: vec3<f32>, edit: Edit, edit_data: EditData) -> f32 {
-    let h = edit_data.dimensions[1];
-    let p = transform_pos(edit_data, position) - vec3(0.0, h * 0.5, 0.0);
-    let c = vec2(h, edit_data.dimensions[0] * 0.5);
-    let q = length(p.xz);
-    return max(dot(c, vec2(q, p.y)), -h - p.y);
-}
-
-fn sd_capsule(position: vec3<f32>, edit: Edit, edit_data: EditData) -> f32 {
-    var p = transform_pos(edit_data, position);
-    let h = edit_data.dimensions[1];
-    let r = edit_data.dimensions[0];
-    p = p + vec3(0.0, h * 0.5, 0.0);
-    p = p - vec3(0.0, clamp(p.y, 0.0, h), 0.0);
-    return length(p) - r;
-}
-
-/// Smooth min/max functions
-// [https://iquilezles.org/articles/smin/]
-
-fn ramp(v: f32, l: f32, h: f32) -> f32 {
-    return v * (h - l) + l;
-}
-
-// "Polynomial smooth min 2" with mix factor result, for mixing the two materials
-// - Removed one multiplication
-//   (it is possible that empirically quiliez found that his version was faster but I did not test it)
-fn smooth_volume_add(a: f32, b: f32, k: f32) -> vec2<f32> {
-    let kk = ramp(max(k, 0.0), 0.01, 1.0); // scale K to avoid artifacts
-    let h = max(kk - abs(a - b), 0.0) / kk;
-    let m = h * h * 0.5;
-    let s = m * kk * 0.5;
-    return select(
-        vec2(b - s, 1.0 - m), // false
-        vec2(a - s, m),       // true
-        a < b
-    );
-    
-    // let e = max(kk - abs(a - b), 0.0) / kk ;
-    // return vec2(min(a, b) - e * e  * kk * 0.25, 1.0);
-}
-
-fn smooth_volume_difference(a: f32, b: f32, k: f32) -> vec2<f32> {
-    let bb = -b;
-    let kk = ramp(max(k, 0.0), 0.025, 1.0);
-    let h = max(kk - abs(a - bb), 0.0) / kk;
-    let m = h * h * 0.5;
-    let s = m * kk * 0.5;
-    return select(
-        vec2(bb + s, 1.0 - m), // false
-        vec2(a + s, m),        // true
-        a > bb
-    );
-}
+    let d = edit_data.dimensions;
 
-fn distance_to_edit(position: vec3<f32>, edit: Edit, edit_data: EditData) -> f32 {
-    
     // TODO Use preprocessor because constant are not yet supported in naga
     switch (edit.primitive) {
         // EDIT_PRIMITIVE_SPHERE
-        case 0u: { return sd_shpere(position, edit, edit_data); }
+        case 0u: { return sd_sphere(p, d); }
         // EDIT_PRIMITIVE_CUBE
-        case 1u: { return sd_cube(position, edit, edit_data); }
+        case 1u: { return sd_cube(p, d); }
          // EDIT_PRIMITIVE_CYLINDER
-        case 2u: { return sd_cylinder(position, edit, edit_data); }
+        case 2u: { return sd_cylinder(p, d); }
         // EDIT_PRIMITIVE_TORUS
-        case 3u: { return sd_torus(position, edit, edit_data); }
+        case 3u: { return sd_torus(p, d); }
         // EDIT_PRIMITIVE_CONE
-        case 4u: { return sd_cone(position, edit, edit_data); }
+        case 4u: { return sd_cone(p, d); }
         // EDIT_PRIMITIVE_CAPSULE
-        case 5u: { return sd_capsule(position, edit, edit_data); }
+        case 5u: { return sd_capsule(p, d); }
         // Default to make the compiler happy
         default: {
             return 1000000.0;
@@ -293,35 +257,69 @@ struct SDFSample {
     color:    vec4<f32>,
 }
 
+// Combines distance of preceding edits with a distance of an edit or a group, returns the distance and the mix factor of colors
+fn combine(operation: u32, distance: f32, edit_distance: f32, blending: f32) -> vec2<f32> {
+    // TODO Use preprocessor because constant are not yet supported in naga
+    switch (operation) {
+        // EDIT_OPERATION_ADD
+        case 0u: {
+            return smooth_volume_add(distance, edit_distance, blending);
+        }
+        // EDIT_OPERATION_SUBTRACT
+        case 1u: {
+            return smooth_volume_difference(distance, edit_distance, blending);
+        }
+        // EDIT_OPERATION_INTERSECT
+        case 2u: {
+            return smooth_volume_intersection(distance, edit_distance, blending);
+        }
+        default: {
+            return vec2(distance, 0.0); // edit is skipped
+        }
+    }
+}
+
+// Result of edits preceding a group, it is combined with the result of the group once all its edits are sampled
+struct OpenGroup {
+    edit:     Edit,
+    end:      u32, // index of the first edit after the group
+    distance: f32,
+    color:    vec4<f32>,
+}
+
 fn sample_sdf(position: vec3<f32>) -> SDFSample {
     // var was_in_aabb = false;
     var distance = 1000000.0;
     var color = unpack_edit(edits[0]).color;
+    var groups: array<OpenGroup, MAX_GROUP_DEPTH>;
+    var depth = 0u;
     for (var i = 0u; i < edit_count; i = i + 1u) {
         let aabb = edit_aabbs[i];
         let edit = unpack_edit(edits[i]);
-        let distance_to_primitive = distance_to_edit(position, edit, edit_data[i]);
-        var res: vec2<f32>;
         
-        // TODO Use preprocessor because constant are not yet supported in naga
-        switch (edit.operation) {
-            // EDIT_OPERATION_ADD
-            case 0u: {
-                res = smooth_volume_add(distance, distance_to_primitive, edit.blending);
-            }
-            // EDIT_OPERATION_SUBTRACT
-            case 1u: {
-                res = smooth_volume_difference(distance, distance_to_primitive, edit.blending);
+        if (edit.primitive == EDIT_PRIMITIVE_GROUP) {
+            // Edits of the group start from an empty result, same as the whole edit list
+            groups[depth] = OpenGroup(edit, i + 1u + edit.group_edit_count, distance, color);
+            depth = depth + 1u;
+            distance = 1000000.0;
+            if (edit.group_edit_count > 0u) {
+                color = unpack_edit(edits[i + 1u]).color;
             }
-            // // EDIT_OPERATION_INTERSECT
-            // case 2u: {
-            //     distance = smooth_max(distance, distance_to_primitive, edit.blending);
-            // }
-            default: {} // to make naga happy
+        } else {
+            let distance_to_primitive = distance_to_edit(position, edit, edit_data[i]);
+            let res = combine(edit.operation, distance, distance_to_primitive, edit.blending);
+            distance = res.x;
+            color = mix(color, edit.color, res.y * edit.color.w);
         }
         
-        distance = res.x;
-        color = mix(color, edit.color, res.y * edit.color.w);
+        // Groups ending with this edit are combined with edits preceding them
+        while (depth > 0u && groups[depth - 1u].end == i + 1u) {
+            depth = depth - 1u;
+            let group = groups[depth];
+            let res = combine(group.edit.operation, group.distance, distance, group.edit.blending);
+            distance = res.x;
+            color = mix(group.color, color, res.y * group.edit.color.w);
+        }
     }
     return SDFSample(distance, color);
 }
@@ -338,6 +336,8 @@ const BRICK_IS_FILLED = 2u;
 
 var<workgroup> divide: atomic<u32>;
 var<workgroup> brick_index: u32;
+var<workgroup> brick_distance_min: atomic<u32>; // ordered unsigned integers, see `encode_ordered`
+var<workgroup> brick_distance_max: atomic<u32>;
 
 struct BrickEvaluationResult {
     brick_type: u32,
@@ -361,8 +361,48 @@ fn calculate_global_voxel(centered_voxel_index: vec3<i32>, node: Node) -> Global
     return GlobalVoxelDesc(voxel_center_global, voxel_size_global);
 }
 
-fn write_to_brick(voxel_coords: vec3<i32>, sdf_sample: SDFSample) {
-    textureStore(distance_atlas, voxel_coords, vec4(sdf_sample.distance, 0.0, 0.0, 0.0));
+// Maps floats to unsigned integers of the same order
+fn encode_ordered(value: f32) -> u32 {
+    let bits = bitcast<u32>(value);
+    if ((bits & 0x80000000u) != 0u) {
+        return ~bits;
+    }
+    return bits | 0x80000000u;
+}
+
+// Inverse of ordering floats as unsigned integers
+fn decode_ordered(value: u32) -> f32 {
+    if ((value & 0x80000000u) != 0u) {
+        return bitcast<f32>(value & 0x7FFFFFFFu);
+    }
+    return bitcast<f32>(~value);
+}
+
+/// Range of distances stored in the brick as (offset, scale), mirrors `DistanceRange::of_brick`
+///   - Quantized bricks span distances of their voxels clamped to the quantization band, float bricks keep identity.
+fn brick_distance_range(voxel_size: f32) -> vec2<f32> {
+    let band = assigment.distance_quantization * voxel_size;
+    if (band <= 0.0) {
+        return vec2(0.0, 1.0);
+    }
+    let low = clamp(decode_ordered(atomicLoad(&brick_distance_min)), -band, band);
+    let high = clamp(decode_ordered(atomicLoad(&brick_distance_max)), -band, band);
+    // one step of 8-bit band keeps flat bricks decodable
+    return vec2(low, max(high - low, band / 255.0));
+}
+
+/// Encodes distance to be stored in distance atlas, mirrors `DistanceFormat::encode_texel`
+fn encode_distance(distance: f32, range: vec2<f32>) -> f32 {
+    let value = (distance - range.x) / range.y;
+    if (assigment.distance_quantization > 0.0) {
+        return clamp(value, 0.0, 1.0);
+    }
+    return value;
+}
+
+fn write_to_brick(voxel_coords: vec3<i32>, sdf_sample: SDFSample, range: vec2<f32>) {
+    let distance = encode_distance(sdf_sample.distance, range);
+    textureStore(distance_atlas, voxel_coords, vec4(distance, 0.0, 0.0, 0.0));
     textureStore(color_atlas, voxel_coords, sdf_sample.color);
 }
 
@@ -384,6 +424,8 @@ fn evaluate_node_brick(in: ShaderInput, node: Node) -> BrickEvaluationResult {
     // vote if voxel intersects sdf surface
     if (in.local_invocation_index == 0u) {
         atomicStore(&divide, 0u);
+        atomicStore(&brick_distance_min, encode_ordered(3.40282347e+38));
+        atomicStore(&brick_distance_max, encode_ordered(-3.40282347e+38));
     }
     workgroupBarrier();
     
@@ -397,10 +439,17 @@ fn evaluate_node_brick(in: ShaderInput, node: Node) -> BrickEvaluationResult {
         
         // Take next brick index
         if (in.local_invocation_index == 0u) {
-            brick_index = atomicAdd(&brick_count, 1u);
+            brick_index = allocate_brick();
         }
         workgroupBarrier();  // synchronize allocation of brick index
         
+        // Brick pool is full - skip writing the brick, host will grow the brick pool and evaluate this level again
+        if (brick_index >= brick_pool_capacity()) {
+            result.brick_type = BRICK_IS_BOUONDARY;
+            result.voxel_size = voxel_global_desc.size;
+            return result;
+        }
+        
         // All threads in group will find voxel coordinate in brick pool based on the brick index
         let brick_coords = brick_index_to_coords(brick_index);
         let brick_coords_10 = brick_coords * 10u;
@@ -408,17 +457,32 @@ fn evaluate_node_brick(in: ShaderInput, node: Node) -> BrickEvaluationResult {
         // Get coordinates of voxel in brick (10 = 8 + 2 padding)
         let voxel_coords = brick_coords_10 + in.local_invocation_id + 1u;
         
-        // save voxel value
-        write_to_brick(vec3<i32>(voxel_coords), sdf_sample);
-        
-        // Write padding
-        if (in.local_invocation_index < 488u) {
+        // Sample padding before writing anything, so the range of the brick covers all its voxels
+        let has_padding = in.local_invocation_index < 488u;
+        var padding_sample: SDFSample;
+        var padding_coords = vec3(0u);
+        atomicMin(&brick_distance_min, encode_ordered(sdf_sample.distance));
+        atomicMax(&brick_distance_max, encode_ordered(sdf_sample.distance));
+        if (has_padding) {
             let padding_index = brick_padding_indices.data[in.local_invocation_index];
             let centered_voxel_index = vec3<i32>(padding_index) - 5;
-            let voxel_global_desc = calculate_global_voxel(centered_voxel_index, node);
-            let sdf_sample = sample_sdf(voxel_global_desc.center);
-            let voxel_coords = brick_coords_10 + padding_index;
-            write_to_brick(vec3<i32>(voxel_coords), sdf_sample);
+            let padding_voxel_desc = calculate_global_voxel(centered_voxel_index, node);
+            padding_sample = sample_sdf(padding_voxel_desc.center);
+            padding_coords = brick_coords_10 + padding_index;
+            atomicMin(&brick_distance_min, encode_ordered(padding_sample.distance));
+            atomicMax(&brick_distance_max, encode_ordered(padding_sample.distance));
+        }
+        workgroupBarrier();  // wait for the range of all voxels
+        
+        let range = brick_distance_range(voxel_global_desc.size);
+        if (in.local_invocation_index == 0u) {
+            textureStore(distance_ranges, vec3<i32>(brick_coords), vec4(range, 0.0, 0.0));
+        }
+        
+        // save voxel and padding values
+        write_to_brick(vec3<i32>(voxel_coords), sdf_sample, range);
+        if (has_padding) {
+            write_to_brick(vec3<i32>(padding_coords), padding_sample, range);
         }
         workgroupBarrier();  // wait for all threads to finish writing padding to brick
         
@@ -444,21 +508,17 @@ fn evaluate_node_brick(in: ShaderInput, node: Node) -> BrickEvaluationResult {
 // =================================================================================================
 
 // Allocates a new tile and returns its index
+//   - Node count is always incremented so it tells how many nodes were requested even when node pool overflows.
+//     When it ends up greater than capacity, host grows the node pool and evaluates the level again.
 var<workgroup> tile_index_shared: u32;
 fn create_tile(in: ShaderInput) -> u32 {
     if (in.local_invocation_index == 0u) {
         tile_index_shared = 0u;
-        if (atomicLoad(&node_count) < node_pool_capacity - 8u) {
-            // tile might still exceed node pool capacity
-            let first_tile_node_index = atomicAdd(&node_count, 8u);
-            if (node_pool_capacity > (first_tile_node_index + 8u)) {
-                tile_index_shared = first_tile_node_index >> 3u;
-            } else {
-                // Refuse to initialize the tile becauase there is no more capacity node count increment has to be corrected.
-                // atomicSub(&node_count, 8u); // TODO: This will not be needed when trimming incomplete levels
-                atomicStore(&node_count, node_pool_capacity);
-            }
+        let first_tile_node_index = atomicAdd(&node_count, 8u);
+        if (first_tile_node_index + 8u <= node_pool_capacity) {
+            tile_index_shared = first_tile_node_index >> 3u;
         }
+        // otherwise refuse to initialize the tile because there is no more capacity
     }
     workgroupBarrier(); // synch tile_start_index value
     return tile_index_shared;
@@ -540,6 +600,7 @@ fn process_root(in: ShaderInput) {
     if (in.local_invocation_index == 0u) {
         atomicStore(&node_count, 0u);
         atomicStore(&brick_count, 0u);
+        atomicStore(&brick_free_count, 0u);
     }
     workgroupBarrier();
     
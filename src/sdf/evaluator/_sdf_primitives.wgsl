@@ -0,0 +1,84 @@
+// Distance functions of edit primitives and smooth blending of their volumes, shared by the evaluation kernel and generated shaders.
+//   - Primitives are sampled at position `p` in local space of an edit, `d` holds dimensions of the edit
+//     as laid out by `Primitive::dimension_data`.
+//   - Blending functions return the combined distance in `x` and the mix factor of colors in `y`.
+//   - `EditSampler` samples the same formulas on CPU, changes have to be mirrored there and in `_sdf_primitives.glsl`.
+
+fn sd_sphere(p: vec3<f32>, d: vec4<f32>) -> f32 {
+    return length(p) - d.x;
+}
+
+fn sd_cube(p: vec3<f32>, d: vec4<f32>) -> f32 {
+    let q = abs(p) - d.xyz * 0.5 + d.w;
+    let e = length(max(q, vec3<f32>(0.0)));
+    let i = min(max(q.x, max(q.y, q.z)), 0.0);
+    return e + i - d.w;
+}
+
+fn sd_cylinder(p: vec3<f32>, d: vec4<f32>) -> f32 {
+    let w = d.x * 0.5 - d.z;
+    let h = d.y * 0.5 - d.z;
+    let q = abs(vec2<f32>(length(p.xz), p.y)) - vec2<f32>(w, h);
+    return min(max(q.x, q.y), 0.0) + length(max(q, vec2<f32>(0.0))) - d.z;
+}
+
+fn sd_torus(p: vec3<f32>, d: vec4<f32>) -> f32 {
+    let x = length(p.xz) - d.x;
+    return length(vec2<f32>(x, p.y)) - d.y;
+}
+
+fn sd_cone(p: vec3<f32>, d: vec4<f32>) -> f32 {
+    let h = d.y;
+    let q = p - vec3<f32>(0.0, h * 0.5, 0.0);
+    let c = vec2<f32>(h, d.x * 0.5);
+    let l = length(q.xz);
+    return max(dot(c, vec2<f32>(l, q.y)), -h - q.y);
+}
+
+fn sd_capsule(p: vec3<f32>, d: vec4<f32>) -> f32 {
+    let h = d.y;
+    let r = d.x;
+    var q = p + vec3<f32>(0.0, h * 0.5, 0.0);
+    q = q - vec3<f32>(0.0, clamp(q.y, 0.0, h), 0.0);
+    return length(q) - r;
+}
+
+/// Smooth min/max functions
+// [https://iquilezles.org/articles/smin/]
+
+fn ramp(v: f32, l: f32, h: f32) -> f32 {
+    return v * (h - l) + l;
+}
+
+// "Polynomial smooth min 2" with mix factor result, for mixing the two materials
+// - Removed one multiplication
+//   (it is possible that empirically quiliez found that his version was faster but I did not test it)
+fn smooth_volume_add(a: f32, b: f32, k: f32) -> vec2<f32> {
+    let kk = ramp(max(k, 0.0), 0.01, 1.0); // scale K to avoid artifacts
+    let h = max(kk - abs(a - b), 0.0) / kk;
+    let m = h * h * 0.5;
+    let s = m * kk * 0.5;
+    return select(
+        vec2<f32>(b - s, 1.0 - m), // false
+        vec2<f32>(a - s, m),       // true
+        a < b
+    );
+}
+
+fn smooth_volume_difference(a: f32, b: f32, k: f32) -> vec2<f32> {
+    let bb = -b;
+    let kk = ramp(max(k, 0.0), 0.025, 1.0);
+    let h = max(kk - abs(a - bb), 0.0) / kk;
+    let m = h * h * 0.5;
+    let s = m * kk * 0.5;
+    return select(
+        vec2<f32>(bb + s, 1.0 - m), // false
+        vec2<f32>(a + s, m),        // true
+        a > bb
+    );
+}
+
+// Smooth max of both volumes, the second one is not inverted as by the difference
+fn smooth_volume_intersection(a: f32, b: f32, k: f32) -> vec2<f32> {
+    return smooth_volume_difference(a, -b, k);
+}
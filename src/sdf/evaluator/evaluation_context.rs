@@ -1,13 +1,19 @@
 use crate::{
-    framework::gpu,
+    framework::{gpu, math},
     sdf::{geometry, svo},
 };
 
+/// Everything a kernel needs to evaluate levels of one particular SVO.
+///   - It does not own the SVO itself, so the SVO can stay in its geometry and be rendered while it is being refined.
 pub struct EvaluationContext {
-    pub svo: svo::Svo,
-    pub edits: geometry::GPUEdits,
     pub layouts: EvaluationContextLayouts,
     pub bind_groups: EvaluationContextBindGroups,
+
+    /// Bounding cube of the evaluated SVO.
+    pub domain: math::BoundingCube,
+
+    /// Nodes are subdivided until their voxels are smaller than this value.
+    pub minium_voxel_size: f32,
 }
 
 pub struct EvaluationContextLayouts {
@@ -24,8 +30,14 @@ pub struct EvaluationContextBindGroups {
 
 impl EvaluationContext {
     #[profiler::function]
-    pub fn new(gpu: &gpu::Context, svo: svo::Svo, edits: geometry::GPUEdits) -> Self {
-        let layouts = EvaluationContextLayouts::new(gpu);
+    pub fn new(
+        gpu: &gpu::Context,
+        svo: &svo::Svo,
+        edits: &geometry::GPUEdits,
+        domain: math::BoundingCube,
+        minium_voxel_size: f32,
+    ) -> Self {
+        let layouts = EvaluationContextLayouts::new(gpu, &svo.brick_pool.format().voxel_format);
         let bind_groups = EvaluationContextBindGroups {
             node_pool: svo.node_pool.create_bind_group(gpu, &layouts.node_pool),
             brick_pool: svo
@@ -34,17 +46,28 @@ impl EvaluationContext {
             edits: edits.create_bind_group(gpu, &layouts.edits),
         };
         Self {
-            svo,
-            edits,
             layouts,
             bind_groups,
+            domain,
+            minium_voxel_size,
         }
     }
+
+    /// Recreates SVO bind groups after the SVO pools were reallocated.
+    #[profiler::function]
+    pub fn rebind_svo(&mut self, gpu: &gpu::Context, svo: &svo::Svo) {
+        self.bind_groups.node_pool = svo
+            .node_pool
+            .create_bind_group(gpu, &self.layouts.node_pool);
+        self.bind_groups.brick_pool = svo
+            .brick_pool
+            .create_write_bind_group(gpu, &self.layouts.brick_pool);
+    }
 }
 
 impl EvaluationContextLayouts {
     #[profiler::function]
-    pub fn new(gpu: &gpu::Context) -> Self {
+    pub fn new(gpu: &gpu::Context, voxel_format: &svo::BrickVoxelFormat) -> Self {
         Self {
             node_pool: svo::NodePool::create_bind_group_layout(
                 gpu,
@@ -54,6 +77,7 @@ impl EvaluationContextLayouts {
             brick_pool: svo::BrickPool::create_write_bind_group_layout(
                 gpu,
                 wgpu::ShaderStages::COMPUTE,
+                voxel_format,
             ),
             edits: geometry::GPUEdits::create_bind_group_layout(gpu, wgpu::ShaderStages::COMPUTE),
         }
@@ -1,50 +1,153 @@
 //! evaluator is meant to run asynchronously, and is responsible for computing a geometry octree from its edit list
 
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::{
     framework::gpu,
     sdf::{
-        geometry::{self, EvaluationStatus, Geometry, GeometryPool},
+        geometry::{self, EvaluationStatus, Geometry, GeometryID, GeometryPool},
         svo,
     },
+    warn,
 };
 
-use super::{EvaluationContext, KernelSVOLevel};
+use super::{EvaluationContext, KernelSVOLevel, SvoCache};
+
+/// A state of an SVO evaluation which is spread across multiple updates.
+struct EvaluationJob {
+    context: EvaluationContext,
+
+    /// A next level to be evaluated by the kernel.
+    next_level: svo::Level,
+
+    /// An estimate of level count used to report evaluation progress.
+    expected_level_count: u32,
+
+    /// A key under which the evaluated SVO is stored into the cache.
+    cache_key: u64,
+
+    /// Pool counts before the next level is evaluated, so an incomplete level can be trimmed without reading them back.
+    counts: svo::PoolCounts,
+}
+
 pub struct Evaluator {
     gpu: Arc<gpu::Context>, // this is and Arc because in the future evaluator will run on a separate thread asynchronously
     level_evaluation_kernel: KernelSVOLevel,
+
+    /// Evaluations in progress, each is advanced by one level at a time.
+    jobs: HashMap<GeometryID, EvaluationJob>,
+
+    /// Maximum time spent by evaluating levels in one update.
+    ///   - At least one level is evaluated in each update, so it can be exceeded by the duration of one level evaluation.
+    time_budget: Duration,
+
+    /// When set, evaluated SVOs are stored into it and geometries found in it are not evaluated at all.
+    cache: Option<SvoCache>,
 }
 
 impl Evaluator {
+    pub const DEFAULT_TIME_BUDGET: Duration = Duration::from_millis(10);
+
+    /// Brick pool is compacted after evaluation when more than 1/N of its bricks are released and not reused.
+    const DEFRAGMENTATION_RATIO: u32 = 8;
+
     #[profiler::function]
     pub fn new(gpu: Arc<gpu::Context>) -> Self {
         let level_evaluation_kernel = KernelSVOLevel::new(&gpu);
         Self {
             gpu,
             level_evaluation_kernel,
+            jobs: HashMap::new(),
+            time_budget: Self::DEFAULT_TIME_BUDGET,
+            cache: None,
         }
     }
+
+    pub fn set_time_budget(&mut self, time_budget: Duration) {
+        self.time_budget = time_budget;
+    }
+
+    pub fn set_cache(&mut self, cache: Option<SvoCache>) {
+        self.cache = cache;
+    }
+
+    /// Returns true if there is any geometry which is not yet fully evaluated.
+    pub fn is_evaluating(&self) -> bool {
+        !self.jobs.is_empty()
+    }
 }
 
 impl Evaluator {
+    /// Starts evaluation of all geometries requesting it.
+    ///   - When a geometry is already being evaluated, its evaluation is restarted.
+    ///   - Geometry references are expanded first, so geometries are evaluated again when geometries they reference change.
     #[profiler::function]
     pub fn evaluate_geometries(&mut self, geometry_pool: &mut GeometryPool) {
+        // forget jobs of geometries removed from the pool
+        self.jobs.retain(|id, _| geometry_pool.contains_key(*id));
+        geometry::expand_geometry_references(geometry_pool);
+
         for (geometry_id, geometry) in geometry_pool.iter_mut() {
-            if let EvaluationStatus::NeedsEvaluation = geometry.evaluation_status {
-                geometry.evaluation_status = EvaluationStatus::Evaluating;
-                self.evaluate_geometry(format!("{:?}", geometry_id), geometry);
+            if matches!(
+                geometry.evaluation_status,
+                EvaluationStatus::NeedsEvaluation | EvaluationStatus::NeedsRefinement
+            ) && self.load_from_cache(format!("{:?}", geometry_id), geometry)
+            {
+                self.jobs.remove(&geometry_id);
+                continue;
             }
+
+            let job = match geometry.evaluation_status {
+                EvaluationStatus::NeedsEvaluation => {
+                    self.start_evaluation(format!("{:?}", geometry_id), geometry)
+                }
+                EvaluationStatus::NeedsRefinement => {
+                    match self.start_refinement(geometry) {
+                        Some(job) => job,
+                        None => self.start_evaluation(format!("{:?}", geometry_id), geometry),
+                    }
+                }
+                _ => continue,
+            };
+            self.jobs.insert(geometry_id, job);
         }
     }
 
+    /// Advances evaluations in progress level by level until time budget for this update is exhausted.
     #[profiler::function]
     pub fn update_evaluated_geometries(&mut self, geometry_pool: &mut GeometryPool) {
-        // TODO: look for evaluation results in evaluator and update geometry when it has result for it ready
+        let start = Instant::now();
+        let mut ids: Vec<GeometryID> = self.jobs.keys().copied().collect();
+
+        // Take turns between geometries so one large geometry does not block the others
+        while !ids.is_empty() {
+            ids.retain(|id| {
+                if start.elapsed() >= self.time_budget {
+                    return false;
+                }
+                let Some(geometry) = geometry_pool.get_mut(*id) else {
+                    self.jobs.remove(id);
+                    return false;
+                };
+                let finished = self.evaluate_next_level(*id, geometry);
+                if finished {
+                    self.jobs.remove(id);
+                }
+                !finished
+            });
+
+            if start.elapsed() >= self.time_budget {
+                break;
+            }
+        }
     }
 
     #[profiler::function(pinned)]
-    fn evaluate_geometry(&mut self, svo_label: String, geometry: &mut Geometry) {
+    fn start_evaluation(&mut self, svo_label: String, geometry: &mut Geometry) -> EvaluationJob {
         // Get minimum voxel size for this evaluation run.
         let minium_voxel_size = geometry.min_voxel_size();
 
@@ -56,52 +159,179 @@ impl Evaluator {
         // Construct edit list in gpu memory
         let edits = geometry::GPUEdits::from_edit_list(&self.gpu, &geometry.edits());
 
-        // Extract svo from geometry
-        let svo = geometry
-            .svo
-            .take()
-            .unwrap_or_else(|| svo::Svo::new(svo_label, &self.gpu, svo::Capacity::Nodes(100_000)));
-
-        // Prepare level evaluation kernel for this run
-        let level_kernel = &mut self.level_evaluation_kernel;
-        level_kernel.set_context(
-            EvaluationContext::new(&self.gpu, svo, edits),
-            domain,
-            minium_voxel_size,
-        );
-
-        // Clean svo level list - we will rebuild it
-        let mut levels: Vec<svo::Level> = Vec::with_capacity(5);
-
-        // Evaluation algorithm - evaluate levels in top-down manner until no more nodes are created
-        let mut level = level_kernel.evaluate_root(&self.gpu);
-        loop {
-            // Register level into octree
-            levels.push(level);
-
-            // Evaluate next level
-            level = level_kernel.evaluate_level(&self.gpu, &level);
-
-            // If returned level is empty - no mo nodes were created so it is not a valid level and evaluation is done
-            if level.node_count == 0 {
-                break;
+        // Fall back to the default brick format when the GPU cannot use the selected one, so the geometry shows format in use
+        if !geometry.brick_voxel_format().is_supported(&self.gpu) {
+            warn!(
+                "Brick voxel format {:?} is not supported by the GPU, falling back to the default format",
+                geometry.brick_voxel_format()
+            );
+            geometry.fall_back_to_default_brick_voxel_format();
+        }
+
+        // Reuse svo of the geometry or create a new one when there is none or its bricks have different format
+        let voxel_format = geometry.brick_voxel_format();
+        if let Some(svo) = geometry.svo.as_ref() {
+            if svo.brick_pool.format().voxel_format != voxel_format {
+                geometry.svo = None;
             }
         }
+        let svo = geometry.svo.get_or_insert_with(|| {
+            svo::Svo::new(
+                svo_label,
+                &self.gpu,
+                svo::Capacity::Nodes(100_000),
+                voxel_format,
+            )
+        });
 
-        // Retrieve svo from kernel
-        let EvaluationContext { mut svo, .. } = level_kernel
-            .take_context()
-            .expect("Fatal error: KernelSVOLevel did not return an svo");
+        let context = EvaluationContext::new(&self.gpu, svo, &edits, domain, minium_voxel_size);
 
-        // Update SVO to reflect changes
-        svo.levels = levels;
+        // Clean svo level list - it will be rebuilt as levels are evaluated
+        svo.levels.clear();
+        svo.levels_changed();
         svo.domain = domain;
-        svo.node_pool.buffers_changed();
-        svo.node_pool.load_count(&self.gpu);
-        // svo.node_pool.trim_overflowing_levels(&self.gpu);
 
-        // Store svo back in geometry
+        let next_level = self
+            .level_evaluation_kernel
+            .evaluate_root(&self.gpu, &context, svo);
+
+        let counts = svo.load_pool_counts(&self.gpu);
+
+        geometry.evaluation_status = EvaluationStatus::Evaluating { progress: 0.0 };
+
+        EvaluationJob {
+            context,
+            next_level,
+            expected_level_count: svo::Svo::expected_level_count(&domain, minium_voxel_size),
+            cache_key: SvoCache::key(geometry),
+            counts,
+        }
+    }
+
+    /// Continues evaluation of an existing SVO after minimal voxel size has changed.
+    ///   - Levels with voxels smaller than the new minimal voxel size are discarded and their bricks are released
+    ///     into the free list of the brick pool, so evaluation of new levels reuses them.
+    ///   - The new bottom level is evaluated again, so it is subdivided or turned into leaves based on the new voxel size.
+    ///   - Returns None when there are no levels to reuse and the geometry has to be evaluated from scratch.
+    #[profiler::function(pinned)]
+    fn start_refinement(&mut self, geometry: &mut Geometry) -> Option<EvaluationJob> {
+        let minium_voxel_size = geometry.min_voxel_size();
+        let edits = geometry::GPUEdits::from_edit_list(&self.gpu, geometry.edits());
+        let cache_key = SvoCache::key(geometry);
+        let svo = geometry.svo.as_mut()?;
+        let domain = svo.domain;
+
+        // Find the first level which is a leaf level with the new voxel size
+        let bottom_level_index = (0..svo.levels.len())
+            .find(|index| svo::Svo::level_voxel_size(&domain, *index) <= minium_voxel_size)
+            .unwrap_or(svo.levels.len().checked_sub(1)?);
+        let next_level = svo.release_levels(&self.gpu, bottom_level_index)?;
+
+        let context = EvaluationContext::new(&self.gpu, svo, &edits, domain, minium_voxel_size);
+        let counts = svo.load_pool_counts(&self.gpu);
+
+        geometry.evaluation_status = EvaluationStatus::Evaluating { progress: 0.0 };
+
+        Some(EvaluationJob {
+            context,
+            next_level,
+            expected_level_count: svo::Svo::expected_level_count(&domain, minium_voxel_size),
+            cache_key,
+            counts,
+        })
+    }
+
+    /// Replaces SVO of the geometry by a cached one when the cache contains a valid SVO for current state of the geometry.
+    ///   - Returns true when the geometry was loaded and does not need to be evaluated.
+    #[profiler::function(pinned)]
+    fn load_from_cache(&self, svo_label: String, geometry: &mut Geometry) -> bool {
+        let Some(cache) = self.cache.as_ref() else {
+            return false;
+        };
+        let Some(svo) = cache.load(&self.gpu, svo_label, SvoCache::key(geometry)) else {
+            return false;
+        };
         geometry.svo = Some(svo);
         geometry.evaluation_status = EvaluationStatus::Evaluated;
+        true
+    }
+
+    /// Evaluates one level of the geometry and returns true when the evaluation is finished.
+    #[profiler::function(pinned)]
+    fn evaluate_next_level(&mut self, geometry_id: GeometryID, geometry: &mut Geometry) -> bool {
+        let Some(job) = self.jobs.get_mut(&geometry_id) else {
+            return true;
+        };
+        let Some(svo) = geometry.svo.as_mut() else {
+            return true;
+        };
+
+        let level = job.next_level;
+        let cache_key = job.cache_key;
+
+        let next_level =
+            self.level_evaluation_kernel
+                .evaluate_level(&self.gpu, &job.context, svo, &level);
+
+        // Counts keep growing past capacity of the pools, so they tell how much space the level needed.
+        // They were read back by the kernel together, so this does not wait for GPU again.
+        let requested = svo.load_pool_counts(&self.gpu);
+        if requested.nodes > svo.node_pool.capacity()
+            || requested.bricks > svo.brick_pool.capacity()
+        {
+            // Level is incomplete - trim it by discarding all nodes and bricks allocated while evaluating it
+            svo.set_pool_counts(&self.gpu, job.counts);
+
+            if svo.grow(&self.gpu, requested.nodes, requested.bricks) {
+                // Evaluate the same level again with the grown pools
+                job.context.rebind_svo(&self.gpu, svo);
+                return false;
+            }
+
+            // Pools cannot grow anymore, keep only fully evaluated levels.
+            // Nodes of the trimmed level are discarded too, so their parents are turned into leaves.
+            warn!(
+                "SVO {}: Evaluation stopped at level {} because pools cannot grow anymore",
+                svo.label,
+                svo.levels.len()
+            );
+            svo.node_pool.set_count(&self.gpu, level.start_index);
+            svo.make_bottom_level_leaves(&self.gpu);
+            self.finish_evaluation(geometry, None);
+            return true;
+        }
+
+        // Register level into octree only now when it is fully evaluated, so it can be rendered
+        job.next_level = next_level;
+        job.counts = requested;
+        svo.levels.push(level);
+        svo.levels_changed();
+
+        // If returned level is empty - no mo nodes were created so it is not a valid level and evaluation is done
+        if job.next_level.node_count == 0 {
+            self.finish_evaluation(geometry, Some(cache_key));
+            return true;
+        }
+
+        geometry.evaluation_status = EvaluationStatus::Evaluating {
+            progress: (svo.levels.len() as f32 / job.expected_level_count as f32).min(0.99),
+        };
+        false
+    }
+
+    /// Marks geometry as evaluated and compacts its brick pool when too many released bricks were not reused.
+    ///   - Complete SVO is stored into the cache under `cache_key`, incomplete one (`None`) is not.
+    fn finish_evaluation(&self, geometry: &mut Geometry, cache_key: Option<u64>) {
+        if let Some(svo) = geometry.svo.as_mut() {
+            let free_count = svo.brick_pool.load_free_count(&self.gpu);
+            let count = svo.brick_pool.load_count(&self.gpu);
+            if free_count > count / Self::DEFRAGMENTATION_RATIO {
+                svo.defragment_bricks(&self.gpu);
+            }
+            if let (Some(cache), Some(cache_key)) = (self.cache.as_ref(), cache_key) {
+                cache.store(&self.gpu, svo, cache_key);
+            }
+        }
+        geometry.evaluation_status = EvaluationStatus::Evaluated;
     }
 }
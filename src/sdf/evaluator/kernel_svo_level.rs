@@ -1,7 +1,7 @@
 ///!
 ///! An abstraction representing a kernel that can be dispatched to evaluate a level of an SVO.
 ///!
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashMap};
 
 use crate::{
     framework::{gpu, math},
@@ -11,24 +11,18 @@ use crate::{
 
 use super::{EvaluationContext, EvaluationContextLayouts};
 
-/// An owning struct representing a context set for a kernel.
-///   - When kernel has this context it means it is able to dispatch itself and evaluate SVO data on GPU.
-///   - Context is set from outside defining what and how to evaluate.
-struct KernelContext {
-    evaluation_context: EvaluationContext,
-    domain: math::BoundingCube,
-    minium_voxel_size: f32,
-}
+/// WGSL source with distance functions of edit primitives and blending of their volumes.
+///   - The kernel and shaders generated from edit lists are concatenated with it, so both sample edits the same way.
+pub const SDF_PRIMITIVES_SHADER: &str = include_str!("_sdf_primitives.wgsl");
 
 ///
 /// An abstraction itself representing a kernel that can be dispatched to evaluate a level of an SVO.
-///
+///   - The kernel itself is stateless regarding evaluated SVO, what and how to evaluate is given by `EvaluationContext`
+///     passed into each call, so one kernel can take turns refining multiple SVOs.
 pub struct KernelSVOLevel {
-    /// An internal struct representing a context set for a kernel. See KernelContext for more info.
-    context: Option<KernelContext>,
-
-    /// A compute pipeline used to dispatch the kernel.
-    pipeline: wgpu::ComputePipeline,
+    /// Compute pipelines used to dispatch the kernel, one for each brick voxel format.
+    ///   - Shader writes into brick atlases through storage textures, which have to match the atlas format.
+    pipelines: HashMap<svo::BrickVoxelFormat, wgpu::ComputePipeline>,
 
     /// A uniform buffer used to pass assignment data to the kernel.
     assignment_uniform: AssignmentUniform,
@@ -43,70 +37,60 @@ impl KernelSVOLevel {
     pub fn new(gpu: &gpu::Context) -> KernelSVOLevel {
         let assignment_uniform = AssignmentUniform::new(gpu);
         let brick_padding_indices_uniform = BrickPaddingIndicesUniform::new(gpu);
-        let context_layout = EvaluationContextLayouts::new(gpu);
 
         Self {
-            pipeline: Self::create_pipeline(
-                gpu,
-                &context_layout,
-                &assignment_uniform,
-                &brick_padding_indices_uniform,
-            ),
+            pipelines: HashMap::new(),
             assignment_uniform,
             brick_padding_indices_uniform,
-            context: None,
         }
     }
 
-    pub fn set_context(
-        &mut self,
-        evaluation_context: EvaluationContext,
-        domain: math::BoundingCube,
-        minium_voxel_size: f32,
-    ) {
-        self.context = Some(KernelContext {
-            evaluation_context,
-            domain,
-            minium_voxel_size,
-        });
-    }
-
-    pub fn take_context(&mut self) -> Option<EvaluationContext> {
-        let context = self.context.take()?;
-        Some(context.evaluation_context)
-    }
-
-    pub fn has_context(&self) -> bool {
-        self.context.is_some()
-    }
-
-    /// Returns next unevaluated level.
+    /// Evaluates root node of the SVO (resetting the SVO) and returns next unevaluated level.
     #[profiler::function]
-    pub fn evaluate_root(&mut self, gpu: &gpu::Context) -> svo::Level {
-        let context = self.context.as_ref().expect("Kernel context is not set");
+    pub fn evaluate_root(
+        &mut self,
+        gpu: &gpu::Context,
+        context: &EvaluationContext,
+        svo: &mut svo::Svo,
+    ) -> svo::Level {
         let assignment = Assignment {
             start_index: 0,
             is_root: 1,
             domain: context.domain,
             minium_voxel_size: context.minium_voxel_size,
-            _padding: 0,
+            distance_quantization: svo
+                .brick_pool
+                .format()
+                .voxel_format
+                .distance
+                .quantization_range(),
         };
-        self.evaluate(gpu, 1, 0, assignment)
+        self.evaluate(gpu, context, svo, 1, 0, assignment)
     }
 
-    /// Returns next unevaluated level.
+    /// Evaluates given level of the SVO and returns next unevaluated level.
     #[profiler::function]
-    pub fn evaluate_level(&mut self, gpu: &gpu::Context, level: &svo::Level) -> svo::Level {
-        let context = self.context.as_mut().expect("Kernel context is not set");
-        let node_count = context.evaluation_context.svo.node_pool.load_count(gpu);
+    pub fn evaluate_level(
+        &mut self,
+        gpu: &gpu::Context,
+        context: &EvaluationContext,
+        svo: &mut svo::Svo,
+        level: &svo::Level,
+    ) -> svo::Level {
+        let node_count = svo.node_pool.load_count(gpu);
         let assignment = Assignment {
             start_index: level.start_index,
             is_root: 0,
             domain: context.domain,
             minium_voxel_size: context.minium_voxel_size,
-            _padding: 0,
+            distance_quantization: svo
+                .brick_pool
+                .format()
+                .voxel_format
+                .distance
+                .quantization_range(),
         };
-        self.evaluate(gpu, level.node_count, node_count, assignment)
+        self.evaluate(gpu, context, svo, level.node_count, node_count, assignment)
     }
 }
 
@@ -118,10 +102,12 @@ impl KernelSVOLevel {
     #[profiler::function]
     fn create_pipeline(
         gpu: &gpu::Context,
-        context_layouts: &EvaluationContextLayouts,
+        voxel_format: &svo::BrickVoxelFormat,
         assignment_uniform: &AssignmentUniform,
         brick_padding_indices_uniform: &BrickPaddingIndicesUniform,
     ) -> wgpu::ComputePipeline {
+        let context_layouts = EvaluationContextLayouts::new(gpu, voxel_format);
+
         let pipeline_layout = {
             profiler::scope!("KernelSVOLevel: Create Pipeline Layout");
             gpu.device
@@ -149,9 +135,15 @@ impl KernelSVOLevel {
                         .device
                         .create_shader_module(wgpu::ShaderModuleDescriptor {
                             label: Some("SVO Evaluator Compute Shader Module"),
-                            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
-                                "_kernel_svo_level.wgsl"
-                            ))),
+                            source: wgpu::ShaderSource::Wgsl(Cow::Owned(
+                                voxel_format.specialize_shader(
+                                    &[
+                                        SDF_PRIMITIVES_SHADER,
+                                        include_str!("_kernel_svo_level.wgsl"),
+                                    ]
+                                    .concat(),
+                                ),
+                            )),
                         }),
                 })
         }
@@ -161,19 +153,24 @@ impl KernelSVOLevel {
     fn evaluate(
         &mut self,
         gpu: &gpu::Context,
+        context: &EvaluationContext,
+        svo: &mut svo::Svo,
         to_evaluate_node_count: u32,
         current_node_count: u32,
         assignment: Assignment,
     ) -> svo::Level {
-        // Ensure context is set (panic if not)
-        let context = self.context.as_mut().expect("Kernel context is not set");
+        // Get pipeline matching format of the brick pool
+        let voxel_format = svo.brick_pool.format().voxel_format;
+        let pipeline = &*self.pipelines.entry(voxel_format).or_insert_with(|| {
+            Self::create_pipeline(
+                gpu,
+                &voxel_format,
+                &self.assignment_uniform,
+                &self.brick_padding_indices_uniform,
+            )
+        });
 
-        // Get more consistent access to node_pool and bind_groups from the context
-        let EvaluationContext {
-            bind_groups,
-            svo: svo::Svo { node_pool, .. },
-            ..
-        } = &mut context.evaluation_context;
+        let bind_groups = &context.bind_groups;
 
         // decompose assignment into a buffer and a bind group to be used separately
         let (assignment_buffer, assignment_bind_group) = (
@@ -202,7 +199,7 @@ impl KernelSVOLevel {
 
             {
                 profiler::scope!("Level Evaluator: Setting pipeline");
-                compute_pass.set_pipeline(&self.pipeline);
+                compute_pass.set_pipeline(pipeline);
             }
 
             {
@@ -257,9 +254,10 @@ impl KernelSVOLevel {
             gpu.device.poll(wgpu::Maintain::Wait);
         }
 
-        // Read node count from buffer and calculate newly created level
-        node_pool.buffers_changed();
-        let new_node_count = node_pool.load_count(gpu);
+        // Read counts of both pools at once and calculate newly created level
+        svo.brick_pool.buffers_changed();
+        svo.node_pool.buffers_changed();
+        let new_node_count = svo.load_pool_counts(gpu).nodes;
 
         // Return next unevaluated level
 
@@ -301,8 +299,8 @@ struct Assignment {
     /// Index of first node of first unevaluated tile which is to be evaluated
     start_index: u32,
 
-    /// Padding
-    _padding: u32,
+    /// Half width of quantized distance band in voxels, or 0 when distances are stored as floats.
+    distance_quantization: f32,
 }
 
 ///
@@ -6,3 +6,6 @@ pub use kernel_svo_level::*;
 
 mod evaluation_context;
 pub use evaluation_context::*;
+
+mod svo_cache;
+pub use svo_cache::*;
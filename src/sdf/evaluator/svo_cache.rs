@@ -0,0 +1,141 @@
+use std::{fs, path::PathBuf, time::SystemTime};
+
+use crate::{
+    framework::gpu,
+    sdf::{
+        geometry::Geometry,
+        svo::{Svo, SvoData},
+    },
+    warn,
+};
+
+/// A directory of evaluated SVOs stored on disk, so geometries do not have to be evaluated again when they are opened.
+///   - Files are named by a key computed from everything the evaluation depends on.
+///   - When total size of the files exceeds `max_size`, the least recently used files are removed.
+pub struct SvoCache {
+    directory: PathBuf,
+
+    /// Maximal total size of cache files in bytes.
+    max_size: u64,
+}
+
+impl SvoCache {
+    pub const DEFAULT_MAX_SIZE: u64 = 512 * 1024 * 1024;
+
+    pub fn new(directory: PathBuf, max_size: u64) -> Self {
+        Self {
+            directory,
+            max_size,
+        }
+    }
+
+    /// Returns a cache key of the SVO which would be evaluated from the geometry.
+    ///   - It is a hash of the edit list, minimal voxel size, brick format and version of the file format.
+    pub fn key(geometry: &Geometry) -> u64 {
+        let edits = serde_json::to_vec(geometry.edits()).unwrap_or_default();
+        let voxel_format = geometry.brick_voxel_format();
+
+        // FNV-1a is used because, unlike `DefaultHasher`, it is stable across Rust versions
+        let mut hash = 0xcbf29ce484222325u64;
+        let mut feed = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        };
+        feed(&SvoData::VERSION.to_le_bytes());
+        feed(&edits);
+        feed(&geometry.min_voxel_size().to_le_bytes());
+        feed(voxel_format.distance.as_ref().as_bytes());
+        feed(voxel_format.color.as_ref().as_bytes());
+        hash
+    }
+
+    /// Creates a new SVO from a cache file with given key.
+    ///   - Returns None when there is no such file or it cannot be loaded, files which cannot be loaded are removed.
+    #[profiler::function]
+    pub fn load(&self, gpu: &gpu::Context, label: String, key: u64) -> Option<Svo> {
+        let path = self.path(key);
+        if !path.exists() {
+            return None;
+        }
+        let result = SvoData::load(&path).and_then(|data| Svo::from_data(label, gpu, &data));
+        match result {
+            Ok(svo) => {
+                // Modification time marks when the file was used last, so it is not the first one to be evicted
+                if let Err(_err) = fs::File::options()
+                    .append(true)
+                    .open(&path)
+                    .and_then(|file| file.set_modified(SystemTime::now()))
+                {
+                    warn!("SVO cache: Failed to touch {}: {}", path.display(), _err);
+                }
+                Some(svo)
+            }
+            Err(_err) => {
+                warn!("SVO cache: Failed to load {}: {}", path.display(), _err);
+                // Invalid file is evicted, so the SVO is evaluated and stored again
+                if let Err(_err) = fs::remove_file(&path) {
+                    warn!("SVO cache: Failed to remove {}: {}", path.display(), _err);
+                }
+                None
+            }
+        }
+    }
+
+    /// Reads the SVO back from GPU and stores it into a cache file with given key.
+    ///   - The read back blocks until the GPU is done, so it is done only when the cache is enabled.
+    ///   - Least recently used files are removed afterwards to keep the cache within `max_size`.
+    #[profiler::function]
+    pub fn store(&self, gpu: &gpu::Context, svo: &mut Svo, key: u64) {
+        if let Err(_err) = fs::create_dir_all(&self.directory) {
+            warn!(
+                "SVO cache: Failed to create directory {}: {}",
+                self.directory.display(),
+                _err
+            );
+            return;
+        }
+        let path = self.path(key);
+        if let Err(_err) = svo.read_back(gpu).store(&path) {
+            warn!("SVO cache: Failed to store {}: {}", path.display(), _err);
+        }
+        self.evict();
+    }
+
+    /// Removes least recently used cache files until their total size fits into `max_size`.
+    #[profiler::function]
+    fn evict(&self) {
+        let Ok(entries) = fs::read_dir(&self.directory) else {
+            return;
+        };
+        let mut files: Vec<(PathBuf, u64, SystemTime)> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "svo" {
+                    return None;
+                }
+                let metadata = path.metadata().ok()?;
+                Some((path, metadata.len(), metadata.modified().ok()?))
+            })
+            .collect();
+
+        let mut total_size: u64 = files.iter().map(|(_, size, _)| size).sum();
+        files.sort_by_key(|(_, _, modified)| *modified);
+        for (path, size, _) in files {
+            if total_size <= self.max_size {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => total_size -= size,
+                Err(_err) => {
+                    warn!("SVO cache: Failed to remove {}: {}", path.display(), _err);
+                }
+            }
+        }
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.directory.join(format!("{:016x}.svo", key))
+    }
+}
@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 use super::{Operation, Primitive};
 use crate::framework::math::{Transform, AABB};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Edit {
     pub primitive: Primitive,
     pub operation: Operation,
@@ -20,3 +20,62 @@ impl Edit {
             .inflate(0.05)
     }
 }
+
+/// Depth of nested groups the evaluation kernel can evaluate, same as `MAX_GROUP_DEPTH` in `_kernel_svo_level.wgsl`.
+pub const MAX_GROUP_DEPTH: usize = 8;
+
+/// Checks that an edit list can be evaluated.
+///   - References have to be expanded by the geometry pool, they are never evaluated directly.
+///   - Each group has to end within the group or the edit list containing it,
+///     and groups cannot be nested deeper than `MAX_GROUP_DEPTH`.
+pub fn check_edits(edits: &[Edit]) -> Result<(), String> {
+    // Ends of groups containing the current edit, innermost last
+    let mut group_ends: Vec<usize> = vec![];
+    for (index, edit) in edits.iter().enumerate() {
+        match edit.primitive {
+            Primitive::GeometryRef(geometry_id) => {
+                return Err(format!(
+                    "Edit {} references geometry {:?} which was not expanded",
+                    index, geometry_id
+                ));
+            }
+            Primitive::Group { edit_count } => {
+                let end = index + 1 + edit_count as usize;
+                if end > group_ends.last().copied().unwrap_or(edits.len()) {
+                    return Err(format!(
+                        "Group of edit {} ends after the edits containing it",
+                        index
+                    ));
+                }
+                if group_ends.len() == MAX_GROUP_DEPTH {
+                    return Err(format!(
+                        "Group of edit {} is nested deeper than {} groups",
+                        index, MAX_GROUP_DEPTH
+                    ));
+                }
+                group_ends.push(end);
+            }
+            _ => {}
+        }
+        while group_ends.last() == Some(&(index + 1)) {
+            group_ends.pop();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Sphere edit placed at a position, without blending and colored white.
+    pub(crate) fn sphere_edit(radius: f32, operation: Operation, position: glam::Vec3) -> Edit {
+        Edit {
+            primitive: Primitive::Sphere { radius },
+            operation,
+            transform: Transform::from_vec3(position),
+            blending: 0.0,
+            color: glam::Vec4::ONE,
+        }
+    }
+}
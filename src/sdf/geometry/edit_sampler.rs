@@ -0,0 +1,198 @@
+use super::{check_edits, Edit, Operation, Primitive};
+
+/// A distance and color sampled from an edit list.
+#[derive(Clone, Copy, Debug)]
+pub struct SdfSample {
+    pub distance: f32,
+    pub color: glam::Vec4,
+    /// Index of the edit which contributes most to the sample, None for an empty edit list.
+    pub edit_index: Option<usize>,
+}
+
+/// Samples the exact SDF of an edit list on CPU, the same way `sample_sdf` in `_kernel_svo_level.wgsl` does on GPU.
+///   - It is a reference for results sampled from SVOs, changes of the kernel have to be mirrored here.
+pub struct EditSampler<'a> {
+    edits: &'a [Edit],
+    /// Inverted transforms of edits, same as `GPUEditData::transform_inverse`.
+    inverse_transforms: Vec<glam::Mat4>,
+}
+
+/// Distance returned for an empty edit list, same as initial distance in the kernel.
+const EMPTY_DISTANCE: f32 = 1000000.0;
+
+/// Result of edits preceding a group, it is combined with the result of the group once all its edits are sampled.
+struct OpenGroup {
+    /// Index of the group edit.
+    index: usize,
+    /// Index of the first edit after the group.
+    end: usize,
+    distance: f32,
+    color: glam::Vec4,
+    edit_index: Option<usize>,
+}
+
+impl<'a> EditSampler<'a> {
+    /// Fails for edit lists which cannot be evaluated, see `check_edits`.
+    pub fn new(edits: &'a [Edit]) -> Result<Self, String> {
+        check_edits(edits)?;
+        Ok(Self {
+            edits,
+            inverse_transforms: edits
+                .iter()
+                .map(|edit| edit.transform.as_mat().inverse())
+                .collect(),
+        })
+    }
+
+    pub fn sample(&self, position: glam::Vec3) -> SdfSample {
+        let mut distance = EMPTY_DISTANCE;
+        let mut color = self
+            .edits
+            .first()
+            .map_or(glam::Vec4::ZERO, |edit| edit.color);
+        let mut edit_index = None;
+        let mut groups: Vec<OpenGroup> = vec![];
+        for (index, (edit, inverse_transform)) in self
+            .edits
+            .iter()
+            .zip(self.inverse_transforms.iter())
+            .enumerate()
+        {
+            if let Primitive::Group { edit_count } = edit.primitive {
+                // Edits of the group start from an empty result, same as the whole edit list
+                groups.push(OpenGroup {
+                    index,
+                    end: index + 1 + edit_count as usize,
+                    distance,
+                    color,
+                    edit_index,
+                });
+                distance = EMPTY_DISTANCE;
+                color = self.edits.get(index + 1).map_or(color, |edit| edit.color);
+                edit_index = None;
+            } else {
+                let local = inverse_transform.transform_point3(position);
+                let distance_to_primitive = edit.primitive.distance(local);
+                let (new_distance, mix) =
+                    combine(&edit.operation, distance, distance_to_primitive, edit.blending);
+                distance = new_distance;
+                color = color.lerp(edit.color, mix * edit.color.w);
+                // Mix factor is the weight of the edit in the blended result
+                if mix >= 0.5 || edit_index.is_none() {
+                    edit_index = Some(index);
+                }
+            }
+
+            // Groups ending with this edit are combined with edits preceding them
+            while let Some(group) = groups.pop() {
+                if group.end != index + 1 {
+                    groups.push(group);
+                    break;
+                }
+                let edit = &self.edits[group.index];
+                let (new_distance, mix) =
+                    combine(&edit.operation, group.distance, distance, edit.blending);
+                distance = new_distance;
+                color = group.color.lerp(color, mix * edit.color.w);
+                if mix < 0.5 && group.edit_index.is_some() {
+                    edit_index = group.edit_index;
+                }
+            }
+        }
+        SdfSample {
+            distance,
+            color,
+            edit_index,
+        }
+    }
+}
+
+/// Combines distance of preceding edits with a distance of an edit or a group, returns the distance and the mix factor of colors.
+fn combine(operation: &Operation, distance: f32, edit_distance: f32, blending: f32) -> (f32, f32) {
+    match operation {
+        Operation::Add => smooth_volume_add(distance, edit_distance, blending),
+        Operation::Subtract => smooth_volume_difference(distance, edit_distance, blending),
+        Operation::Intersect => smooth_volume_intersection(distance, edit_distance, blending),
+    }
+}
+
+impl Primitive {
+    /// Signed distance of a position in local space of the primitive.
+    pub fn distance(&self, p: glam::Vec3) -> f32 {
+        match *self {
+            Primitive::Sphere { radius } => p.length() - radius,
+            Primitive::Cube {
+                width,
+                height,
+                depth,
+                bevel,
+            } => {
+                let d = p.abs() - glam::Vec3::new(width, height, depth) * 0.5 + bevel;
+                let e = d.max(glam::Vec3::ZERO).length();
+                let i = d.max_element().min(0.0);
+                e + i - bevel
+            }
+            Primitive::Cylinder { diameter, height } => {
+                let d = glam::Vec2::new(glam::Vec2::new(p.x, p.z).length(), p.y).abs()
+                    - glam::Vec2::new(diameter * 0.5, height * 0.5);
+                d.max_element().min(0.0) + d.max(glam::Vec2::ZERO).length()
+            }
+            Primitive::Torus {
+                inner_radius,
+                outer_radius,
+            } => {
+                let x = glam::Vec2::new(p.x, p.z).length() - inner_radius;
+                glam::Vec2::new(x, p.y).length() - outer_radius
+            }
+            Primitive::Cone { diameter, height } => {
+                let p = p - glam::Vec3::new(0.0, height * 0.5, 0.0);
+                let c = glam::Vec2::new(height, diameter * 0.5);
+                let q = glam::Vec2::new(p.x, p.z).length();
+                c.dot(glam::Vec2::new(q, p.y)).max(-height - p.y)
+            }
+            Primitive::Capsule { radius, height } => {
+                let mut p = p + glam::Vec3::new(0.0, height * 0.5, 0.0);
+                p.y -= p.y.clamp(0.0, height);
+                p.length() - radius
+            }
+            // References are rejected by `check_edits` and groups are sampled from their edits
+            Primitive::GeometryRef(_) | Primitive::Group { .. } => EMPTY_DISTANCE,
+        }
+    }
+}
+
+fn ramp(v: f32, l: f32, h: f32) -> f32 {
+    v * (h - l) + l
+}
+
+/// Smooth minimum with factor for mixing colors, same as `smooth_volume_add` in the kernel.
+fn smooth_volume_add(a: f32, b: f32, k: f32) -> (f32, f32) {
+    let kk = ramp(k.max(0.0), 0.01, 1.0);
+    let h = (kk - (a - b).abs()).max(0.0) / kk;
+    let m = h * h * 0.5;
+    let s = m * kk * 0.5;
+    if a < b {
+        (a - s, m)
+    } else {
+        (b - s, 1.0 - m)
+    }
+}
+
+/// Smooth difference with factor for mixing colors, same as `smooth_volume_difference` in the kernel.
+fn smooth_volume_difference(a: f32, b: f32, k: f32) -> (f32, f32) {
+    let bb = -b;
+    let kk = ramp(k.max(0.0), 0.025, 1.0);
+    let h = (kk - (a - bb).abs()).max(0.0) / kk;
+    let m = h * h * 0.5;
+    let s = m * kk * 0.5;
+    if a > bb {
+        (a + s, m)
+    } else {
+        (bb + s, 1.0 - m)
+    }
+}
+
+/// Smooth intersection with factor for mixing colors, same as `smooth_volume_intersection` in the kernel.
+fn smooth_volume_intersection(a: f32, b: f32, k: f32) -> (f32, f32) {
+    smooth_volume_difference(a, -b, k)
+}
@@ -2,9 +2,17 @@ use std::ops::RangeInclusive;
 
 use slotmap::{new_key_type, SlotMap};
 
-use crate::{framework::math::AABB, sdf::svo::Svo};
+use crate::{
+    framework::math::{Transform, AABB},
+    sdf::{
+        query::instance_distance_scale,
+        svo::{BrickVoxelFormat, Svo},
+        volume::{MassIntegrator, MassProperties},
+    },
+    warn,
+};
 
-use super::{Edit, Operation};
+use super::{check_edits, Edit, EditSampler, Operation, Primitive};
 
 // ============================================================================================
 // Geometry Pool
@@ -22,7 +30,12 @@ pub type GeometryPool = SlotMap<GeometryID, Geometry>;
 
 pub enum EvaluationStatus {
     NeedsEvaluation,
-    Evaluating,
+    /// Only minimal voxel size has changed, so levels of existing SVO which are still valid can be kept.
+    NeedsRefinement,
+    Evaluating {
+        /// Ratio of already evaluated levels to expected number of levels in range [0, 1].
+        progress: f32,
+    },
     Evaluated,
 }
 
@@ -31,25 +44,41 @@ pub enum EvaluationStatus {
 // ============================================================================================
 
 pub struct Geometry {
-    /// An Evaluated SVO in GPU memory, when None, the geometry is not evaluated or is being evaluated and evaluator is owning it.
+    /// An Evaluated SVO in GPU memory, when None, the geometry was not evaluated yet.
     /// To determine if the geometry is being evaluated, check the `evaluation_status` field.
-    /// TODO: When evaluator will be asynchronous, this field will ne none until new svo is ready, but maybe we should keep the old one until new is ready.
+    ///   - While being evaluated, `svo.levels` contains only levels that are already fully evaluated.
     pub svo: Option<Svo>,
 
     /// The status of the geometry evaluation used by evaluator
     ///   - `NeedsEvaluation` means that the geometry has been edited and needs to be evaluated
     ///      evaluator on next update collects all geometries with this status and spawns and evaluation job.
+    ///   - `NeedsRefinement` means that only minimal voxel size has changed, evaluator continues from still valid levels.
     ///   - `Evaluating` means that the geometry is currently being evaluated by evaluator.
+    ///      The svo is refined level by level across multiple updates and levels already evaluated can be rendered.
     ///   - `Evaluated` means that the geometry does not need to be evaluated.
     pub evaluation_status: EvaluationStatus,
 
     /// A list of edits that compose this geometry on CPU
     edits: Vec<Edit>,
 
+    /// Edits with geometry references replaced by edits of referenced geometries, None when there are no references.
+    ///   - Updated by `expand_geometry_references` and `set_geometry_edits` only when expansion succeeds,
+    ///     until then it holds the previously evaluated edits, so the geometry keeps its last valid shape.
+    expanded_edits: Option<Vec<Edit>>,
+
+    /// Why references could not be expanded last time, the geometry keeps previously expanded edits meanwhile.
+    reference_error: Option<String>,
+
     /// A configuration for this geometry.
     /// This is used to configure next evaluation on a svo, which will redivide the svo until individual voxels are smaller than this value.
     min_voxel_size: f32,
 
+    /// A format of voxels in bricks of the svo, trading precision against memory.
+    brick_voxel_format: BrickVoxelFormat,
+
+    /// A format selected for this geometry which the GPU does not support, the default format is used instead.
+    unsupported_brick_voxel_format: Option<BrickVoxelFormat>,
+
     aabb: AABB,
 }
 
@@ -59,25 +88,28 @@ impl Geometry {
     pub fn new(min_voxel_size: f32) -> Self {
         Self {
             edits: vec![],
+            expanded_edits: None,
+            reference_error: None,
             svo: None,
             evaluation_status: EvaluationStatus::NeedsEvaluation,
             min_voxel_size: min_voxel_size.clamp(
                 *Self::VOXEL_SIZE_RANGE.start(),
                 *Self::VOXEL_SIZE_RANGE.end(),
             ),
+            brick_voxel_format: BrickVoxelFormat::default(),
+            unsupported_brick_voxel_format: None,
             aabb: AABB::ZERO,
         }
     }
 
     pub fn with_edits(mut self, edits: Vec<Edit>) -> Self {
-        self.edits = edits;
-        self.recompute_aabb();
+        self.replace_edits(edits);
         self
     }
 
+    /// Replaces edits without checking geometry references, see `set_geometry_edits`.
     pub fn set_edits(&mut self, edits: Vec<Edit>) {
-        self.edits = edits;
-        self.recompute_aabb();
+        self.replace_edits(edits);
         self.evaluation_status = EvaluationStatus::NeedsEvaluation;
     }
 
@@ -90,29 +122,488 @@ impl Geometry {
             *Self::VOXEL_SIZE_RANGE.start(),
             *Self::VOXEL_SIZE_RANGE.end(),
         );
-        self.evaluation_status = EvaluationStatus::NeedsEvaluation;
+        // Levels of an evaluated svo can be reused, otherwise evaluation has to start from scratch
+        let is_evaluated = matches!(
+            self.evaluation_status,
+            EvaluationStatus::Evaluated | EvaluationStatus::NeedsRefinement
+        );
+        self.evaluation_status = if is_evaluated && self.svo.is_some() {
+            EvaluationStatus::NeedsRefinement
+        } else {
+            EvaluationStatus::NeedsEvaluation
+        };
+    }
+
+    pub fn brick_voxel_format(&self) -> BrickVoxelFormat {
+        self.brick_voxel_format
+    }
+
+    /// Changes format of bricks, the svo has to be evaluated again into a new brick pool.
+    pub fn set_brick_voxel_format(&mut self, brick_voxel_format: BrickVoxelFormat) {
+        if self.brick_voxel_format != brick_voxel_format {
+            self.brick_voxel_format = brick_voxel_format;
+            self.unsupported_brick_voxel_format = None;
+            self.evaluation_status = EvaluationStatus::NeedsEvaluation;
+        }
+    }
+
+    /// Replaces format of bricks which the GPU does not support by the default one.
+    ///   - The rejected format is remembered, so the user can be told why the format has changed.
+    pub fn fall_back_to_default_brick_voxel_format(&mut self) {
+        self.unsupported_brick_voxel_format = Some(self.brick_voxel_format);
+        self.brick_voxel_format = BrickVoxelFormat::default();
+    }
+
+    pub fn unsupported_brick_voxel_format(&self) -> Option<BrickVoxelFormat> {
+        self.unsupported_brick_voxel_format
+    }
+
+    /// Returns progress of evaluation in range [0, 1].
+    pub fn evaluation_progress(&self) -> f32 {
+        match self.evaluation_status {
+            EvaluationStatus::NeedsEvaluation | EvaluationStatus::NeedsRefinement => 0.0,
+            EvaluationStatus::Evaluating { progress } => progress,
+            EvaluationStatus::Evaluated => 1.0,
+        }
     }
 
     pub fn total_aabb(&self) -> &AABB {
         &self.aabb
     }
 
+    /// Edits which are evaluated, geometry references are already expanded in them.
     pub fn edits(&self) -> &[Edit] {
-        &self.edits
+        self.expanded_edits.as_deref().unwrap_or(&self.edits)
+    }
+
+    pub fn reference_error(&self) -> Option<&str> {
+        self.reference_error.as_deref()
+    }
+
+    /// Integrates mass properties of the exact edit list on CPU, see `MassIntegrator`.
+    pub fn mass_properties(&self, density: f32, accuracy: f32) -> Result<MassProperties, String> {
+        let sampler = EditSampler::new(self.edits())?;
+        MassIntegrator::new(|position| sampler.sample(position).distance)
+            .integrate(&self.aabb, density, accuracy)
+    }
+
+    /// Creates a geometry combining this one with a tool geometry, both placed by their instance transforms.
+    ///   - Edits of the tool are moved into local space of this geometry and appended to its edit list
+    ///     as a group combined by the operation, so operations of the tool edits apply only to the tool.
+    ///   - Edit scale does not scale distances, so the relative scale of instances is applied to primitive dimensions,
+    ///     it is exact for uniform scales, the rest of non-uniform scales stays in edit transforms.
+    pub fn combined(
+        &self,
+        transform: &Transform,
+        tool: &Geometry,
+        tool_transform: &Transform,
+        operation: Operation,
+    ) -> Result<Geometry, String> {
+        let to_local = transform.as_mat().inverse() * tool_transform.as_mat();
+        let scale = instance_distance_scale(tool_transform) / instance_distance_scale(transform);
+        let mut edits = self.edits().to_vec();
+        edits.push(Edit {
+            primitive: Primitive::Group {
+                edit_count: tool.edits().len() as u32,
+            },
+            operation,
+            transform: Transform::from_mat(&to_local),
+            blending: 0.0,
+            color: glam::Vec4::ONE,
+        });
+        edits.extend(placed_edits(tool.edits(), &to_local, scale));
+        check_edits(&edits)?;
+
+        let mut geometry = Geometry::new(self.min_voxel_size).with_edits(edits);
+        geometry.brick_voxel_format = self.brick_voxel_format;
+        Ok(geometry)
+    }
+
+    /// Edits with references are not expanded here, previously evaluated edits are kept until they are.
+    fn replace_edits(&mut self, edits: Vec<Edit>) {
+        let has_references = edits
+            .iter()
+            .any(|edit| matches!(edit.primitive, Primitive::GeometryRef(_)));
+        self.expanded_edits = has_references.then(|| self.edits().to_vec());
+        self.edits = edits;
+        self.reference_error = None;
+        self.recompute_aabb();
+    }
+
+    /// Stores result of expanding references, the geometry is evaluated again only when its expanded edits changed.
+    fn set_expanded_edits(&mut self, expanded_edits: Result<Vec<Edit>, String>) {
+        match expanded_edits.and_then(|edits| check_edits(&edits).map(|()| edits)) {
+            Ok(edits) => {
+                self.reference_error = None;
+                if self.expanded_edits.as_ref() != Some(&edits) {
+                    self.expanded_edits = Some(edits);
+                    self.recompute_aabb();
+                    self.evaluation_status = EvaluationStatus::NeedsEvaluation;
+                }
+            }
+            Err(err) => {
+                if self.reference_error.as_ref() != Some(&err) {
+                    warn!("Failed to expand geometry references: {}", err);
+                    self.reference_error = Some(err);
+                }
+            }
+        }
     }
 
+    /// Groups have no volume of their own, edits of subtractive groups only carve the geometry.
     fn recompute_aabb(&mut self) {
-        let mut edit_iter = self.edits.iter();
-        let Some(first_edit) = edit_iter.next() else {
-            self.aabb = AABB::ZERO;
-            return;
+        let mut aabb: Option<AABB> = None;
+        // Edits before this index belong to a subtractive group
+        let mut subtracted_until = 0;
+        for (index, edit) in self.edits().iter().enumerate() {
+            if let Primitive::Group { edit_count } = edit.primitive {
+                if edit.operation != Operation::Add {
+                    subtracted_until = subtracted_until.max(index + 1 + edit_count as usize);
+                }
+                continue;
+            }
+            aabb = match aabb {
+                None => Some(edit.aabb()),
+                Some(aabb) if edit.operation == Operation::Add && index >= subtracted_until => {
+                    Some(aabb.add(&edit.aabb()))
+                }
+                aabb => aabb,
+            };
+        }
+        self.aabb = aabb.unwrap_or(AABB::ZERO);
+    }
+}
+
+/// Places edits into another local space, their operations are kept.
+///   - Edit scale does not scale distances, so the uniform `scale` of the placement is applied to primitive dimensions
+///     and taken out of edit transforms.
+fn placed_edits(edits: &[Edit], placement: &glam::Mat4, scale: f32) -> Vec<Edit> {
+    let unscale = Transform::from_uniform_scale(1.0 / scale).as_mat();
+    edits
+        .iter()
+        .map(|edit| Edit {
+            primitive: edit.primitive.scaled(scale),
+            transform: Transform::from_mat(&(*placement * edit.transform.as_mat() * unscale)),
+            blending: edit.blending * scale,
+            ..edit.clone()
+        })
+        .collect()
+}
+
+// ============================================================================================
+// Geometry References
+// ============================================================================================
+
+/// Expands references in edit lists of all geometries of the pool into edits of referenced geometries.
+///   - Geometries whose expanded edits changed, by their own edits or by edits of geometries they reference,
+///     are marked for evaluation.
+///   - Geometries with a reference cycle, a reference to a removed geometry or references nested deeper than
+///     `MAX_GROUP_DEPTH` keep edits of their last successful expansion, or edits they had before references were added,
+///     and report the problem by `Geometry::reference_error`.
+///   - Each reference is expanded into a group of edits of the referenced geometry, so its subtractive edits carve
+///     only the referenced geometry and the group is combined with the rest by operation and blending of the reference.
+#[profiler::function]
+pub fn expand_geometry_references(geometry_pool: &mut GeometryPool) {
+    let referencing: Vec<GeometryID> = geometry_pool
+        .iter()
+        .filter(|(_, geometry)| geometry.expanded_edits.is_some())
+        .map(|(geometry_id, _)| geometry_id)
+        .collect();
+    for geometry_id in referencing {
+        let expanded_edits = expanded_edits(geometry_pool, geometry_id, &mut vec![]);
+        geometry_pool[geometry_id].set_expanded_edits(expanded_edits);
+    }
+}
+
+/// Replaces edits of a geometry, edits referencing the geometry itself, directly or through other geometries, are rejected.
+///   - References are expanded right away, see `expand_geometry_references` for what happens when that fails.
+pub fn set_geometry_edits(
+    geometry_pool: &mut GeometryPool,
+    geometry_id: GeometryID,
+    edits: Vec<Edit>,
+) -> Result<(), String> {
+    for edit in edits.iter() {
+        if let Primitive::GeometryRef(referenced) = edit.primitive {
+            if references(geometry_pool, referenced, geometry_id, &mut vec![]) {
+                return Err(format!(
+                    "Geometry {:?} would reference itself through {:?}",
+                    geometry_id, referenced
+                ));
+            }
+        }
+    }
+    let Some(geometry) = geometry_pool.get_mut(geometry_id) else {
+        return Err(format!("Geometry {:?} does not exist", geometry_id));
+    };
+    geometry.set_edits(edits);
+    if geometry.expanded_edits.is_some() {
+        let expanded_edits = expanded_edits(geometry_pool, geometry_id, &mut vec![]);
+        geometry_pool[geometry_id].set_expanded_edits(expanded_edits);
+    }
+    Ok(())
+}
+
+/// Edits of a geometry with references expanded recursively, `stack` holds geometries being expanded to detect cycles.
+fn expanded_edits(
+    geometry_pool: &GeometryPool,
+    geometry_id: GeometryID,
+    stack: &mut Vec<GeometryID>,
+) -> Result<Vec<Edit>, String> {
+    if stack.contains(&geometry_id) {
+        return Err(format!(
+            "Reference cycle through geometry {:?}",
+            geometry_id
+        ));
+    }
+    let Some(geometry) = geometry_pool.get(geometry_id) else {
+        return Err(format!(
+            "Referenced geometry {:?} does not exist",
+            geometry_id
+        ));
+    };
+
+    stack.push(geometry_id);
+    let mut edits = vec![];
+    for edit in geometry.edits.iter() {
+        let Primitive::GeometryRef(referenced) = edit.primitive else {
+            edits.push(edit.clone());
+            continue;
         };
+        let referenced_edits = expanded_edits(geometry_pool, referenced, stack)?;
+        edits.push(Edit {
+            primitive: Primitive::Group {
+                edit_count: referenced_edits.len() as u32,
+            },
+            ..edit.clone()
+        });
+        edits.extend(placed_edits(
+            &referenced_edits,
+            &edit.transform.as_mat(),
+            instance_distance_scale(&edit.transform),
+        ));
+    }
+    stack.pop();
+    Ok(edits)
+}
+
+/// Returns true when a geometry is the target or references it, `visited` guards against cycles elsewhere in the pool.
+fn references(
+    geometry_pool: &GeometryPool,
+    geometry_id: GeometryID,
+    target: GeometryID,
+    visited: &mut Vec<GeometryID>,
+) -> bool {
+    if geometry_id == target {
+        return true;
+    }
+    if visited.contains(&geometry_id) {
+        return false;
+    }
+    visited.push(geometry_id);
+    let Some(geometry) = geometry_pool.get(geometry_id) else {
+        return false;
+    };
+    geometry.edits.iter().any(|edit| match edit.primitive {
+        Primitive::GeometryRef(referenced) => {
+            references(geometry_pool, referenced, target, visited)
+        }
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdf::geometry::sphere_edit;
+
+    fn edit(primitive: Primitive, operation: Operation, position: glam::Vec3) -> Edit {
+        Edit {
+            primitive,
+            operation,
+            transform: Transform::from_vec3(position),
+            blending: 0.0,
+            color: glam::Vec4::ONE,
+        }
+    }
+
+    fn reference(geometry_id: GeometryID, position: glam::Vec3) -> Edit {
+        edit(
+            Primitive::GeometryRef(geometry_id),
+            Operation::Add,
+            position,
+        )
+    }
+
+    #[test]
+    fn expands_references_into_placed_edits() {
+        let mut geometry_pool = GeometryPool::with_key();
+        let sphere_id = geometry_pool.insert(Geometry::new(0.01).with_edits(vec![sphere_edit(
+            0.5,
+            Operation::Add,
+            glam::Vec3::ZERO,
+        )]));
+        let host_id = geometry_pool.insert(Geometry::new(0.01));
+        let offset = glam::Vec3::new(2.0, 0.0, 0.0);
+        set_geometry_edits(
+            &mut geometry_pool,
+            host_id,
+            vec![reference(sphere_id, offset)],
+        )
+        .unwrap();
+
+        let host = &geometry_pool[host_id];
+        assert!(host.reference_error().is_none());
+        assert_eq!(host.edits().len(), 2);
+        assert_eq!(
+            host.edits()[0].primitive,
+            Primitive::Group { edit_count: 1 }
+        );
+        assert_eq!(host.edits()[1].primitive, Primitive::Sphere { radius: 0.5 });
+        assert!(host.edits()[1].transform.position.abs_diff_eq(offset, 1e-6));
+        assert!(host.total_aabb().max.x > 2.0);
+        assert!(host.total_aabb().min.x > 1.0);
+    }
+
+    /// A hollow sphere, its subtractive edit carves only the sphere when the geometry is referenced.
+    fn hollow_sphere_edit() -> Geometry {
+        Geometry::new(0.01).with_edits(vec![
+            sphere_edit(0.5, Operation::Add, glam::Vec3::ZERO),
+            sphere_edit(0.2, Operation::Subtract, glam::Vec3::ZERO),
+        ])
+    }
+
+    #[test]
+    fn subtractive_edits_of_references_carve_only_referenced_geometry() {
+        let mut geometry_pool = GeometryPool::with_key();
+        let hollow_id = geometry_pool.insert(hollow_sphere_edit());
+        let host_id = geometry_pool.insert(Geometry::new(0.01));
+        set_geometry_edits(
+            &mut geometry_pool,
+            host_id,
+            vec![
+                sphere_edit(1.0, Operation::Add, glam::Vec3::ZERO),
+                reference(hollow_id, glam::Vec3::ZERO),
+            ],
+        )
+        .unwrap();
+        let host = &geometry_pool[host_id];
+        assert!(host.reference_error().is_none());
+        let sampler = EditSampler::new(host.edits()).unwrap();
+        assert!((sampler.sample(glam::Vec3::ZERO).distance + 1.0).abs() < 0.01);
+
+        // Subtracting the reference carves its shell and leaves its hollow inside
+        let subtracted = Edit {
+            operation: Operation::Subtract,
+            ..reference(hollow_id, glam::Vec3::ZERO)
+        };
+        set_geometry_edits(
+            &mut geometry_pool,
+            host_id,
+            vec![
+                sphere_edit(1.0, Operation::Add, glam::Vec3::ZERO),
+                subtracted,
+            ],
+        )
+        .unwrap();
+        let sampler = EditSampler::new(geometry_pool[host_id].edits()).unwrap();
+        assert!(sampler.sample(glam::Vec3::ZERO).distance < -0.15);
+        assert!(sampler.sample(glam::Vec3::new(0.35, 0.0, 0.0)).distance > 0.1);
+        assert!(sampler.sample(glam::Vec3::new(0.75, 0.0, 0.0)).distance < -0.2);
+    }
+
+    #[test]
+    fn nested_references_are_expanded_into_nested_groups() {
+        let mut geometry_pool = GeometryPool::with_key();
+        let hollow_id = geometry_pool.insert(hollow_sphere_edit());
+        let middle_id = geometry_pool.insert(Geometry::new(0.01));
+        set_geometry_edits(
+            &mut geometry_pool,
+            middle_id,
+            vec![reference(hollow_id, glam::Vec3::X)],
+        )
+        .unwrap();
+        let host_id = geometry_pool.insert(Geometry::new(0.01));
+        set_geometry_edits(
+            &mut geometry_pool,
+            host_id,
+            vec![reference(middle_id, glam::Vec3::Y)],
+        )
+        .unwrap();
+
+        let edits = geometry_pool[host_id].edits();
+        assert_eq!(edits[0].primitive, Primitive::Group { edit_count: 3 });
+        assert_eq!(edits[1].primitive, Primitive::Group { edit_count: 2 });
+        assert!(edits[3]
+            .transform
+            .position
+            .abs_diff_eq(glam::Vec3::new(1.0, 1.0, 0.0), 1e-6));
+        let sampler = EditSampler::new(edits).unwrap();
+        assert!(sampler.sample(glam::Vec3::new(1.0, 1.0, 0.0)).distance > 0.15);
+        assert!(sampler.sample(glam::Vec3::new(1.35, 1.0, 0.0)).distance < -0.1);
+    }
+
+    #[test]
+    fn failed_expansion_keeps_previous_edits() {
+        let mut geometry_pool = GeometryPool::with_key();
+        let removed_id = geometry_pool.insert(hollow_sphere_edit());
+        geometry_pool.remove(removed_id);
+        let previous = vec![sphere_edit(1.0, Operation::Add, glam::Vec3::ZERO)];
+        let host_id = geometry_pool.insert(Geometry::new(0.01).with_edits(previous.clone()));
+        let previous_aabb = geometry_pool[host_id].total_aabb().clone();
+
+        // References to removed geometries cannot be expanded
+        set_geometry_edits(
+            &mut geometry_pool,
+            host_id,
+            vec![reference(removed_id, glam::Vec3::X)],
+        )
+        .unwrap();
+        let host = &geometry_pool[host_id];
+        assert!(host.reference_error().is_some());
+        assert_eq!(host.edits(), previous.as_slice());
+        assert_eq!(host.total_aabb().min, previous_aabb.min);
+        assert_eq!(host.total_aabb().max, previous_aabb.max);
+
+        // Pool expansion keeps failing and keeps the edits too
+        expand_geometry_references(&mut geometry_pool);
+        let host = &geometry_pool[host_id];
+        assert!(host.reference_error().is_some());
+        assert_eq!(host.edits(), previous.as_slice());
+    }
 
-        let mut aabb = first_edit.aabb();
-        edit_iter
-            .filter(|e| e.operation == Operation::Add)
-            .for_each(|e| aabb = aabb.add(&e.aabb()));
+    #[test]
+    fn reference_cycles_are_rejected() {
+        let mut geometry_pool = GeometryPool::with_key();
+        let a_id = geometry_pool.insert(Geometry::new(0.01).with_edits(vec![sphere_edit(
+            0.5,
+            Operation::Add,
+            glam::Vec3::ZERO,
+        )]));
+        let b_id = geometry_pool.insert(Geometry::new(0.01));
+        set_geometry_edits(
+            &mut geometry_pool,
+            b_id,
+            vec![reference(a_id, glam::Vec3::X)],
+        )
+        .unwrap();
+        assert!(set_geometry_edits(
+            &mut geometry_pool,
+            a_id,
+            vec![reference(a_id, glam::Vec3::X)]
+        )
+        .is_err());
+        assert!(set_geometry_edits(
+            &mut geometry_pool,
+            a_id,
+            vec![reference(b_id, glam::Vec3::X)]
+        )
+        .is_err());
 
-        self.aabb = aabb;
+        // A cycle created without checks is reported by expansion and the previous edits are kept
+        let expanded = geometry_pool[b_id].edits().to_vec();
+        geometry_pool[a_id].set_edits(vec![reference(b_id, glam::Vec3::X)]);
+        expand_geometry_references(&mut geometry_pool);
+        assert!(geometry_pool[b_id].reference_error().is_some());
+        assert_eq!(geometry_pool[b_id].edits(), expanded.as_slice());
     }
 }
@@ -12,7 +12,9 @@ pub struct GPUEdit {
     color: glam::Vec4,
     operation_primitive: u32,
     blending: f32,
-    _padding: [u32; 2],
+    /// Number of edits following a group edit which belong to the group, zero for other edits.
+    group_edit_count: u32,
+    _padding: u32,
 }
 
 impl GPUEdit {
@@ -22,11 +24,16 @@ impl GPUEdit {
         color: glam::Vec4,
         blending: f32,
     ) -> Self {
+        let group_edit_count = match primitive {
+            Primitive::Group { edit_count } => edit_count,
+            _ => 0,
+        };
         Self {
             operation_primitive: (operation.to_index()) << 16 | (primitive.to_index()),
             blending,
             color,
-            _padding: [0; 2],
+            group_edit_count,
+            _padding: 0,
         }
     }
     pub fn from_edit(edit: &Edit) -> Self {
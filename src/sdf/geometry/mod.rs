@@ -6,9 +6,14 @@ pub use primitive::*;
 
 mod edit;
 pub use edit::*;
+#[cfg(test)]
+pub(crate) use edit::tests::sphere_edit;
 
 mod gpu_edits;
 pub use gpu_edits::*;
 
 mod operation;
 pub use operation::*;
+
+mod edit_sampler;
+pub use edit_sampler::*;
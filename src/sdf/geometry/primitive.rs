@@ -4,6 +4,8 @@ use strum_macros::{AsRefStr, EnumIter};
 
 use crate::framework::math::AABB;
 
+use super::GeometryID;
+
 #[derive(Clone, Debug, ToIndex, AsRefStr, EnumIter, PartialEq)]
 pub enum PrimitiveType {
     Sphere,
@@ -12,6 +14,10 @@ pub enum PrimitiveType {
     Torus,
     Cone,
     Capsule,
+    GeometryRef,
+    /// Produced only by expanding references, it cannot be selected.
+    #[strum(disabled)]
+    Group,
 }
 
 /// Might carry additional data which cannot be expressed by Transform
@@ -42,6 +48,15 @@ pub enum Primitive {
         radius: f32,
         height: f32,
     },
+    /// Edits of another geometry placed by the transform of the edit.
+    ///   - References are expanded into edits of the referenced geometry by `expand_geometry_references`,
+    ///     they are never evaluated directly.
+    GeometryRef(GeometryID),
+    /// Edits which follow this one are evaluated on their own and then combined with preceding edits
+    /// by the operation and blending of this edit.
+    ///   - References are expanded into a group, so operations of the referenced geometry apply only to it.
+    ///   - Groups are nested when the referenced geometry references other geometries, `edit_count` includes nested edits.
+    Group { edit_count: u32 },
 }
 
 // API - Primitive
@@ -90,6 +105,8 @@ impl Primitive {
             PrimitiveType::Torus => Primitive::default_torus(),
             PrimitiveType::Cone => Primitive::default_cone(),
             PrimitiveType::Capsule => Primitive::default_capsule(),
+            PrimitiveType::GeometryRef => Primitive::GeometryRef(GeometryID::default()),
+            PrimitiveType::Group => Primitive::Group { edit_count: 0 },
         }
     }
 
@@ -101,6 +118,8 @@ impl Primitive {
             Primitive::Torus { .. } => PrimitiveType::Torus,
             Primitive::Cone { .. } => PrimitiveType::Cone,
             Primitive::Capsule { .. } => PrimitiveType::Capsule,
+            Primitive::GeometryRef(_) => PrimitiveType::GeometryRef,
+            Primitive::Group { .. } => PrimitiveType::Group,
         }
     }
 
@@ -120,6 +139,7 @@ impl Primitive {
             } => [*inner_radius, *outer_radius, 0.0, 0.0],
             Primitive::Cone { diameter, height } => [*diameter, *height, 0.0, 0.0],
             Primitive::Capsule { radius, height } => [*radius, *height, 0.0, 0.0],
+            Primitive::GeometryRef(_) | Primitive::Group { .. } => [0.0; 4],
         }
     }
 
@@ -165,6 +185,49 @@ impl Primitive {
                 glam::Vec3::new(-radius, -height - radius, -radius),
                 glam::Vec3::new(*radius, height + radius, *radius),
             ),
+            // Known only to the geometry pool, expanded edits have their own AABBs
+            Primitive::GeometryRef(_) | Primitive::Group { .. } => AABB::ZERO,
+        }
+    }
+
+    /// The same primitive uniformly scaled, all dimensions are lengths so its distance field scales exactly.
+    pub fn scaled(&self, factor: f32) -> Primitive {
+        match *self {
+            Primitive::Sphere { radius } => Primitive::Sphere {
+                radius: radius * factor,
+            },
+            Primitive::Cube {
+                width,
+                height,
+                depth,
+                bevel,
+            } => Primitive::Cube {
+                width: width * factor,
+                height: height * factor,
+                depth: depth * factor,
+                bevel: bevel * factor,
+            },
+            Primitive::Cylinder { diameter, height } => Primitive::Cylinder {
+                diameter: diameter * factor,
+                height: height * factor,
+            },
+            Primitive::Torus {
+                inner_radius,
+                outer_radius,
+            } => Primitive::Torus {
+                inner_radius: inner_radius * factor,
+                outer_radius: outer_radius * factor,
+            },
+            Primitive::Cone { diameter, height } => Primitive::Cone {
+                diameter: diameter * factor,
+                height: height * factor,
+            },
+            Primitive::Capsule { radius, height } => Primitive::Capsule {
+                radius: radius * factor,
+                height: height * factor,
+            },
+            Primitive::GeometryRef(geometry_id) => Primitive::GeometryRef(geometry_id),
+            Primitive::Group { edit_count } => Primitive::Group { edit_count },
         }
     }
 
@@ -0,0 +1,299 @@
+use std::path::Path;
+
+use serde_json::json;
+
+use super::Mesh;
+use crate::framework::{binary_writer::write_file, math::Transform};
+
+/// A scene of meshes and their instances which can be stored as a binary glTF 2.0 file.
+///   - Each mesh is stored once and every instance is a node referencing it, so instanced scenes stay small.
+#[derive(Debug, Default)]
+pub struct GltfScene {
+    meshes: Vec<Mesh>,
+    instances: Vec<(usize, Transform)>,
+}
+
+/// Appends data into a binary buffer, each view starts at 4 byte boundary as required by accessors of 4 byte components.
+#[derive(Default)]
+struct BufferBuilder {
+    bytes: Vec<u8>,
+    views: Vec<serde_json::Value>,
+}
+
+impl BufferBuilder {
+    fn push_view(&mut self, data: &[u8], target: u32) -> usize {
+        while !self.bytes.len().is_multiple_of(4) {
+            self.bytes.push(0);
+        }
+        self.views.push(json!({
+            "buffer": 0,
+            "byteOffset": self.bytes.len(),
+            "byteLength": data.len(),
+            "target": target,
+        }));
+        self.bytes.extend_from_slice(data);
+        self.views.len() - 1
+    }
+}
+
+impl GltfScene {
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_INT: u32 = 5125;
+
+    /// Adds a mesh into the scene and returns its index to be referenced by instances.
+    pub fn add_mesh(&mut self, mesh: Mesh) -> usize {
+        self.meshes.push(mesh);
+        self.meshes.len() - 1
+    }
+
+    /// Adds an instance of a mesh placed by given transform.
+    pub fn add_instance(&mut self, mesh_index: usize, transform: Transform) {
+        self.instances.push((mesh_index, transform));
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.instances.len()
+    }
+
+    /// Stores the scene as a binary glTF file (`.glb`), see `to_glb`.
+    #[profiler::function]
+    pub fn store_glb<P: AsRef<Path>>(&self, file_name: P) -> Result<(), String> {
+        write_file(file_name, &self.to_glb()?)
+    }
+
+    /// Encodes the scene as a binary glTF file.
+    ///   - Vertex colors are stored as `COLOR_0` attribute of a white material, so viewers show them as base color.
+    ///   - Empty meshes cannot be stored as glTF primitives, so they and their instances are skipped.
+    ///     Fails when there is no instance of a non empty mesh.
+    pub fn to_glb(&self) -> Result<Vec<u8>, String> {
+        let mut buffer = BufferBuilder::default();
+        let mut accessors = vec![];
+        let mut meshes = vec![];
+
+        // Index of each mesh in the glTF document, None for skipped empty meshes
+        let mut mesh_indices: Vec<Option<usize>> = vec![];
+        for mesh in &self.meshes {
+            if mesh.vertex_count() == 0 || mesh.indices.is_empty() {
+                mesh_indices.push(None);
+                continue;
+            }
+            mesh_indices.push(Some(meshes.len()));
+
+            let mut accessor =
+                |data: &[u8], target: u32, component: u32, ty: &str, count: usize| {
+                    let view = buffer.push_view(data, target);
+                    accessors.push(json!({
+                        "bufferView": view,
+                        "componentType": component,
+                        "count": count,
+                        "type": ty,
+                    }));
+                    accessors.len() - 1
+                };
+
+            let positions: Vec<f32> = mesh.positions.iter().flat_map(|p| p.to_array()).collect();
+            // Degenerate triangles can leave vertices without normal, glTF requires unit normals
+            let normals: Vec<f32> = mesh
+                .normals
+                .iter()
+                .map(|n| n.try_normalize().unwrap_or(glam::Vec3::Y))
+                .flat_map(|n| n.to_array())
+                .collect();
+            let colors: Vec<f32> = mesh.colors.iter().flat_map(|c| c.to_array()).collect();
+            let count = mesh.vertex_count();
+            let position_accessor = accessor(
+                &le_bytes(&positions),
+                Self::ARRAY_BUFFER,
+                Self::FLOAT,
+                "VEC3",
+                count,
+            );
+            let normal_accessor = accessor(
+                &le_bytes(&normals),
+                Self::ARRAY_BUFFER,
+                Self::FLOAT,
+                "VEC3",
+                count,
+            );
+            let color_accessor = accessor(
+                &le_bytes(&colors),
+                Self::ARRAY_BUFFER,
+                Self::FLOAT,
+                "VEC4",
+                count,
+            );
+            let index_accessor = accessor(
+                &mesh
+                    .indices
+                    .iter()
+                    .flat_map(|index| index.to_le_bytes())
+                    .collect::<Vec<u8>>(),
+                Self::ELEMENT_ARRAY_BUFFER,
+                Self::UNSIGNED_INT,
+                "SCALAR",
+                mesh.indices.len(),
+            );
+
+            // Position accessor is required to have bounds
+            let min = mesh
+                .positions
+                .iter()
+                .fold(glam::Vec3::splat(f32::MAX), |min, p| min.min(*p));
+            let max = mesh
+                .positions
+                .iter()
+                .fold(glam::Vec3::splat(f32::MIN), |max, p| max.max(*p));
+            accessors[position_accessor]["min"] = json!(min.to_array());
+            accessors[position_accessor]["max"] = json!(max.to_array());
+
+            meshes.push(json!({
+                "primitives": [{
+                    "attributes": {
+                        "POSITION": position_accessor,
+                        "NORMAL": normal_accessor,
+                        "COLOR_0": color_accessor,
+                    },
+                    "indices": index_accessor,
+                    "material": 0,
+                }],
+            }));
+        }
+
+        let nodes: Vec<serde_json::Value> = self
+            .instances
+            .iter()
+            .filter_map(|(mesh_index, transform)| {
+                let mesh_index = mesh_indices.get(*mesh_index).copied().flatten()?;
+                Some(json!({
+                    "mesh": mesh_index,
+                    "translation": transform.position.to_array(),
+                    "rotation": transform.rotation.to_array(),
+                    "scale": transform.scale.to_array(),
+                }))
+            })
+            .collect();
+        if nodes.is_empty() {
+            return Err("Scene has no instance of a non empty mesh".to_string());
+        }
+
+        let document = json!({
+            "asset": { "version": "2.0", "generator": "sdf-edit-rs" },
+            "scene": 0,
+            "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<usize>>() }],
+            "nodes": nodes,
+            "meshes": meshes,
+            "materials": [{
+                "pbrMetallicRoughness": {
+                    "baseColorFactor": [1.0, 1.0, 1.0, 1.0],
+                    "metallicFactor": 0.0,
+                    "roughnessFactor": 1.0,
+                },
+            }],
+            "accessors": accessors,
+            "bufferViews": buffer.views,
+            "buffers": [{ "byteLength": buffer.bytes.len() }],
+        });
+        let mut json_chunk = match serde_json::to_vec(&document) {
+            Ok(json_chunk) => json_chunk,
+            Err(err) => return Err(format!("Failed to serialize glTF document: {}", err)),
+        };
+
+        // Chunks have to be 4 byte aligned, json is padded by spaces and binary data by zeros
+        while json_chunk.len() % 4 != 0 {
+            json_chunk.push(b' ');
+        }
+        let mut bin_chunk = buffer.bytes;
+        while bin_chunk.len() % 4 != 0 {
+            bin_chunk.push(0);
+        }
+
+        let total_length = 12 + 8 + json_chunk.len() + 8 + bin_chunk.len();
+        let mut bytes = Vec::with_capacity(total_length);
+        bytes.extend_from_slice(b"glTF");
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        bytes.extend_from_slice(&(total_length as u32).to_le_bytes());
+        bytes.extend_from_slice(&(json_chunk.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"JSON");
+        bytes.extend_from_slice(&json_chunk);
+        bytes.extend_from_slice(&(bin_chunk.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"BIN\0");
+        bytes.extend_from_slice(&bin_chunk);
+        Ok(bytes)
+    }
+}
+
+fn le_bytes(values: &[f32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> Mesh {
+        Mesh {
+            positions: vec![glam::Vec3::ZERO, glam::Vec3::X, glam::Vec3::Y],
+            normals: vec![glam::Vec3::Z; 3],
+            colors: vec![glam::Vec4::ONE; 3],
+            indices: vec![0, 1, 2],
+        }
+    }
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn glb_has_valid_header_and_aligned_chunks() {
+        let mut scene = GltfScene::default();
+        let mesh_index = scene.add_mesh(triangle());
+        scene.add_instance(mesh_index, Transform::default());
+        scene.add_instance(mesh_index, Transform::from_xyz(1.0, 0.0, 0.0));
+        let bytes = scene.to_glb().unwrap();
+
+        assert_eq!(&bytes[0..4], b"glTF");
+        assert_eq!(read_u32(&bytes, 4), 2);
+        assert_eq!(read_u32(&bytes, 8) as usize, bytes.len());
+
+        let json_length = read_u32(&bytes, 12) as usize;
+        assert_eq!(&bytes[16..20], b"JSON");
+        assert_eq!(json_length % 4, 0);
+        let bin_offset = 20 + json_length;
+        let bin_length = read_u32(&bytes, bin_offset) as usize;
+        assert_eq!(&bytes[bin_offset + 4..bin_offset + 8], b"BIN\0");
+        assert_eq!(bin_length % 4, 0);
+        assert_eq!(bin_offset + 8 + bin_length, bytes.len());
+
+        let document: serde_json::Value = serde_json::from_slice(&bytes[20..bin_offset]).unwrap();
+        assert_eq!(document["nodes"].as_array().unwrap().len(), 2);
+        assert_eq!(document["meshes"].as_array().unwrap().len(), 1);
+        for view in document["bufferViews"].as_array().unwrap() {
+            let offset = view["byteOffset"].as_u64().unwrap() as usize;
+            let length = view["byteLength"].as_u64().unwrap() as usize;
+            assert_eq!(offset % 4, 0);
+            assert!(offset + length <= bin_length);
+        }
+    }
+
+    #[test]
+    fn empty_meshes_are_skipped() {
+        let mut scene = GltfScene::default();
+        let empty_index = scene.add_mesh(Mesh::default());
+        scene.add_instance(empty_index, Transform::default());
+        assert!(scene.to_glb().is_err());
+
+        let mesh_index = scene.add_mesh(triangle());
+        scene.add_instance(mesh_index, Transform::default());
+        let bytes = scene.to_glb().unwrap();
+        let json_length = read_u32(&bytes, 12) as usize;
+        let document: serde_json::Value =
+            serde_json::from_slice(&bytes[20..20 + json_length]).unwrap();
+        assert_eq!(document["nodes"].as_array().unwrap().len(), 1);
+        assert_eq!(document["nodes"][0]["mesh"], 0);
+    }
+}
@@ -0,0 +1,246 @@
+use std::{collections::HashMap, io::Write, path::Path};
+
+use super::ply;
+use crate::framework::binary_writer::{create_file, write_f32s, write_u32s};
+
+/// An indexed triangle mesh with per vertex normals and colors.
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub positions: Vec<glam::Vec3>,
+    pub normals: Vec<glam::Vec3>,
+    pub colors: Vec<glam::Vec4>,
+    /// Three indices per triangle, triangles are counter clockwise when viewed from outside.
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = [u32; 3]> + '_ {
+        self.indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+    }
+
+    /// Minimal and maximal corner of the bounding box of the mesh, None for a mesh without vertices.
+    pub fn bounds(&self) -> Option<(glam::Vec3, glam::Vec3)> {
+        let first = *self.positions.first()?;
+        Some(
+            self.positions
+                .iter()
+                .fold((first, first), |(min, max), p| (min.min(*p), max.max(*p))),
+        )
+    }
+
+    /// Volume enclosed by the mesh, only meaningful for a watertight mesh.
+    pub fn volume(&self) -> f32 {
+        let volume: f64 = self
+            .triangles()
+            .map(|[a, b, c]| {
+                let [a, b, c] = [a, b, c].map(|index| self.positions[index as usize].as_dvec3());
+                a.dot(b.cross(c))
+            })
+            .sum();
+        (volume / 6.0) as f32
+    }
+
+    /// Merges vertices connected by edges shorter than given length and removes triangles collapsed by it.
+    ///   - Collapsing edges keeps a watertight mesh closed, unlike removing degenerate triangles would.
+    ///   - Returns number of removed triangles.
+    pub fn collapse_short_edges(&mut self, min_edge_length: f32) -> usize {
+        fn root(parents: &mut [u32], mut index: u32) -> u32 {
+            while parents[index as usize] != index {
+                let parent = parents[index as usize];
+                parents[index as usize] = parents[parent as usize];
+                index = parent;
+            }
+            index
+        }
+
+        let mut parents: Vec<u32> = (0..self.vertex_count() as u32).collect();
+        let min_length_squared = min_edge_length * min_edge_length;
+        for triangle in self.indices.chunks_exact(3) {
+            for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+                let (a, b) = (triangle[a], triangle[b]);
+                let length_squared =
+                    self.positions[a as usize].distance_squared(self.positions[b as usize]);
+                if length_squared < min_length_squared {
+                    let (a, b) = (root(&mut parents, a), root(&mut parents, b));
+                    parents[b as usize] = a;
+                }
+            }
+        }
+
+        let triangle_count = self.triangle_count();
+        let mut indices = Vec::with_capacity(self.indices.len());
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] =
+                [triangle[0], triangle[1], triangle[2]].map(|index| root(&mut parents, index));
+            if a != b && b != c && c != a {
+                indices.extend_from_slice(&[a, b, c]);
+            }
+        }
+        self.indices = indices;
+        triangle_count - self.triangle_count()
+    }
+
+    /// Checks that every edge is shared by exactly two triangles with opposite winding,
+    /// so the mesh is a closed manifold surface.
+    pub fn check_watertight(&self) -> Result<(), String> {
+        let mut edges: HashMap<(u32, u32), u32> = HashMap::new();
+        for [a, b, c] in self.triangles() {
+            for edge in [(a, b), (b, c), (c, a)] {
+                *edges.entry(edge).or_insert(0) += 1;
+            }
+        }
+        // Each directed edge has to be used once and its opposite once as well
+        let open_edges = edges
+            .iter()
+            .filter(|((from, to), count)| **count != 1 || edges.get(&(*to, *from)) != Some(&1))
+            .count();
+        if open_edges > 0 {
+            return Err(format!(
+                "Mesh is not watertight, {} edges are not shared by exactly two triangles",
+                open_edges
+            ));
+        }
+        Ok(())
+    }
+
+    /// Recomputes vertex normals as area weighted average of normals of adjacent triangles.
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![glam::Vec3::ZERO; self.vertex_count()];
+        for [a, b, c] in self.triangles() {
+            let [pa, pb, pc] = [a, b, c].map(|index| self.positions[index as usize]);
+            let normal = (pb - pa).cross(pc - pa);
+            for index in [a, b, c] {
+                normals[index as usize] += normal;
+            }
+        }
+        self.normals = normals
+            .into_iter()
+            .map(|normal| normal.normalize_or_zero())
+            .collect();
+    }
+
+    /// Stores the mesh as a Wavefront OBJ file.
+    ///   - Vertex colors are written after vertex positions, which is a widely supported extension of the format.
+    #[profiler::function]
+    pub fn store_obj<P: AsRef<Path>>(&self, file_name: P) -> Result<(), String> {
+        let mut writer = create_file(file_name)?;
+        let mut write = || -> std::io::Result<()> {
+            writeln!(writer, "# sdf-edit-rs mesh")?;
+            for (position, color) in self.positions.iter().zip(self.colors.iter()) {
+                writeln!(
+                    writer,
+                    "v {} {} {} {} {} {}",
+                    position.x, position.y, position.z, color.x, color.y, color.z
+                )?;
+            }
+            for normal in &self.normals {
+                writeln!(writer, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+            }
+            // OBJ indices start at 1
+            for [a, b, c] in self.triangles() {
+                let (a, b, c) = (a + 1, b + 1, c + 1);
+                writeln!(writer, "f {}//{} {}//{} {}//{}", a, a, b, b, c, c)?;
+            }
+            writer.flush()
+        };
+        write().map_err(|err| format!("Failed to write to file: {}", err))
+    }
+
+    /// Stores the mesh as a binary little endian PLY file.
+    #[profiler::function]
+    pub fn store_ply<P: AsRef<Path>>(&self, file_name: P) -> Result<(), String> {
+        let mut writer = create_file(file_name)?;
+        self.write_ply(&mut writer)
+            .map_err(|err| format!("Failed to write to file: {}", err))
+    }
+
+    /// Writes the mesh in binary little endian PLY format, faces follow vertices as lists of three indices.
+    pub fn write_ply<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        ply::write_header(
+            writer,
+            true,
+            "sdf-edit-rs mesh",
+            self.vertex_count(),
+            Some(self.triangle_count()),
+        )?;
+        ply::write_vertices(writer, true, &self.positions, &self.normals, &self.colors)?;
+        for triangle in self.triangles() {
+            writer.write_all(&[3u8])?;
+            write_u32s(writer, &triangle)?;
+        }
+        writer.flush()
+    }
+
+    /// Stores the mesh as a binary STL file, positions are multiplied by given scale.
+    ///   - STL has no units, printing software usually reads them as millimetres.
+    #[profiler::function]
+    pub fn store_stl<P: AsRef<Path>>(&self, file_name: P, scale: f32) -> Result<(), String> {
+        let mut writer = create_file(file_name)?;
+        let mut write = || -> std::io::Result<()> {
+            let mut header = [0u8; 80];
+            let title = b"sdf-edit-rs mesh";
+            header[..title.len()].copy_from_slice(title);
+            writer.write_all(&header)?;
+            write_u32s(&mut writer, &[self.triangle_count() as u32])?;
+            for [a, b, c] in self.triangles() {
+                let [pa, pb, pc] = [a, b, c].map(|index| self.positions[index as usize] * scale);
+                let normal = (pb - pa).cross(pc - pa).normalize_or_zero();
+                for vector in [normal, pa, pb, pc] {
+                    write_f32s(&mut writer, &vector.to_array())?;
+                }
+                // Attribute byte count, unused
+                writer.write_all(&[0u8; 2])?;
+            }
+            writer.flush()
+        };
+        write().map_err(|err| format!("Failed to write to file: {}", err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ply_has_vertex_and_face_records() {
+        let mesh = Mesh {
+            positions: vec![glam::Vec3::ZERO, glam::Vec3::X, glam::Vec3::Y],
+            normals: vec![glam::Vec3::Z; 3],
+            colors: vec![glam::Vec4::ONE; 3],
+            indices: vec![0, 1, 2],
+        };
+
+        let mut bytes = vec![];
+        mesh.write_ply(&mut bytes).unwrap();
+        let header_end = b"end_header\n";
+        let header_length = bytes
+            .windows(header_end.len())
+            .position(|window| window == header_end)
+            .unwrap()
+            + header_end.len();
+        let header = std::str::from_utf8(&bytes[..header_length]).unwrap();
+        assert!(header.starts_with("ply\nformat binary_little_endian 1.0\n"));
+        assert!(header.contains("element vertex 3\n"));
+        assert!(header.contains("element face 1\nproperty list uchar uint vertex_indices\n"));
+
+        // Six floats and four color bytes per vertex, then the index count and three indices per face
+        let faces = &bytes[header_length + 3 * (6 * 4 + 4)..];
+        assert_eq!(faces.len(), 1 + 3 * 4);
+        assert_eq!(faces[0], 3);
+        let indices: Vec<u32> = faces[1..]
+            .chunks_exact(4)
+            .map(|index| u32::from_le_bytes(index.try_into().unwrap()))
+            .collect();
+        assert_eq!(indices, mesh.indices);
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+
+use super::Mesh;
+use crate::sdf::svo::{BrickPoolFormat, SvoSample, SvoSampler};
+
+/// Extracts a triangle mesh from an SVO read back to CPU by dual contouring.
+///   - Each leaf brick is sampled into its own lattice of `BRICK_SIZE^3` cells with corners at corners of its voxels,
+///     so memory grows with the number of leaf bricks and not with the domain size.
+///     Corners shared by multiple bricks are sampled only once from the most detailed brick containing them.
+///   - Each cell around a lattice edge crossed by the surface gets one vertex, which is placed into the mass point
+///     of edge crossings of the cell and then projected onto the surface along the SDF gradient.
+///   - Each crossed edge produces a quad connecting vertices of cells around it. Edges are taken from the smallest cells,
+///     so neighboring bricks of different levels are connected by triangles where a larger cell is on multiple sides
+///     of the edge and the mesh has no cracks.
+pub struct Mesher<'a> {
+    sampler: &'a SvoSampler<'a>,
+}
+
+/// A cell of a leaf brick lattice given by its minimal corner and size in lattice units.
+type LatticeCell = (glam::IVec3, i32);
+
+/// Corner offsets of a lattice cell, corner `c` has offset `(c & 1, (c >> 1) & 1, (c >> 2) & 1)`.
+const CORNERS: [glam::IVec3; 8] = [
+    glam::IVec3::new(0, 0, 0),
+    glam::IVec3::new(1, 0, 0),
+    glam::IVec3::new(0, 1, 0),
+    glam::IVec3::new(1, 1, 0),
+    glam::IVec3::new(0, 0, 1),
+    glam::IVec3::new(1, 0, 1),
+    glam::IVec3::new(0, 1, 1),
+    glam::IVec3::new(1, 1, 1),
+];
+
+/// Rounds each coordinate of a lattice point down to a multiple of `size`.
+fn floor_to_multiple(point: glam::IVec3, size: i32) -> glam::IVec3 {
+    glam::IVec3::new(
+        point.x.div_euclid(size),
+        point.y.div_euclid(size),
+        point.z.div_euclid(size),
+    ) * size
+}
+
+/// Samples at corners of a lattice cell, with trilinear interpolation in cell local coordinates.
+struct Cell([SvoSample; 8]);
+
+impl Cell {
+    fn weights(t: glam::Vec3) -> [f32; 8] {
+        let mut weights = [0.0; 8];
+        for (corner, weight) in weights.iter_mut().enumerate() {
+            let w = glam::Vec3::select(CORNERS[corner].cmpeq(glam::IVec3::ONE), t, 1.0 - t);
+            *weight = w.x * w.y * w.z;
+        }
+        weights
+    }
+
+    fn distance(&self, t: glam::Vec3) -> f32 {
+        let weights = Self::weights(t);
+        (0..8).map(|c| weights[c] * self.0[c].distance).sum()
+    }
+
+    fn color(&self, t: glam::Vec3) -> glam::Vec4 {
+        let weights = Self::weights(t);
+        (0..8).map(|c| weights[c] * self.0[c].color).sum()
+    }
+
+    /// Gradient of trilinearly interpolated distance in cell local coordinates.
+    fn gradient(&self, t: glam::Vec3) -> glam::Vec3 {
+        let mut gradient = glam::Vec3::ZERO;
+        for (corner, sample) in self.0.iter().enumerate() {
+            let offset = CORNERS[corner].cmpeq(glam::IVec3::ONE);
+            let w = glam::Vec3::select(offset, t, 1.0 - t);
+            let sign = glam::Vec3::select(offset, glam::Vec3::ONE, -glam::Vec3::ONE);
+            gradient += sign * glam::Vec3::new(w.y * w.z, w.x * w.z, w.x * w.y) * sample.distance;
+        }
+        gradient
+    }
+}
+
+impl<'a> Mesher<'a> {
+    /// Number of gradient steps projecting cell vertices onto the surface.
+    const PROJECTION_STEPS: u32 = 2;
+
+    pub fn new(sampler: &'a SvoSampler<'a>) -> Self {
+        Self { sampler }
+    }
+
+    #[profiler::function]
+    pub fn mesh(&self) -> Mesh {
+        let mut mesh = Mesh::default();
+        let Some(min_voxel_size) = self.sampler.min_voxel_size() else {
+            return mesh;
+        };
+        // Half of the smallest voxel, so centers of edges of the smallest cells have integer coordinates
+        let unit = min_voxel_size * 0.5;
+        let domain_min = self.sampler.domain_min();
+        let position = |corner: glam::IVec3| domain_min + corner.as_vec3() * unit;
+        let cells_per_side = BrickPoolFormat::BRICK_SIZE as i32;
+
+        // Leaves as their minimal corner and size in lattice units, the most detailed first
+        let mut leaves = self.sampler.leaf_bricks().to_vec();
+        leaves.sort_by_key(|leaf| std::cmp::Reverse(leaf.level));
+        let lattice_leaves: Vec<(glam::IVec3, i32)> = leaves
+            .iter()
+            .map(|leaf| {
+                let min = ((leaf.min - domain_min) / unit).round().as_ivec3();
+                (min, (leaf.size / unit).round() as i32)
+            })
+            .collect();
+        let leaf_lookup: HashMap<(glam::IVec3, i32), usize> = lattice_leaves
+            .iter()
+            .enumerate()
+            .map(|(index, leaf)| (*leaf, index))
+            .collect();
+        let mut leaf_sizes: Vec<i32> = lattice_leaves.iter().map(|(_, size)| *size).collect();
+        leaf_sizes.sort();
+        leaf_sizes.dedup();
+
+        // Sample corners of cells of each leaf
+        let mut corners: HashMap<glam::IVec3, SvoSample> = HashMap::new();
+        for (leaf, (min, size)) in leaves.iter().zip(lattice_leaves.iter()) {
+            let cell_size = size / cells_per_side;
+            for z in 0..=cells_per_side {
+                for y in 0..=cells_per_side {
+                    for x in 0..=cells_per_side {
+                        let corner = *min + glam::IVec3::new(x, y, z) * cell_size;
+                        corners
+                            .entry(corner)
+                            .or_insert_with(|| self.sampler.sample_brick(leaf, position(corner)));
+                    }
+                }
+            }
+        }
+
+        // Finds the cell containing a lattice point in the most detailed leaf
+        let cell_at = |point: glam::IVec3| -> Option<LatticeCell> {
+            leaf_sizes.iter().find_map(|size| {
+                let min = floor_to_multiple(point, *size);
+                leaf_lookup.get(&(min, *size))?;
+                let cell_size = size / cells_per_side;
+                let lower = min + floor_to_multiple(point - min, cell_size);
+                Some((lower, cell_size))
+            })
+        };
+        let cell = |(lower, size): LatticeCell| {
+            Cell(CORNERS.map(|offset| corners[&(lower + offset * size)]))
+        };
+
+        // Vertices are created only for cells around crossed edges
+        let mut cell_vertices: HashMap<LatticeCell, u32> = HashMap::new();
+        let mut vertex = |mesh: &mut Mesh, lattice_cell: LatticeCell| -> u32 {
+            *cell_vertices.entry(lattice_cell).or_insert_with(|| {
+                let cell = cell(lattice_cell);
+                let t = self.cell_vertex(&cell);
+                mesh.positions
+                    .push(position(lattice_cell.0) + t * lattice_cell.1 as f32 * unit);
+                mesh.normals.push(cell.gradient(t).normalize_or_zero());
+                mesh.colors.push(cell.color(t));
+                mesh.positions.len() as u32 - 1
+            })
+        };
+
+        // Connect vertices of cells around each lattice edge crossed by the surface
+        for (min, size) in lattice_leaves.iter() {
+            let cell_size = size / cells_per_side;
+            for local in 0..cells_per_side.pow(3) {
+                let local = glam::IVec3::new(
+                    local % cells_per_side,
+                    (local / cells_per_side) % cells_per_side,
+                    local / (cells_per_side * cells_per_side),
+                );
+                let lower = *min + local * cell_size;
+                for axis in 0..3 {
+                    let along = glam::IVec3::AXES[axis];
+                    let u = glam::IVec3::AXES[(axis + 1) % 3];
+                    let v = glam::IVec3::AXES[(axis + 2) % 3];
+                    for (du, dv) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let start = lower + (u * du + v * dv) * cell_size;
+                        let inside = corners[&start].distance < 0.0;
+                        if inside == (corners[&(start + along * cell_size)].distance < 0.0) {
+                            continue;
+                        }
+
+                        let middle = start + along * (cell_size / 2);
+                        let around =
+                            [u + v, v - u, -u - v, u - v].map(|offset| cell_at(middle + offset));
+                        let [Some(a), Some(b), Some(c), Some(d)] = around else {
+                            continue;
+                        };
+
+                        // Edge belongs to the first of the smallest cells around it, only they contain the whole edge
+                        let smallest = [a, b, c, d].iter().map(|(_, size)| *size).min();
+                        let owner = [a, b, c, d]
+                            .into_iter()
+                            .find(|(_, size)| Some(*size) == smallest);
+                        if owner != Some((lower, cell_size)) {
+                            continue;
+                        }
+
+                        // Larger cells can be on multiple sides of the edge, they make a triangle instead of a quad
+                        let mut polygon: Vec<u32> = vec![];
+                        for index in [a, b, c, d].map(|cell| vertex(&mut mesh, cell)) {
+                            if polygon.last() != Some(&index) && polygon.first() != Some(&index) {
+                                polygon.push(index);
+                            }
+                        }
+                        if polygon.len() < 3 {
+                            continue;
+                        }
+
+                        // Surface faces from inside towards outside along the edge
+                        let outward = if inside { along } else { -along }.as_vec3();
+                        let points: Vec<glam::Vec3> = polygon
+                            .iter()
+                            .map(|index| mesh.positions[*index as usize])
+                            .collect();
+                        let normal: glam::Vec3 = (1..points.len() - 1)
+                            .map(|i| (points[i] - points[0]).cross(points[i + 1] - points[0]))
+                            .sum();
+                        if normal.dot(outward) < 0.0 {
+                            polygon.reverse();
+                        }
+                        for i in 1..polygon.len() - 1 {
+                            mesh.indices.extend_from_slice(&[
+                                polygon[0],
+                                polygon[i],
+                                polygon[i + 1],
+                            ]);
+                        }
+                    }
+                }
+            }
+        }
+
+        mesh
+    }
+
+    /// Returns position of a cell vertex in cell local coordinates.
+    ///   - Cells whose corners are not crossed by the surface start from their center,
+    ///     they are around a crossed edge of a smaller neighboring cell.
+    fn cell_vertex(&self, cell: &Cell) -> glam::Vec3 {
+        let mut crossing_sum = glam::Vec3::ZERO;
+        let mut crossing_count = 0;
+        for (from, from_corner) in CORNERS.iter().enumerate() {
+            for bit in [1, 2, 4] {
+                let to = from | bit;
+                if to == from {
+                    continue;
+                }
+                let (d0, d1) = (cell.0[from].distance, cell.0[to].distance);
+                if (d0 < 0.0) == (d1 < 0.0) {
+                    continue;
+                }
+                let t = d0 / (d0 - d1);
+                crossing_sum += from_corner.as_vec3().lerp(CORNERS[to].as_vec3(), t);
+                crossing_count += 1;
+            }
+        }
+
+        let mut t = if crossing_count == 0 {
+            glam::Vec3::splat(0.5)
+        } else {
+            crossing_sum / crossing_count as f32
+        };
+        for _ in 0..Self::PROJECTION_STEPS {
+            let gradient = cell.gradient(t);
+            let length_squared = gradient.length_squared();
+            if length_squared < 1e-12 {
+                break;
+            }
+            t = (t - gradient * cell.distance(t) / length_squared)
+                .clamp(glam::Vec3::ZERO, glam::Vec3::ONE);
+        }
+        t
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        framework::math::BoundingCube,
+        sdf::svo::{svo_data_from_distance_function, SvoData},
+    };
+
+    const RADIUS: f32 = 0.3;
+
+    fn sphere_svo(subdivide: impl Fn(glam::Vec3, f32) -> bool) -> SvoData {
+        svo_data_from_distance_function(BoundingCube::UNIT, 2, subdivide, |position| {
+            position.length() - RADIUS
+        })
+    }
+
+    fn assert_sphere(mesh: &Mesh) {
+        assert!(mesh.triangle_count() > 0);
+        mesh.check_watertight().unwrap();
+
+        let volume = 4.0 / 3.0 * std::f32::consts::PI * RADIUS.powi(3);
+        assert!(
+            (mesh.volume() - volume).abs() < volume * 0.05,
+            "volume {} differs from {}",
+            mesh.volume(),
+            volume
+        );
+        for position in mesh.positions.iter() {
+            assert!((position.length() - RADIUS).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn meshes_uniform_svo_into_closed_surface() {
+        let data = sphere_svo(|_, _| true);
+        assert_sphere(&Mesher::new(&SvoSampler::new(&data)).mesh());
+    }
+
+    #[test]
+    fn stitches_bricks_of_different_levels_without_cracks() {
+        let data = sphere_svo(|center, size| size > 0.3 || center.x > 0.0);
+        let sampler = SvoSampler::new(&data);
+        let levels: Vec<usize> = sampler
+            .leaf_bricks()
+            .iter()
+            .map(|leaf| leaf.level)
+            .collect();
+        assert!(levels.contains(&1) && levels.contains(&2));
+
+        assert_sphere(&Mesher::new(&sampler).mesh());
+    }
+}
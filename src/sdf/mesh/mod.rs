@@ -0,0 +1,16 @@
+mod mesh;
+pub use mesh::*;
+
+mod mesher;
+pub use mesher::*;
+
+mod gltf;
+pub use gltf::*;
+
+mod watertight_mesher;
+pub use watertight_mesher::*;
+
+mod point_cloud;
+pub use point_cloud::*;
+
+mod ply;
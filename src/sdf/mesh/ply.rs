@@ -0,0 +1,81 @@
+use std::io::Write;
+
+use crate::framework::binary_writer::write_f32s;
+
+/// Writes a PLY header declaring vertices with position, normal and color, optionally followed by triangle faces.
+pub(super) fn write_header<W: Write>(
+    writer: &mut W,
+    binary: bool,
+    comment: &str,
+    vertex_count: usize,
+    face_count: Option<usize>,
+) -> std::io::Result<()> {
+    write!(
+        writer,
+        "ply\n\
+         format {} 1.0\n\
+         comment {}\n\
+         element vertex {}\n\
+         property float x\n\
+         property float y\n\
+         property float z\n\
+         property float nx\n\
+         property float ny\n\
+         property float nz\n\
+         property uchar red\n\
+         property uchar green\n\
+         property uchar blue\n\
+         property uchar alpha\n",
+        if binary {
+            "binary_little_endian"
+        } else {
+            "ascii"
+        },
+        comment,
+        vertex_count
+    )?;
+    if let Some(face_count) = face_count {
+        write!(
+            writer,
+            "element face {}\n\
+             property list uchar uint vertex_indices\n",
+            face_count
+        )?;
+    }
+    writer.write_all(b"end_header\n")
+}
+
+/// Writes vertex records declared by `write_header`, colors are clamped and quantized to bytes.
+pub(super) fn write_vertices<W: Write>(
+    writer: &mut W,
+    binary: bool,
+    positions: &[glam::Vec3],
+    normals: &[glam::Vec3],
+    colors: &[glam::Vec4],
+) -> std::io::Result<()> {
+    for ((position, normal), color) in positions.iter().zip(normals.iter()).zip(colors.iter()) {
+        let color = (color.clamp(glam::Vec4::ZERO, glam::Vec4::ONE) * 255.0).round();
+        let color = [color.x as u8, color.y as u8, color.z as u8, color.w as u8];
+        if binary {
+            write_f32s(writer, &position.to_array())?;
+            write_f32s(writer, &normal.to_array())?;
+            writer.write_all(&color)?;
+        } else {
+            writeln!(
+                writer,
+                "{} {} {} {} {} {} {} {} {} {}",
+                position.x,
+                position.y,
+                position.z,
+                normal.x,
+                normal.y,
+                normal.z,
+                color[0],
+                color[1],
+                color[2],
+                color[3]
+            )?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,336 @@
+use std::{collections::HashMap, io::Write, path::Path};
+
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+
+use super::ply;
+use crate::framework::{binary_writer::create_file, math::AABB};
+
+/// Points on a surface with their normals and colors.
+#[derive(Debug, Clone, Default)]
+pub struct PointCloud {
+    pub positions: Vec<glam::Vec3>,
+    pub normals: Vec<glam::Vec3>,
+    pub colors: Vec<glam::Vec4>,
+}
+
+impl PointCloud {
+    pub fn point_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Stores the points into a PLY file, either binary little endian or ASCII.
+    #[profiler::function]
+    pub fn store_ply<P: AsRef<Path>>(&self, file_name: P, binary: bool) -> Result<(), String> {
+        let mut writer = create_file(file_name)?;
+        self.write_ply(&mut writer, binary)
+            .map_err(|err| format!("Failed to write to file: {}", err))
+    }
+
+    /// Writes the points in PLY format, either binary little endian or ASCII.
+    pub fn write_ply<W: Write>(&self, writer: &mut W, binary: bool) -> std::io::Result<()> {
+        ply::write_header(
+            writer,
+            binary,
+            "sdf-edit-rs point cloud",
+            self.point_count(),
+            None,
+        )?;
+        ply::write_vertices(writer, binary, &self.positions, &self.normals, &self.colors)?;
+        writer.flush()
+    }
+}
+
+/// Spreads points evenly over the zero level set of a distance field.
+///   - Candidate points are scattered in cells of a coarse grid which the surface passes through,
+///     and projected onto the surface along the gradient of the distance field.
+///   - Candidates are then thinned out by Poisson disk sampling, the disk radius shrinks until enough points are accepted.
+///   - Sampling is deterministic, the same field always gives the same points.
+pub struct SurfaceSampler<F>
+where
+    F: Fn(glam::Vec3) -> (f32, glam::Vec4),
+{
+    sample: F,
+}
+
+impl<F> SurfaceSampler<F>
+where
+    F: Fn(glam::Vec3) -> (f32, glam::Vec4),
+{
+    /// Candidates generated per requested point, more candidates give more even results.
+    const CANDIDATES_PER_POINT: usize = 8;
+    const PROJECTION_STEPS: usize = 6;
+    /// Ratio of Poisson disk radius decrease when not enough points were accepted.
+    const RADIUS_DECREASE: f32 = 0.85;
+    const SEED: u64 = 0;
+
+    pub fn new(sample: F) -> Self {
+        Self { sample }
+    }
+
+    #[profiler::function]
+    pub fn sample(&self, aabb: &AABB, point_count: usize) -> Result<PointCloud, String> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(Self::SEED);
+
+        // Grid is just fine enough to find thin features, density of points comes from candidates
+        let resolution = ((point_count as f32).sqrt() as u32).clamp(16, 128);
+        let size = aabb.max - aabb.min;
+        let cell_size = size.max_element().max(f32::EPSILON) / resolution as f32;
+        let cell_count = (size / cell_size).ceil().as_uvec3().max(glam::UVec3::ONE);
+        let surface_cells = self.surface_cells(aabb.min, cell_size, cell_count);
+        if surface_cells.is_empty() {
+            return Err("Geometry has no surface".to_string());
+        }
+
+        let candidates_per_cell = (point_count * Self::CANDIDATES_PER_POINT)
+            .div_ceil(surface_cells.len())
+            .max(1);
+        let epsilon = cell_size * 0.01;
+        let mut candidates = vec![];
+        for cell_min in surface_cells.iter() {
+            for _ in 0..candidates_per_cell {
+                let offset = glam::Vec3::new(rng.random(), rng.random(), rng.random());
+                if let Some(candidate) = self.project(*cell_min + offset * cell_size, epsilon) {
+                    candidates.push(candidate);
+                }
+            }
+        }
+        if candidates.len() < point_count {
+            return Err(format!(
+                "Only {} of {} points could be projected onto the surface",
+                candidates.len(),
+                point_count
+            ));
+        }
+        candidates.shuffle(&mut rng);
+
+        // Start from radius of disks tightly covering surface area estimated from the surface cells
+        let area = surface_cells.len() as f32 * cell_size * cell_size;
+        let mut radius = (area / (point_count as f32 * std::f32::consts::PI)).sqrt() * 2.0;
+        loop {
+            let accepted = poisson_disk(&candidates, radius);
+            if accepted.len() >= point_count || radius < epsilon {
+                let mut cloud = PointCloud::default();
+                for index in accepted.into_iter().take(point_count) {
+                    let (position, normal, color) = candidates[index];
+                    cloud.positions.push(position);
+                    cloud.normals.push(normal);
+                    cloud.colors.push(color);
+                }
+                return Ok(cloud);
+            }
+            radius *= Self::RADIUS_DECREASE;
+        }
+    }
+
+    /// Minimal corners of cells with a sign change of distance or with distance small enough for the surface to pass through.
+    fn surface_cells(
+        &self,
+        min: glam::Vec3,
+        cell_size: f32,
+        cell_count: glam::UVec3,
+    ) -> Vec<glam::Vec3> {
+        let corner_count = cell_count + 1;
+        let corner = |x: u32, y: u32, z: u32| min + glam::UVec3::new(x, y, z).as_vec3() * cell_size;
+        let mut distances =
+            Vec::with_capacity((corner_count.x * corner_count.y * corner_count.z) as usize);
+        for z in 0..corner_count.z {
+            for y in 0..corner_count.y {
+                for x in 0..corner_count.x {
+                    distances.push((self.sample)(corner(x, y, z)).0);
+                }
+            }
+        }
+        let distance = |x: u32, y: u32, z: u32| {
+            distances[(x + corner_count.x * (y + corner_count.y * z)) as usize]
+        };
+
+        let mut cells = vec![];
+        let half_diagonal = cell_size * 3f32.sqrt() * 0.5;
+        for z in 0..cell_count.z {
+            for y in 0..cell_count.y {
+                for x in 0..cell_count.x {
+                    let corners = [0u32, 1, 2, 3, 4, 5, 6, 7]
+                        .map(|c| distance(x + (c & 1), y + ((c >> 1) & 1), z + ((c >> 2) & 1)));
+                    let inside = corners.iter().filter(|d| **d < 0.0).count();
+                    let center = corners.iter().sum::<f32>() / 8.0;
+                    if (inside > 0 && inside < 8) || center.abs() < half_diagonal {
+                        cells.push(corner(x, y, z));
+                    }
+                }
+            }
+        }
+        cells
+    }
+
+    /// Moves a position onto the surface by Newton steps along the gradient.
+    ///   - Returns the position with outward normal and color, None when the surface was not reached.
+    fn project(
+        &self,
+        mut position: glam::Vec3,
+        epsilon: f32,
+    ) -> Option<(glam::Vec3, glam::Vec3, glam::Vec4)> {
+        for _ in 0..Self::PROJECTION_STEPS {
+            let (distance, color) = (self.sample)(position);
+            let gradient = self.gradient(position, epsilon);
+            let length_squared = gradient.length_squared();
+            if length_squared < 1e-8 {
+                return None;
+            }
+            if distance.abs() < epsilon {
+                return Some((position, gradient.normalize(), color));
+            }
+            position -= gradient * distance / length_squared;
+        }
+        None
+    }
+
+    /// Central differences of the distance field.
+    fn gradient(&self, position: glam::Vec3, epsilon: f32) -> glam::Vec3 {
+        let difference = |axis: glam::Vec3| {
+            ((self.sample)(position + axis * epsilon).0
+                - (self.sample)(position - axis * epsilon).0)
+                / (2.0 * epsilon)
+        };
+        glam::Vec3::new(
+            difference(glam::Vec3::X),
+            difference(glam::Vec3::Y),
+            difference(glam::Vec3::Z),
+        )
+    }
+}
+
+/// Accepts candidates in their order which are not closer than the radius to already accepted ones.
+///   - Returns indices of accepted candidates, nearby points are found in a hash grid with cells of the radius size.
+fn poisson_disk(candidates: &[(glam::Vec3, glam::Vec3, glam::Vec4)], radius: f32) -> Vec<usize> {
+    let cell = |position: glam::Vec3| (position / radius).floor().as_ivec3();
+    let mut grid: HashMap<glam::IVec3, Vec<usize>> = HashMap::new();
+    let mut accepted = vec![];
+    let radius_squared = radius * radius;
+    for (index, (position, _, _)) in candidates.iter().enumerate() {
+        let center = cell(*position);
+        let mut is_free = true;
+        'search: for z in -1..=1 {
+            for y in -1..=1 {
+                for x in -1..=1 {
+                    let Some(neighbors) = grid.get(&(center + glam::IVec3::new(x, y, z))) else {
+                        continue;
+                    };
+                    if neighbors.iter().any(|neighbor| {
+                        candidates[*neighbor].0.distance_squared(*position) < radius_squared
+                    }) {
+                        is_free = false;
+                        break 'search;
+                    }
+                }
+            }
+        }
+        if is_free {
+            grid.entry(center).or_default().push(index);
+            accepted.push(index);
+        }
+    }
+    accepted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RADIUS: f32 = 0.5;
+    const COLOR: glam::Vec4 = glam::Vec4::new(1.0, 0.5, 0.0, 1.0);
+
+    fn sphere_cloud(point_count: usize) -> PointCloud {
+        SurfaceSampler::new(|position: glam::Vec3| (position.length() - RADIUS, COLOR))
+            .sample(
+                &AABB::new(glam::Vec3::splat(-1.0), glam::Vec3::ONE),
+                point_count,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn points_lie_on_sphere_with_outward_normals() {
+        let cloud = sphere_cloud(500);
+
+        assert_eq!(cloud.point_count(), 500);
+        assert_eq!(cloud.normals.len(), 500);
+        assert_eq!(cloud.colors.len(), 500);
+        for ((position, normal), color) in cloud
+            .positions
+            .iter()
+            .zip(cloud.normals.iter())
+            .zip(cloud.colors.iter())
+        {
+            assert!(
+                (position.length() - RADIUS).abs() < 1e-3,
+                "position {position}"
+            );
+            assert!(normal.dot(position.normalize()) > 0.999, "normal {normal}");
+            assert_eq!(*color, COLOR);
+        }
+    }
+
+    #[test]
+    fn points_are_spread_evenly() {
+        let cloud = sphere_cloud(500);
+
+        // Spacing of points in a hexagonal packing covering the sphere
+        let area = 4.0 * std::f32::consts::PI * RADIUS * RADIUS;
+        let spacing = (2.0 * area / (500.0 * 3f32.sqrt())).sqrt();
+        for (index, position) in cloud.positions.iter().enumerate() {
+            let closest = cloud
+                .positions
+                .iter()
+                .enumerate()
+                .filter(|(other_index, _)| *other_index != index)
+                .map(|(_, other)| position.distance(*other))
+                .fold(f32::MAX, f32::min);
+            assert!(
+                closest > 0.3 * spacing,
+                "closest {closest}, spacing {spacing}"
+            );
+        }
+        assert_eq!(sphere_cloud(500).positions, cloud.positions);
+    }
+
+    #[test]
+    fn fails_without_surface() {
+        let result = SurfaceSampler::new(|position: glam::Vec3| (position.length() + 1.0, COLOR))
+            .sample(&AABB::new(glam::Vec3::splat(-1.0), glam::Vec3::ONE), 100);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn ply_has_header_and_point_records() {
+        let cloud = sphere_cloud(10);
+
+        let mut binary = vec![];
+        cloud.write_ply(&mut binary, true).unwrap();
+        let header_end = b"end_header\n";
+        let header_length = binary
+            .windows(header_end.len())
+            .position(|window| window == header_end)
+            .unwrap()
+            + header_end.len();
+        let header = std::str::from_utf8(&binary[..header_length]).unwrap();
+        assert!(header.starts_with("ply\nformat binary_little_endian 1.0\n"));
+        assert!(header.contains("element vertex 10\n"));
+        // Six floats and four color bytes per point
+        assert_eq!(binary.len() - header_length, 10 * (6 * 4 + 4));
+        let x = f32::from_le_bytes(binary[header_length..header_length + 4].try_into().unwrap());
+        assert_eq!(x, cloud.positions[0].x);
+
+        let mut ascii = vec![];
+        cloud.write_ply(&mut ascii, false).unwrap();
+        let ascii = String::from_utf8(ascii).unwrap();
+        let (_, records) = ascii.split_once("end_header\n").unwrap();
+        let records: Vec<&str> = records.lines().collect();
+        assert_eq!(records.len(), 10);
+        assert!(
+            records[0].ends_with(" 255 128 0 255"),
+            "record {}",
+            records[0]
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+
+use super::Mesh;
+use crate::{
+    framework::math::BoundingCube,
+    sdf::geometry::{EditSampler, SdfSample},
+};
+
+/// Extracts a closed manifold triangle mesh from an exact edit list on CPU, intended for 3D printing.
+///   - The domain is sampled in a regular lattice of chosen resolution, so detail of the mesh depends only on the resolution
+///     and not on voxel size of an SVO. The lattice extends one cell past the domain on each side.
+///   - Each lattice cell is split into 6 tetrahedra around its main diagonal, the same way in all cells,
+///     so the tetrahedra of neighboring cells share faces. Surface is extracted by marching tetrahedra,
+///     which has no ambiguous cases, therefore the surface is always a closed manifold.
+///   - The surface is open when it touches the lattice boundary, such edit list is refused.
+pub struct WatertightMesher<'a> {
+    sampler: &'a EditSampler<'a>,
+    domain: BoundingCube,
+    resolution: u32,
+}
+
+/// Corner offsets of a lattice cell, corner `c` has offset `(c & 1, (c >> 1) & 1, (c >> 2) & 1)`.
+const CORNERS: [glam::UVec3; 8] = [
+    glam::UVec3::new(0, 0, 0),
+    glam::UVec3::new(1, 0, 0),
+    glam::UVec3::new(0, 1, 0),
+    glam::UVec3::new(1, 1, 0),
+    glam::UVec3::new(0, 0, 1),
+    glam::UVec3::new(1, 0, 1),
+    glam::UVec3::new(0, 1, 1),
+    glam::UVec3::new(1, 1, 1),
+];
+
+/// Tetrahedra of a cell, all share the diagonal from corner 0 to corner 7.
+const TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 1, 3, 7],
+    [0, 3, 2, 7],
+    [0, 2, 6, 7],
+    [0, 6, 4, 7],
+    [0, 4, 5, 7],
+    [0, 5, 1, 7],
+];
+
+impl<'a> WatertightMesher<'a> {
+    pub const MIN_RESOLUTION: u32 = 2;
+    pub const MAX_RESOLUTION: u32 = 1024;
+
+    /// Edges shorter than this fraction of the lattice spacing are collapsed.
+    const MIN_EDGE_LENGTH: f32 = 1e-4;
+
+    /// Resolution is the number of lattice cells along a side of the domain.
+    pub fn new(sampler: &'a EditSampler<'a>, domain: BoundingCube, resolution: u32) -> Self {
+        Self {
+            sampler,
+            domain,
+            resolution: resolution.clamp(Self::MIN_RESOLUTION, Self::MAX_RESOLUTION),
+        }
+    }
+
+    #[profiler::function]
+    pub fn mesh(&self) -> Result<Mesh, String> {
+        let mut mesh = Mesh::default();
+        let spacing = self.domain.size / self.resolution as f32;
+        let origin = self.domain.pos - self.domain.size * 0.5 - spacing;
+        let cell_count = self.resolution + 2;
+        let corner_count = cell_count + 1;
+        let position = |corner: glam::UVec3| origin + corner.as_vec3() * spacing;
+        let corner_index = |corner: glam::UVec3| {
+            corner.x as u64
+                + corner_count as u64 * (corner.y as u64 + corner_count as u64 * corner.z as u64)
+        };
+
+        // Lattice is sampled one layer at a time, only two layers are needed for cells between them
+        let sample_layer = |z: u32| -> Result<Vec<SdfSample>, String> {
+            let mut layer = Vec::with_capacity((corner_count * corner_count) as usize);
+            for y in 0..corner_count {
+                for x in 0..corner_count {
+                    let corner = glam::UVec3::new(x, y, z);
+                    let sample = self.sampler.sample(position(corner));
+                    let on_boundary = x == 0
+                        || y == 0
+                        || z == 0
+                        || x == cell_count
+                        || y == cell_count
+                        || z == cell_count;
+                    if on_boundary && sample.distance < 0.0 {
+                        return Err(format!(
+                            "Surface touches the domain boundary at {}, the mesh would be open",
+                            position(corner)
+                        ));
+                    }
+                    layer.push(sample);
+                }
+            }
+            Ok(layer)
+        };
+
+        let mut edge_vertices: HashMap<(u64, u64), u32> = HashMap::new();
+        let mut lower_layer = sample_layer(0)?;
+        for z in 0..cell_count {
+            let upper_layer = sample_layer(z + 1)?;
+            for y in 0..cell_count {
+                for x in 0..cell_count {
+                    let lower = glam::UVec3::new(x, y, z);
+                    let corners = CORNERS.map(|offset| lower + offset);
+                    let samples = CORNERS.map(|offset| {
+                        let layer = if offset.z == 0 {
+                            &lower_layer
+                        } else {
+                            &upper_layer
+                        };
+                        layer[((y + offset.y) * corner_count + x + offset.x) as usize]
+                    });
+                    if samples.iter().all(|sample| sample.distance < 0.0)
+                        || samples.iter().all(|sample| sample.distance >= 0.0)
+                    {
+                        continue;
+                    }
+
+                    for tetrahedron in TETRAHEDRA {
+                        let mut edge_vertex = |from: usize, to: usize| {
+                            let (a, b) = (tetrahedron[from], tetrahedron[to]);
+                            let key = (corner_index(corners[a]), corner_index(corners[b]));
+                            let key = (key.0.min(key.1), key.0.max(key.1));
+                            *edge_vertices.entry(key).or_insert_with(|| {
+                                let (sa, sb) = (samples[a], samples[b]);
+                                let t = sa.distance / (sa.distance - sb.distance);
+                                mesh.positions
+                                    .push(position(corners[a]).lerp(position(corners[b]), t));
+                                mesh.colors.push(sa.color.lerp(sb.color, t));
+                                mesh.positions.len() as u32 - 1
+                            })
+                        };
+
+                        let inside: Vec<usize> = (0..4)
+                            .filter(|vertex| samples[tetrahedron[*vertex]].distance < 0.0)
+                            .collect();
+                        let outside: Vec<usize> = (0..4)
+                            .filter(|vertex| samples[tetrahedron[*vertex]].distance >= 0.0)
+                            .collect();
+                        let triangles = match (inside.as_slice(), outside.as_slice()) {
+                            ([i], [o0, o1, o2]) | ([o0, o1, o2], [i]) => {
+                                vec![[
+                                    edge_vertex(*i, *o0),
+                                    edge_vertex(*i, *o1),
+                                    edge_vertex(*i, *o2),
+                                ]]
+                            }
+                            ([i0, i1], [o0, o1]) => {
+                                let quad = [
+                                    edge_vertex(*i0, *o0),
+                                    edge_vertex(*i0, *o1),
+                                    edge_vertex(*i1, *o1),
+                                    edge_vertex(*i1, *o0),
+                                ];
+                                vec![[quad[0], quad[1], quad[2]], [quad[0], quad[2], quad[3]]]
+                            }
+                            _ => vec![],
+                        };
+
+                        // Triangles face from inside towards outside of the tetrahedron
+                        let centroid = |vertices: &[usize]| {
+                            vertices
+                                .iter()
+                                .map(|vertex| position(corners[tetrahedron[*vertex]]))
+                                .sum::<glam::Vec3>()
+                                / vertices.len() as f32
+                        };
+                        let outward = centroid(&outside) - centroid(&inside);
+                        for [a, b, c] in triangles {
+                            let [pa, pb, pc] =
+                                [a, b, c].map(|index| mesh.positions[index as usize]);
+                            if (pb - pa).cross(pc - pa).dot(outward) >= 0.0 {
+                                mesh.indices.extend_from_slice(&[a, b, c]);
+                            } else {
+                                mesh.indices.extend_from_slice(&[a, c, b]);
+                            }
+                        }
+                    }
+                }
+            }
+            lower_layer = upper_layer;
+        }
+
+        if mesh.indices.is_empty() {
+            return Err("Edit list has no surface".to_string());
+        }
+        mesh.collapse_short_edges(spacing * Self::MIN_EDGE_LENGTH);
+        mesh.compute_normals();
+        mesh.check_watertight()?;
+        Ok(mesh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdf::geometry::{sphere_edit, Operation};
+
+    const RADIUS: f32 = 0.3;
+
+    fn domain(size: f32) -> BoundingCube {
+        BoundingCube {
+            pos: glam::Vec3::ZERO,
+            size,
+        }
+    }
+
+    #[test]
+    fn meshes_sphere_into_closed_outward_surface() {
+        let edits = [sphere_edit(RADIUS, Operation::Add, glam::Vec3::ZERO)];
+        let sampler = EditSampler::new(&edits).unwrap();
+        let mesh = WatertightMesher::new(&sampler, domain(1.0), 32)
+            .mesh()
+            .unwrap();
+        mesh.check_watertight().unwrap();
+
+        for [a, b, c] in mesh.triangles() {
+            let [pa, pb, pc] = [a, b, c].map(|index| mesh.positions[index as usize]);
+            let normal = (pb - pa).cross(pc - pa);
+            assert!(
+                normal.dot(pa + pb + pc) > 0.0,
+                "triangle {:?} faces inwards",
+                [pa, pb, pc]
+            );
+        }
+        let volume = 4.0 / 3.0 * std::f32::consts::PI * RADIUS.powi(3);
+        assert!(
+            (mesh.volume() - volume).abs() < volume * 0.05,
+            "volume {} differs from {}",
+            mesh.volume(),
+            volume
+        );
+    }
+
+    #[test]
+    fn refuses_surface_touching_domain_boundary() {
+        let edits = [sphere_edit(RADIUS, Operation::Add, glam::Vec3::ZERO)];
+        let sampler = EditSampler::new(&edits).unwrap();
+        let err = WatertightMesher::new(&sampler, domain(RADIUS), 16)
+            .mesh()
+            .unwrap_err();
+        assert!(err.contains("boundary"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn refuses_empty_edit_list() {
+        let sampler = EditSampler::new(&[]).unwrap();
+        let err = WatertightMesher::new(&sampler, domain(1.0), 16)
+            .mesh()
+            .unwrap_err();
+        assert!(err.contains("no surface"), "unexpected error: {}", err);
+    }
+}
@@ -1,3 +1,9 @@
+pub mod codegen;
+pub mod collision;
 pub mod evaluator;
 pub mod geometry;
+pub mod mesh;
+pub mod query;
+pub mod slice;
 pub mod svo;
+pub mod volume;
@@ -0,0 +1,87 @@
+// Samples signed distances and their gradients of an instance of an SVO at world positions.
+
+// SVO: Node pool Read-only bind group
+// -----------------------------------------------------------------------------------
+
+@group(0) @binding(0) var<storage, read> node_count:         u32;
+@group(0) @binding(1) var<storage, read> node_headers:       array<u32>;
+@group(0) @binding(2) var<storage, read> node_payload:       array<u32>;
+@group(0) @binding(3) var<storage, read> node_vertices:      array<vec4<f32>>;
+@group(0) @binding(4) var<uniform>       node_pool_capacity: u32;
+
+
+// SVO: Brick pool Read-only bind group
+// -----------------------------------------------------------------------------------
+
+@group(1) @binding(0) var                distance_atlas:         texture_3d<f32>;
+@group(1) @binding(1) var                distance_atlas_sampler: sampler;
+@group(1) @binding(2) var                color_atlas:            texture_3d<f32>;
+@group(1) @binding(3) var                color_atlas_sampler:    sampler;
+@group(1) @binding(4) var<storage, read> brick_count:            u32;
+@group(1) @binding(5) var<uniform>       brick_pool_side_size:   u32;
+@group(1) @binding(6) var                distance_ranges:        texture_3d<f32>;
+
+
+// Query: positions to sample and samples
+// -----------------------------------------------------------------------------------
+
+struct Query {
+    to_local:              mat4x4<f32>, // inverse transform of the instance
+    domain:                vec4<f32>,   // bounding cube of the SVO
+    distance_scale:        f32,         // scale of local distances into world distances
+    atlas_scale:           f32,
+    atlas_stride:          f32,
+    atlas_voxel_size:      f32,
+    gradient_epsilon:      f32,         // step of central differences in world space
+    position_count:        u32,
+}
+
+struct DistanceSample {
+    gradient: vec3<f32>,
+    distance: f32,
+}
+
+@group(2) @binding(0) var<uniform>             query:     Query;
+@group(2) @binding(1) var<storage, read>       positions: array<vec4<f32>>;
+@group(2) @binding(2) var<storage, read_write> samples:   array<DistanceSample>;
+
+
+// Sampling of the queried SVO, see `_svo_sampling.wgsl`
+fn query_svo() -> SvoSampling {
+    return SvoSampling(
+        query.domain,
+        query.atlas_scale,
+        query.atlas_stride,
+        query.atlas_voxel_size,
+    );
+}
+
+// World distance of a world position
+fn query_distance_at(position: vec3<f32>) -> f32 {
+    return distance_at(query_svo(), query.to_local, query.distance_scale, position);
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(num_workgroups) workgroup_count: vec3<u32>,
+) {
+    // Large batches are dispatched as rows of workgroups to stay within the dispatch size limit
+    let index = global_id.y * workgroup_count.x * 64u + global_id.x;
+    if (index >= query.position_count) {
+        return;
+    }
+
+    let position = positions[index].xyz;
+    let epsilon = query.gradient_epsilon;
+    let dx = vec3(epsilon, 0.0, 0.0);
+    let dy = vec3(0.0, epsilon, 0.0);
+    let dz = vec3(0.0, 0.0, epsilon);
+    let gradient = vec3(
+        query_distance_at(position + dx) - query_distance_at(position - dx),
+        query_distance_at(position + dy) - query_distance_at(position - dy),
+        query_distance_at(position + dz) - query_distance_at(position - dz),
+    ) / (2.0 * epsilon);
+
+    samples[index] = DistanceSample(gradient, query_distance_at(position));
+}
@@ -0,0 +1,96 @@
+// Samples signed distances of an SVO from its node pool and distance atlas, shared by shaders reading SVOs.
+//   - Shaders using it are concatenated with this source and have to declare the read-only node pool bindings
+//     `node_count`, `node_headers`, `node_payload`, `node_vertices` and the brick pool bindings
+//     `distance_atlas`, `distance_atlas_sampler`, `distance_ranges` in any of their bind groups.
+
+// Placement of the SVO and layout of its brick atlas
+struct SvoSampling {
+    domain:                vec4<f32>, // bounding cube of the SVO
+    atlas_scale:           f32,
+    atlas_stride:          f32,
+    atlas_voxel_size:      f32,
+}
+
+
+const HEADER_TILE_INDEX_MASK = 0x3FFFFFFFu;
+const HEADER_SUBDIVIDED_FLAG = 0x80000000u;
+const HEADER_HAS_BRICK_FLAG = 0x40000000u;
+const BRICK_IS_FILLED = 2u;
+
+// Distance returned when the SVO has no evaluated nodes, same as initial distance in the evaluation kernel.
+const EMPTY_DISTANCE = 1000000.0;
+
+fn max_component(v: vec3<f32>) -> f32 {
+    return max(v.x, max(v.y, v.z));
+}
+
+// Finds the most detailed evaluated node containing a local position, which has to be inside of the domain.
+fn find_node(svo: SvoSampling, position: vec3<f32>) -> u32 {
+    // Root node is not stored in node pool, its children are the first tile
+    var first_child = 0u;
+    var node = 0u;
+    loop {
+        // Node whose center is closest to the position in maximum norm is the one containing it
+        var closest_distance = 3.40282347e+38;
+        for (var i = 0u; i < 8u; i++) {
+            let center = node_vertices[first_child + i].xyz * svo.domain.w + svo.domain.xyz;
+            let distance = max_component(abs(center - position));
+            if (distance < closest_distance) {
+                closest_distance = distance;
+                node = first_child + i;
+            }
+        }
+
+        // Descend only into children which were already evaluated
+        let header = node_headers[node];
+        first_child = (header & HEADER_TILE_INDEX_MASK) << 3u;
+        if ((header & HEADER_SUBDIVIDED_FLAG) == 0u || first_child + 8u > node_count) {
+            return node;
+        }
+    }
+    return node;
+}
+
+// Samples the brick of the node containing a local position, nodes without brick return their voxel size
+// with sign telling whether they are inside or outside.
+fn sample_svo(svo: SvoSampling, position: vec3<f32>) -> f32 {
+    if (node_count < 8u) {
+        return EMPTY_DISTANCE;
+    }
+
+    let node = find_node(svo, position);
+    let vertex = node_vertices[node];
+    let size = vertex.w * svo.domain.w;
+    let voxel_size = size * 0.125; // 8 voxels in brick
+
+    let header = node_headers[node];
+    let payload = node_payload[node];
+    if ((header & HEADER_HAS_BRICK_FLAG) == 0u) {
+        if (payload == BRICK_IS_FILLED) {
+            return -voxel_size;
+        }
+        return voxel_size;
+    }
+
+    let node_min = vertex.xyz * svo.domain.w + svo.domain.xyz - vec3(size * 0.5);
+    let brick_position = clamp((position - node_min) / size, vec3(0.0), vec3(1.0));
+    let brick_coords = vec3((payload >> 20u) & 0x3FFu, (payload >> 10u) & 0x3FFu, payload & 0x3FFu);
+    let lookup = brick_position * svo.atlas_scale
+        + svo.atlas_stride * vec3<f32>(brick_coords)
+        + vec3(svo.atlas_voxel_size);
+    let distance = textureSampleLevel(distance_atlas, distance_atlas_sampler, lookup, 0.0).r;
+
+    // decode distance by the range of the brick
+    let range = textureLoad(distance_ranges, vec3<i32>(brick_coords), 0).xy;
+    return distance * range.y + range.x;
+}
+
+// World distance of an instance of the SVO at a world position, positions outside of the SVO domain are clamped
+// into it and their distance to the domain is added.
+//   - `to_local` is the inverse transform of the instance, `distance_scale` scales local distances into world ones.
+fn distance_at(svo: SvoSampling, to_local: mat4x4<f32>, distance_scale: f32, position: vec3<f32>) -> f32 {
+    let local = (to_local * vec4(position, 1.0)).xyz;
+    let half_size = vec3(svo.domain.w * 0.5);
+    let clamped = clamp(local, svo.domain.xyz - half_size, svo.domain.xyz + half_size);
+    return (sample_svo(svo, clamped) + length(local - clamped)) * distance_scale;
+}
@@ -0,0 +1,203 @@
+use crate::{
+    framework::math::Transform,
+    sdf::geometry::{Edit, EditSampler},
+};
+
+/// A signed distance and its gradient at a position in world space.
+///   - Layout matches `DistanceSample` in `_kernel_distance_query.wgsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DistanceSample {
+    pub gradient: glam::Vec3,
+    pub distance: f32,
+}
+
+impl DistanceSample {
+    /// A position on the surface reached by a single step along the gradient from the sampled position.
+    pub fn closest_surface_point(&self, position: glam::Vec3) -> glam::Vec3 {
+        let length_squared = self.gradient.length_squared();
+        if length_squared < 1e-12 {
+            return position;
+        }
+        position - self.gradient * self.distance / length_squared
+    }
+}
+
+/// Scale of local distances of an instance into world distances.
+///   - Exact for uniform scale, non-uniform scale stretches the field, so the smallest axis gives a lower bound.
+pub fn instance_distance_scale(transform: &Transform) -> f32 {
+    transform.scale.abs().min_element()
+}
+
+/// The SDF of a geometry placed into world by an instance transform, sampled on CPU from geometry edits.
+pub struct InstanceSdf<'a> {
+    sampler: EditSampler<'a>,
+    to_local: glam::Mat4,
+    distance_scale: f32,
+}
+
+impl<'a> InstanceSdf<'a> {
+    /// Step of central differences in world space.
+    const GRADIENT_EPSILON: f32 = 1e-3;
+    const PROJECTION_STEPS: usize = 16;
+    /// Surface is considered reached when the distance is below this fraction of the gradient epsilon.
+    const PROJECTION_TOLERANCE: f32 = 0.1;
+
+    /// Fails for edit lists which cannot be evaluated, see `check_edits`.
+    pub fn new(edits: &'a [Edit], transform: &Transform) -> Result<Self, String> {
+        Ok(Self {
+            sampler: EditSampler::new(edits)?,
+            to_local: transform.as_mat().inverse(),
+            distance_scale: instance_distance_scale(transform),
+        })
+    }
+
+    /// Signed distance in world units from a world position to the surface.
+    pub fn distance_at(&self, position: glam::Vec3) -> f32 {
+        self.sampler
+            .sample(self.to_local.transform_point3(position))
+            .distance
+            * self.distance_scale
+    }
+
+    /// Gradient of the world space distance by central differences, it points away from the surface.
+    pub fn gradient(&self, position: glam::Vec3) -> glam::Vec3 {
+        let epsilon = Self::GRADIENT_EPSILON;
+        let difference = |axis: glam::Vec3| {
+            (self.distance_at(position + axis * epsilon)
+                - self.distance_at(position - axis * epsilon))
+                / (2.0 * epsilon)
+        };
+        glam::Vec3::new(
+            difference(glam::Vec3::X),
+            difference(glam::Vec3::Y),
+            difference(glam::Vec3::Z),
+        )
+    }
+
+    pub fn sample(&self, position: glam::Vec3) -> DistanceSample {
+        DistanceSample {
+            gradient: self.gradient(position),
+            distance: self.distance_at(position),
+        }
+    }
+
+    /// Projects a world position onto the surface by Newton steps along the gradient.
+    ///   - Positions where the gradient vanishes are returned as they are.
+    pub fn closest_surface_point(&self, mut position: glam::Vec3) -> glam::Vec3 {
+        for _ in 0..Self::PROJECTION_STEPS {
+            let sample = self.sample(position);
+            if sample.distance.abs() < Self::GRADIENT_EPSILON * Self::PROJECTION_TOLERANCE {
+                break;
+            }
+            position = sample.closest_surface_point(position);
+        }
+        position
+    }
+
+    /// Samples many world positions at once, split between all available threads.
+    #[profiler::function]
+    pub fn sample_batch(&self, positions: &[glam::Vec3]) -> Vec<DistanceSample> {
+        let thread_count = std::thread::available_parallelism().map_or(1, |count| count.get());
+        let chunk_size = positions.len().div_ceil(thread_count).max(1);
+        let mut samples = vec![DistanceSample::default(); positions.len()];
+        std::thread::scope(|scope| {
+            for (positions, samples) in positions
+                .chunks(chunk_size)
+                .zip(samples.chunks_mut(chunk_size))
+            {
+                scope.spawn(move || {
+                    for (position, sample) in positions.iter().zip(samples.iter_mut()) {
+                        *sample = self.sample(*position);
+                    }
+                });
+            }
+        });
+        samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdf::geometry::{sphere_edit, Operation};
+
+    /// Sphere of local radius 0.5 scaled twice, so its world radius is 1.
+    const RADIUS: f32 = 1.0;
+    const CENTER: glam::Vec3 = glam::Vec3::new(1.0, 2.0, 3.0);
+
+    fn sphere() -> (Vec<Edit>, Transform) {
+        let edits = vec![sphere_edit(0.5, Operation::Add, glam::Vec3::ZERO)];
+        (
+            edits,
+            Transform::from_uniform_scale(2.0).with_position(CENTER),
+        )
+    }
+
+    fn positions() -> Vec<glam::Vec3> {
+        [
+            glam::Vec3::new(3.0, 0.0, 0.0),
+            glam::Vec3::new(0.0, -0.5, 0.0),
+            glam::Vec3::new(0.3, 2.0, -1.0),
+            glam::Vec3::new(-0.6, 0.2, 0.7),
+        ]
+        .map(|offset| CENTER + offset)
+        .to_vec()
+    }
+
+    #[test]
+    fn distance_and_gradient_match_sphere() {
+        let (edits, transform) = sphere();
+        let sdf = InstanceSdf::new(&edits, &transform).unwrap();
+
+        for position in positions() {
+            let offset = position - CENTER;
+            let distance = sdf.distance_at(position);
+            assert!(
+                (distance - (offset.length() - RADIUS)).abs() < 1e-4,
+                "distance {distance} at {position}"
+            );
+            let gradient = sdf.gradient(position);
+            assert!(
+                gradient.distance(offset.normalize()) < 1e-3,
+                "gradient {gradient} at {position}"
+            );
+        }
+    }
+
+    #[test]
+    fn closest_surface_point_lies_on_sphere() {
+        let (edits, transform) = sphere();
+        let sdf = InstanceSdf::new(&edits, &transform).unwrap();
+
+        for position in positions() {
+            let expected = CENTER + (position - CENTER).normalize() * RADIUS;
+            let closest = sdf.closest_surface_point(position);
+            assert!(
+                closest.distance(expected) < 1e-3,
+                "closest {closest}, expected {expected}"
+            );
+        }
+        // Every surface point is closest to the center, rounding decides which one is reached
+        let closest = sdf.closest_surface_point(CENTER);
+        assert!(
+            (closest.distance(CENTER) - RADIUS).abs() < 1e-3,
+            "closest {closest}"
+        );
+    }
+
+    #[test]
+    fn batch_matches_single_samples() {
+        let (edits, transform) = sphere();
+        let sdf = InstanceSdf::new(&edits, &transform).unwrap();
+        let positions = positions();
+
+        let samples = sdf.sample_batch(&positions);
+        assert_eq!(samples.len(), positions.len());
+        for (position, sample) in positions.iter().zip(samples.iter()) {
+            let expected = sdf.sample(*position);
+            assert_eq!(sample.distance, expected.distance);
+            assert_eq!(sample.gradient, expected.gradient);
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//!
+//! A kernel sampling distances of SVO instances at many world positions at once.
+//!
+use std::borrow::Cow;
+
+use crate::{
+    framework::{
+        gpu,
+        math::{self, Transform},
+    },
+    sdf::svo,
+};
+
+use super::{instance_distance_scale, DistanceSample};
+
+/// WGSL source sampling distances of SVOs from their node and brick pools.
+///   - Shaders reading SVOs are concatenated with it and declare the pool bindings it uses.
+pub const SVO_SAMPLING_SHADER: &str = include_str!("_svo_sampling.wgsl");
+
+///
+/// A compute kernel sampling signed distances and gradients of an instance of an SVO at batches of world positions.
+///   - Distances are sampled from bricks of the most detailed evaluated nodes the same way the renderer does,
+///     so they are as precise as the SVO is and nodes without brick only tell on which side of the surface they are.
+///   - Positions outside of the SVO domain are clamped into it and their distance to the domain is added.
+pub struct KernelDistanceQuery {
+    pipeline: wgpu::ComputePipeline,
+    node_pool_layout: wgpu::BindGroupLayout,
+    brick_pool_layout: wgpu::BindGroupLayout,
+    query_layout: wgpu::BindGroupLayout,
+}
+
+// Public API
+impl KernelDistanceQuery {
+    const WORKGROUP_SIZE: u32 = 64;
+
+    #[profiler::function]
+    pub fn new(gpu: &gpu::Context) -> KernelDistanceQuery {
+        let node_pool_layout =
+            svo::NodePool::create_bind_group_layout(gpu, wgpu::ShaderStages::COMPUTE, true);
+        let brick_pool_layout =
+            svo::BrickPool::create_read_bind_group_layout(gpu, wgpu::ShaderStages::COMPUTE);
+        let query_layout = Self::create_query_layout(gpu);
+
+        let pipeline_layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("KernelDistanceQuery Pipeline Layout"),
+                bind_group_layouts: &[
+                    &node_pool_layout,  // 0
+                    &brick_pool_layout, // 1
+                    &query_layout,      // 2
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let pipeline = gpu
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("KernelDistanceQuery Compute Pipeline"),
+                layout: Some(&pipeline_layout),
+                entry_point: "main",
+                module: &gpu
+                    .device
+                    .create_shader_module(wgpu::ShaderModuleDescriptor {
+                        label: Some("Distance Query Compute Shader Module"),
+                        source: wgpu::ShaderSource::Wgsl(Cow::Owned(
+                            [
+                                SVO_SAMPLING_SHADER,
+                                include_str!("_kernel_distance_query.wgsl"),
+                            ]
+                            .concat(),
+                        )),
+                    }),
+            });
+
+        Self {
+            pipeline,
+            node_pool_layout,
+            brick_pool_layout,
+            query_layout,
+        }
+    }
+
+    /// Samples the SVO placed into world by an instance transform at given world positions.
+    ///   - Blocks until the samples are read back from GPU.
+    #[profiler::function]
+    pub fn sample_batch(
+        &self,
+        gpu: &gpu::Context,
+        svo: &svo::Svo,
+        transform: &Transform,
+        positions: &[glam::Vec3],
+    ) -> Vec<DistanceSample> {
+        if positions.is_empty() {
+            return vec![];
+        }
+
+        // Gradient steps over half of the smallest voxel, so that it spans neighbouring voxels of bricks
+        let distance_scale = instance_distance_scale(transform);
+        let min_voxel_size =
+            svo::Svo::level_voxel_size(&svo.domain, svo.levels.len().saturating_sub(1));
+        let query = Query {
+            to_local: transform.as_mat().inverse(),
+            domain: svo.domain,
+            distance_scale,
+            atlas_scale: svo.brick_pool.atlas_scale(),
+            atlas_stride: svo.brick_pool.atlas_stride(),
+            atlas_voxel_size: svo.brick_pool.atlas_voxel_size(),
+            gradient_epsilon: min_voxel_size * distance_scale * 0.5,
+            position_count: positions.len() as u32,
+            _padding: [0; 2],
+        };
+
+        let query_buffer = gpu::Buffer::new(
+            gpu,
+            Some("KernelDistanceQuery: Query Uniform Buffer"),
+            &[query],
+            wgpu::BufferUsages::UNIFORM,
+        );
+        let positions: Vec<glam::Vec4> = positions
+            .iter()
+            .map(|position| position.extend(1.0))
+            .collect();
+        let position_buffer = gpu::Buffer::new(
+            gpu,
+            Some("KernelDistanceQuery: Position Buffer"),
+            &positions,
+            wgpu::BufferUsages::STORAGE,
+        );
+        let sample_buffer = gpu::Buffer::<DistanceSample>::new_empty(
+            gpu,
+            Some("KernelDistanceQuery: Sample Buffer"),
+            positions.len(),
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        );
+
+        let node_pool_bind_group = svo.node_pool.create_bind_group(gpu, &self.node_pool_layout);
+        let brick_pool_bind_group = svo
+            .brick_pool
+            .create_read_bind_group(gpu, &self.brick_pool_layout);
+        let query_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("KernelDistanceQuery: Query Bind Group"),
+            layout: &self.query_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: query_buffer.buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: position_buffer.buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: sample_buffer.buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        // Workgroups are laid out in rows, because their count in one dimension is limited
+        let workgroup_count = (positions.len() as u32).div_ceil(Self::WORKGROUP_SIZE);
+        let max_row_size = gpu.device.limits().max_compute_workgroups_per_dimension;
+        let row_count = workgroup_count.div_ceil(max_row_size);
+        let row_size = workgroup_count.div_ceil(row_count);
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("KernelDistanceQuery: Command Encoder"),
+            });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("KernelDistanceQuery: Compute Pass"),
+            });
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, &node_pool_bind_group, &[]);
+            compute_pass.set_bind_group(1, &brick_pool_bind_group, &[]);
+            compute_pass.set_bind_group(2, &query_bind_group, &[]);
+            compute_pass.dispatch_workgroups(row_size, row_count, 1);
+        }
+        gpu.queue.submit(Some(encoder.finish()));
+
+        gpu::Buffer::<DistanceSample>::static_read_range(
+            &sample_buffer.buffer,
+            gpu,
+            0,
+            positions.len(),
+        )
+    }
+}
+
+// =================================================================================================
+// Private Implementation
+// =================================================================================================
+
+impl KernelDistanceQuery {
+    fn create_query_layout(gpu: &gpu::Context) -> wgpu::BindGroupLayout {
+        let storage = |binding: u32, read_only: bool| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            count: None,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+        };
+        gpu.device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("KernelDistanceQuery: Query Bind Group Layout"),
+                entries: &[
+                    // query
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        count: None,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                    },
+                    // positions
+                    storage(1, true),
+                    // samples
+                    storage(2, false),
+                ],
+            })
+    }
+}
+
+// =================================================================================================
+// Internal structs
+// =================================================================================================
+
+///
+/// An internal struct meant to be uploaded to GPU uniform buffer describing sampled instance and positions.
+///
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Query {
+    /// Inverse transform of the instance.
+    to_local: glam::Mat4,
+
+    /// Bounding cube of the SVO domain.
+    domain: math::BoundingCube,
+
+    /// Scale of local distances into world distances.
+    distance_scale: f32,
+
+    atlas_scale: f32,
+    atlas_stride: f32,
+    atlas_voxel_size: f32,
+
+    /// Step of central differences in world space.
+    gradient_epsilon: f32,
+
+    position_count: u32,
+    _padding: [u32; 2],
+}
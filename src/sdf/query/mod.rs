@@ -0,0 +1,5 @@
+mod instance_sdf;
+pub use instance_sdf::*;
+
+mod kernel_distance_query;
+pub use kernel_distance_query::*;
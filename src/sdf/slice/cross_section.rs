@@ -0,0 +1,332 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::framework::{binary_writer::write_file, math::AABB};
+
+/// A plane of positions `p` with `p.dot(normal) == offset`.
+#[derive(Debug, Clone, Copy)]
+pub struct SlicePlane {
+    pub normal: glam::Vec3,
+    pub offset: f32,
+}
+
+impl SlicePlane {
+    pub fn new(normal: glam::Vec3, offset: f32) -> Self {
+        Self {
+            normal: normal.try_normalize().unwrap_or(glam::Vec3::Z),
+            offset,
+        }
+    }
+
+    /// Parallel planes spaced evenly, the first one goes through `center` shifted by `offset` along the normal.
+    pub fn stack(
+        normal: glam::Vec3,
+        center: glam::Vec3,
+        offset: f32,
+        spacing: f32,
+        count: u32,
+    ) -> Vec<Self> {
+        let first = Self::new(normal, 0.0);
+        let first_offset = center.dot(first.normal) + offset;
+        (0..count.max(1))
+            .map(|index| Self::new(first.normal, first_offset + spacing * index as f32))
+            .collect()
+    }
+
+    /// Axes of 2D coordinates in the plane, they form a right handed basis with the normal.
+    ///   - The `u` axis points right and the `v` axis points up when looking against the normal,
+    ///     vertical planes keep the scene up axis as `v`, horizontal planes are seen from above.
+    pub fn axes(&self) -> (glam::Vec3, glam::Vec3) {
+        let up = if self.normal.y.abs() < 0.99 {
+            glam::Vec3::Y
+        } else {
+            glam::Vec3::NEG_Z * self.normal.y.signum()
+        };
+        let u = up.cross(self.normal).normalize();
+        let v = self.normal.cross(u);
+        (u, v)
+    }
+
+    pub fn to_world(self, position: glam::Vec2) -> glam::Vec3 {
+        let (u, v) = self.axes();
+        self.normal * self.offset + u * position.x + v * position.y
+    }
+}
+
+/// Zero isocontours of a distance field in a plane.
+///   - Contours are closed polygons in plane coordinates, the last point connects to the first one.
+///   - Inside is always on the left of a contour, so outer boundaries wind counterclockwise and holes clockwise.
+#[derive(Debug, Clone)]
+pub struct CrossSection {
+    pub plane: SlicePlane,
+    pub contours: Vec<Vec<glam::Vec2>>,
+}
+
+impl CrossSection {
+    /// Extracts contours by marching squares on a grid with `resolution` cells along the longer side of AABB projection.
+    ///   - Samples on the border of the grid are forced outside, so contours crossing the AABB are cut by it but stay closed.
+    ///   - Ambiguous cells are resolved by the average of their corner samples.
+    #[profiler::function]
+    pub fn slice<F>(plane: SlicePlane, aabb: &AABB, resolution: u32, distance: F) -> Self
+    where
+        F: Fn(glam::Vec3) -> f32,
+    {
+        let (u, v) = plane.axes();
+        let vertices = aabb.vertices();
+        let project = |axis: glam::Vec3| {
+            vertices
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(min, max), vertex| {
+                    (min.min(vertex.dot(axis)), max.max(vertex.dot(axis)))
+                })
+        };
+        let ((min_u, max_u), (min_v, max_v)) = (project(u), project(v));
+        let size = glam::Vec2::new(max_u - min_u, max_v - min_v);
+        let cell_size = size.max_element().max(f32::EPSILON) / resolution.max(1) as f32;
+        // One cell is added on each side for the forced outside border
+        let cell_count = (size / cell_size).ceil().as_uvec2() + 2;
+        let min = glam::Vec2::new(min_u, min_v) - cell_size
+            + (size - (cell_count - 2).as_vec2() * cell_size) * 0.5;
+
+        let (width, height) = (cell_count.x + 1, cell_count.y + 1);
+        let position = |x: u32, y: u32| min + glam::UVec2::new(x, y).as_vec2() * cell_size;
+        let mut samples = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let border = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+                let sample = distance(plane.to_world(position(x, y)));
+                samples.push(if border {
+                    sample.max(cell_size)
+                } else {
+                    sample
+                });
+            }
+        }
+        let sample = |x: u32, y: u32| samples[(y * width + x) as usize];
+
+        // Crossings are identified by grid edges, horizontal edge from sample (x, y) has index `2 * (y * width + x)`
+        // and vertical edge `2 * (y * width + x) + 1`
+        let mut points: HashMap<u32, glam::Vec2> = HashMap::new();
+        let mut crossing = |x: u32, y: u32, vertical: bool| {
+            let index = 2 * (y * width + x) + vertical as u32;
+            let (x1, y1) = if vertical { (x, y + 1) } else { (x + 1, y) };
+            points.entry(index).or_insert_with(|| {
+                let (d0, d1) = (sample(x, y), sample(x1, y1));
+                position(x, y).lerp(position(x1, y1), d0 / (d0 - d1))
+            });
+            index
+        };
+
+        let mut next: HashMap<u32, u32> = HashMap::new();
+        for y in 0..cell_count.y {
+            for x in 0..cell_count.x {
+                // Corners and edges in counterclockwise order, edges are given by their lower corner
+                let corners = [(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)];
+                let edges = [
+                    (x, y, false),
+                    (x + 1, y, true),
+                    (x, y + 1, false),
+                    (x, y, true),
+                ];
+                let inside = corners.map(|(x, y)| sample(x, y) < 0.0);
+
+                // Crossings with flag whether the boundary goes from inside to outside there
+                let crossings: Vec<(u32, bool)> = (0..4)
+                    .filter(|edge| inside[*edge] != inside[(*edge + 1) % 4])
+                    .map(|edge| {
+                        let (x, y, vertical) = edges[edge];
+                        (crossing(x, y, vertical), inside[edge])
+                    })
+                    .collect();
+
+                // Contour goes from a crossing to outside to a crossing to inside, keeping inside on its left
+                let center_inside = corners.iter().map(|(x, y)| sample(*x, *y)).sum::<f32>() < 0.0;
+                let step = if crossings.len() == 4 && !center_inside {
+                    crossings.len() - 1
+                } else {
+                    1
+                };
+                for (index, (start, to_outside)) in crossings.iter().enumerate() {
+                    if *to_outside {
+                        next.insert(*start, crossings[(index + step) % crossings.len()].0);
+                    }
+                }
+            }
+        }
+
+        // Every crossing has exactly one successor and one predecessor, so following them yields closed loops
+        let mut contours = vec![];
+        while let Some((&first, _)) = next.iter().next() {
+            let mut contour = vec![];
+            let mut current = first;
+            while let Some(following) = next.remove(&current) {
+                let point = points[&current];
+                if contour.last() != Some(&point) {
+                    contour.push(point);
+                }
+                current = following;
+            }
+            if contour.len() >= 3 {
+                contours.push(contour);
+            }
+        }
+
+        Self { plane, contours }
+    }
+
+    /// Number of contours winding clockwise, which bound holes.
+    pub fn hole_count(&self) -> usize {
+        self.contours
+            .iter()
+            .filter(|contour| signed_area(contour) < 0.0)
+            .count()
+    }
+
+    /// Area inside of the contours, holes are subtracted.
+    pub fn area(&self) -> f32 {
+        self.contours
+            .iter()
+            .map(|contour| signed_area(contour))
+            .sum()
+    }
+
+    /// Stores cross sections laid out in a grid into an SVG file with millimetres as units.
+    ///   - Each cross section is a single path with nonzero fill rule, holes are cut out thanks to their opposite winding.
+    ///   - Y axis of SVG points down, so the contours are flipped vertically to keep their look.
+    #[profiler::function]
+    pub fn store_svg<P: AsRef<Path>>(
+        sections: &[CrossSection],
+        file_name: P,
+        millimeters_per_unit: f32,
+    ) -> Result<(), String> {
+        let margin = 5.0;
+        let bounds = sections
+            .iter()
+            .flat_map(|section| section.contours.iter().flatten())
+            .fold(None, |bounds: Option<(glam::Vec2, glam::Vec2)>, point| {
+                Some(bounds.map_or((*point, *point), |(min, max)| {
+                    (min.min(*point), max.max(*point))
+                }))
+            })
+            .unwrap_or((glam::Vec2::ZERO, glam::Vec2::ZERO));
+        let cell = (bounds.1 - bounds.0) * millimeters_per_unit + margin;
+        let columns = (sections.len() as f32).sqrt().ceil().max(1.0) as usize;
+        let rows = sections.len().div_ceil(columns);
+        let size = glam::Vec2::new(columns as f32, rows.max(1) as f32) * cell + margin;
+
+        let mut svg = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" viewBox=\"0 0 {w} {h}\">\n",
+            w = size.x,
+            h = size.y
+        );
+        for (index, section) in sections.iter().enumerate() {
+            // Plane position of the top left corner of the bounds lands at the top left corner of the grid cell
+            let cell_min =
+                glam::Vec2::new((index % columns) as f32, (index / columns) as f32) * cell + margin;
+            let to_svg = |point: &glam::Vec2| {
+                let offset = glam::Vec2::new(point.x - bounds.0.x, bounds.1.y - point.y);
+                cell_min + offset * millimeters_per_unit
+            };
+
+            let mut data = String::new();
+            for contour in section.contours.iter() {
+                for (point_index, point) in contour.iter().enumerate() {
+                    let point = to_svg(point);
+                    let command = if point_index == 0 { 'M' } else { 'L' };
+                    data += &format!("{}{:.4} {:.4} ", command, point.x, point.y);
+                }
+                data += "Z ";
+            }
+            svg += &format!(
+                "  <path id=\"slice_{}\" data-normal=\"{} {} {}\" data-offset=\"{}\" fill=\"#d0d0d0\" fill-rule=\"nonzero\" stroke=\"#000000\" stroke-width=\"0.1\" d=\"{}\"/>\n",
+                index,
+                section.plane.normal.x,
+                section.plane.normal.y,
+                section.plane.normal.z,
+                section.plane.offset,
+                data.trim_end()
+            );
+        }
+        svg += "</svg>\n";
+
+        write_file(file_name, svg.as_bytes())
+    }
+}
+
+/// Shoelace formula, positive for counterclockwise polygons.
+fn signed_area(contour: &[glam::Vec2]) -> f32 {
+    let mut area = 0.0;
+    for (index, point) in contour.iter().enumerate() {
+        let next = contour[(index + 1) % contour.len()];
+        area += point.perp_dot(next);
+    }
+    area * 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_domain() -> AABB {
+        AABB::new(glam::Vec3::splat(-0.5), glam::Vec3::splat(0.5))
+    }
+
+    /// Checks that consecutive points, including the last and the first one, are at most a cell apart.
+    fn assert_closed(contour: &[glam::Vec2], cell_size: f32) {
+        assert!(contour.len() >= 3);
+        for (index, point) in contour.iter().enumerate() {
+            let next = contour[(index + 1) % contour.len()];
+            assert!(point.distance(next) <= cell_size * 1.5);
+        }
+    }
+
+    #[test]
+    fn slices_sphere_into_closed_circle() {
+        let radius = 0.3;
+        let resolution = 64;
+        let plane = SlicePlane::new(glam::Vec3::Z, 0.0);
+        let section = CrossSection::slice(plane, &unit_domain(), resolution, |pos| {
+            pos.length() - radius
+        });
+
+        assert_eq!(section.contours.len(), 1);
+        assert_eq!(section.hole_count(), 0);
+        let cell_size = 1.0 / resolution as f32;
+        assert_closed(&section.contours[0], cell_size);
+        for point in &section.contours[0] {
+            assert!((point.length() - radius).abs() < cell_size);
+        }
+        let area = std::f32::consts::PI * radius * radius;
+        assert!((section.area() - area).abs() < area * 0.02);
+    }
+
+    /// Inside is where `x * y > threshold`, the cell around the origin has only its diagonal corners inside and
+    /// the threshold decides whether its center is inside too.
+    fn slice_saddle(threshold: f32) -> CrossSection {
+        // odd resolution centers a cell at the origin
+        let resolution = 63;
+        let plane = SlicePlane::new(glam::Vec3::Z, 0.0);
+        CrossSection::slice(plane, &unit_domain(), resolution, |pos| {
+            (threshold - pos.x * pos.y).max(pos.x.abs().max(pos.y.abs()) - 0.4)
+        })
+    }
+
+    #[test]
+    fn separates_saddle_with_outside_center() {
+        let section = slice_saddle(1.0e-5);
+
+        assert_eq!(section.contours.len(), 2);
+        assert_eq!(section.hole_count(), 0);
+        for contour in &section.contours {
+            assert_closed(contour, 1.0 / 63.0);
+        }
+    }
+
+    #[test]
+    fn joins_saddle_with_inside_center() {
+        let section = slice_saddle(-1.0e-5);
+
+        assert_eq!(section.contours.len(), 1);
+        assert_eq!(section.hole_count(), 0);
+        assert_closed(&section.contours[0], 1.0 / 63.0);
+    }
+}
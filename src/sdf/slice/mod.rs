@@ -0,0 +1,2 @@
+mod cross_section;
+pub use cross_section::*;
@@ -1,5 +1,9 @@
+use std::collections::HashSet;
+
 use wgpu::util::DeviceExt;
 
+use strum_macros::{AsRefStr, EnumIter};
+
 use super::Capacity;
 use crate::{framework::gpu, warn};
 
@@ -10,28 +14,212 @@ struct ResourceLabels {
     color_atlas: String,
     side_size_buffer: String,
     count_buffer: String,
+    free_count_buffer: String,
+    free_list_buffer: String,
+    distance_range_atlas: String,
 }
 
-/// A format of one voxel in brick pool texture.
-/// - It determines how many bits are used for each voxel.
-#[derive(Debug)]
-pub enum BrickVoxelFormat {
-    Distance,
-    DistanceColor, // TODO: f16 | f32 | f16f16 | f32f32
+/// A precision of distance values stored in the distance atlas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, AsRefStr, EnumIter)]
+pub enum DistanceFormat {
+    F16,
+    F32,
+    /// 8-bit normalized distance quantized into a narrow band around the surface.
+    ///   - Every brick stores its own range of distances, see `DistanceRange`.
+    Unorm8,
 }
-impl BrickVoxelFormat {
-    pub fn voxel_ints(&self) -> u32 {
+
+impl DistanceFormat {
+    /// Half width of quantized distance band in voxels, ranges of bricks never exceed it.
+    const QUANTIZATION_RANGE_IN_VOXELS: f32 = 4.0;
+
+    pub fn texture_format(&self) -> wgpu::TextureFormat {
+        match self {
+            DistanceFormat::F16 => wgpu::TextureFormat::R16Float,
+            DistanceFormat::F32 => wgpu::TextureFormat::R32Float,
+            DistanceFormat::Unorm8 => wgpu::TextureFormat::R8Unorm,
+        }
+    }
+    /// Texel format name used in WGSL storage texture declarations.
+    pub fn wgsl_format(&self) -> &'static str {
+        match self {
+            DistanceFormat::F16 => "r16float",
+            DistanceFormat::F32 => "r32float",
+            DistanceFormat::Unorm8 => "r8unorm",
+        }
+    }
+    pub fn bytes(&self) -> u32 {
+        match self {
+            DistanceFormat::F16 => 2,
+            DistanceFormat::F32 => 4,
+            DistanceFormat::Unorm8 => 1,
+        }
+    }
+    /// Half width of quantized distance band in voxels, or 0 when distances are stored as floats.
+    ///   - Passed to the evaluation kernel which derives ranges of bricks from it.
+    pub fn quantization_range(&self) -> f32 {
+        match self {
+            DistanceFormat::Unorm8 => Self::QUANTIZATION_RANGE_IN_VOXELS,
+            _ => 0.0,
+        }
+    }
+
+    /// Encodes distance into a texel of this format, the same way as `encode_distance` in `_kernel_svo_level.wgsl`.
+    pub fn encode_texel(&self, distance: f32, range: &DistanceRange) -> Vec<u8> {
+        let value = range.encode(distance);
+        match self {
+            DistanceFormat::F16 => f32_to_f16(value).to_le_bytes().to_vec(),
+            DistanceFormat::F32 => value.to_le_bytes().to_vec(),
+            DistanceFormat::Unorm8 => vec![(value.clamp(0.0, 1.0) * 255.0).round() as u8],
+        }
+    }
+
+    /// Decodes distance from a texel of this format, the same way as shaders sampling the distance atlas.
+    pub fn decode_texel(&self, bytes: &[u8], range: &DistanceRange) -> f32 {
+        let value = match self {
+            DistanceFormat::F16 => f16_to_f32(u16::from_le_bytes([bytes[0], bytes[1]])),
+            DistanceFormat::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            DistanceFormat::Unorm8 => bytes[0] as f32 / 255.0,
+        };
+        range.decode(value)
+    }
+}
+
+/// A range of distances stored in one brick, distances are stored as `(distance - offset) / scale`.
+///   - Stored in the distance range atlas, one texel per brick at coordinates of the brick.
+///   - Quantized bricks span distances found in the brick, clamped to the quantization band around the surface.
+///     Bricks of floating point distances keep the identity range.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DistanceRange {
+    pub offset: f32,
+    pub scale: f32,
+}
+
+impl DistanceRange {
+    pub const IDENTITY: Self = Self {
+        offset: 0.0,
+        scale: 1.0,
+    };
+
+    pub const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Float;
+    pub const BYTES: u32 = 8;
+
+    /// Range of a brick whose voxels have distances between `min` and `max`.
+    ///   - Mirrors `brick_distance_range` in `_kernel_svo_level.wgsl`.
+    pub fn of_brick(format: DistanceFormat, min: f32, max: f32, voxel_size: f32) -> Self {
+        let band = format.quantization_range() * voxel_size;
+        if band <= 0.0 {
+            return Self::IDENTITY;
+        }
+        let low = min.clamp(-band, band);
+        let high = max.clamp(-band, band);
+        Self {
+            offset: low,
+            // one step of 8-bit band keeps flat bricks decodable
+            scale: (high - low).max(band / 255.0),
+        }
+    }
+
+    pub fn encode(&self, distance: f32) -> f32 {
+        (distance - self.offset) / self.scale
+    }
+
+    pub fn decode(&self, value: f32) -> f32 {
+        value * self.scale + self.offset
+    }
+
+    /// Reads range from a texel of the distance range atlas.
+    pub fn from_texel(bytes: &[u8]) -> Self {
+        bytemuck::pod_read_unaligned(&bytes[..Self::BYTES as usize])
+    }
+}
+
+/// A precision of color values stored in the color atlas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, AsRefStr, EnumIter)]
+pub enum ColorFormat {
+    Unorm8,
+    F16,
+}
+
+impl ColorFormat {
+    pub fn texture_format(&self) -> wgpu::TextureFormat {
+        match self {
+            ColorFormat::Unorm8 => wgpu::TextureFormat::Rgba8Unorm,
+            ColorFormat::F16 => wgpu::TextureFormat::Rgba16Float,
+        }
+    }
+    /// Texel format name used in WGSL storage texture declarations.
+    pub fn wgsl_format(&self) -> &'static str {
         match self {
-            BrickVoxelFormat::Distance => 1,
-            BrickVoxelFormat::DistanceColor => 2,
+            ColorFormat::Unorm8 => "rgba8unorm",
+            ColorFormat::F16 => "rgba16float",
+        }
+    }
+    pub fn bytes(&self) -> u32 {
+        match self {
+            ColorFormat::Unorm8 => 4,
+            ColorFormat::F16 => 8,
+        }
+    }
+}
+
+/// A format of one voxel in brick pool texture.
+/// - It determines texture formats of the distance and color atlases and how many bytes are used for each voxel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BrickVoxelFormat {
+    pub distance: DistanceFormat,
+    pub color: ColorFormat,
+}
+
+impl Default for BrickVoxelFormat {
+    fn default() -> Self {
+        Self {
+            distance: DistanceFormat::F16,
+            color: ColorFormat::Unorm8,
         }
     }
 }
 
+impl BrickVoxelFormat {
+    pub fn voxel_bytes(&self) -> u32 {
+        self.distance.bytes() + self.color.bytes()
+    }
+
+    /// Replaces texel formats of brick atlas storage textures in a WGSL source written for the default format.
+    pub fn specialize_shader(&self, source: &str) -> String {
+        let default = Self::default();
+        source
+            .replace(
+                &format!("texture_storage_3d<{}", default.distance.wgsl_format()),
+                &format!("texture_storage_3d<{}", self.distance.wgsl_format()),
+            )
+            .replace(
+                &format!("texture_storage_3d<{}", default.color.wgsl_format()),
+                &format!("texture_storage_3d<{}", self.color.wgsl_format()),
+            )
+    }
+
+    /// Checks that atlases of this format can be written by shaders and filtered when sampled on given GPU.
+    pub fn is_supported(&self, gpu: &gpu::Context) -> bool {
+        [self.distance.texture_format(), self.color.texture_format()]
+            .iter()
+            .all(|format| {
+                let features = gpu.adapter.get_texture_format_features(*format);
+                features
+                    .allowed_usages
+                    .contains(wgpu::TextureUsages::STORAGE_BINDING)
+                    && features
+                        .flags
+                        .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE)
+            })
+    }
+}
+
 /// A format of brick pool texture.
 /// - Used to initialize brick pool texture.
 /// - It determines total size of the texture by defining voxel format and padding around each brick.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct BrickPoolFormat {
     /// What is stored in one voxel of the brick in brick pool.
     pub voxel_format: BrickVoxelFormat,
@@ -43,7 +231,7 @@ pub struct BrickPoolFormat {
 impl Default for BrickPoolFormat {
     fn default() -> Self {
         BrickPoolFormat {
-            voxel_format: BrickVoxelFormat::Distance,
+            voxel_format: BrickVoxelFormat::default(),
             padding: 1,
         }
     }
@@ -52,24 +240,146 @@ impl Default for BrickPoolFormat {
 impl BrickPoolFormat {
     pub const BRICK_SIZE: u32 = 8;
 
-    pub fn voxel_format(&self) -> &BrickVoxelFormat {
-        &self.voxel_format
-    }
-    pub fn padding(&self) -> u32 {
-        self.padding
-    }
     pub fn voxels_per_brick_in_one_dimension(&self) -> u32 {
         Self::BRICK_SIZE + 2 * self.padding // NOTE: "+2" here signifies that there is padding on both sides of the brick actual brick is 10x10x10
     }
-    pub fn ints_per_brick_in_one_dimension(&self) -> u32 {
-        self.voxels_per_brick_in_one_dimension() * self.voxel_format.voxel_ints()
+    pub fn bytes_per_brick(&self) -> u32 {
+        self.voxels_per_brick_in_one_dimension().pow(3) * self.voxel_format.voxel_bytes()
     }
-    pub fn bytes_per_brick_in_one_dimension(&self) -> u32 {
-        (std::mem::size_of::<u32>() as u32) * self.ints_per_brick_in_one_dimension()
+}
+
+/// A cubical corner region of a distance atlas read back to CPU.
+pub struct DistanceAtlasRegion {
+    /// Number of voxels in each dimension of the region.
+    pub edge: u32,
+
+    /// Number of voxels of one brick including padding in each dimension.
+    pub voxels_per_brick: u32,
+
+    /// Distances of all voxels in the region, x coordinate changes fastest.
+    pub values: Vec<f32>,
+}
+
+impl DistanceAtlasRegion {
+    /// Returns distance of a voxel of given brick, voxel coordinates include padding.
+    ///   - Returns None when the brick is outside of the region.
+    pub fn brick_voxel(&self, brick_coords: glam::UVec3, voxel: glam::UVec3) -> Option<f32> {
+        let coords = brick_coords * self.voxels_per_brick + voxel;
+        if coords.max_element() >= self.edge {
+            return None;
+        }
+        let edge = self.edge as usize;
+        self.values
+            .get(coords.x as usize + edge * (coords.y as usize + edge * coords.z as usize))
+            .copied()
+    }
+}
+
+/// Converts f32 into bits of the nearest half precision float, the same way as GPUs store `r16float` texels.
+pub(super) fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32 - 127 + 15;
+    let mantissa = bits & 0x7F_FFFF;
+    if value.is_nan() {
+        return sign | 0x7E00;
+    }
+    if exponent >= 0x1F {
+        return sign | 0x7C00;
+    }
+
+    // Normal halves drop 13 bits of mantissa, subnormal ones drop the implicit bit too
+    let (half, shift) = if exponent > 0 {
+        (((exponent as u32) << 10) | (mantissa >> 13), 13)
+    } else if exponent >= -10 {
+        let shift = (14 - exponent) as u32;
+        ((mantissa | 0x80_0000) >> shift, shift)
+    } else {
+        return sign;
+    };
+    let rest = (mantissa | if exponent > 0 { 0 } else { 0x80_0000 }) & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    // round half to even, carry into exponent rounds up to the next power of two or infinity
+    let round_up = rest > halfway || (rest == halfway && half & 1 == 1);
+    sign | (half + round_up as u32) as u16
+}
+
+/// Converts bits of a half precision float into f32.
+pub(super) fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1F) as i32;
+    let mantissa = (bits & 0x3FF) as f32;
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1F if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// Bookkeeping of allocated and released bricks on CPU.
+///   - Bricks are allocated the same way as by `allocate_brick` in `_kernel_svo_level.wgsl`,
+///     released bricks are reused first (the last released one is taken first) and a new brick is taken only
+///     when there is no released brick.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BrickAllocator {
+    /// Number of bricks taken from the atlas including released ones.
+    pub count: u32,
+
+    /// Indices of released bricks waiting to be reused.
+    pub free: Vec<u32>,
+}
+
+impl BrickAllocator {
+    /// Number of bricks which are allocated and not released.
+    pub fn live_count(&self) -> u32 {
+        self.count - self.free.len() as u32
+    }
+
+    /// Returns index of a brick to be written.
+    pub fn allocate(&mut self) -> u32 {
+        self.free.pop().unwrap_or_else(|| {
+            self.count += 1;
+            self.count - 1
+        })
+    }
+
+    /// Marks given bricks as released so they can be reused by next allocations.
+    pub fn release(&mut self, brick_indices: &[u32]) {
+        self.free.extend_from_slice(brick_indices);
+    }
+
+    /// Plans compaction so live bricks occupy first `live_count` brick indices.
+    ///   - Live bricks from the end of the pool are reallocated into holes left by released bricks.
+    ///   - Returns pairs of (old index, new index) of moved bricks, the allocator ends up without released bricks.
+    pub fn compact(&mut self) -> Vec<(u32, u32)> {
+        let released: HashSet<u32> = self.free.drain(..).collect();
+        let live_count = self.count - released.len() as u32;
+
+        // holes are reused from the lowest index
+        self.free = released
+            .iter()
+            .copied()
+            .filter(|index| *index < live_count)
+            .collect();
+        self.free.sort_unstable_by(|a, b| b.cmp(a));
+
+        let moved: Vec<u32> = (live_count..self.count)
+            .filter(|index| !released.contains(index))
+            .collect();
+        self.count = live_count;
+        moved
+            .into_iter()
+            .map(|index| (index, self.allocate()))
+            .collect()
     }
 }
 
 /// A Brick Pool of the SVO residing on GPU.
+///   - Each SVO owns its brick pool. One atlas shared by many geometries is not implemented,
+///     geometries may pick different brick formats which could not live in a single atlas.
+///   - The free list recycles only bricks released by refinement of the same SVO,
+///     bricks of a deleted geometry are not reused but freed together with its SVO.
 #[derive(Debug)]
 pub struct BrickPool {
     /// A label of the brick pool.
@@ -79,6 +389,9 @@ pub struct BrickPool {
     #[cfg(debug_assertions)]
     resource_labels: ResourceLabels,
 
+    /// A format of bricks stored in this pool.
+    format: BrickPoolFormat,
+
     /// A gpu texture that stores distances of all the bricks.
     distance_atlas: wgpu::Texture,
 
@@ -91,6 +404,12 @@ pub struct BrickPool {
     /// A Texture view for the color atlas.
     color_atlas_view: wgpu::TextureView,
 
+    /// A gpu texture that stores `DistanceRange` of each brick in one texel at coordinates of the brick.
+    distance_range_atlas: wgpu::Texture,
+
+    /// A Texture view for the distance range atlas.
+    distance_range_atlas_view: wgpu::TextureView,
+
     /// An amount of bricks that can be stored in this texture in each dimension.
     side_size: u32,
 
@@ -104,10 +423,29 @@ pub struct BrickPool {
     /// In this buffer number of bricks in SVO is stored.
     /// - It is used for atomic increments in shaders
     count_buffer: wgpu::Buffer,
+
+    /// A value into which a number of released bricks is read from free_count_buffer.
+    /// - If None, `load_free_count` method must be call to populate this value.
+    free_count: Option<u32>,
+
+    /// In this buffer number of released bricks in `free_list_buffer` is stored.
+    /// - Shaders pop bricks from the free list by atomic decrements before allocating a new brick.
+    /// - It underflows when shaders try to pop from an empty list, `load_free_count` fixes it.
+    free_count_buffer: wgpu::Buffer,
+
+    /// A list of indices of released bricks which can be reused.
+    free_list_buffer: wgpu::Buffer,
+
+    /// A copy of `free_list_buffer` content, only first `free_count` indices are valid.
+    /// - Shaders only pop bricks from the end of the list, so the content never changes on GPU.
+    free_list: Vec<u32>,
 }
 
 // getters
 impl BrickPool {
+    pub fn format(&self) -> &BrickPoolFormat {
+        &self.format
+    }
     pub fn distance_atlas(&self) -> &wgpu::Texture {
         &self.distance_atlas
     }
@@ -120,9 +458,19 @@ impl BrickPool {
     pub fn count(&self) -> Option<u32> {
         self.count
     }
+    /// A total number of bricks that can be stored in the brick pool.
+    pub fn capacity(&self) -> u32 {
+        self.side_size * self.side_size * self.side_size
+    }
     pub fn count_buffer(&self) -> &wgpu::Buffer {
         &self.count_buffer
     }
+    pub fn free_count(&self) -> Option<u32> {
+        self.free_count
+    }
+    pub fn free_count_buffer(&self) -> &wgpu::Buffer {
+        &self.free_count_buffer
+    }
     /// Number of voxels in one dimension of entire brick pool.
     pub fn atlas_edge_size(&self) -> u32 {
         (BrickPoolFormat::BRICK_SIZE + 2) * self.side_size
@@ -144,6 +492,7 @@ impl BrickPool {
 // Statics and constructors
 impl BrickPool {
     /// Creates empty brick pool texture.
+    ///   - Voxel format of `format` has to be supported by the GPU, see `BrickVoxelFormat::is_supported`.
     ///   `capacity` - Used to set minimal amount of bricks that can be stored in this texture.
     ///   `context`  - GPU context.
     #[profiler::function]
@@ -159,6 +508,9 @@ impl BrickPool {
             color_atlas: format!("{} - Brick Pool Color Texture", svo_name),
             side_size_buffer: format!("{} - Brick Pool Side Size Buffer", svo_name),
             count_buffer: format!("{} - Brick Pool Count Buffer", svo_name),
+            free_count_buffer: format!("{} - Brick Pool Free Count Buffer", svo_name),
+            free_list_buffer: format!("{} - Brick Pool Free List Buffer", svo_name),
+            distance_range_atlas: format!("{} - Brick Pool Distance Range Texture", svo_name),
         };
 
         let side_size = Self::dimension_from_capacity(capacity.nodes());
@@ -182,8 +534,9 @@ impl BrickPool {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D3,
-            format: wgpu::TextureFormat::R16Float,
+            format: format.voxel_format.distance.texture_format(),
             usage: wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST
                 | wgpu::TextureUsages::STORAGE_BINDING
                 | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
@@ -194,7 +547,7 @@ impl BrickPool {
         crate::error!(
             "Creating brick bool of size: {}^3, {}B",
             voxels_per_side,
-            voxels_per_side * voxels_per_side * voxels_per_side * 2
+            (voxels_per_side as u64).pow(3) * format.voxel_format.voxel_bytes() as u64
         );
 
         let color_atlas = gpu.device.create_texture(&wgpu::TextureDescriptor {
@@ -210,14 +563,38 @@ impl BrickPool {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D3,
-            format: wgpu::TextureFormat::Rgba8Unorm,
+            format: format.voxel_format.color.texture_format(),
             usage: wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST
                 | wgpu::TextureUsages::STORAGE_BINDING
                 | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         });
         let color_atlas_view = color_atlas.create_view(&wgpu::TextureViewDescriptor::default());
 
+        let distance_range_atlas = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            #[cfg(debug_assertions)]
+            label: Some(&resource_labels.distance_range_atlas),
+            #[cfg(not(debug_assertions))]
+            label: None,
+            size: wgpu::Extent3d {
+                width: side_size,
+                height: side_size,
+                depth_or_array_layers: side_size,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: DistanceRange::TEXTURE_FORMAT,
+            usage: wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let distance_range_atlas_view =
+            distance_range_atlas.create_view(&wgpu::TextureViewDescriptor::default());
+
         let side_size_buffer = gpu
             .device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -238,24 +615,548 @@ impl BrickPool {
                 #[cfg(not(debug_assertions))]
                 label: None,
                 contents: bytemuck::cast_slice(&[count]),
-                usage: wgpu::BufferUsages::STORAGE,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::MAP_READ
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
             });
 
+        let free_count_buffer = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                #[cfg(debug_assertions)]
+                label: Some(&resource_labels.free_count_buffer),
+                #[cfg(not(debug_assertions))]
+                label: None,
+                contents: bytemuck::cast_slice(&[0u32]),
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::MAP_READ
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let free_list_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            #[cfg(debug_assertions)]
+            label: Some(&resource_labels.free_list_buffer),
+            #[cfg(not(debug_assertions))]
+            label: None,
+            size: (side_size * side_size * side_size).max(1) as u64
+                * std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
         Self {
             svo_name,
+            format,
             distance_atlas,
             distance_atlas_view,
             color_atlas,
             color_atlas_view,
+            distance_range_atlas,
+            distance_range_atlas_view,
             side_size,
             side_size_buffer,
             count: Some(count),
             count_buffer,
+            free_count: Some(0),
+            free_count_buffer,
+            free_list_buffer,
+            free_list: vec![],
             #[cfg(debug_assertions)]
             resource_labels,
         }
     }
 
+    /// Reads value from count buffer on GPU into internal `count` property and returns its value.
+    #[profiler::function]
+    pub fn load_count(&mut self, gpu: &gpu::Context) -> u32 {
+        self.count
+            .get_or_insert_with(|| gpu::Buffer::<u32>::static_read(&self.count_buffer, gpu)[0])
+            .clone()
+    }
+
+    /// Overwrites brick count on GPU.
+    ///   - Used to discard bricks of incomplete levels.
+    #[profiler::function]
+    pub fn set_count(&mut self, gpu: &gpu::Context, count: u32) {
+        gpu.queue
+            .write_buffer(&self.count_buffer, 0, bytemuck::cast_slice(&[count]));
+        self.count = Some(count);
+    }
+
+    /// Invalidates loaded counts after a kernel allocated new bricks.
+    pub fn buffers_changed(&mut self) {
+        self.count = None;
+        self.free_count = None;
+    }
+
+    /// Reads number of released bricks from GPU into internal `free_count` property and returns its value.
+    ///   - When shaders underflowed the count by popping from an empty free list, it is reset to zero.
+    #[profiler::function]
+    pub fn load_free_count(&mut self, gpu: &gpu::Context) -> u32 {
+        if let Some(free_count) = self.free_count {
+            return free_count;
+        }
+        let free_count = gpu::Buffer::<u32>::static_read(&self.free_count_buffer, gpu)[0];
+        self.free_count_loaded(gpu, free_count)
+    }
+
+    /// Sets counts read back from GPU together with counts of other buffers, see `Svo::load_pool_counts`.
+    ///   - Returns number of released bricks, which is reset to zero when shaders underflowed it.
+    pub(super) fn counts_loaded(&mut self, gpu: &gpu::Context, count: u32, free_count: u32) -> u32 {
+        self.count = Some(count);
+        self.free_count_loaded(gpu, free_count)
+    }
+
+    fn free_count_loaded(&mut self, gpu: &gpu::Context, free_count: u32) -> u32 {
+        if free_count as usize > self.free_list.len() {
+            self.set_free_count(gpu, 0);
+            return 0;
+        }
+        self.free_count = Some(free_count);
+        free_count
+    }
+
+    /// Overwrites number of released bricks on GPU.
+    ///   - Released bricks popped by shaders stay in the free list, so restoring the count restores the list.
+    #[profiler::function]
+    pub fn set_free_count(&mut self, gpu: &gpu::Context, free_count: u32) {
+        gpu.queue.write_buffer(
+            &self.free_count_buffer,
+            0,
+            bytemuck::cast_slice(&[free_count]),
+        );
+        self.free_count = Some(free_count);
+    }
+
+    /// Reads allocation state of the pool, number of allocated bricks and indices of released bricks.
+    #[profiler::function]
+    pub fn load_allocator(&mut self, gpu: &gpu::Context) -> BrickAllocator {
+        let free_count = self.load_free_count(gpu) as usize;
+        BrickAllocator {
+            count: self.load_count(gpu),
+            free: self.free_list[..free_count].to_vec(),
+        }
+    }
+
+    /// Pushes given bricks into the free list so they can be reused by next allocations.
+    #[profiler::function]
+    pub fn release(&mut self, gpu: &gpu::Context, brick_indices: &[u32]) {
+        if brick_indices.is_empty() {
+            return;
+        }
+        let free_count = self.load_free_count(gpu);
+        self.free_list.truncate(free_count as usize);
+        self.free_list.extend_from_slice(brick_indices);
+        gpu.queue.write_buffer(
+            &self.free_list_buffer,
+            free_count as u64 * std::mem::size_of::<u32>() as u64,
+            bytemuck::cast_slice(brick_indices),
+        );
+        self.set_free_count(gpu, self.free_list.len() as u32);
+    }
+
+    /// Reallocates brick pool so it can hold at least `brick_capacity` bricks and copies all existing bricks into it.
+    ///   - Bricks are allocated in cubical shells (see `brick_index_to_coords` in `_kernel_svo_level.wgsl`),
+    ///     so all existing bricks are in the corner of the atlas and keep their coordinates in the grown atlas.
+    ///   - All bind groups created for this brick pool are invalidated.
+    #[profiler::function]
+    pub fn grow(&mut self, gpu: &gpu::Context, brick_capacity: u32) {
+        if brick_capacity <= self.capacity() {
+            return;
+        }
+
+        let new_pool = Self::new(
+            self.svo_name.clone(),
+            gpu,
+            Capacity::Nodes(brick_capacity),
+            self.format,
+        );
+
+        let voxels_per_side = self.side_size * self.format.voxels_per_brick_in_one_dimension();
+        let copy_size = wgpu::Extent3d {
+            width: voxels_per_side,
+            height: voxels_per_side,
+            depth_or_array_layers: voxels_per_side,
+        };
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Brick Pool Grow Encoder"),
+            });
+        encoder.copy_texture_to_texture(
+            self.distance_atlas.as_image_copy(),
+            new_pool.distance_atlas.as_image_copy(),
+            copy_size,
+        );
+        encoder.copy_texture_to_texture(
+            self.color_atlas.as_image_copy(),
+            new_pool.color_atlas.as_image_copy(),
+            copy_size,
+        );
+        encoder.copy_texture_to_texture(
+            self.distance_range_atlas.as_image_copy(),
+            new_pool.distance_range_atlas.as_image_copy(),
+            wgpu::Extent3d {
+                width: self.side_size,
+                height: self.side_size,
+                depth_or_array_layers: self.side_size,
+            },
+        );
+        encoder.copy_buffer_to_buffer(
+            &self.count_buffer,
+            0,
+            &new_pool.count_buffer,
+            0,
+            std::mem::size_of::<u32>() as u64,
+        );
+        encoder.copy_buffer_to_buffer(
+            &self.free_count_buffer,
+            0,
+            &new_pool.free_count_buffer,
+            0,
+            std::mem::size_of::<u32>() as u64,
+        );
+        encoder.copy_buffer_to_buffer(
+            &self.free_list_buffer,
+            0,
+            &new_pool.free_list_buffer,
+            0,
+            self.free_list_buffer.size(),
+        );
+        gpu.queue.submit(Some(encoder.finish()));
+
+        let (count, free_count) = (self.count, self.free_count);
+        let free_list = std::mem::take(&mut self.free_list);
+        *self = new_pool;
+        self.count = count;
+        self.free_count = free_count;
+        self.free_list = free_list;
+    }
+
+    /// Repacks live bricks so they occupy first `live_count` brick indices and clears the free list.
+    ///   - `moves` - pairs of (old index, new index) of live bricks which are moved into holes left by released bricks,
+    ///     see `BrickAllocator::compact`.
+    ///   - Bricks cannot be copied within one texture, so the pool is reallocated with the same capacity.
+    ///   - All bind groups created for this brick pool are invalidated.
+    #[profiler::function]
+    pub fn repack(&mut self, gpu: &gpu::Context, live_count: u32, moves: &[(u32, u32)]) {
+        let new_pool = Self::new(
+            self.svo_name.clone(),
+            gpu,
+            Capacity::Nodes(self.capacity()),
+            self.format,
+        );
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Brick Pool Repack Encoder"),
+            });
+
+        // Copy whole atlas first, so bricks which stay in place need no extra copy
+        let voxels_per_brick = self.format.voxels_per_brick_in_one_dimension();
+        let voxels_per_side = self.side_size * voxels_per_brick;
+        let atlas_size = wgpu::Extent3d {
+            width: voxels_per_side,
+            height: voxels_per_side,
+            depth_or_array_layers: voxels_per_side,
+        };
+        for (source, target) in [
+            (&self.distance_atlas, &new_pool.distance_atlas),
+            (&self.color_atlas, &new_pool.color_atlas),
+        ] {
+            encoder.copy_texture_to_texture(
+                source.as_image_copy(),
+                target.as_image_copy(),
+                atlas_size,
+            );
+        }
+        encoder.copy_texture_to_texture(
+            self.distance_range_atlas.as_image_copy(),
+            new_pool.distance_range_atlas.as_image_copy(),
+            wgpu::Extent3d {
+                width: self.side_size,
+                height: self.side_size,
+                depth_or_array_layers: self.side_size,
+            },
+        );
+
+        // Move bricks from the end of the pool into holes
+        let brick_origin = |index: u32, texels_per_brick: u32| {
+            let coords = Self::brick_index_to_coords(index) * texels_per_brick;
+            wgpu::Origin3d {
+                x: coords.x,
+                y: coords.y,
+                z: coords.z,
+            }
+        };
+        for (from, to) in moves {
+            // range atlas has one texel per brick
+            for (source, target, texels_per_brick) in [
+                (
+                    &self.distance_atlas,
+                    &new_pool.distance_atlas,
+                    voxels_per_brick,
+                ),
+                (&self.color_atlas, &new_pool.color_atlas, voxels_per_brick),
+                (
+                    &self.distance_range_atlas,
+                    &new_pool.distance_range_atlas,
+                    1,
+                ),
+            ] {
+                encoder.copy_texture_to_texture(
+                    wgpu::ImageCopyTexture {
+                        origin: brick_origin(*from, texels_per_brick),
+                        ..source.as_image_copy()
+                    },
+                    wgpu::ImageCopyTexture {
+                        origin: brick_origin(*to, texels_per_brick),
+                        ..target.as_image_copy()
+                    },
+                    wgpu::Extent3d {
+                        width: texels_per_brick,
+                        height: texels_per_brick,
+                        depth_or_array_layers: texels_per_brick,
+                    },
+                );
+            }
+        }
+        gpu.queue.submit(Some(encoder.finish()));
+
+        *self = new_pool;
+        self.set_count(gpu, live_count);
+    }
+
+    /// Reads distance atlas region containing first `brick_count` bricks back to CPU.
+    ///   - Distances are returned as they are stored, ranges of bricks are not applied.
+    #[profiler::function]
+    pub fn read_distances(
+        &self,
+        gpu: &gpu::Context,
+        brick_count: u32,
+    ) -> Result<DistanceAtlasRegion, String> {
+        let edge = self.region_edge(brick_count);
+        let texel_bytes = self.format.voxel_format.distance.bytes();
+        let bytes = Self::read_texture_region(gpu, &self.distance_atlas, texel_bytes, edge);
+
+        let values = match self.format.voxel_format.distance {
+            DistanceFormat::F16 => bytes
+                .chunks_exact(2)
+                .map(|b| f16_to_f32(u16::from_le_bytes([b[0], b[1]])))
+                .collect(),
+            DistanceFormat::F32 => bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            DistanceFormat::Unorm8 => bytes.iter().map(|b| *b as f32 / 255.0).collect(),
+        };
+
+        Ok(DistanceAtlasRegion {
+            edge,
+            voxels_per_brick: self.format.voxels_per_brick_in_one_dimension(),
+            values,
+        })
+    }
+
+    /// Reads raw texels of distance, color and distance range atlas regions containing first `brick_count` bricks
+    /// back to CPU.
+    ///   - Returns edge size of the cubical region in voxels, distance texels, color texels and range texels.
+    #[profiler::function]
+    pub fn read_atlases(
+        &self,
+        gpu: &gpu::Context,
+        brick_count: u32,
+    ) -> (u32, Vec<u8>, Vec<u8>, Vec<u8>) {
+        let edge = self.region_edge(brick_count);
+        let distances = Self::read_texture_region(
+            gpu,
+            &self.distance_atlas,
+            self.format.voxel_format.distance.bytes(),
+            edge,
+        );
+        let colors = Self::read_texture_region(
+            gpu,
+            &self.color_atlas,
+            self.format.voxel_format.color.bytes(),
+            edge,
+        );
+        let ranges = Self::read_texture_region(
+            gpu,
+            &self.distance_range_atlas,
+            DistanceRange::BYTES,
+            edge / self.format.voxels_per_brick_in_one_dimension(),
+        );
+        (edge, distances, colors, ranges)
+    }
+
+    /// Uploads raw texels read by `read_atlases` into the corner of the atlases and sets brick count.
+    ///   - The pool has to be large enough to contain the region.
+    #[profiler::function]
+    pub fn write_atlases(
+        &mut self,
+        gpu: &gpu::Context,
+        brick_count: u32,
+        edge: u32,
+        distances: &[u8],
+        colors: &[u8],
+        ranges: &[u8],
+    ) {
+        Self::write_texture_region(
+            gpu,
+            &self.distance_atlas,
+            self.format.voxel_format.distance.bytes(),
+            edge,
+            distances,
+        );
+        Self::write_texture_region(
+            gpu,
+            &self.color_atlas,
+            self.format.voxel_format.color.bytes(),
+            edge,
+            colors,
+        );
+        Self::write_texture_region(
+            gpu,
+            &self.distance_range_atlas,
+            DistanceRange::BYTES,
+            edge / self.format.voxels_per_brick_in_one_dimension(),
+            ranges,
+        );
+        self.set_count(gpu, brick_count);
+    }
+
+    /// Edge size in voxels of the atlas corner region containing first `brick_count` bricks.
+    fn region_edge(&self, brick_count: u32) -> u32 {
+        // first n^3 bricks always fill n x n x n corner of the atlas
+        let side_size = Self::dimension_from_capacity(brick_count).min(self.side_size);
+        side_size * self.format.voxels_per_brick_in_one_dimension()
+    }
+
+    /// Copies cubical corner region of a texture into a staging buffer and returns its tightly packed texels.
+    fn read_texture_region(
+        gpu: &gpu::Context,
+        texture: &wgpu::Texture,
+        texel_bytes: u32,
+        edge: u32,
+    ) -> Vec<u8> {
+        if edge == 0 {
+            return vec![];
+        }
+
+        // rows copied into a buffer have to be aligned
+        let row_bytes = edge * texel_bytes;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let bytes_per_row = row_bytes.div_ceil(align) * align;
+
+        let staging_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Brick Atlas Read Staging Buffer"),
+            size: bytes_per_row as u64 * edge as u64 * edge as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Brick Atlas Read Encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &staging_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(bytes_per_row),
+                    rows_per_image: std::num::NonZeroU32::new(edge),
+                },
+            },
+            wgpu::Extent3d {
+                width: edge,
+                height: edge,
+                depth_or_array_layers: edge,
+            },
+        );
+        gpu.queue.submit(Some(encoder.finish()));
+
+        let bytes = gpu::Buffer::<u8>::static_read(&staging_buffer, gpu);
+        let mut texels = Vec::with_capacity(row_bytes as usize * (edge as usize).pow(2));
+        for row in bytes.chunks_exact(bytes_per_row as usize) {
+            texels.extend_from_slice(&row[..row_bytes as usize]);
+        }
+        texels
+    }
+
+    /// Writes tightly packed texels into cubical corner region of a texture.
+    fn write_texture_region(
+        gpu: &gpu::Context,
+        texture: &wgpu::Texture,
+        texel_bytes: u32,
+        edge: u32,
+        texels: &[u8],
+    ) {
+        if edge == 0 {
+            return;
+        }
+        gpu.queue.write_texture(
+            texture.as_image_copy(),
+            texels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(edge * texel_bytes),
+                rows_per_image: std::num::NonZeroU32::new(edge),
+            },
+            wgpu::Extent3d {
+                width: edge,
+                height: edge,
+                depth_or_array_layers: edge,
+            },
+        );
+    }
+
+    /// Converts brick index into brick coordinates in the atlas.
+    ///   - Mirrors `brick_index_to_coords` in `_kernel_svo_level.wgsl`, bricks are laid out in cubical shells.
+    pub fn brick_index_to_coords(index: u32) -> glam::UVec3 {
+        let mut s = (index as f32).cbrt() as u32;
+        while s * s * s > index {
+            s -= 1;
+        }
+        while (s + 1) * (s + 1) * (s + 1) <= index {
+            s += 1;
+        }
+
+        let mut r = index - s * s * s;
+        let face = (s + 1) * (s + 1);
+        if r < face {
+            return glam::UVec3::new(s, r / (s + 1), r % (s + 1));
+        }
+        r -= face;
+        let strip = s * (s + 1);
+        if r < strip {
+            return glam::UVec3::new(r / (s + 1), s, r % (s + 1));
+        }
+        r -= strip;
+        glam::UVec3::new(r / s, r % s, s)
+    }
+
+    /// Converts brick coordinates in the atlas into brick index, inverse of `brick_index_to_coords`.
+    pub fn brick_coords_to_index(coords: glam::UVec3) -> u32 {
+        let s = coords.max_element();
+        let shell_start = s * s * s;
+        if coords.x == s {
+            shell_start + coords.y * (s + 1) + coords.z
+        } else if coords.y == s {
+            shell_start + (s + 1) * (s + 1) + coords.x * (s + 1) + coords.z
+        } else {
+            shell_start + (s + 1) * (s + 1) + s * (s + 1) + coords.x * s + coords.y
+        }
+    }
+
     /// Calculates minimum number of bricks in one dimension of (cubical) brick pool which can contain given amount of bricks.
     /// `brick_count` - Amount of bricks that need to be stored in brick pool.
     pub fn dimension_from_capacity(brick_count: u32) -> u32 {
@@ -304,6 +1205,21 @@ impl BrickPool {
                     binding: 3,
                     resource: self.side_size_buffer().as_entire_binding(),
                 },
+                // free_count_buffer
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.free_count_buffer.as_entire_binding(),
+                },
+                // free_list_buffer
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: self.free_list_buffer.as_entire_binding(),
+                },
+                // distance_range_atlas
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(&self.distance_range_atlas_view),
+                },
             ],
         })
     }
@@ -358,6 +1274,11 @@ impl BrickPool {
                     binding: 5,
                     resource: self.side_size_buffer().as_entire_binding(),
                 },
+                // distance_range_atlas
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(&self.distance_range_atlas_view),
+                },
             ],
         })
     }
@@ -367,6 +1288,7 @@ impl BrickPool {
     pub fn create_write_bind_group_layout(
         gpu: &gpu::Context,
         visibility: wgpu::ShaderStages,
+        voxel_format: &BrickVoxelFormat,
     ) -> wgpu::BindGroupLayout {
         gpu.device
             .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -379,7 +1301,7 @@ impl BrickPool {
                         count: None,
                         ty: wgpu::BindingType::StorageTexture {
                             access: wgpu::StorageTextureAccess::WriteOnly,
-                            format: wgpu::TextureFormat::R16Float, // TODO: Use format of given BrickVoxelFormat
+                            format: voxel_format.distance.texture_format(),
                             view_dimension: wgpu::TextureViewDimension::D3,
                         },
                     },
@@ -390,7 +1312,7 @@ impl BrickPool {
                         count: None,
                         ty: wgpu::BindingType::StorageTexture {
                             access: wgpu::StorageTextureAccess::WriteOnly,
-                            format: wgpu::TextureFormat::Rgba8Unorm,
+                            format: voxel_format.color.texture_format(),
                             view_dimension: wgpu::TextureViewDimension::D3,
                         },
                     },
@@ -416,6 +1338,39 @@ impl BrickPool {
                             min_binding_size: None,
                         },
                     },
+                    // free_count_buffer
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility,
+                        count: None,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                    },
+                    // free_list_buffer
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility,
+                        count: None,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                    },
+                    // distance_range_atlas
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility,
+                        count: None,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            format: DistanceRange::TEXTURE_FORMAT,
+                            view_dimension: wgpu::TextureViewDimension::D3,
+                        },
+                    },
                 ],
             })
     }
@@ -487,7 +1442,155 @@ impl BrickPool {
                             min_binding_size: None,
                         },
                     },
+                    // distance_range_atlas, ranges are loaded per brick so they are not filtered
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility,
+                        count: None,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D3,
+                            multisampled: false,
+                        },
+                    },
                 ],
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn distances_round_trip_through_texels_of_every_format() {
+        let voxel_size = 0.01;
+        let distances = [-0.013, -0.004, 0.0, 0.0025, 0.017];
+        for format in DistanceFormat::iter() {
+            let range = DistanceRange::of_brick(format, -0.013, 0.017, voxel_size);
+            let tolerance = match format {
+                DistanceFormat::F32 => 0.0,
+                // 11 bits of mantissa
+                DistanceFormat::F16 => 0.017 / 2048.0,
+                // half of a quantization step
+                DistanceFormat::Unorm8 => range.scale / 255.0 * 0.5 + 1e-7,
+            };
+            for distance in distances {
+                let texel = format.encode_texel(distance, &range);
+                assert_eq!(texel.len(), format.bytes() as usize);
+                let decoded = format.decode_texel(&texel, &range);
+                assert!(
+                    (decoded - distance).abs() <= tolerance,
+                    "{:?} decoded {} as {}",
+                    format,
+                    distance,
+                    decoded
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn quantized_range_is_limited_to_band_around_surface() {
+        let voxel_size = 0.01;
+        let band = DistanceFormat::Unorm8.quantization_range() * voxel_size;
+        let range = DistanceRange::of_brick(DistanceFormat::Unorm8, -1.0, 0.005, voxel_size);
+        assert_eq!(range.offset, -band);
+        assert!((range.decode(1.0) - 0.005).abs() < 1e-7);
+
+        // distances beyond the band are clamped to its edge
+        let texel = DistanceFormat::Unorm8.encode_texel(-1.0, &range);
+        assert_eq!(DistanceFormat::Unorm8.decode_texel(&texel, &range), -band);
+
+        // a brick of one distance still gets a usable scale
+        let flat = DistanceRange::of_brick(DistanceFormat::Unorm8, 0.002, 0.002, voxel_size);
+        assert!(flat.scale > 0.0);
+        let texel = DistanceFormat::Unorm8.encode_texel(0.002, &flat);
+        assert_eq!(DistanceFormat::Unorm8.decode_texel(&texel, &flat), 0.002);
+
+        // float formats store distances unchanged
+        let range = DistanceRange::of_brick(DistanceFormat::F16, -1.0, 1.0, voxel_size);
+        assert_eq!(range, DistanceRange::IDENTITY);
+    }
+
+    #[test]
+    fn half_floats_round_to_nearest() {
+        for value in [
+            0.0,
+            -0.0,
+            1.0,
+            -2.5,
+            65504.0,
+            6.1035156e-5,
+            5.9604645e-8,
+            0.1,
+        ] {
+            let half = f32_to_f16(value);
+            let decoded = f16_to_f32(half);
+            assert!(
+                (decoded - value).abs() <= value.abs() / 2048.0,
+                "{} decoded as {}",
+                value,
+                decoded
+            );
+        }
+        assert_eq!(f32_to_f16(1.0), 0x3C00);
+        // exactly between 1 and the next half rounds to even
+        assert_eq!(f32_to_f16(1.0 + 1.0 / 2048.0), 0x3C00);
+        assert_eq!(f32_to_f16(1e6), 0x7C00);
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+    }
+
+    #[test]
+    fn released_bricks_are_reused_before_new_ones() {
+        let mut allocator = BrickAllocator::default();
+        let allocated: Vec<u32> = (0..5).map(|_| allocator.allocate()).collect();
+        assert_eq!(allocated, vec![0, 1, 2, 3, 4]);
+
+        allocator.release(&[1, 3]);
+        assert_eq!(allocator.live_count(), 3);
+        assert_eq!(allocator.allocate(), 3);
+        assert_eq!(allocator.allocate(), 1);
+        assert_eq!(allocator.allocate(), 5);
+        assert_eq!(allocator.count, 6);
+        assert!(allocator.free.is_empty());
+    }
+
+    #[test]
+    fn compaction_moves_live_bricks_into_holes() {
+        let mut allocator = BrickAllocator {
+            count: 8,
+            free: vec![6, 1, 3],
+        };
+        let moves = allocator.compact();
+
+        assert_eq!(allocator.count, 5);
+        assert!(allocator.free.is_empty());
+        // only live bricks past the new count move, each into a distinct hole
+        let mut sources: Vec<u32> = moves.iter().map(|(from, _)| *from).collect();
+        let mut targets: Vec<u32> = moves.iter().map(|(_, to)| *to).collect();
+        sources.sort_unstable();
+        targets.sort_unstable();
+        assert_eq!(sources, vec![5, 7]);
+        assert_eq!(targets, vec![1, 3]);
+
+        // compacted pool allocates right after its live bricks
+        assert_eq!(allocator.allocate(), 5);
+    }
+
+    #[test]
+    fn compaction_without_released_bricks_moves_nothing() {
+        let mut allocator = BrickAllocator {
+            count: 4,
+            free: vec![],
+        };
+        assert!(allocator.compact().is_empty());
+        assert_eq!(allocator.count, 4);
+
+        // releasing the last bricks only shrinks the pool
+        allocator.release(&[3, 2]);
+        assert!(allocator.compact().is_empty());
+        assert_eq!(allocator.count, 2);
+    }
+}
@@ -1,7 +1,18 @@
 mod brick_pool;
 mod node_pool;
 mod svo;
+mod svo_data;
+mod svo_sampler;
+mod svo_statistics;
 
 pub use brick_pool::*;
 pub use node_pool::*;
 pub use svo::*;
+pub use svo_data::*;
+pub use svo_sampler::*;
+pub use svo_statistics::*;
+
+#[cfg(test)]
+pub(crate) use svo_data::tests::{
+    sphere_svo_data, svo_data_from_distance_function, SPHERE_SVO_RADIUS,
+};
@@ -108,7 +108,10 @@ impl NodePool {
                 #[cfg(not(debug_assertions))]
                 label: None,
                 contents: bytemuck::cast_slice(&[count]),
-                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::MAP_READ,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::MAP_READ
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
             });
 
         let header_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
@@ -117,7 +120,9 @@ impl NodePool {
             #[cfg(not(debug_assertions))]
             label: None,
             size: capacity64 * std::mem::size_of::<u32>() as u64,
-            usage: wgpu::BufferUsages::STORAGE,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
 
@@ -127,7 +132,9 @@ impl NodePool {
             #[cfg(not(debug_assertions))]
             label: None,
             size: capacity64 * std::mem::size_of::<u32>() as u64,
-            usage: wgpu::BufferUsages::STORAGE,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
 
@@ -137,7 +144,9 @@ impl NodePool {
             #[cfg(not(debug_assertions))]
             label: None,
             size: capacity64 * std::mem::size_of::<glam::Vec4>() as u64,
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
 
@@ -174,6 +183,90 @@ impl NodePool {
             .clone()
     }
 
+    /// Sets node count read back from GPU together with counts of other buffers, see `Svo::load_pool_counts`.
+    pub(super) fn count_loaded(&mut self, count: u32) {
+        self.count = Some(count);
+    }
+
+    /// Overwrites node count on GPU.
+    ///   - Used to discard nodes of incomplete levels.
+    #[profiler::function]
+    pub fn set_count(&mut self, gpu: &gpu::Context, count: u32) {
+        gpu.queue
+            .write_buffer(&self.count_buffer, 0, bytemuck::cast_slice(&[count]));
+        self.count = Some(count);
+    }
+
+    /// Writes given nodes at the beginning of the pool and sets node count to their number.
+    ///   - The pool has to have capacity for all of them.
+    #[profiler::function]
+    pub fn upload(
+        &mut self,
+        gpu: &gpu::Context,
+        headers: &[u32],
+        payloads: &[u32],
+        vertices: &[glam::Vec4],
+    ) {
+        gpu.queue
+            .write_buffer(&self.header_buffer, 0, bytemuck::cast_slice(headers));
+        gpu.queue
+            .write_buffer(&self.payload_buffer, 0, bytemuck::cast_slice(payloads));
+        gpu.queue
+            .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(vertices));
+        self.set_count(gpu, headers.len() as u32);
+    }
+
+    /// Reallocates node pool with a new capacity copying all existing nodes into the new buffers.
+    ///   - All bind groups created for this node pool are invalidated.
+    #[profiler::function]
+    pub fn grow(&mut self, gpu: &gpu::Context, new_capacity: u32) {
+        if new_capacity <= self.capacity {
+            return;
+        }
+
+        let new_pool = Self::new(self.svo_name.clone(), gpu, Capacity::Nodes(new_capacity));
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Node Pool Grow Encoder"),
+            });
+
+        let capacity64 = self.capacity as u64;
+        encoder.copy_buffer_to_buffer(
+            &self.count_buffer,
+            0,
+            &new_pool.count_buffer,
+            0,
+            std::mem::size_of::<u32>() as u64,
+        );
+        encoder.copy_buffer_to_buffer(
+            &self.header_buffer,
+            0,
+            &new_pool.header_buffer,
+            0,
+            capacity64 * std::mem::size_of::<u32>() as u64,
+        );
+        encoder.copy_buffer_to_buffer(
+            &self.payload_buffer,
+            0,
+            &new_pool.payload_buffer,
+            0,
+            capacity64 * std::mem::size_of::<u32>() as u64,
+        );
+        encoder.copy_buffer_to_buffer(
+            &self.vertex_buffer,
+            0,
+            &new_pool.vertex_buffer,
+            0,
+            capacity64 * std::mem::size_of::<glam::Vec4>() as u64,
+        );
+        gpu.queue.submit(Some(encoder.finish()));
+
+        let count = self.count;
+        *self = new_pool;
+        self.count = count;
+    }
+
     /// Returns existing bind group or creates a new one with given layout.
     #[profiler::function]
     pub fn create_bind_group(
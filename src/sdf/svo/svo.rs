@@ -1,5 +1,15 @@
-use super::{BrickPool, BrickPoolFormat, BrickVoxelFormat, NodePool};
-use crate::framework::{gpu, math::BoundingCube};
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use super::{
+    BrickPool, BrickPoolFormat, BrickVoxelFormat, DistanceFormat, DistanceRange, NodePool,
+};
+use crate::{
+    framework::{gpu, math::BoundingCube},
+    warn,
+};
 
 /// A helper struct to express desired octree capacity
 #[derive(Debug, Clone)]
@@ -35,12 +45,37 @@ impl Capacity {
     }
 }
 
+/// Result of comparing floating point distances of an SVO with distances quantized into 8 bits.
+#[derive(Clone, Debug, Default)]
+pub struct QuantizationErrorReport {
+    /// Number of compared voxels near the surface.
+    pub sample_count: usize,
+
+    /// Maximal absolute distance error in world units.
+    pub max_error: f32,
+
+    /// Mean absolute distance error in world units.
+    pub mean_error: f32,
+
+    /// Maximal absolute distance error relative to size of the voxel where it occurred.
+    pub max_error_in_voxels: f32,
+}
+
 #[derive(Clone, Debug, Copy)]
 pub struct Level {
     pub start_index: u32,
     pub node_count: u32,
 }
 
+/// Allocation counts of both pools of an SVO.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub struct PoolCounts {
+    pub nodes: u32,
+    pub bricks: u32,
+    /// Number of released bricks in the free list of the brick pool.
+    pub free_bricks: u32,
+}
+
 /// A Sparse Voxel Octree residing on GPU.
 #[derive(Debug)]
 pub struct Svo {
@@ -59,18 +94,68 @@ pub struct Svo {
 
     // A bounding cube of the SVO.
     pub domain: BoundingCube,
+
+    /// A unique identifier of current GPU resources of the SVO.
+    ///   - It changes whenever node pool or brick pool is reallocated, so users can tell that their bind groups are stale.
+    resources_version: u64,
+
+    /// A unique identifier of current evaluated levels of the SVO.
+    ///   - It changes whenever levels are evaluated again or a new level is added, so users can tell that data
+    ///     they derived from the SVO are stale.
+    levels_version: u64,
+}
+
+/// Node header flag signaling that the node has children.
+pub(super) const HEADER_SUBDIVIDED_FLAG: u32 = 1 << 31;
+
+/// Node header flag signaling that the node has a brick linked in its payload.
+pub(super) const HEADER_HAS_BRICK_FLAG: u32 = 1 << 30;
+
+/// Node header bits holding index of the tile of 8 children, first child has index `tile_index * 8`.
+pub(super) const HEADER_TILE_INDEX_MASK: u32 = 0x3FFFFFFF;
+
+/// Payload of a node without brick which lies fully inside of the surface, same as `BRICK_IS_FILLED` in `_kernel_svo_level.wgsl`.
+pub(super) const PAYLOAD_FILLED: u32 = 2;
+
+/// Returns index of a brick linked to a node or None if the node has no brick.
+pub(super) fn node_brick_index(header: u32, payload: u32) -> Option<u32> {
+    if header & HEADER_HAS_BRICK_FLAG == 0 {
+        return None;
+    }
+    Some(BrickPool::brick_coords_to_index(glam::UVec3::new(
+        (payload >> 20) & 0x3FF,
+        (payload >> 10) & 0x3FF,
+        payload & 0x3FF,
+    )))
+}
+
+/// Encodes brick coordinates into a node payload, same as `create_node_brick_payload` in `_kernel_svo_level.wgsl`.
+pub(super) fn brick_payload(coords: glam::UVec3) -> u32 {
+    ((coords.x & 0x3FF) << 20) | ((coords.y & 0x3FF) << 10) | (coords.z & 0x3FF)
+}
+
+/// Returns a new unique resources or levels version.
+///   - Versions are shared by all SVOs and both kinds, so a newly created SVO never reuses version of another one.
+fn next_resources_version() -> u64 {
+    static NEXT_RESOURCES_VERSION: AtomicU64 = AtomicU64::new(0);
+    NEXT_RESOURCES_VERSION.fetch_add(1, Ordering::Relaxed)
 }
 
 impl Svo {
     #[profiler::function]
-    pub fn new(label: String, gpu: &gpu::Context, initial_capacity: Capacity) -> Self {
+    pub fn new(
+        label: String,
+        gpu: &gpu::Context,
+        initial_capacity: Capacity,
+        voxel_format: BrickVoxelFormat,
+    ) -> Self {
         let node_pool = NodePool::new(label.clone(), gpu, initial_capacity.clone());
         let brick_pool = BrickPool::new(
             label.clone(),
             gpu,
             initial_capacity.clone(),
             BrickPoolFormat {
-                voxel_format: BrickVoxelFormat::Distance,
+                voxel_format,
                 padding: 1,
             },
         );
@@ -81,6 +166,322 @@ impl Svo {
             brick_pool,
             domain: BoundingCube::UNIT,
             levels: vec![],
+            resources_version: next_resources_version(),
+            levels_version: next_resources_version(),
+        }
+    }
+
+    pub fn resources_version(&self) -> u64 {
+        self.resources_version
+    }
+
+    pub fn levels_version(&self) -> u64 {
+        self.levels_version
+    }
+
+    /// Marks levels as changed, it has to be called whenever `levels` are modified.
+    pub fn levels_changed(&mut self) {
+        self.levels_version = next_resources_version();
+    }
+
+    /// Grows node pool and brick pool so they can hold at least given number of nodes and bricks.
+    ///   - Capacity of a pool is at least doubled when it grows, so repeated overflows are rare.
+    ///   - Existing nodes and bricks are preserved.
+    ///   - Returns false when the pools cannot grow enough because of device limits,
+    ///     both limits are checked first so neither pool is grown in that case.
+    #[profiler::function]
+    pub fn grow(&mut self, gpu: &gpu::Context, node_capacity: u32, brick_capacity: u32) -> bool {
+        let limits = gpu.device.limits();
+
+        // vertex buffer is the largest buffer in node pool
+        let max_node_capacity = (limits.max_storage_buffer_binding_size as u64)
+            .min(limits.max_buffer_size)
+            / std::mem::size_of::<glam::Vec4>() as u64;
+        if node_capacity as u64 > max_node_capacity {
+            warn!(
+                "SVO {}: Cannot grow node pool to {} nodes, device limit is {} nodes",
+                self.label, node_capacity, max_node_capacity
+            );
+            return false;
+        }
+
+        // atlas is limited by maximal texture size and by 10 bits per brick coordinate in node payload
+        let max_side_size = (limits.max_texture_dimension_3d
+            / self.brick_pool.format().voxels_per_brick_in_one_dimension())
+        .min(1 << 10);
+        let max_brick_capacity = max_side_size * max_side_size * max_side_size;
+        if brick_capacity > max_brick_capacity {
+            warn!(
+                "SVO {}: Cannot grow brick pool to {} bricks, device limit is {} bricks",
+                self.label, brick_capacity, max_brick_capacity
+            );
+            return false;
+        }
+
+        let node_pool_capacity = self.node_pool.capacity();
+        if node_capacity > node_pool_capacity {
+            let new_capacity = node_capacity.max(node_pool_capacity.saturating_mul(2));
+            self.node_pool
+                .grow(gpu, (new_capacity as u64).min(max_node_capacity) as u32);
+            self.resources_version = next_resources_version();
+        }
+
+        let brick_pool_capacity = self.brick_pool.capacity();
+        if brick_capacity > brick_pool_capacity {
+            let new_capacity = brick_capacity.max(brick_pool_capacity.saturating_mul(2));
+            self.brick_pool
+                .grow(gpu, new_capacity.min(max_brick_capacity));
+            self.resources_version = next_resources_version();
+        }
+
+        true
+    }
+
+    /// Number of nodes in all evaluated levels.
+    ///   - Node pool might contain more nodes than this (a tile of a level which is yet to be evaluated),
+    ///     but those nodes do not have valid headers and payloads yet and must not be traversed.
+    pub fn evaluated_node_count(&self) -> u32 {
+        self.levels.last().map_or(0, |level| {
+            level.start_index.saturating_add(level.node_count)
+        })
+    }
+
+    /// Returns allocation counts of both pools, counts which are not loaded yet are read back from GPU at once.
+    #[profiler::function]
+    pub fn load_pool_counts(&mut self, gpu: &gpu::Context) -> PoolCounts {
+        if let (Some(nodes), Some(bricks), Some(free_bricks)) = (
+            self.node_pool.count(),
+            self.brick_pool.count(),
+            self.brick_pool.free_count(),
+        ) {
+            return PoolCounts {
+                nodes,
+                bricks,
+                free_bricks,
+            };
+        }
+        let counts = gpu::Buffer::<u32>::static_read_all(
+            &[
+                self.node_pool.count_buffer(),
+                self.brick_pool.count_buffer(),
+                self.brick_pool.free_count_buffer(),
+            ],
+            gpu,
+        );
+        let (nodes, bricks) = (counts[0][0], counts[1][0]);
+        self.node_pool.count_loaded(nodes);
+        let free_bricks = self.brick_pool.counts_loaded(gpu, bricks, counts[2][0]);
+        PoolCounts {
+            nodes,
+            bricks,
+            free_bricks,
+        }
+    }
+
+    /// Overwrites allocation counts of both pools on GPU.
+    ///   - Used to discard nodes and bricks of incomplete levels.
+    pub fn set_pool_counts(&mut self, gpu: &gpu::Context, counts: PoolCounts) {
+        self.node_pool.set_count(gpu, counts.nodes);
+        self.brick_pool.set_count(gpu, counts.bricks);
+        self.brick_pool.set_free_count(gpu, counts.free_bricks);
+    }
+
+    /// Discards levels starting at `level_index` and releases their bricks into the free list of the brick pool.
+    ///   - Nodes of the first discarded level stay allocated (they are children of the new bottom level),
+    ///     so SVO ends up in the same state as during evaluation and the returned level can be evaluated again.
+    ///   - Returns the first discarded level or None if there is no level at `level_index`.
+    #[profiler::function]
+    pub fn release_levels(&mut self, gpu: &gpu::Context, level_index: usize) -> Option<Level> {
+        let first_level = *self.levels.get(level_index)?;
+        let start = first_level.start_index as usize;
+        let count = self.evaluated_node_count() as usize - start;
+
+        let headers = gpu::Buffer::<u32>::static_read_range(
+            self.node_pool.header_buffer(),
+            gpu,
+            start,
+            count,
+        );
+        let payloads = gpu::Buffer::<u32>::static_read_range(
+            self.node_pool.payload_buffer(),
+            gpu,
+            start,
+            count,
+        );
+        let released: Vec<u32> = headers
+            .iter()
+            .zip(payloads.iter())
+            .filter_map(|(header, payload)| node_brick_index(*header, *payload))
+            .collect();
+        self.brick_pool.release(gpu, &released);
+
+        self.levels.truncate(level_index);
+        self.levels_changed();
+        self.node_pool
+            .set_count(gpu, first_level.start_index + first_level.node_count);
+        Some(first_level)
+    }
+
+    /// Turns subdivided nodes of the bottom level into leaves keeping their bricks.
+    ///   - Used when children of the bottom level were discarded, so no node points past the evaluated nodes.
+    #[profiler::function]
+    pub fn make_bottom_level_leaves(&mut self, gpu: &gpu::Context) {
+        let Some(level) = self.levels.last().copied() else {
+            return;
+        };
+        let mut headers = gpu::Buffer::<u32>::static_read_range(
+            self.node_pool.header_buffer(),
+            gpu,
+            level.start_index as usize,
+            level.node_count as usize,
+        );
+        let mut changed = false;
+        for header in headers.iter_mut() {
+            if *header & HEADER_SUBDIVIDED_FLAG != 0 {
+                *header &= !(HEADER_SUBDIVIDED_FLAG | HEADER_TILE_INDEX_MASK);
+                changed = true;
+            }
+        }
+        if !changed {
+            return;
+        }
+        gpu.queue.write_buffer(
+            self.node_pool.header_buffer(),
+            level.start_index as u64 * std::mem::size_of::<u32>() as u64,
+            bytemuck::cast_slice(&headers),
+        );
+        self.levels_changed();
+    }
+
+    /// Compacts the brick pool by moving bricks from its end into holes left by released bricks.
+    ///   - Payloads of nodes pointing to moved bricks are patched.
+    ///   - Returns false when there was nothing to compact.
+    #[profiler::function]
+    pub fn defragment_bricks(&mut self, gpu: &gpu::Context) -> bool {
+        let mut allocator = self.brick_pool.load_allocator(gpu);
+        if allocator.free.is_empty() {
+            return false;
+        }
+        let moves: HashMap<u32, u32> = allocator.compact().into_iter().collect();
+
+        let node_count = self.evaluated_node_count() as usize;
+        let headers =
+            gpu::Buffer::<u32>::static_read_range(self.node_pool.header_buffer(), gpu, 0, node_count);
+        let mut payloads =
+            gpu::Buffer::<u32>::static_read_range(self.node_pool.payload_buffer(), gpu, 0, node_count);
+        for (header, payload) in headers.iter().zip(payloads.iter_mut()) {
+            let new_index = node_brick_index(*header, *payload).and_then(|index| moves.get(&index));
+            if let Some(new_index) = new_index {
+                *payload = brick_payload(BrickPool::brick_index_to_coords(*new_index));
+            }
+        }
+        gpu.queue.write_buffer(
+            self.node_pool.payload_buffer(),
+            0,
+            bytemuck::cast_slice(&payloads),
+        );
+
+        let moves: Vec<(u32, u32)> = moves.into_iter().collect();
+        self.brick_pool.repack(gpu, allocator.count, &moves);
+        self.resources_version = next_resources_version();
+        true
+    }
+
+    /// Measures error of surface distances which quantizing this SVO into `DistanceFormat::Unorm8` would introduce.
+    ///   - Every brick is given the range the evaluation kernel would store for its distances, then every voxel
+    ///     near the surface (closer than its bounding sphere radius) is quantized and decoded on CPU and compared
+    ///     with the stored floating point distance.
+    #[profiler::function]
+    pub fn quantization_error(
+        &mut self,
+        gpu: &gpu::Context,
+    ) -> Result<QuantizationErrorReport, String> {
+        let distance_format = self.brick_pool.format().voxel_format.distance;
+        if distance_format == DistanceFormat::Unorm8 {
+            return Err("SVO distances are already quantized".to_string());
+        }
+
+        let node_count = self.evaluated_node_count() as usize;
+        let headers =
+            gpu::Buffer::<u32>::static_read_range(self.node_pool.header_buffer(), gpu, 0, node_count);
+        let payloads =
+            gpu::Buffer::<u32>::static_read_range(self.node_pool.payload_buffer(), gpu, 0, node_count);
+        let vertices = gpu::Buffer::<glam::Vec4>::static_read_range(
+            self.node_pool.vertex_buffer(),
+            gpu,
+            0,
+            node_count,
+        );
+        let brick_count = self.brick_pool.load_count(gpu);
+        let atlas = self.brick_pool.read_distances(gpu, brick_count)?;
+
+        let padding = self.brick_pool.format().padding;
+        let voxels_per_brick = atlas.voxels_per_brick;
+        let mut report = QuantizationErrorReport::default();
+        let mut error_sum = 0.0f64;
+        for ((header, payload), vertex) in headers.iter().zip(payloads.iter()).zip(vertices.iter()) {
+            let Some(brick_index) = node_brick_index(*header, *payload) else {
+                continue;
+            };
+            let brick_coords = BrickPool::brick_index_to_coords(brick_index);
+            let voxel_size = vertex.w * self.domain.size / BrickPoolFormat::BRICK_SIZE as f32;
+            let surface_band = voxel_size * 3f32.sqrt();
+
+            // Kernel derives the range from all voxels of the brick including padding
+            let mut distances = vec![];
+            for z in 0..voxels_per_brick {
+                for y in 0..voxels_per_brick {
+                    for x in 0..voxels_per_brick {
+                        let voxel = glam::UVec3::new(x, y, z);
+                        if let Some(distance) = atlas.brick_voxel(brick_coords, voxel) {
+                            distances.push((voxel, distance));
+                        }
+                    }
+                }
+            }
+            let (min, max) = distances
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(min, max), (_, distance)| {
+                    (min.min(*distance), max.max(*distance))
+                });
+            let range = DistanceRange::of_brick(DistanceFormat::Unorm8, min, max, voxel_size);
+
+            for (voxel, distance) in distances {
+                let inner = voxel.cmpge(glam::UVec3::splat(padding)).all()
+                    && voxel
+                        .cmplt(glam::UVec3::splat(padding + BrickPoolFormat::BRICK_SIZE))
+                        .all();
+                if !inner || distance.abs() > surface_band {
+                    continue;
+                }
+                let texel = DistanceFormat::Unorm8.encode_texel(distance, &range);
+                let quantized = DistanceFormat::Unorm8.decode_texel(&texel, &range);
+                let error = (quantized - distance).abs();
+                report.sample_count += 1;
+                report.max_error = report.max_error.max(error);
+                report.max_error_in_voxels = report.max_error_in_voxels.max(error / voxel_size);
+                error_sum += error as f64;
+            }
+        }
+        if report.sample_count > 0 {
+            report.mean_error = (error_sum / report.sample_count as f64) as f32;
+        }
+        Ok(report)
+    }
+
+    /// Size of a voxel in bricks of nodes on given level.
+    pub fn level_voxel_size(domain: &BoundingCube, level_index: usize) -> f32 {
+        // First level are children of the root node, hence its nodes are half the size of the domain and there are 8 voxels in node.
+        domain.size * 0.5f32.powi(level_index as i32 + 1) / BrickPoolFormat::BRICK_SIZE as f32
+    }
+
+    /// Estimates how many levels an SVO of given domain will have when evaluated until its voxels are smaller than `min_voxel_size`.
+    ///   - It is an upper bound, evaluation might end earlier when no node on the level intersects the surface.
+    pub fn expected_level_count(domain: &BoundingCube, min_voxel_size: f32) -> u32 {
+        let mut level_count = 1;
+        while Self::level_voxel_size(domain, level_count - 1) > min_voxel_size {
+            level_count += 1;
         }
+        level_count as u32
     }
 }
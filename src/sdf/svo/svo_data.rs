@@ -0,0 +1,659 @@
+use std::{io::Write, path::Path};
+
+use strum::IntoEnumIterator;
+
+use super::{
+    BrickAllocator, BrickPoolFormat, BrickVoxelFormat, Capacity, ColorFormat, DistanceFormat,
+    DistanceRange, Level, Svo, SvoStatistics,
+};
+use crate::framework::{
+    binary_writer::{create_file, write_f32s, write_u32s},
+    gpu,
+    math::BoundingCube,
+};
+
+/// A complete copy of an evaluated SVO in CPU memory.
+///   - It can be stored into a binary file and uploaded into a new SVO without evaluation.
+#[derive(Debug, Clone)]
+pub struct SvoData {
+    pub voxel_format: BrickVoxelFormat,
+    pub padding: u32,
+    pub domain: BoundingCube,
+    pub levels: Vec<Level>,
+
+    pub node_headers: Vec<u32>,
+    pub node_payloads: Vec<u32>,
+    pub node_vertices: Vec<glam::Vec4>,
+
+    /// Number of allocated bricks including released ones.
+    pub brick_count: u32,
+
+    /// Indices of released bricks waiting to be reused.
+    pub free_bricks: Vec<u32>,
+
+    /// Edge size in voxels of the cubical atlas corner containing all allocated bricks.
+    pub atlas_edge: u32,
+
+    /// Tightly packed texels of the distance atlas corner, x coordinate changes fastest.
+    pub distance_atlas: Vec<u8>,
+
+    /// Tightly packed texels of the color atlas corner, x coordinate changes fastest.
+    pub color_atlas: Vec<u8>,
+
+    /// Tightly packed `DistanceRange` texels of the distance range atlas corner, one texel per brick.
+    pub distance_ranges: Vec<u8>,
+}
+
+impl SvoData {
+    /// Identifies SVO files.
+    const MAGIC: &'static [u8; 8] = b"SDFSVO\0\0";
+
+    /// Version of the binary format, files of other versions are rejected.
+    ///   - Has to be increased whenever the format or layout of nodes and bricks changes.
+    pub const VERSION: u32 = 4;
+
+    /// Stores the SVO data into a binary file.
+    ///   - Numbers are stored in little endian byte order.
+    #[profiler::function]
+    pub fn store<P: AsRef<Path>>(&self, file_name: P) -> Result<(), String> {
+        let mut writer = create_file(file_name)?;
+        let mut write = || -> std::io::Result<()> {
+            writer.write_all(Self::MAGIC)?;
+            write_u32s(&mut writer, &[Self::VERSION])?;
+
+            write_str(&mut writer, self.voxel_format.distance.as_ref())?;
+            write_str(&mut writer, self.voxel_format.color.as_ref())?;
+            write_u32s(&mut writer, &[self.padding])?;
+            write_f32s(&mut writer, &self.domain.pos.to_array())?;
+            write_f32s(&mut writer, &[self.domain.size])?;
+
+            write_u32s(&mut writer, &[self.levels.len() as u32])?;
+            for level in &self.levels {
+                write_u32s(&mut writer, &[level.start_index, level.node_count])?;
+            }
+
+            write_u32s(&mut writer, &[self.node_headers.len() as u32])?;
+            write_u32s(&mut writer, &self.node_headers)?;
+            write_u32s(&mut writer, &self.node_payloads)?;
+            for vertex in &self.node_vertices {
+                write_f32s(&mut writer, &vertex.to_array())?;
+            }
+
+            write_u32s(&mut writer, &[self.brick_count, self.free_bricks.len() as u32])?;
+            write_u32s(&mut writer, &self.free_bricks)?;
+            write_u32s(&mut writer, &[self.atlas_edge])?;
+            writer.write_all(&self.distance_atlas)?;
+            writer.write_all(&self.color_atlas)?;
+            writer.write_all(&self.distance_ranges)?;
+            writer.flush()
+        };
+        write().map_err(|err| format!("Failed to write to file: {}", err))
+    }
+
+    /// Loads SVO data from a binary file created by `store`.
+    #[profiler::function]
+    pub fn load<P: AsRef<Path>>(file_name: P) -> Result<Self, String> {
+        let bytes = match std::fs::read(file_name) {
+            Ok(bytes) => bytes,
+            Err(err) => return Err(format!("Failed to read file: {}", err)),
+        };
+        let mut reader = Reader { bytes: &bytes };
+
+        if reader.take(Self::MAGIC.len())? != Self::MAGIC {
+            return Err("Not an SVO file".to_string());
+        }
+        let version = reader.u32()?;
+        if version != Self::VERSION {
+            return Err(format!(
+                "Unsupported SVO file version {}, expected {}",
+                version,
+                Self::VERSION
+            ));
+        }
+
+        let distance = reader.str()?;
+        let distance = DistanceFormat::iter()
+            .find(|format| format.as_ref() == distance)
+            .ok_or_else(|| format!("Unknown distance format: {}", distance))?;
+        let color = reader.str()?;
+        let color = ColorFormat::iter()
+            .find(|format| format.as_ref() == color)
+            .ok_or_else(|| format!("Unknown color format: {}", color))?;
+        let voxel_format = BrickVoxelFormat { distance, color };
+        let padding = reader.u32()?;
+        let pos = reader.f32s(3)?;
+        let size = reader.f32s(1)?[0];
+        let domain = BoundingCube {
+            pos: glam::Vec3::from_slice(&pos),
+            size,
+        };
+
+        let level_count = reader.u32()? as usize;
+        let mut levels = Vec::with_capacity(level_count.min(64));
+        for _ in 0..level_count {
+            levels.push(Level {
+                start_index: reader.u32()?,
+                node_count: reader.u32()?,
+            });
+        }
+
+        let node_count = reader.u32()? as usize;
+        let node_headers = reader.u32s(node_count)?;
+        let node_payloads = reader.u32s(node_count)?;
+        let node_vertices = reader
+            .f32s(node_count * 4)?
+            .chunks_exact(4)
+            .map(glam::Vec4::from_slice)
+            .collect();
+
+        let brick_count = reader.u32()?;
+        let free_count = reader.u32()? as usize;
+        let free_bricks = reader.u32s(free_count)?;
+        let atlas_edge = reader.u32()?;
+        let distance_atlas = reader
+            .take(atlas_bytes(atlas_edge, voxel_format.distance.bytes()).ok_or(ATLAS_TOO_LARGE)?)?
+            .to_vec();
+        let color_atlas = reader
+            .take(atlas_bytes(atlas_edge, voxel_format.color.bytes()).ok_or(ATLAS_TOO_LARGE)?)?
+            .to_vec();
+        let voxels_per_brick = BrickPoolFormat {
+            voxel_format,
+            padding,
+        }
+        .voxels_per_brick_in_one_dimension();
+        let range_edge = atlas_edge / voxels_per_brick;
+        let distance_ranges = reader
+            .take(atlas_bytes(range_edge, DistanceRange::BYTES).ok_or(ATLAS_TOO_LARGE)?)?
+            .to_vec();
+
+        Ok(Self {
+            voxel_format,
+            padding,
+            domain,
+            levels,
+            node_headers,
+            node_payloads,
+            node_vertices,
+            brick_count,
+            free_bricks,
+            atlas_edge,
+            distance_atlas,
+            color_atlas,
+            distance_ranges,
+        })
+    }
+
+    /// Checks that levels, nodes, bricks and atlases are consistent with each other.
+    ///   - Data loaded from a file can be corrupted, so all sizes are checked before they are used for indexing.
+    ///   - Links of nodes to children and bricks are checked by `SvoStatistics::from_nodes`.
+    pub fn validate(&self) -> Result<(), String> {
+        let node_count = self.node_headers.len();
+        if self.node_payloads.len() != node_count || self.node_vertices.len() != node_count {
+            return Err("Node buffers have different lengths".to_string());
+        }
+        let mut evaluated_node_count = 0u32;
+        for level in &self.levels {
+            if level.start_index != evaluated_node_count {
+                return Err("Levels do not follow each other".to_string());
+            }
+            evaluated_node_count = level
+                .start_index
+                .checked_add(level.node_count)
+                .ok_or("Level node count overflows node indices")?;
+        }
+        if evaluated_node_count as usize != node_count {
+            return Err("Levels do not match number of nodes".to_string());
+        }
+
+        let mut free_bricks = self.free_bricks.clone();
+        free_bricks.sort_unstable();
+        free_bricks.dedup();
+        if free_bricks.len() != self.free_bricks.len()
+            || free_bricks.last().is_some_and(|index| *index >= self.brick_count)
+        {
+            return Err("Released bricks are not unique allocated bricks".to_string());
+        }
+
+        // Links of nodes are checked before upload, children pointing back to earlier levels would make traversals loop
+        let allocator = BrickAllocator {
+            count: self.brick_count,
+            free: self.free_bricks.clone(),
+        };
+        let statistics = SvoStatistics::from_nodes(
+            &self.domain,
+            &self.levels,
+            &self.node_headers,
+            &self.node_payloads,
+            &allocator,
+        );
+        if let Some(error) = statistics.errors.first() {
+            return Err(format!("Invalid nodes: {}", error));
+        }
+
+        let format = BrickPoolFormat {
+            voxel_format: self.voxel_format,
+            padding: self.padding,
+        };
+        if format.padding != BrickPoolFormat::default().padding {
+            return Err(format!("Unsupported brick padding {}", format.padding));
+        }
+        // Atlas corner has to be the smallest cube of bricks fitting all of them
+        let voxels_per_brick = format.voxels_per_brick_in_one_dimension();
+        let side_size = self.atlas_edge / voxels_per_brick;
+        let fits = |side_size: u32| {
+            side_size
+                .checked_pow(3)
+                .is_none_or(|capacity| capacity >= self.brick_count)
+        };
+        if !self.atlas_edge.is_multiple_of(voxels_per_brick)
+            || !fits(side_size)
+            || (side_size > 0 && fits(side_size - 1))
+        {
+            return Err("Atlas size does not match number of bricks".to_string());
+        }
+        let distance_bytes = atlas_bytes(self.atlas_edge, self.voxel_format.distance.bytes());
+        let color_bytes = atlas_bytes(self.atlas_edge, self.voxel_format.color.bytes());
+        let range_bytes = atlas_bytes(side_size, DistanceRange::BYTES);
+        if distance_bytes != Some(self.distance_atlas.len())
+            || color_bytes != Some(self.color_atlas.len())
+            || range_bytes != Some(self.distance_ranges.len())
+        {
+            return Err("Atlas data does not match atlas size".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Svo {
+    /// Reads all evaluated nodes, used bricks and levels of the SVO back to CPU.
+    #[profiler::function]
+    pub fn read_back(&mut self, gpu: &gpu::Context) -> SvoData {
+        let node_count = self.evaluated_node_count() as usize;
+        let node_headers =
+            gpu::Buffer::<u32>::static_read_range(self.node_pool.header_buffer(), gpu, 0, node_count);
+        let node_payloads =
+            gpu::Buffer::<u32>::static_read_range(self.node_pool.payload_buffer(), gpu, 0, node_count);
+        let node_vertices = gpu::Buffer::<glam::Vec4>::static_read_range(
+            self.node_pool.vertex_buffer(),
+            gpu,
+            0,
+            node_count,
+        );
+
+        let allocator = self.brick_pool.load_allocator(gpu);
+        let (atlas_edge, distance_atlas, color_atlas, distance_ranges) =
+            self.brick_pool.read_atlases(gpu, allocator.count);
+
+        SvoData {
+            voxel_format: self.brick_pool.format().voxel_format,
+            padding: self.brick_pool.format().padding,
+            domain: self.domain,
+            levels: self.levels.clone(),
+            node_headers,
+            node_payloads,
+            node_vertices,
+            brick_count: allocator.count,
+            free_bricks: allocator.free,
+            atlas_edge,
+            distance_atlas,
+            color_atlas,
+            distance_ranges,
+        }
+    }
+
+    /// Creates a new SVO on GPU from data read back by `read_back`, no evaluation is needed.
+    ///   - Fails when the data is inconsistent or its brick format is not supported by the GPU.
+    #[profiler::function]
+    pub fn from_data(label: String, gpu: &gpu::Context, data: &SvoData) -> Result<Self, String> {
+        if !data.voxel_format.is_supported(gpu) {
+            return Err(format!(
+                "Brick voxel format {:?} is not supported by the GPU",
+                data.voxel_format
+            ));
+        }
+        data.validate()?;
+        let node_count = data.node_headers.len();
+
+        let mut svo = Svo::new(
+            label,
+            gpu,
+            Capacity::Nodes(node_count.max(1) as u32),
+            data.voxel_format,
+        );
+        if !svo.grow(gpu, node_count as u32, data.brick_count) {
+            return Err("SVO does not fit into GPU limits".to_string());
+        }
+
+        svo.node_pool.upload(
+            gpu,
+            &data.node_headers,
+            &data.node_payloads,
+            &data.node_vertices,
+        );
+        svo.brick_pool.write_atlases(
+            gpu,
+            data.brick_count,
+            data.atlas_edge,
+            &data.distance_atlas,
+            &data.color_atlas,
+            &data.distance_ranges,
+        );
+        svo.brick_pool.release(gpu, &data.free_bricks);
+        svo.levels = data.levels.clone();
+        svo.levels_changed();
+        svo.domain = data.domain;
+        Ok(svo)
+    }
+}
+
+const ATLAS_TOO_LARGE: &str = "Atlas size overflows memory size";
+
+/// Number of bytes of a tightly packed cubical atlas region, None when it does not fit into memory.
+fn atlas_bytes(edge: u32, texel_bytes: u32) -> Option<usize> {
+    (edge as usize)
+        .checked_pow(3)?
+        .checked_mul(texel_bytes as usize)
+}
+
+fn write_str<W: Write>(writer: &mut W, value: &str) -> std::io::Result<()> {
+    write_u32s(writer, &[value.len() as u32])?;
+    writer.write_all(value.as_bytes())
+}
+
+/// Reads values from a byte slice in the order they were written, failing at unexpected end of file.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if count > self.bytes.len() {
+            return Err("Unexpected end of SVO file".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u32s(&mut self, count: usize) -> Result<Vec<u32>, String> {
+        Ok(self
+            .take(count.saturating_mul(4))?
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect())
+    }
+
+    fn f32s(&mut self, count: usize) -> Result<Vec<f32>, String> {
+        Ok(self
+            .u32s(count)?
+            .into_iter()
+            .map(f32::from_bits)
+            .collect())
+    }
+
+    fn str(&mut self) -> Result<String, String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| "Invalid string in SVO file".to_string())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::sdf::svo::{
+        brick_payload, node_brick_index, BrickPool, HEADER_HAS_BRICK_FLAG, HEADER_SUBDIVIDED_FLAG,
+        PAYLOAD_FILLED,
+    };
+
+    /// Builds an SVO on CPU from a distance function, so CPU tools reading SVOs can be tested without GPU.
+    ///   - Nodes near the surface get a brick and are subdivided up to `max_level` where `subdivide` returns true.
+    pub(crate) fn svo_data_from_distance_function(
+        domain: BoundingCube,
+        max_level: usize,
+        subdivide: impl Fn(glam::Vec3, f32) -> bool,
+        distance: impl Fn(glam::Vec3) -> f32,
+    ) -> SvoData {
+        let format = BrickPoolFormat::default();
+        let mut data = SvoData {
+            voxel_format: BrickVoxelFormat {
+                distance: DistanceFormat::F32,
+                color: ColorFormat::Unorm8,
+            },
+            padding: format.padding,
+            domain,
+            levels: vec![],
+            node_headers: vec![],
+            node_payloads: vec![],
+            node_vertices: vec![],
+            // Root brick is not linked to any node, so it is left empty
+            brick_count: 1,
+            free_bricks: vec![],
+            atlas_edge: 0,
+            distance_atlas: vec![],
+            color_atlas: vec![],
+            distance_ranges: vec![],
+        };
+
+        // Nodes as (center, size), root children are the first level
+        let mut level_nodes: Vec<(glam::Vec3, f32)> = (0..8)
+            .map(|child| (child, domain.pos, domain.size))
+            .map(child_node)
+            .collect();
+        let mut bricks: Vec<(glam::Vec3, f32)> = vec![];
+        for level_index in 0..=max_level {
+            let start_index = data.node_headers.len() as u32;
+            let node_count = level_nodes.len() as u32;
+            data.levels.push(Level {
+                start_index,
+                node_count,
+            });
+
+            let mut next_level_nodes = vec![];
+            for (center, size) in level_nodes {
+                let center_distance = distance(center);
+                let near_surface = center_distance.abs() <= size * 0.87;
+                let mut header = 0;
+                let mut payload = if center_distance < 0.0 {
+                    PAYLOAD_FILLED
+                } else {
+                    0
+                };
+                if near_surface {
+                    header |= HEADER_HAS_BRICK_FLAG;
+                    payload = brick_payload(BrickPool::brick_index_to_coords(data.brick_count));
+                    data.brick_count += 1;
+                    bricks.push((center, size));
+                }
+                if near_surface && level_index < max_level && subdivide(center, size) {
+                    let first_child = start_index + node_count + next_level_nodes.len() as u32;
+                    header |= HEADER_SUBDIVIDED_FLAG | (first_child >> 3);
+                    next_level_nodes.extend((0..8).map(|child| child_node((child, center, size))));
+                }
+                data.node_headers.push(header);
+                data.node_payloads.push(payload);
+                data.node_vertices
+                    .push(((center - domain.pos) / domain.size).extend(size / domain.size));
+            }
+            level_nodes = next_level_nodes;
+            if level_nodes.is_empty() {
+                break;
+            }
+        }
+
+        // Smallest cubical atlas corner fitting all bricks
+        let voxels_per_brick = format.voxels_per_brick_in_one_dimension();
+        let mut side = 1;
+        while side * side * side < data.brick_count {
+            side += 1;
+        }
+        data.atlas_edge = side * voxels_per_brick;
+        let texel_count = (data.atlas_edge as usize).pow(3);
+        data.distance_atlas = vec![0; texel_count * 4];
+        data.color_atlas = vec![255; texel_count * 4];
+        // Distances are stored as floats, so all bricks keep the identity range
+        data.distance_ranges =
+            bytemuck::cast_slice(&[DistanceRange::IDENTITY]).repeat((side as usize).pow(3));
+        for (brick_index, (center, size)) in bricks.into_iter().enumerate() {
+            let coords =
+                BrickPool::brick_index_to_coords(brick_index as u32 + 1) * voxels_per_brick;
+            let voxel_size = size / BrickPoolFormat::BRICK_SIZE as f32;
+            let min = center - size * 0.5;
+            for z in 0..voxels_per_brick {
+                for y in 0..voxels_per_brick {
+                    for x in 0..voxels_per_brick {
+                        let voxel = glam::UVec3::new(x, y, z);
+                        let position =
+                            min + (voxel.as_vec3() - data.padding as f32 + 0.5) * voxel_size;
+                        let texel = coords + voxel;
+                        let edge = data.atlas_edge as usize;
+                        let index =
+                            texel.x as usize + edge * (texel.y as usize + edge * texel.z as usize);
+                        data.distance_atlas[index * 4..index * 4 + 4]
+                            .copy_from_slice(&distance(position).to_le_bytes());
+                    }
+                }
+            }
+        }
+        data
+    }
+
+    /// Radius of the sphere stored by `sphere_svo_data`.
+    pub(crate) const SPHERE_SVO_RADIUS: f32 = 0.3;
+
+    /// SVO of a sphere centered in the unit domain, with all nodes near its surface subdivided up to level 2.
+    pub(crate) fn sphere_svo_data() -> SvoData {
+        svo_data_from_distance_function(
+            BoundingCube::UNIT,
+            2,
+            |_, _| true,
+            |position| position.length() - SPHERE_SVO_RADIUS,
+        )
+    }
+
+    /// Center and size of a child of a node given as (child index, center, size).
+    fn child_node((child, center, size): (u32, glam::Vec3, f32)) -> (glam::Vec3, f32) {
+        let offset =
+            glam::UVec3::new(child & 1, (child >> 1) & 1, (child >> 2) & 1).as_vec3() - 0.5;
+        (center + offset * size * 0.5, size * 0.5)
+    }
+
+    #[test]
+    fn stored_data_loads_unchanged() {
+        let mut data = sphere_svo_data();
+        // Release the last brick, its node has to be unlinked first
+        let released = data.brick_count - 1;
+        let node_index = (0..data.node_headers.len())
+            .find(|index| {
+                node_brick_index(data.node_headers[*index], data.node_payloads[*index])
+                    == Some(released)
+            })
+            .unwrap();
+        data.node_headers[node_index] &= !HEADER_HAS_BRICK_FLAG;
+        data.free_bricks = vec![released];
+        data.validate().unwrap();
+
+        let path = std::env::temp_dir().join(format!("svo_data_test_{}.svo", std::process::id()));
+        data.store(&path).unwrap();
+        let loaded = SvoData::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        loaded.validate().unwrap();
+        assert_eq!(loaded.levels.len(), data.levels.len());
+        for (loaded_level, level) in loaded.levels.iter().zip(&data.levels) {
+            assert_eq!(loaded_level.start_index, level.start_index);
+            assert_eq!(loaded_level.node_count, level.node_count);
+        }
+        assert_eq!(loaded.node_headers, data.node_headers);
+        assert_eq!(loaded.node_payloads, data.node_payloads);
+        assert_eq!(loaded.node_vertices, data.node_vertices);
+        assert_eq!(loaded.brick_count, data.brick_count);
+        assert_eq!(loaded.free_bricks, data.free_bricks);
+        assert_eq!(loaded.atlas_edge, data.atlas_edge);
+        assert_eq!(loaded.distance_atlas, data.distance_atlas);
+        assert_eq!(loaded.color_atlas, data.color_atlas);
+        assert_eq!(loaded.distance_ranges, data.distance_ranges);
+    }
+
+    #[test]
+    fn truncated_file_is_rejected() {
+        let path = std::env::temp_dir().join(format!(
+            "svo_data_truncated_test_{}.svo",
+            std::process::id()
+        ));
+        sphere_svo_data().store(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        let loaded = SvoData::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.is_err());
+    }
+
+    #[test]
+    fn overflowing_levels_are_rejected() {
+        let mut data = sphere_svo_data();
+        data.levels.last_mut().unwrap().node_count = u32::MAX;
+        assert!(data.validate().is_err());
+
+        let mut data = sphere_svo_data();
+        data.levels[1].start_index += 1;
+        assert!(data.validate().is_err());
+    }
+
+    #[test]
+    fn nodes_with_invalid_links_are_rejected() {
+        // Children pointing back to the first tile would make traversals loop forever
+        let mut data = sphere_svo_data();
+        let node_index = data.levels[1].start_index as usize;
+        data.node_headers[node_index] = HEADER_SUBDIVIDED_FLAG;
+        assert!(data.validate().is_err());
+
+        let mut data = sphere_svo_data();
+        let node_index = data.levels.last().unwrap().start_index as usize;
+        data.node_headers[node_index] = HEADER_SUBDIVIDED_FLAG;
+        assert!(data.validate().is_err());
+
+        let mut data = sphere_svo_data();
+        let node_index = data.levels[1].start_index as usize;
+        data.node_headers[node_index] |= HEADER_HAS_BRICK_FLAG;
+        data.node_payloads[node_index] =
+            brick_payload(BrickPool::brick_index_to_coords(data.brick_count));
+        assert!(data.validate().is_err());
+    }
+
+    #[test]
+    fn mismatched_atlas_is_rejected() {
+        let mut data = sphere_svo_data();
+        data.brick_count = u32::MAX;
+        assert!(data.validate().is_err());
+
+        let mut data = sphere_svo_data();
+        data.atlas_edge = u32::MAX - u32::MAX % data.atlas_edge;
+        assert!(data.validate().is_err());
+
+        let mut data = sphere_svo_data();
+        data.distance_atlas.pop();
+        assert!(data.validate().is_err());
+
+        let mut data = sphere_svo_data();
+        data.distance_ranges.pop();
+        assert!(data.validate().is_err());
+    }
+
+    #[test]
+    fn invalid_free_bricks_are_rejected() {
+        let mut data = sphere_svo_data();
+        data.free_bricks = vec![data.brick_count];
+        assert!(data.validate().is_err());
+
+        let mut data = sphere_svo_data();
+        data.free_bricks = vec![1, 1];
+        assert!(data.validate().is_err());
+    }
+}
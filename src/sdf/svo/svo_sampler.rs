@@ -0,0 +1,396 @@
+use super::{
+    brick_pool::f16_to_f32, node_brick_index, BrickPool, BrickPoolFormat, ColorFormat,
+    DistanceRange, SvoData, HEADER_SUBDIVIDED_FLAG, HEADER_TILE_INDEX_MASK, PAYLOAD_FILLED,
+};
+
+/// A node of an SVO which has a brick and no evaluated children, so its brick holds the most detailed samples of its region.
+#[derive(Clone, Copy, Debug)]
+pub struct LeafBrick {
+    /// Index of the SVO level of the node.
+    pub level: usize,
+    /// Minimal corner of the node in world space.
+    pub min: glam::Vec3,
+    /// Size of the node in world space.
+    pub size: f32,
+    /// Coordinates of the brick in the brick atlas.
+    pub brick_coords: glam::UVec3,
+}
+
+impl LeafBrick {
+    pub fn voxel_size(&self) -> f32 {
+        self.size / BrickPoolFormat::BRICK_SIZE as f32
+    }
+}
+
+/// A distance and color sampled from an SVO.
+#[derive(Clone, Copy, Debug)]
+pub struct SvoSample {
+    pub distance: f32,
+    pub color: glam::Vec4,
+}
+
+/// Samples distances and colors of an SVO read back to CPU, the same way renderer samples brick atlas on GPU.
+///   - Samples are trilinearly interpolated between voxels of the most detailed brick containing the position.
+pub struct SvoSampler<'a> {
+    data: &'a SvoData,
+    leaves: Vec<LeafBrick>,
+}
+
+impl<'a> SvoSampler<'a> {
+    pub fn new(data: &'a SvoData) -> Self {
+        let mut sampler = Self {
+            data,
+            leaves: vec![],
+        };
+        for (level_index, level) in data.levels.iter().enumerate() {
+            for node_index in level.start_index..level.start_index + level.node_count {
+                if sampler.brick(node_index).is_some() && sampler.children(node_index).is_none() {
+                    sampler
+                        .leaves
+                        .push(sampler.leaf_brick(node_index, level_index));
+                }
+            }
+        }
+        sampler
+    }
+
+    /// Nodes whose bricks together cover the surface of the SVO.
+    pub fn leaf_bricks(&self) -> &[LeafBrick] {
+        &self.leaves
+    }
+
+    /// Size of voxels in the most detailed leaf bricks.
+    pub fn min_voxel_size(&self) -> Option<f32> {
+        self.leaves
+            .iter()
+            .map(|leaf| leaf.voxel_size())
+            .min_by(|a, b| a.total_cmp(b))
+    }
+
+    /// Minimal corner of the SVO domain in world space.
+    pub fn domain_min(&self) -> glam::Vec3 {
+        self.data.domain.pos - self.data.domain.size * 0.5
+    }
+
+    /// Size of the SVO domain in world space.
+    pub fn domain_size(&self) -> f32 {
+        self.data.domain.size
+    }
+
+    /// Distance returned for positions whose node is not read back, no distance inside the domain is larger.
+    pub fn outside_distance(&self) -> f32 {
+        self.data.domain.size * 3f32.sqrt()
+    }
+
+    /// Samples distance and color at any position, so exports can sample grids overlapping the SVO domain.
+    ///   - Positions outside of the domain are clamped to its boundary and the distance to it is added.
+    ///   - Positions whose node is not read back are outside with `outside_distance` and transparent color.
+    pub fn sample_clamped(&self, position: glam::Vec3) -> SvoSample {
+        let domain_min = self.domain_min();
+        let clamped = position.clamp(domain_min, domain_min + self.domain_size());
+        match self.sample(clamped) {
+            Some(sample) => SvoSample {
+                distance: sample.distance + position.distance(clamped),
+                color: sample.color,
+            },
+            None => SvoSample {
+                distance: self.outside_distance(),
+                color: glam::Vec4::ZERO,
+            },
+        }
+    }
+
+    /// Samples distance and color at given position from the most detailed node containing it.
+    ///   - Nodes without brick are far from the surface, they return distance of their voxel size
+    ///     with sign telling whether they are inside or outside, and transparent color.
+    ///     Evaluator gives a brick to every node with a voxel center closer than `sqrt(3) / 0.9` voxels
+    ///     to the surface, so the voxel size is a lower bound of the distance anywhere in such node.
+    ///   - Returns None for positions outside of the SVO domain.
+    pub fn sample(&self, position: glam::Vec3) -> Option<SvoSample> {
+        // Tolerate rounding errors of positions on the domain boundary
+        let half_size = self.data.domain.size * 0.5 * (1.0 + 1e-5);
+        if (position - self.data.domain.pos).abs().max_element() > half_size {
+            return None;
+        }
+
+        // Root node is not stored in node pool, its children are the first tile
+        let mut nodes = 0..8u32;
+        let mut level = 0;
+        loop {
+            if nodes.end as usize > self.data.node_headers.len() {
+                return None;
+            }
+            let node_index = self.closest_node(nodes, position);
+            let leaf = self.leaf_brick(node_index, level);
+            if let Some(children) = self.children(node_index) {
+                nodes = children;
+                level += 1;
+                continue;
+            }
+            if self.brick(node_index).is_some() {
+                return Some(self.sample_brick(&leaf, position));
+            }
+            let inside = self.data.node_payloads[node_index as usize] == PAYLOAD_FILLED;
+            return Some(SvoSample {
+                distance: if inside {
+                    -leaf.voxel_size()
+                } else {
+                    leaf.voxel_size()
+                },
+                color: glam::Vec4::ZERO,
+            });
+        }
+    }
+
+    /// Samples distance and color of a brick at given position, positions outside of the brick are clamped into it.
+    pub fn sample_brick(&self, leaf: &LeafBrick, position: glam::Vec3) -> SvoSample {
+        let padding = self.data.padding as f32;
+        let last = (BrickPoolFormat::BRICK_SIZE + 2 * self.data.padding - 1) as f32;
+        // Voxel `padding` has its center half a voxel from the minimal corner of the node
+        let local = ((position - leaf.min) / leaf.voxel_size() + padding - 0.5)
+            .clamp(glam::Vec3::ZERO, glam::Vec3::splat(last));
+        let base = local.floor().min(glam::Vec3::splat(last - 1.0));
+        let t = local - base;
+        let base = base.as_uvec3();
+
+        let mut distance = 0.0;
+        let mut color = glam::Vec4::ZERO;
+        for corner in 0..8u32 {
+            let offset = glam::UVec3::new(corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+            let weight = glam::Vec3::select(offset.cmpeq(glam::UVec3::ONE), t, 1.0 - t);
+            let weight = weight.x * weight.y * weight.z;
+            let voxel = base + offset;
+            distance += weight * self.voxel_distance(leaf, voxel);
+            color += weight * self.voxel_color(leaf, voxel);
+        }
+        SvoSample { distance, color }
+    }
+
+    /// Decoded distance of a voxel of a brick, voxel coordinates include padding.
+    pub fn voxel_distance(&self, leaf: &LeafBrick, voxel: glam::UVec3) -> f32 {
+        let format = self.data.voxel_format.distance;
+        let bytes = self.texel(&self.data.distance_atlas, format.bytes(), leaf, voxel);
+        format.decode_texel(bytes, &self.distance_range(leaf))
+    }
+
+    /// Range of distances stored in a brick.
+    pub fn distance_range(&self, leaf: &LeafBrick) -> DistanceRange {
+        let edge =
+            (self.data.atlas_edge / (BrickPoolFormat::BRICK_SIZE + 2 * self.data.padding)) as usize;
+        let coords = leaf.brick_coords;
+        let index = coords.x as usize + edge * (coords.y as usize + edge * coords.z as usize);
+        let start = index * DistanceRange::BYTES as usize;
+        DistanceRange::from_texel(&self.data.distance_ranges[start..])
+    }
+
+    /// Decoded color of a voxel of a brick, voxel coordinates include padding.
+    pub fn voxel_color(&self, leaf: &LeafBrick, voxel: glam::UVec3) -> glam::Vec4 {
+        let format = self.data.voxel_format.color;
+        let bytes = self.texel(&self.data.color_atlas, format.bytes(), leaf, voxel);
+        match format {
+            ColorFormat::Unorm8 => {
+                glam::Vec4::new(
+                    bytes[0] as f32,
+                    bytes[1] as f32,
+                    bytes[2] as f32,
+                    bytes[3] as f32,
+                ) / 255.0
+            }
+            ColorFormat::F16 => glam::Vec4::new(
+                f16_to_f32(u16::from_le_bytes([bytes[0], bytes[1]])),
+                f16_to_f32(u16::from_le_bytes([bytes[2], bytes[3]])),
+                f16_to_f32(u16::from_le_bytes([bytes[4], bytes[5]])),
+                f16_to_f32(u16::from_le_bytes([bytes[6], bytes[7]])),
+            ),
+        }
+    }
+
+    fn texel<'b>(
+        &self,
+        atlas: &'b [u8],
+        texel_bytes: u32,
+        leaf: &LeafBrick,
+        voxel: glam::UVec3,
+    ) -> &'b [u8] {
+        let voxels_per_brick = BrickPoolFormat::BRICK_SIZE + 2 * self.data.padding;
+        let coords = leaf.brick_coords * voxels_per_brick + voxel;
+        let edge = self.data.atlas_edge as usize;
+        let index = coords.x as usize + edge * (coords.y as usize + edge * coords.z as usize);
+        let start = index * texel_bytes as usize;
+        &atlas[start..start + texel_bytes as usize]
+    }
+
+    fn leaf_brick(&self, node_index: u32, level: usize) -> LeafBrick {
+        let vertex = self.data.node_vertices[node_index as usize];
+        let domain = &self.data.domain;
+        let size = vertex.w * domain.size;
+        LeafBrick {
+            level,
+            min: domain.pos + vertex.truncate() * domain.size - size * 0.5,
+            size,
+            brick_coords: self
+                .brick(node_index)
+                .map_or(glam::UVec3::ZERO, BrickPool::brick_index_to_coords),
+        }
+    }
+
+    /// Node whose center is closest to a position in maximum norm, which is the node containing it.
+    fn closest_node(&self, nodes: std::ops::Range<u32>, position: glam::Vec3) -> u32 {
+        let domain = &self.data.domain;
+        let distance = |node_index: &u32| {
+            let center = self.data.node_vertices[*node_index as usize].truncate();
+            (domain.pos + center * domain.size - position)
+                .abs()
+                .max_element()
+        };
+        nodes
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .unwrap_or(0)
+    }
+
+    /// Index of the brick of a node when it is inside of the read back atlas region.
+    fn brick(&self, node_index: u32) -> Option<u32> {
+        let index = node_index as usize;
+        node_brick_index(
+            self.data.node_headers[index],
+            self.data.node_payloads[index],
+        )
+        .filter(|brick_index| *brick_index < self.data.brick_count)
+    }
+
+    /// Indices of children of a node when they are evaluated.
+    fn children(&self, node_index: u32) -> Option<std::ops::Range<u32>> {
+        let header = self.data.node_headers[node_index as usize];
+        if header & HEADER_SUBDIVIDED_FLAG == 0 {
+            return None;
+        }
+        let first_child = (header & HEADER_TILE_INDEX_MASK) << 3;
+        if first_child as usize + 8 > self.data.node_headers.len() {
+            return None;
+        }
+        Some(first_child..first_child + 8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdf::svo::{sphere_svo_data, DistanceFormat, SPHERE_SVO_RADIUS};
+
+    #[test]
+    fn samples_near_surface_match_distance_function() {
+        let data = sphere_svo_data();
+        let sampler = SvoSampler::new(&data);
+        let voxel_size = sampler.min_voxel_size().unwrap();
+        for direction in [glam::Vec3::X, -glam::Vec3::Y, glam::Vec3::ONE.normalize()] {
+            for offset in [-0.5, 0.0, 0.5] {
+                let position = direction * (SPHERE_SVO_RADIUS + offset * voxel_size);
+                let sample = sampler.sample(position).unwrap();
+                let expected = position.length() - SPHERE_SVO_RADIUS;
+                assert!(
+                    (sample.distance - expected).abs() < voxel_size * 0.1,
+                    "distance {} at {} differs from {}",
+                    sample.distance,
+                    position,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn quantized_bricks_are_decoded_by_their_range() {
+        let data = sphere_svo_data();
+        let sampler = SvoSampler::new(&data);
+        let voxel_size = sampler.min_voxel_size().unwrap();
+
+        // Quantize every brick the way the evaluation kernel does
+        let mut quantized = data.clone();
+        quantized.voxel_format.distance = DistanceFormat::Unorm8;
+        let texel_count = (data.atlas_edge as usize).pow(3);
+        quantized.distance_atlas = vec![0; texel_count];
+        for leaf in sampler.leaf_bricks() {
+            let voxels = BrickPoolFormat::BRICK_SIZE + 2 * data.padding;
+            let coords = (0..voxels.pow(3)).map(|index| {
+                glam::UVec3::new(
+                    index % voxels,
+                    index / voxels % voxels,
+                    index / voxels.pow(2),
+                )
+            });
+            let (min, max) = coords
+                .clone()
+                .fold((f32::MAX, f32::MIN), |(min, max), voxel| {
+                    let distance = sampler.voxel_distance(leaf, voxel);
+                    (min.min(distance), max.max(distance))
+                });
+            let range =
+                DistanceRange::of_brick(DistanceFormat::Unorm8, min, max, leaf.voxel_size());
+            for voxel in coords {
+                let texel = leaf.brick_coords * voxels + voxel;
+                let edge = data.atlas_edge as usize;
+                let index = texel.x as usize + edge * (texel.y as usize + edge * texel.z as usize);
+                quantized.distance_atlas[index] = DistanceFormat::Unorm8
+                    .encode_texel(sampler.voxel_distance(leaf, voxel), &range)[0];
+            }
+            let edge = (data.atlas_edge / voxels) as usize;
+            let coords = leaf.brick_coords;
+            let index = coords.x as usize + edge * (coords.y as usize + edge * coords.z as usize);
+            quantized.distance_ranges[index * 8..index * 8 + 8]
+                .copy_from_slice(bytemuck::bytes_of(&range));
+        }
+        quantized.validate().unwrap();
+
+        let quantized_sampler = SvoSampler::new(&quantized);
+        for direction in [glam::Vec3::X, -glam::Vec3::Z, glam::Vec3::ONE.normalize()] {
+            let position = direction * SPHERE_SVO_RADIUS;
+            let distance = sampler.sample(position).unwrap().distance;
+            let quantized_distance = quantized_sampler.sample(position).unwrap().distance;
+            assert!(
+                (quantized_distance - distance).abs() < voxel_size * 0.05,
+                "quantized distance {} differs from {}",
+                quantized_distance,
+                distance
+            );
+        }
+    }
+
+    #[test]
+    fn nodes_without_brick_bound_distance_by_voxel_size() {
+        let data = sphere_svo_data();
+        let sampler = SvoSampler::new(&data);
+        for position in [glam::Vec3::ZERO, glam::Vec3::splat(0.45)] {
+            let sample = sampler.sample(position).unwrap();
+            let expected = position.length() - SPHERE_SVO_RADIUS;
+            assert_eq!(sample.distance.signum(), expected.signum());
+            assert!(sample.distance.abs() <= expected.abs());
+            assert_eq!(sample.color, glam::Vec4::ZERO);
+        }
+    }
+
+    #[test]
+    fn positions_outside_of_domain_are_clamped() {
+        let data = sphere_svo_data();
+        let sampler = SvoSampler::new(&data);
+        let boundary = glam::Vec3::new(0.5, 0.0, 0.0);
+        let position = glam::Vec3::new(0.75, 0.0, 0.0);
+        assert!(sampler.sample(position).is_none());
+
+        let expected = sampler.sample(boundary).unwrap().distance + 0.25;
+        let sample = sampler.sample_clamped(position);
+        assert!((sample.distance - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn positions_of_missing_nodes_are_outside() {
+        let mut data = sphere_svo_data();
+        data.levels.clear();
+        data.node_headers.clear();
+        data.node_payloads.clear();
+        data.node_vertices.clear();
+        let sampler = SvoSampler::new(&data);
+        let sample = sampler.sample_clamped(glam::Vec3::ZERO);
+        assert_eq!(sample.distance, sampler.outside_distance());
+        assert_eq!(sample.color, glam::Vec4::ZERO);
+    }
+}
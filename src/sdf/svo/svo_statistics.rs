@@ -0,0 +1,381 @@
+use std::collections::HashSet;
+
+use super::{
+    node_brick_index, BrickAllocator, DistanceRange, Level, Svo, HEADER_SUBDIVIDED_FLAG,
+    HEADER_TILE_INDEX_MASK,
+};
+use crate::framework::{gpu, math::BoundingCube};
+
+/// Node counts of one level of an SVO.
+#[derive(Clone, Debug, Default)]
+pub struct LevelStatistics {
+    pub start_index: u32,
+    pub node_count: u32,
+
+    /// Nodes whose children are in the next level.
+    pub subdivided_count: u32,
+
+    /// Nodes which are not subdivided.
+    pub leaf_count: u32,
+
+    /// Nodes which have a brick linked, they can be both subdivided and leaves.
+    pub brick_node_count: u32,
+
+    /// Size of a voxel in bricks of this level.
+    pub voxel_size: f32,
+}
+
+/// Allocated GPU memory of an SVO in bytes.
+#[derive(Clone, Debug, Default)]
+pub struct SvoMemoryUsage {
+    pub node_headers: u64,
+    pub node_payloads: u64,
+    pub node_vertices: u64,
+    pub distance_atlas: u64,
+    pub color_atlas: u64,
+    pub distance_ranges: u64,
+    pub brick_free_list: u64,
+}
+
+impl SvoMemoryUsage {
+    pub fn node_pool(&self) -> u64 {
+        self.node_headers + self.node_payloads + self.node_vertices
+    }
+    pub fn brick_pool(&self) -> u64 {
+        self.distance_atlas + self.color_atlas + self.distance_ranges + self.brick_free_list
+    }
+    pub fn total(&self) -> u64 {
+        self.node_pool() + self.brick_pool()
+    }
+}
+
+/// Summary of an SVO read back from GPU, see `Svo::statistics`.
+#[derive(Clone, Debug, Default)]
+pub struct SvoStatistics {
+    pub levels: Vec<LevelStatistics>,
+
+    pub node_count: u32,
+    pub node_capacity: u32,
+    pub subdivided_count: u32,
+    pub leaf_count: u32,
+    pub brick_node_count: u32,
+
+    /// Number of allocated bricks including released ones.
+    pub brick_count: u32,
+    /// Number of released bricks waiting to be reused.
+    pub free_brick_count: u32,
+    pub brick_capacity: u32,
+
+    pub memory: SvoMemoryUsage,
+
+    /// Descriptions of inconsistencies found in the SVO, only first `MAX_REPORTED_ERRORS` are kept.
+    pub errors: Vec<String>,
+    /// Total number of inconsistencies found in the SVO.
+    pub error_count: usize,
+}
+
+impl SvoStatistics {
+    pub const MAX_REPORTED_ERRORS: usize = 32;
+
+    fn error(&mut self, message: impl FnOnce() -> String) {
+        if self.errors.len() < Self::MAX_REPORTED_ERRORS {
+            self.errors.push(message());
+        }
+        self.error_count += 1;
+    }
+
+    /// Ratio of allocated bricks to capacity of the brick atlas.
+    pub fn atlas_occupancy(&self) -> f32 {
+        if self.brick_capacity == 0 {
+            return 0.0;
+        }
+        self.brick_count as f32 / self.brick_capacity as f32
+    }
+}
+
+impl Svo {
+    /// Reads nodes of evaluated levels back from GPU, summarizes them and validates their links,
+    /// see `SvoStatistics::from_nodes` for the detected errors.
+    #[profiler::function]
+    pub fn statistics(&mut self, gpu: &gpu::Context) -> SvoStatistics {
+        let node_count = self.evaluated_node_count();
+        let headers = gpu::Buffer::<u32>::static_read_range(
+            self.node_pool.header_buffer(),
+            gpu,
+            0,
+            node_count as usize,
+        );
+        let payloads = gpu::Buffer::<u32>::static_read_range(
+            self.node_pool.payload_buffer(),
+            gpu,
+            0,
+            node_count as usize,
+        );
+        let allocator = self.brick_pool.load_allocator(gpu);
+
+        let node_capacity = self.node_pool.capacity() as u64;
+        let format = self.brick_pool.format();
+        let brick_capacity = self.brick_pool.capacity();
+        let voxels_per_brick = (format.voxels_per_brick_in_one_dimension() as u64).pow(3);
+        SvoStatistics {
+            node_capacity: self.node_pool.capacity(),
+            brick_capacity,
+            memory: SvoMemoryUsage {
+                node_headers: node_capacity * std::mem::size_of::<u32>() as u64,
+                node_payloads: node_capacity * std::mem::size_of::<u32>() as u64,
+                node_vertices: node_capacity * std::mem::size_of::<glam::Vec4>() as u64,
+                distance_atlas: brick_capacity as u64
+                    * voxels_per_brick
+                    * format.voxel_format.distance.bytes() as u64,
+                color_atlas: brick_capacity as u64
+                    * voxels_per_brick
+                    * format.voxel_format.color.bytes() as u64,
+                distance_ranges: brick_capacity as u64 * DistanceRange::BYTES as u64,
+                brick_free_list: brick_capacity.max(1) as u64 * std::mem::size_of::<u32>() as u64,
+            },
+            ..SvoStatistics::from_nodes(&self.domain, &self.levels, &headers, &payloads, &allocator)
+        }
+    }
+}
+
+impl SvoStatistics {
+    /// Summarizes nodes of given levels and validates their links, capacities and memory usage are left empty.
+    ///   - Detected errors are: levels which do not follow each other, children outside of the next level
+    ///     or past the node count, children of the bottom level among evaluated nodes, bricks outside of allocated bricks, bricks linked to multiple nodes
+    ///     and released bricks still linked to a node.
+    ///   - Children of the bottom level are not evaluated yet or were trimmed, they are only checked
+    ///     not to point back into evaluated nodes. Bricks of nodes with invalid children are still checked.
+    pub fn from_nodes(
+        domain: &BoundingCube,
+        levels: &[Level],
+        headers: &[u32],
+        payloads: &[u32],
+        allocator: &BrickAllocator,
+    ) -> Self {
+        let node_count = headers.len().min(payloads.len()) as u32;
+        let brick_count = allocator.count;
+        let free_bricks: HashSet<u32> = allocator.free.iter().copied().collect();
+        let mut statistics = SvoStatistics {
+            node_count,
+            brick_count,
+            free_brick_count: free_bricks.len() as u32,
+            ..Default::default()
+        };
+
+        // Root brick is not linked to any node, but it always has index 0
+        let mut linked_bricks = HashSet::from([0u32]);
+        let mut expected_start = 0;
+        for (level_index, level) in levels.iter().enumerate() {
+            if level.start_index != expected_start {
+                statistics.error(|| {
+                    format!(
+                        "Level {} starts at node {}, but previous level ends at node {}",
+                        level_index, level.start_index, expected_start
+                    )
+                });
+            }
+            expected_start = level.start_index.saturating_add(level.node_count);
+
+            let next_level = levels.get(level_index + 1);
+            let mut level_statistics = LevelStatistics {
+                start_index: level.start_index,
+                node_count: level.node_count,
+                voxel_size: Svo::level_voxel_size(domain, level_index),
+                ..Default::default()
+            };
+            let nodes = level.start_index..expected_start.min(node_count);
+            for node_index in nodes {
+                let header = headers[node_index as usize];
+                let payload = payloads[node_index as usize];
+
+                if header & HEADER_SUBDIVIDED_FLAG != 0 {
+                    level_statistics.subdivided_count += 1;
+                    let tile_index = header & HEADER_TILE_INDEX_MASK;
+                    let children = tile_index
+                        .checked_mul(8)
+                        .and_then(|first_child| Some(first_child..first_child.checked_add(8)?));
+                    let next_nodes = next_level.map(|level| {
+                        level.start_index..level.start_index.saturating_add(level.node_count)
+                    });
+                    match (children, next_nodes) {
+                        // Children of the bottom level are not evaluated yet or were trimmed,
+                        // they only must not point back into evaluated nodes
+                        (Some(children), None) if children.start < node_count => {
+                            statistics.error(|| {
+                                format!(
+                                    "Node {} of bottom level {} points to evaluated nodes {:?}",
+                                    node_index, level_index, children
+                                )
+                            });
+                        }
+                        (Some(children), Some(_)) if children.end > node_count => {
+                            statistics.error(|| {
+                                format!(
+                                    "Node {} of level {} points to children {:?} past node count {}",
+                                    node_index, level_index, children, node_count
+                                )
+                            });
+                        }
+                        (Some(children), Some(next_nodes))
+                            if children.start < next_nodes.start
+                                || children.end > next_nodes.end =>
+                        {
+                            statistics.error(|| {
+                                format!(
+                                    "Node {} of level {} points to children {:?} outside of the next level",
+                                    node_index, level_index, children
+                                )
+                            });
+                        }
+                        (Some(_), _) => {}
+                        (None, _) => {
+                            statistics.error(|| {
+                                format!(
+                                    "Node {} of level {} points to tile {} whose children overflow node indices",
+                                    node_index, level_index, tile_index
+                                )
+                            });
+                        }
+                    }
+                } else {
+                    level_statistics.leaf_count += 1;
+                }
+
+                if let Some(brick_index) = node_brick_index(header, payload) {
+                    level_statistics.brick_node_count += 1;
+                    if brick_index >= brick_count {
+                        statistics.error(|| {
+                            format!(
+                                "Node {} links brick {} past brick count {}",
+                                node_index, brick_index, brick_count
+                            )
+                        });
+                    } else if free_bricks.contains(&brick_index) {
+                        statistics.error(|| {
+                            format!(
+                                "Node {} links brick {} which is released",
+                                node_index, brick_index
+                            )
+                        });
+                    } else if !linked_bricks.insert(brick_index) {
+                        statistics.error(|| {
+                            format!(
+                                "Node {} links brick {} which is linked to another node",
+                                node_index, brick_index
+                            )
+                        });
+                    }
+                }
+            }
+
+            statistics.subdivided_count += level_statistics.subdivided_count;
+            statistics.leaf_count += level_statistics.leaf_count;
+            statistics.brick_node_count += level_statistics.brick_node_count;
+            statistics.levels.push(level_statistics);
+        }
+
+        statistics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdf::svo::{brick_payload, BrickPool, HEADER_HAS_BRICK_FLAG};
+
+    /// Root level of 8 nodes followed by a level of their 8 children.
+    const LEVELS: [Level; 2] = [
+        Level {
+            start_index: 0,
+            node_count: 8,
+        },
+        Level {
+            start_index: 8,
+            node_count: 8,
+        },
+    ];
+
+    fn statistics(
+        levels: &[Level],
+        headers: &[u32],
+        payloads: &[u32],
+        brick_count: u32,
+    ) -> SvoStatistics {
+        let allocator = BrickAllocator {
+            count: brick_count,
+            free: vec![],
+        };
+        SvoStatistics::from_nodes(&BoundingCube::UNIT, levels, headers, payloads, &allocator)
+    }
+
+    #[test]
+    fn valid_nodes_have_no_errors() {
+        let mut headers = vec![0; 16];
+        let mut payloads = vec![0; 16];
+        headers[0] = HEADER_SUBDIVIDED_FLAG | HEADER_HAS_BRICK_FLAG | 1;
+        payloads[0] = brick_payload(BrickPool::brick_index_to_coords(1));
+        headers[8] = HEADER_HAS_BRICK_FLAG;
+        payloads[8] = brick_payload(BrickPool::brick_index_to_coords(2));
+        let statistics = statistics(&LEVELS, &headers, &payloads, 3);
+
+        assert_eq!(statistics.errors, Vec::<String>::new());
+        assert_eq!(statistics.subdivided_count, 1);
+        assert_eq!(statistics.leaf_count, 15);
+        assert_eq!(statistics.brick_node_count, 2);
+    }
+
+    #[test]
+    fn detects_levels_which_do_not_follow_each_other() {
+        let levels = [
+            LEVELS[0],
+            Level {
+                start_index: 9,
+                node_count: 7,
+            },
+        ];
+        let statistics = statistics(&levels, &[0; 16], &[0; 16], 1);
+
+        assert_eq!(statistics.error_count, 1);
+        assert!(statistics.errors[0].contains("Level 1 starts at node 9"));
+    }
+
+    #[test]
+    fn detects_children_out_of_range_and_still_counts_their_bricks() {
+        let mut headers = vec![0; 16];
+        let mut payloads = vec![0; 16];
+        // Children past the node count
+        headers[0] = HEADER_SUBDIVIDED_FLAG | HEADER_HAS_BRICK_FLAG | 5;
+        payloads[0] = brick_payload(BrickPool::brick_index_to_coords(1));
+        // Children in the first level instead of the next one
+        headers[1] = HEADER_SUBDIVIDED_FLAG;
+        let statistics = statistics(&LEVELS, &headers, &payloads, 2);
+
+        assert_eq!(statistics.error_count, 2);
+        assert!(statistics.errors[0].contains("past node count"));
+        assert!(statistics.errors[1].contains("outside of the next level"));
+        assert_eq!(statistics.brick_node_count, 1);
+    }
+
+    #[test]
+    fn detects_bricks_linked_to_multiple_nodes() {
+        let mut headers = vec![0; 16];
+        let mut payloads = vec![0; 16];
+        for node_index in [2, 9] {
+            headers[node_index] = HEADER_HAS_BRICK_FLAG;
+            payloads[node_index] = brick_payload(BrickPool::brick_index_to_coords(1));
+        }
+        // Root brick is never linked to a node
+        headers[10] = HEADER_HAS_BRICK_FLAG;
+        payloads[10] = brick_payload(BrickPool::brick_index_to_coords(0));
+        let statistics = statistics(&LEVELS, &headers, &payloads, 2);
+
+        assert_eq!(statistics.error_count, 2);
+        assert!(
+            statistics.errors[0].contains("Node 9 links brick 1 which is linked to another node")
+        );
+        assert!(
+            statistics.errors[1].contains("Node 10 links brick 0 which is linked to another node")
+        );
+        assert_eq!(statistics.brick_node_count, 3);
+    }
+}
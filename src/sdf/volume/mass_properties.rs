@@ -0,0 +1,267 @@
+use glam::{DMat3, DVec3};
+
+use crate::framework::math::AABB;
+
+/// Physical properties of a solid bounded by the zero level set of a distance field.
+#[derive(Debug, Clone, Copy)]
+pub struct MassProperties {
+    pub volume: f32,
+    /// Estimated volume error, the difference from the volume integrated on the previous, twice coarser level.
+    pub volume_error: f32,
+    pub surface_area: f32,
+    pub density: f32,
+    pub mass: f32,
+    pub center_of_mass: glam::Vec3,
+    /// Inertia tensor about the center of mass, scaled by density.
+    pub inertia: glam::Mat3,
+    /// Edge length of the finest cells the integration ended with.
+    pub cell_size: f32,
+    pub sample_count: usize,
+}
+
+/// Integrates volume, surface area and moments of a solid given by a distance field over an adaptive octree.
+///   - Cells further from the surface than their edge length lie fully inside or outside and are integrated exactly,
+///     cells around the surface are subdivided level by level until the volume changes by less than the requested accuracy.
+///   - In the finest cells the indicator function of the solid is smoothed by a hat kernel one cell wide,
+///     its derivative gives the surface area by the coarea formula.
+pub struct MassIntegrator<F>
+where
+    F: Fn(glam::Vec3) -> f32 + Sync,
+{
+    distance: F,
+}
+
+impl<F> MassIntegrator<F>
+where
+    F: Fn(glam::Vec3) -> f32 + Sync,
+{
+    /// Cells of the deepest level are 512 times smaller than the bounding cube.
+    pub const MAX_DEPTH: u32 = 9;
+    /// Coarse levels are always refined, so that their estimates do not agree just by chance.
+    const MIN_DEPTH: u32 = 4;
+
+    pub fn new(distance: F) -> Self {
+        Self { distance }
+    }
+
+    /// Integrates the solid inside of AABB.
+    ///   - `accuracy` is the acceptable volume error relative to the volume.
+    #[profiler::function]
+    pub fn integrate(
+        &self,
+        aabb: &AABB,
+        density: f32,
+        accuracy: f32,
+    ) -> Result<MassProperties, String> {
+        let cube = aabb.bounding_cube();
+        let mut size = cube.size.max(f32::EPSILON) as f64;
+        let mut centers = vec![cube.pos];
+        let mut interior = Moments::default();
+        let mut sample_count = 0;
+        let mut previous_volume = None;
+
+        for depth in 0..=Self::MAX_DEPTH {
+            let distances = self.sample(&centers);
+            sample_count += distances.len();
+
+            // Cells with the surface further than their edge length cannot be crossed by it
+            let mut boundary = vec![];
+            for (center, distance) in centers.iter().zip(distances.iter()) {
+                let distance = *distance as f64;
+                if distance <= -size {
+                    interior.add_cube(center.as_dvec3(), size, 1.0);
+                } else if distance < size {
+                    boundary.push((*center, distance));
+                }
+            }
+
+            // Boundary cells are integrated as leaves to see whether the estimate is accurate enough
+            let mut estimate = interior;
+            let mut area = 0.0;
+            for (center, distance) in boundary.iter() {
+                let t = distance / size;
+                estimate.add_cube(center.as_dvec3(), size, smoothed_inside_fraction(t));
+                area += hat(t) * size * size;
+            }
+
+            let error =
+                previous_volume.map_or(f64::MAX, |volume: f64| (estimate.volume - volume).abs());
+            let is_accurate =
+                depth >= Self::MIN_DEPTH && error <= accuracy as f64 * estimate.volume;
+            if is_accurate || boundary.is_empty() || depth == Self::MAX_DEPTH {
+                let error = if boundary.is_empty() { 0.0 } else { error };
+                return estimate.into_properties(density, area, error, size, sample_count);
+            }
+            previous_volume = Some(estimate.volume);
+
+            // Refine boundary cells into their 8 children
+            size *= 0.5;
+            let offset = (size * 0.5) as f32;
+            centers = boundary
+                .iter()
+                .flat_map(|(center, _)| {
+                    (0..8u32).map(move |child| {
+                        let direction = glam::UVec3::new(child & 1, (child >> 1) & 1, child >> 2)
+                            .as_vec3()
+                            * 2.0
+                            - 1.0;
+                        *center + direction * offset
+                    })
+                })
+                .collect();
+        }
+        Err("Integration did not finish".to_string())
+    }
+
+    /// Samples distances at positions, split between all available threads.
+    fn sample(&self, positions: &[glam::Vec3]) -> Vec<f32> {
+        let thread_count = std::thread::available_parallelism().map_or(1, |count| count.get());
+        let chunk_size = positions.len().div_ceil(thread_count).max(1);
+        let mut distances = vec![0.0; positions.len()];
+        std::thread::scope(|scope| {
+            for (positions, distances) in positions
+                .chunks(chunk_size)
+                .zip(distances.chunks_mut(chunk_size))
+            {
+                scope.spawn(move || {
+                    for (position, distance) in positions.iter().zip(distances.iter_mut()) {
+                        *distance = (self.distance)(*position);
+                    }
+                });
+            }
+        });
+        distances
+    }
+}
+
+/// Hat kernel with support `[-1, 1]` and unit integral.
+fn hat(t: f64) -> f64 {
+    (1.0 - t.abs()).max(0.0)
+}
+
+/// Integral of the hat kernel from `t` to infinity, a smoothed step going from 1 inside to 0 outside.
+fn smoothed_inside_fraction(t: f64) -> f64 {
+    let t = t.clamp(-1.0, 1.0);
+    if t < 0.0 {
+        1.0 - 0.5 * (1.0 + t) * (1.0 + t)
+    } else {
+        0.5 * (1.0 - t) * (1.0 - t)
+    }
+}
+
+/// Volume with its first and second moments about the origin.
+#[derive(Clone, Copy)]
+struct Moments {
+    volume: f64,
+    first: DVec3,
+    second: DMat3,
+}
+
+impl Default for Moments {
+    fn default() -> Self {
+        Self {
+            volume: 0.0,
+            first: DVec3::ZERO,
+            second: DMat3::ZERO,
+        }
+    }
+}
+
+impl Moments {
+    /// Adds a part of a cube, its mass is assumed to be spread evenly over the whole cube.
+    fn add_cube(&mut self, center: DVec3, size: f64, fraction: f64) {
+        let volume = size.powi(3) * fraction;
+        self.volume += volume;
+        self.first += center * volume;
+        self.second += (outer(center, center)
+            + DMat3::from_diagonal(DVec3::splat(size * size / 12.0)))
+            * volume;
+    }
+
+    fn into_properties(
+        self,
+        density: f32,
+        surface_area: f64,
+        volume_error: f64,
+        cell_size: f64,
+        sample_count: usize,
+    ) -> Result<MassProperties, String> {
+        if self.volume <= 0.0 {
+            return Err("Geometry encloses no volume".to_string());
+        }
+        let center_of_mass = self.first / self.volume;
+        let central = self.second - outer(center_of_mass, center_of_mass) * self.volume;
+        let trace = central.x_axis.x + central.y_axis.y + central.z_axis.z;
+        let inertia = (DMat3::from_diagonal(DVec3::splat(trace)) - central) * density as f64;
+        Ok(MassProperties {
+            volume: self.volume as f32,
+            volume_error: volume_error as f32,
+            surface_area: surface_area as f32,
+            density,
+            mass: (self.volume * density as f64) as f32,
+            center_of_mass: center_of_mass.as_vec3(),
+            inertia: inertia.as_mat3(),
+            cell_size: cell_size as f32,
+            sample_count,
+        })
+    }
+}
+
+fn outer(a: DVec3, b: DVec3) -> DMat3 {
+    DMat3::from_cols(a * b.x, a * b.y, a * b.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integrates_sphere_like_analytic_solution() {
+        let radius = 0.5;
+        let center = glam::Vec3::new(0.2, -0.1, 0.3);
+        let density = 2.0;
+        let aabb = AABB::new(center - 0.75, center + 0.75);
+        let properties = MassIntegrator::new(|pos: glam::Vec3| (pos - center).length() - radius)
+            .integrate(&aabb, density, 1e-4)
+            .unwrap();
+
+        let pi = std::f32::consts::PI;
+        let volume = 4.0 / 3.0 * pi * radius.powi(3);
+        let mass = volume * density;
+        assert!((properties.volume - volume).abs() < volume * 0.01);
+        assert!((properties.mass - properties.volume * density).abs() < 1e-5);
+        assert!((properties.mass - mass).abs() < mass * 0.01);
+        let area = 4.0 * pi * radius * radius;
+        assert!((properties.surface_area - area).abs() < area * 0.03);
+        assert!(properties.center_of_mass.abs_diff_eq(center, 1e-3));
+
+        // Inertia of a solid sphere is the same about every axis through its center
+        let inertia = 0.4 * mass * radius * radius;
+        let expected = glam::Mat3::from_diagonal(glam::Vec3::splat(inertia));
+        assert!(properties.inertia.abs_diff_eq(expected, inertia * 0.02));
+    }
+
+    #[test]
+    fn integrates_box_off_center() {
+        let half_size = glam::Vec3::new(0.5, 0.25, 0.25);
+        let center = glam::Vec3::new(0.5, 0.25, 0.25);
+        let aabb = AABB::new(glam::Vec3::splat(-1.0), glam::Vec3::splat(1.0));
+        let properties = MassIntegrator::new(|pos: glam::Vec3| {
+            let q = (pos - center).abs() - half_size;
+            q.max(glam::Vec3::ZERO).length() + q.max_element().min(0.0)
+        })
+        .integrate(&aabb, 1.0, 1e-3)
+        .unwrap();
+
+        assert!((properties.volume - 0.25).abs() < 0.25 * 0.01);
+        assert!(properties.center_of_mass.abs_diff_eq(center, 1e-3));
+    }
+
+    #[test]
+    fn refuses_empty_solid() {
+        let aabb = AABB::new(glam::Vec3::splat(-1.0), glam::Vec3::splat(1.0));
+        let properties = MassIntegrator::new(|pos: glam::Vec3| (pos - 5.0).length() - 1.0)
+            .integrate(&aabb, 1.0, 1e-3);
+        assert!(properties.is_err());
+    }
+}
@@ -0,0 +1,8 @@
+mod sdf_volume;
+pub use sdf_volume::*;
+
+mod vox;
+pub use vox::*;
+
+mod mass_properties;
+pub use mass_properties::*;
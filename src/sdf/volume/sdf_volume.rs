@@ -0,0 +1,356 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use strum_macros::{AsRefStr, EnumIter};
+
+use crate::framework::{
+    binary_writer::{create_file, write_f32s, write_u32s},
+    math::AABB,
+};
+
+/// What a dense volume is sampled from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumIter)]
+pub enum VolumeSource {
+    /// Bricks of the evaluated SVO, fast but limited by its voxel size and distance format.
+    Svo,
+    /// The exact edit list of the geometry, slow but serves as a reference.
+    Edits,
+}
+
+/// Distances and optionally colors sampled on a regular grid.
+///   - Samples are at centers of cubical cells, the cell with index `(x, y, z)` has its center at
+///     `min + (x + 0.5, y + 0.5, z + 0.5) * voxel_size`.
+///   - Samples are stored with x coordinate changing fastest and z coordinate changing slowest.
+#[derive(Debug, Clone)]
+pub struct SdfVolume {
+    pub min: glam::Vec3,
+    pub voxel_size: f32,
+    pub resolution: glam::UVec3,
+    pub distances: Vec<f32>,
+    pub colors: Option<Vec<glam::Vec4>>,
+}
+
+impl SdfVolume {
+    /// Identifies narrow band files.
+    const BAND_MAGIC: &'static [u8; 8] = b"SDFBAND\0";
+    const BAND_VERSION: u32 = 1;
+
+    /// Samples a volume covering an AABB, with `resolution` cells along its longest side.
+    #[profiler::function]
+    pub fn sample<F>(aabb: &AABB, resolution: u32, with_colors: bool, sample: F) -> Self
+    where
+        F: Fn(glam::Vec3) -> (f32, glam::Vec4),
+    {
+        let size = aabb.max - aabb.min;
+        let voxel_size = size.max_element().max(f32::EPSILON) / resolution.max(1) as f32;
+        let resolution = (size / voxel_size).ceil().as_uvec3().max(glam::UVec3::ONE);
+        // Center the grid on the AABB, cells along shorter sides may overlap it
+        let min = aabb.min + (size - resolution.as_vec3() * voxel_size) * 0.5;
+
+        let sample_count = (resolution.x * resolution.y * resolution.z) as usize;
+        let mut distances = Vec::with_capacity(sample_count);
+        let mut colors = with_colors.then(|| Vec::with_capacity(sample_count));
+        for z in 0..resolution.z {
+            for y in 0..resolution.y {
+                for x in 0..resolution.x {
+                    let cell = glam::UVec3::new(x, y, z).as_vec3() + 0.5;
+                    let (distance, color) = sample(min + cell * voxel_size);
+                    distances.push(distance);
+                    if let Some(colors) = colors.as_mut() {
+                        colors.push(color);
+                    }
+                }
+            }
+        }
+
+        Self {
+            min,
+            voxel_size,
+            resolution,
+            distances,
+            colors,
+        }
+    }
+
+    /// Stores distances as raw little endian f32 values.
+    ///   - Colors are stored next to it into `<name>_color.raw` as four f32 values per sample.
+    ///   - Layout of the volume is described in `<name>.json`, because raw files carry no header.
+    #[profiler::function]
+    pub fn store_raw<P: AsRef<Path>>(&self, file_name: P) -> Result<(), String> {
+        let file_name = file_name.as_ref();
+        let mut writer = create_file(file_name)?;
+        let mut write = || -> std::io::Result<()> {
+            write_f32s(&mut writer, &self.distances)?;
+            writer.flush()
+        };
+        write().map_err(|err| format!("Failed to write to file: {}", err))?;
+
+        if let Some(colors) = &self.colors {
+            let mut writer = create_file(sibling(file_name, "_color", "raw"))?;
+            let mut write = || -> std::io::Result<()> {
+                for color in colors {
+                    write_f32s(&mut writer, &color.to_array())?;
+                }
+                writer.flush()
+            };
+            write().map_err(|err| format!("Failed to write to file: {}", err))?;
+        }
+
+        let description = serde_json::json!({
+            "resolution": self.resolution.to_array(),
+            "min": self.min.to_array(),
+            "voxel_size": self.voxel_size,
+            "sample_type": "f32le",
+            "order": "x fastest, z slowest, samples at cell centers",
+            "colors": self.colors.is_some(),
+        });
+        let json = serde_json::to_string_pretty(&description)
+            .map_err(|err| format!("Failed to serialize volume description: {}", err))?;
+        std::fs::write(file_name.with_extension("json"), json)
+            .map_err(|err| format!("Failed to write to file: {}", err))
+    }
+
+    /// Stores distances as a NumPy array of shape `(z, y, x)`.
+    ///   - Colors are stored next to it into `<name>_color.npy` as an array of shape `(z, y, x, 4)`.
+    #[profiler::function]
+    pub fn store_npy<P: AsRef<Path>>(&self, file_name: P) -> Result<(), String> {
+        let file_name = file_name.as_ref();
+        let shape = [self.resolution.z, self.resolution.y, self.resolution.x];
+        store_npy_f32(file_name, &shape, &self.distances)?;
+        if let Some(colors) = &self.colors {
+            let colors: Vec<f32> = colors.iter().flat_map(|color| color.to_array()).collect();
+            store_npy_f32(
+                sibling(file_name, "_color", "npy"),
+                &[shape[0], shape[1], shape[2], 4],
+                &colors,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Stores only samples closer to the surface than `band_width`, in a little endian binary format:
+    ///   - magic `SDFBAND\0`, u32 version, u32 flags (bit 0 set when colors are present),
+    ///   - u32 resolution x, y, z, f32 min x, y, z, f32 voxel size, f32 band width, u32 sample count,
+    ///   - for each sample u32 linear index `x + res_x * (y + res_y * z)`, f32 distance and f32 rgba when colors are present.
+    ///
+    /// Returns number of stored samples.
+    #[profiler::function]
+    pub fn store_narrow_band<P: AsRef<Path>>(
+        &self,
+        file_name: P,
+        band_width: f32,
+    ) -> Result<usize, String> {
+        let band: Vec<usize> = (0..self.distances.len())
+            .filter(|index| self.distances[*index].abs() <= band_width)
+            .collect();
+
+        let mut writer = create_file(file_name)?;
+        let mut write = || -> std::io::Result<()> {
+            writer.write_all(Self::BAND_MAGIC)?;
+            write_u32s(
+                &mut writer,
+                &[Self::BAND_VERSION, self.colors.is_some() as u32],
+            )?;
+            write_u32s(&mut writer, &self.resolution.to_array())?;
+            write_f32s(&mut writer, &self.min.to_array())?;
+            write_f32s(&mut writer, &[self.voxel_size, band_width])?;
+            write_u32s(&mut writer, &[band.len() as u32])?;
+            for index in band.iter() {
+                write_u32s(&mut writer, &[*index as u32])?;
+                write_f32s(&mut writer, &[self.distances[*index]])?;
+                if let Some(colors) = &self.colors {
+                    write_f32s(&mut writer, &colors[*index].to_array())?;
+                }
+            }
+            writer.flush()
+        };
+        write().map_err(|err| format!("Failed to write to file: {}", err))?;
+        Ok(band.len())
+    }
+}
+
+/// Stores a C ordered f32 array in NumPy format version 1.0.
+fn store_npy_f32<P: AsRef<Path>>(
+    file_name: P,
+    shape: &[u32],
+    values: &[f32],
+) -> Result<(), String> {
+    let shape = shape
+        .iter()
+        .map(|dimension| format!("{}, ", dimension))
+        .collect::<String>();
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}), }}",
+        shape.trim_end()
+    );
+    // Data has to start at 64 byte boundary, header is padded by spaces and ends by a new line
+    let preamble_length = 10;
+    while (preamble_length + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    let mut writer = create_file(file_name)?;
+    let mut write = || -> std::io::Result<()> {
+        writer.write_all(b"\x93NUMPY")?;
+        writer.write_all(&[1, 0])?;
+        writer.write_all(&(header.len() as u16).to_le_bytes())?;
+        writer.write_all(header.as_bytes())?;
+        write_f32s(&mut writer, values)?;
+        writer.flush()
+    };
+    write().map_err(|err| format!("Failed to write to file: {}", err))
+}
+
+/// A file next to given file, with a suffix appended to its name and a different extension.
+fn sibling(file_name: &Path, suffix: &str, extension: &str) -> PathBuf {
+    let stem = file_name
+        .file_stem()
+        .map_or("volume".into(), |stem| stem.to_string_lossy());
+    file_name.with_file_name(format!("{}{}.{}", stem, suffix, extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Volume over an AABB which is not a multiple of the voxel size, colors hold positions of samples.
+    fn position_volume() -> SdfVolume {
+        let aabb = AABB::new(
+            glam::Vec3::new(1.0, 1.0, 1.0),
+            glam::Vec3::new(3.0, 1.8, 1.3),
+        );
+        SdfVolume::sample(&aabb, 4, true, |pos| (pos.x - 2.0, pos.extend(1.0)))
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("sdf_volume_test_{}_{}", std::process::id(), name))
+    }
+
+    /// Reads and removes a stored file.
+    fn take_file(path: &Path) -> Vec<u8> {
+        let bytes = std::fs::read(path);
+        std::fs::remove_file(path).unwrap();
+        bytes.unwrap()
+    }
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn samples_cell_centers_of_grid_centered_on_aabb() {
+        let volume = position_volume();
+
+        assert_eq!(volume.voxel_size, 0.5);
+        assert_eq!(volume.resolution, glam::UVec3::new(4, 2, 1));
+        let center = volume.min + volume.resolution.as_vec3() * volume.voxel_size * 0.5;
+        assert!(center.abs_diff_eq(glam::Vec3::new(2.0, 1.4, 1.15), 1e-6));
+
+        let colors = volume.colors.as_ref().unwrap();
+        assert_eq!(volume.distances.len(), 8);
+        assert_eq!(colors.len(), 8);
+        for z in 0..volume.resolution.z {
+            for y in 0..volume.resolution.y {
+                for x in 0..volume.resolution.x {
+                    let index = (x + volume.resolution.x * (y + volume.resolution.y * z)) as usize;
+                    let expected = volume.min
+                        + (glam::UVec3::new(x, y, z).as_vec3() + 0.5) * volume.voxel_size;
+                    assert!(colors[index].truncate().abs_diff_eq(expected, 1e-6));
+                    assert_eq!(volume.distances[index], expected.x - 2.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stored_npy_has_aligned_header_and_shape() {
+        let volume = position_volume();
+        let path = temp_path("volume.npy");
+        volume.store_npy(&path).unwrap();
+        let bytes = take_file(&path);
+        let color_bytes = take_file(&sibling(&path, "_color", "npy"));
+
+        for (bytes, shape, values) in [
+            (&bytes, "(1, 2, 4,)", volume.distances.clone()),
+            (
+                &color_bytes,
+                "(1, 2, 4, 4,)",
+                volume
+                    .colors
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .flat_map(|color| color.to_array())
+                    .collect(),
+            ),
+        ] {
+            assert_eq!(&bytes[0..6], b"\x93NUMPY");
+            assert_eq!(&bytes[6..8], &[1, 0]);
+            let header_length = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+            let data_offset = 10 + header_length;
+            assert_eq!(data_offset % 64, 0);
+
+            let header = std::str::from_utf8(&bytes[10..data_offset]).unwrap();
+            assert!(header.ends_with('\n'));
+            assert!(header.contains("'descr': '<f4'"));
+            assert!(header.contains(&format!("'shape': {}", shape)));
+
+            assert_eq!(bytes.len(), data_offset + values.len() * 4);
+            for (index, value) in values.iter().enumerate() {
+                assert_eq!(read_f32(bytes, data_offset + index * 4), *value);
+            }
+        }
+    }
+
+    #[test]
+    fn narrow_band_stores_only_samples_near_surface() {
+        let volume = position_volume();
+        let path = temp_path("volume.band");
+        let band_width = 0.3;
+        let sample_count = volume.store_narrow_band(&path, band_width).unwrap();
+        let bytes = take_file(&path);
+
+        // Samples at x = 1.75 and x = 2.25 of both rows lie within the band
+        assert_eq!(sample_count, 4);
+        assert_eq!(&bytes[0..8], SdfVolume::BAND_MAGIC);
+        assert_eq!(read_u32(&bytes, 8), SdfVolume::BAND_VERSION);
+        assert_eq!(read_u32(&bytes, 12), 1);
+        assert_eq!(
+            [16, 20, 24].map(|offset| read_u32(&bytes, offset)),
+            volume.resolution.to_array()
+        );
+        assert_eq!(
+            [28, 32, 36].map(|offset| read_f32(&bytes, offset)),
+            volume.min.to_array()
+        );
+        assert_eq!(read_f32(&bytes, 40), volume.voxel_size);
+        assert_eq!(read_f32(&bytes, 44), band_width);
+        assert_eq!(read_u32(&bytes, 48) as usize, sample_count);
+
+        // Each sample has a linear index, distance and rgba color
+        let sample_size = 4 * 6;
+        assert_eq!(bytes.len(), 52 + sample_count * sample_size);
+        let colors = volume.colors.as_ref().unwrap();
+        let indices: Vec<u32> = (0..sample_count)
+            .map(|sample| {
+                let offset = 52 + sample * sample_size;
+                let index = read_u32(&bytes, offset);
+                assert_eq!(
+                    read_f32(&bytes, offset + 4),
+                    volume.distances[index as usize]
+                );
+                let color = [8, 12, 16, 20].map(|component| read_f32(&bytes, offset + component));
+                assert_eq!(color, colors[index as usize].to_array());
+                index
+            })
+            .collect();
+        assert_eq!(indices, vec![1, 2, 5, 6]);
+    }
+}
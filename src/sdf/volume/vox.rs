@@ -0,0 +1,460 @@
+use std::{collections::HashMap, io::Write, path::Path};
+
+use super::SdfVolume;
+use crate::{
+    framework::{
+        binary_writer::{create_file, write_u32s},
+        math::Transform,
+    },
+    sdf::geometry::{Edit, Operation, Primitive},
+};
+
+/// A voxel model in MagicaVoxel `.vox` format.
+///   - Voxel coordinates are in MagicaVoxel space, which has z axis pointing up.
+///     Scene space has y axis pointing up, voxel `(x, y, z)` is at scene position `(x, z, -y)`.
+///   - Only the first model of a file is loaded, scene graph and material chunks are ignored.
+#[derive(Debug, Clone)]
+pub struct VoxModel {
+    pub size: glam::UVec3,
+    /// Coordinates and color index of each filled voxel, color index 0 means empty and is never used.
+    pub voxels: Vec<([u8; 3], u8)>,
+    /// RGBA colors of color indices, entry 0 is unused.
+    pub palette: [[u8; 4]; 256],
+}
+
+impl VoxModel {
+    const MAGIC: &'static [u8; 4] = b"VOX ";
+    const VERSION: u32 = 150;
+
+    /// Largest model size along any axis supported by MagicaVoxel.
+    pub const MAX_SIZE: u32 = 256;
+
+    /// Colors available for voxels, color index 0 is reserved for empty voxels.
+    const PALETTE_SIZE: usize = 255;
+
+    /// Creates a model from voxels with negative distance, their colors are quantized into a palette by median cut.
+    ///   - The volume has to be at most `MAX_SIZE` voxels along each axis.
+    #[profiler::function]
+    pub fn from_volume(volume: &SdfVolume) -> Result<Self, String> {
+        let resolution = volume.resolution;
+        if resolution.max_element() > Self::MAX_SIZE {
+            return Err(format!(
+                "Volume {}x{}x{} is larger than {} voxels",
+                resolution.x,
+                resolution.y,
+                resolution.z,
+                Self::MAX_SIZE
+            ));
+        }
+
+        // Collect filled voxels with 8-bit colors
+        let mut filled: Vec<([u8; 3], [u8; 3])> = vec![];
+        for z in 0..resolution.z {
+            for y in 0..resolution.y {
+                for x in 0..resolution.x {
+                    let index = (x + resolution.x * (y + resolution.y * z)) as usize;
+                    if volume.distances[index] >= 0.0 {
+                        continue;
+                    }
+                    let color = volume.colors.as_ref().map_or(glam::Vec4::ONE, |c| c[index]);
+                    let color =
+                        (color.truncate().clamp(glam::Vec3::ZERO, glam::Vec3::ONE) * 255.0).round();
+                    // Scene (x, y, z) is MagicaVoxel (x, -z, y)
+                    let coords = [x as u8, (resolution.z - 1 - z) as u8, y as u8];
+                    filled.push((coords, [color.x as u8, color.y as u8, color.z as u8]));
+                }
+            }
+        }
+
+        let mut histogram: HashMap<[u8; 3], u32> = HashMap::new();
+        for (_, color) in filled.iter() {
+            *histogram.entry(*color).or_insert(0) += 1;
+        }
+        let colors: Vec<([u8; 3], u32)> = histogram.into_iter().collect();
+        let (palette_colors, color_indices) = median_cut(colors, Self::PALETTE_SIZE);
+
+        let mut palette = [[0u8; 4]; 256];
+        for (index, color) in palette_colors.iter().enumerate() {
+            palette[index + 1] = [color[0], color[1], color[2], 255];
+        }
+        Ok(Self {
+            size: glam::UVec3::new(resolution.x, resolution.z, resolution.y),
+            voxels: filled
+                .into_iter()
+                .map(|(coords, color)| (coords, color_indices[&color] as u8 + 1))
+                .collect(),
+            palette,
+        })
+    }
+
+    /// Number of distinct colors used by voxels.
+    pub fn color_count(&self) -> usize {
+        let mut used = [false; 256];
+        for (_, color_index) in self.voxels.iter() {
+            used[*color_index as usize] = true;
+        }
+        used.iter().filter(|used| **used).count()
+    }
+
+    /// Converts voxels into cube edits, neighboring voxels of the same color are merged greedily into boxes.
+    ///   - Boxes grow along x first, then along y and z, so a model made of few large boxes produces few edits.
+    ///   - The model is centered at origin, with cubical voxels of given size.
+    #[profiler::function]
+    pub fn to_edits(&self, voxel_size: f32) -> Vec<Edit> {
+        let size = self.size;
+        let index = |x: u32, y: u32, z: u32| (x + size.x * (y + size.y * z)) as usize;
+        let mut grid = vec![0u8; (size.x * size.y * size.z) as usize];
+        for (coords, color_index) in self.voxels.iter() {
+            let [x, y, z] = coords.map(|c| c as u32);
+            if x < size.x && y < size.y && z < size.z {
+                grid[index(x, y, z)] = *color_index;
+            }
+        }
+
+        let mut edits = vec![];
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let color_index = grid[index(x, y, z)];
+                    if color_index == 0 {
+                        continue;
+                    }
+
+                    let same =
+                        |x: u32, y: u32, z: u32, grid: &[u8]| grid[index(x, y, z)] == color_index;
+                    let mut end = glam::UVec3::new(x + 1, y + 1, z + 1);
+                    while end.x < size.x && same(end.x, y, z, &grid) {
+                        end.x += 1;
+                    }
+                    while end.y < size.y && (x..end.x).all(|x| same(x, end.y, z, &grid)) {
+                        end.y += 1;
+                    }
+                    while end.z < size.z
+                        && (y..end.y).all(|y| (x..end.x).all(|x| same(x, y, end.z, &grid)))
+                    {
+                        end.z += 1;
+                    }
+                    for bz in z..end.z {
+                        for by in y..end.y {
+                            for bx in x..end.x {
+                                grid[index(bx, by, bz)] = 0;
+                            }
+                        }
+                    }
+
+                    let start = glam::UVec3::new(x, y, z).as_vec3();
+                    let extent = end.as_vec3() - start;
+                    let center = (start + extent * 0.5 - size.as_vec3() * 0.5) * voxel_size;
+                    let [r, g, b, a] = self.palette[color_index as usize].map(|c| c as f32 / 255.0);
+                    edits.push(Edit {
+                        primitive: Primitive::Cube {
+                            width: extent.x * voxel_size,
+                            height: extent.z * voxel_size,
+                            depth: extent.y * voxel_size,
+                            bevel: 0.0,
+                        },
+                        operation: Operation::Add,
+                        // MagicaVoxel (x, y, z) is scene (x, z, -y)
+                        transform: Transform::from_xyz(center.x, center.z, -center.y),
+                        blending: 0.0,
+                        color: glam::Vec4::new(r, g, b, a),
+                    });
+                }
+            }
+        }
+        edits
+    }
+
+    #[profiler::function]
+    pub fn store<P: AsRef<Path>>(&self, file_name: P) -> Result<(), String> {
+        let mut writer = create_file(file_name)?;
+        let mut write = || -> std::io::Result<()> {
+            // Chunks are written into memory first, because their sizes precede them
+            let mut size_chunk = vec![];
+            write_u32s(&mut size_chunk, &self.size.to_array())?;
+            let mut voxels_chunk = vec![];
+            write_u32s(&mut voxels_chunk, &[self.voxels.len() as u32])?;
+            for (coords, color_index) in self.voxels.iter() {
+                voxels_chunk.extend_from_slice(coords);
+                voxels_chunk.push(*color_index);
+            }
+            // Palette chunk entry `i` is the color of color index `i + 1`
+            let mut palette_chunk = vec![];
+            for index in 0..256 {
+                palette_chunk.extend_from_slice(&self.palette[(index + 1) % 256]);
+            }
+
+            let mut children = vec![];
+            write_chunk(&mut children, b"SIZE", &size_chunk)?;
+            write_chunk(&mut children, b"XYZI", &voxels_chunk)?;
+            write_chunk(&mut children, b"RGBA", &palette_chunk)?;
+
+            writer.write_all(Self::MAGIC)?;
+            write_u32s(&mut writer, &[Self::VERSION])?;
+            writer.write_all(b"MAIN")?;
+            write_u32s(&mut writer, &[0, children.len() as u32])?;
+            writer.write_all(&children)?;
+            writer.flush()
+        };
+        write().map_err(|err| format!("Failed to write to file: {}", err))
+    }
+
+    #[profiler::function]
+    pub fn load<P: AsRef<Path>>(file_name: P) -> Result<Self, String> {
+        let bytes = match std::fs::read(file_name) {
+            Ok(bytes) => bytes,
+            Err(err) => return Err(format!("Failed to read file: {}", err)),
+        };
+        if bytes.len() < 8 || &bytes[0..4] != Self::MAGIC {
+            return Err("Not a MagicaVoxel file".to_string());
+        }
+
+        let read_u32 = |offset: usize| -> Result<u32, String> {
+            match bytes.get(offset..offset + 4) {
+                Some(b) => Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                None => Err("Unexpected end of file".to_string()),
+            }
+        };
+
+        let mut size = None;
+        let mut voxels = None;
+        let mut palette = default_palette();
+        // Chunks of the main chunk follow each other, chunks of other chunks are skipped
+        let mut offset = 8;
+        while offset + 12 <= bytes.len() {
+            let id = &bytes[offset..offset + 4];
+            let content_size = read_u32(offset + 4)? as usize;
+            let children_size = read_u32(offset + 8)? as usize;
+            let content_start = offset + 12;
+            let Some(content) = bytes.get(content_start..content_start + content_size) else {
+                return Err("Unexpected end of file".to_string());
+            };
+            offset = match id {
+                b"MAIN" => content_start + content_size,
+                _ => content_start + content_size + children_size,
+            };
+
+            match id {
+                b"SIZE" if size.is_none() && content.len() >= 12 => {
+                    let [x, y, z] = [0, 4, 8].map(|start| {
+                        u32::from_le_bytes([
+                            content[start],
+                            content[start + 1],
+                            content[start + 2],
+                            content[start + 3],
+                        ])
+                    });
+                    size = Some(glam::UVec3::new(x, y, z));
+                }
+                b"XYZI" if voxels.is_none() && content.len() >= 4 => {
+                    let count =
+                        u32::from_le_bytes([content[0], content[1], content[2], content[3]]);
+                    let Some(data) = content.get(4..4 + count as usize * 4) else {
+                        return Err("Voxel chunk is shorter than its voxel count".to_string());
+                    };
+                    voxels = Some(
+                        data.chunks_exact(4)
+                            .map(|voxel| ([voxel[0], voxel[1], voxel[2]], voxel[3]))
+                            .filter(|(_, color_index)| *color_index != 0)
+                            .collect::<Vec<_>>(),
+                    );
+                }
+                b"RGBA" if content.len() >= 1024 => {
+                    for index in 0..255 {
+                        let entry = &content[index * 4..index * 4 + 4];
+                        palette[index + 1] = [entry[0], entry[1], entry[2], entry[3]];
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        match (size, voxels) {
+            (Some(size), Some(voxels)) => Ok(Self {
+                size: size.min(glam::UVec3::splat(Self::MAX_SIZE)),
+                voxels,
+                palette,
+            }),
+            _ => Err("File contains no voxel model".to_string()),
+        }
+    }
+}
+
+/// Reduces weighted colors to at most `max_colors` colors by median cut.
+///   - Returns the palette and index of palette color for each input color.
+fn median_cut(
+    colors: Vec<([u8; 3], u32)>,
+    max_colors: usize,
+) -> (Vec<[u8; 3]>, HashMap<[u8; 3], usize>) {
+    let channel_range = |colors: &[([u8; 3], u32)], channel: usize| {
+        let min = colors.iter().map(|(c, _)| c[channel]).min().unwrap_or(0);
+        let max = colors.iter().map(|(c, _)| c[channel]).max().unwrap_or(0);
+        max - min
+    };
+
+    let mut boxes = vec![colors];
+    while boxes.len() < max_colors {
+        // Split box with the widest channel range at weighted median of the channel
+        let Some((box_index, channel, _)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .flat_map(|(index, colors)| {
+                (0..3).map(move |channel| (index, channel, channel_range(colors, channel)))
+            })
+            .max_by_key(|(_, _, range)| *range)
+        else {
+            break;
+        };
+
+        let mut colors = boxes.swap_remove(box_index);
+        colors.sort_by_key(|(color, _)| color[channel]);
+        let total: u32 = colors.iter().map(|(_, count)| count).sum();
+        let mut accumulated = 0;
+        let mut split = 1;
+        for (index, (_, count)) in colors.iter().enumerate() {
+            accumulated += count;
+            if accumulated * 2 >= total {
+                split = (index + 1).clamp(1, colors.len() - 1);
+                break;
+            }
+        }
+        let upper = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    let mut palette = Vec::with_capacity(boxes.len());
+    let mut indices = HashMap::new();
+    for colors in boxes.iter() {
+        let total: u64 = colors.iter().map(|(_, count)| *count as u64).sum();
+        let mut sum = [0u64; 3];
+        for (color, count) in colors.iter() {
+            for channel in 0..3 {
+                sum[channel] += color[channel] as u64 * *count as u64;
+            }
+            indices.insert(*color, palette.len());
+        }
+        palette.push(sum.map(|s| (s / total.max(1)) as u8));
+    }
+    (palette, indices)
+}
+
+/// Palette used by MagicaVoxel for files without palette chunk.
+///   - It is a 6x6x6 color cube without black followed by ramps of red, green, blue and gray.
+fn default_palette() -> [[u8; 4]; 256] {
+    let mut palette = [[0u8; 4]; 256];
+    let levels = [0xFF, 0xCC, 0x99, 0x66, 0x33, 0x00];
+    let mut index = 1;
+    for r in levels {
+        for g in levels {
+            for b in levels {
+                if r == 0 && g == 0 && b == 0 {
+                    continue;
+                }
+                palette[index] = [r, g, b, 0xFF];
+                index += 1;
+            }
+        }
+    }
+    let ramp = [0xEE, 0xDD, 0xBB, 0xAA, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    for channels in [[1, 0, 0], [0, 1, 0], [0, 0, 1], [1, 1, 1]] {
+        for value in ramp {
+            palette[index] = [
+                value * channels[0],
+                value * channels[1],
+                value * channels[2],
+                0xFF,
+            ];
+            index += 1;
+        }
+    }
+    palette
+}
+
+fn write_chunk<W: Write>(writer: &mut W, id: &[u8; 4], content: &[u8]) -> std::io::Result<()> {
+    writer.write_all(id)?;
+    write_u32s(writer, &[content.len() as u32, 0])?;
+    writer.write_all(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framework::math::AABB;
+
+    /// Sphere which is red for negative x and blue otherwise.
+    fn sphere_model() -> VoxModel {
+        let aabb = AABB::new(glam::Vec3::splat(-0.5), glam::Vec3::splat(0.5));
+        let volume = SdfVolume::sample(&aabb, 16, true, |pos| {
+            let color = if pos.x < 0.0 {
+                glam::Vec4::new(1.0, 0.0, 0.0, 1.0)
+            } else {
+                glam::Vec4::new(0.0, 0.0, 1.0, 1.0)
+            };
+            (pos.length() - 0.4, color)
+        });
+        VoxModel::from_volume(&volume).unwrap()
+    }
+
+    #[test]
+    fn quantizes_volume_colors() {
+        let model = sphere_model();
+
+        assert_eq!(model.size, glam::UVec3::splat(16));
+        assert_eq!(model.color_count(), 2);
+        let colors: Vec<[u8; 4]> = model
+            .voxels
+            .iter()
+            .map(|(_, color_index)| model.palette[*color_index as usize])
+            .collect();
+        assert!(colors.contains(&[255, 0, 0, 255]));
+        assert!(colors.contains(&[0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn stored_model_loads_unchanged() {
+        let model = sphere_model();
+        assert!(!model.voxels.is_empty());
+
+        let path = std::env::temp_dir().join(format!("vox_test_{}.vox", std::process::id()));
+        model.store(&path).unwrap();
+        let loaded = VoxModel::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.size, model.size);
+        assert_eq!(loaded.voxels, model.voxels);
+        assert_eq!(loaded.palette[1..], model.palette[1..]);
+    }
+
+    #[test]
+    fn edits_cover_all_voxels() {
+        let model = sphere_model();
+        let edits = model.to_edits(1.0);
+
+        assert!(edits.len() < model.voxels.len());
+        let covered: f32 = edits
+            .iter()
+            .map(|edit| match edit.primitive {
+                Primitive::Cube {
+                    width,
+                    height,
+                    depth,
+                    ..
+                } => width * height * depth,
+                _ => 0.0,
+            })
+            .sum();
+        assert_eq!(covered, model.voxels.len() as f32);
+    }
+
+    #[test]
+    fn rejects_file_without_magic() {
+        let path = std::env::temp_dir().join(format!("vox_magic_test_{}.vox", std::process::id()));
+        std::fs::write(&path, b"NOT A VOX FILE").unwrap();
+        let loaded = VoxModel::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.is_err());
+    }
+}